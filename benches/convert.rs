@@ -0,0 +1,96 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::iter::repeat_n;
+use videorama::{
+    convert::{self, ColorSpec, Kernel, ParallelConverter, RgbaImage, YuvToRgb},
+    v4l2::{FrameFormat, PixelFormat},
+};
+
+const SIZES: [(usize, usize); 4] = [(640, 480), (1280, 720), (1920, 1080), (3840, 2160)];
+
+// Something that isn't flat, so nothing gets to take shortcuts
+fn test_frame(width: usize, height: usize) -> Vec<u8> {
+    (0..width * height * 2)
+        .map(|i| (i * 7 + i / 3) as u8)
+        .collect()
+}
+
+// What the viewer used to do
+fn iterator_chain(data: &[u8]) -> Vec<[u8; 4]> {
+    let ys = data.iter().step_by(2);
+    let us = data.iter().skip(1).step_by(4).flat_map(|u| repeat_n(u, 2));
+    let vs = data.iter().skip(3).step_by(4).flat_map(|v| repeat_n(v, 2));
+
+    ys.zip(us)
+        .zip(vs)
+        .map(|((y, u), v)| {
+            let y = *y as f32 - 16.;
+            let u = *u as f32 - 128.;
+            let v = *v as f32 - 128.;
+
+            let r = 1.164 * y + 1.596 * v;
+            let g = 1.164 * y - 0.392 * u - 0.813 * v;
+            let b = 1.164 * y + 2.017 * u;
+
+            [r as u8, g as u8, b as u8, 255]
+        })
+        .collect()
+}
+
+// Per pixel floating point, allocating the output every frame
+fn float(data: &[u8], spec: ColorSpec) -> Vec<u8> {
+    let yuv_to_rgb = YuvToRgb::new(spec);
+    let mut rgba = Vec::with_capacity(data.len() * 2);
+
+    for yuyv in data.chunks_exact(4) {
+        let [r, g, b] = yuv_to_rgb.convert(yuyv[0], yuyv[1], yuyv[3]);
+        rgba.extend_from_slice(&[r, g, b, 255]);
+        let [r, g, b] = yuv_to_rgb.convert(yuyv[2], yuyv[1], yuyv[3]);
+        rgba.extend_from_slice(&[r, g, b, 255]);
+    }
+
+    rgba
+}
+
+fn yuyv_to_rgba(c: &mut Criterion) {
+    let spec = ColorSpec::default();
+
+    for (width, height) in SIZES {
+        let frame = test_frame(width, height);
+        let format = FrameFormat {
+            pixel_format: PixelFormat::YUYV,
+            width,
+            height,
+            color: spec,
+            ..Default::default()
+        };
+        let size = format!("{width}x{height}");
+
+        let mut group = c.benchmark_group("yuyv_to_rgba");
+        group.throughput(Throughput::Elements((width * height) as u64));
+
+        group.bench_function(BenchmarkId::new("iterator chain", &size), |b| {
+            b.iter(|| iterator_chain(&frame))
+        });
+        group.bench_function(BenchmarkId::new("float", &size), |b| {
+            b.iter(|| float(&frame, spec))
+        });
+
+        let mut rgba = RgbaImage::new();
+        for kernel in Kernel::ALL.into_iter().filter(|k| k.is_supported()) {
+            group.bench_function(BenchmarkId::new(kernel.to_string(), &size), |b| {
+                b.iter(|| convert::frame_to_rgba_with(kernel, &[&frame], &format, spec, &mut rgba))
+            });
+        }
+
+        let converter = ParallelConverter::new(0).unwrap();
+        let name = format!("{} threads", converter.threads());
+        group.bench_function(BenchmarkId::new(name, &size), |b| {
+            b.iter(|| converter.frame_to_rgba(&[&frame], &format, spec, &mut rgba))
+        });
+
+        group.finish();
+    }
+}
+
+criterion_group!(benches, yuyv_to_rgba);
+criterion_main!(benches);
@@ -1,8 +1,19 @@
-use std::path::PathBuf;
-use std::process::Command;
-use std::{env, io};
+use std::io;
 
 fn main() -> io::Result<()> {
+    println!("cargo:rerun-if-changed=build.rs");
+    // Otherwise the bindings checked in under src/v4l2/sys do
+    #[cfg(feature = "bindgen")]
+    generate()?;
+    Ok(())
+}
+
+#[cfg(feature = "bindgen")]
+fn generate() -> io::Result<()> {
+    use std::env;
+    use std::path::PathBuf;
+    use std::process::Command;
+
     // The bindgen::Builder is the main entry point
     // to bindgen, and lets you build up options for
     // the resulting bindings.
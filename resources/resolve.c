@@ -2,6 +2,77 @@
 #include <linux/videodev2.h>
 #include <stdio.h>
 
+// Formats newer than the headers on some distributions
+#ifndef V4L2_PIX_FMT_ABGR64_12
+#define V4L2_PIX_FMT_ABGR64_12 v4l2_fourcc('B','4','1','2')
+#endif
+#ifndef V4L2_PIX_FMT_AJPG
+#define V4L2_PIX_FMT_AJPG v4l2_fourcc('A','J','P','G')
+#endif
+#ifndef V4L2_PIX_FMT_ARGB2101010
+#define V4L2_PIX_FMT_ARGB2101010 v4l2_fourcc('A','R','3','0')
+#endif
+#ifndef V4L2_PIX_FMT_AV1_FRAME
+#define V4L2_PIX_FMT_AV1_FRAME v4l2_fourcc('A','V','1','F')
+#endif
+#ifndef V4L2_PIX_FMT_BGR48_12
+#define V4L2_PIX_FMT_BGR48_12 v4l2_fourcc('B','3','1','2')
+#endif
+#ifndef V4L2_PIX_FMT_HEXTILE
+#define V4L2_PIX_FMT_HEXTILE v4l2_fourcc('H','X','T','L')
+#endif
+#ifndef V4L2_PIX_FMT_MT2110R
+#define V4L2_PIX_FMT_MT2110R v4l2_fourcc('M','T','2','R')
+#endif
+#ifndef V4L2_PIX_FMT_MT2110T
+#define V4L2_PIX_FMT_MT2110T v4l2_fourcc('M','T','2','T')
+#endif
+#ifndef V4L2_PIX_FMT_NV12_10BE_8L128
+#define V4L2_PIX_FMT_NV12_10BE_8L128 v4l2_fourcc_be('A','X','1','2')
+#endif
+#ifndef V4L2_PIX_FMT_NV12_8L128
+#define V4L2_PIX_FMT_NV12_8L128 v4l2_fourcc('A','T','1','2')
+#endif
+#ifndef V4L2_PIX_FMT_NV15_4L4
+#define V4L2_PIX_FMT_NV15_4L4 v4l2_fourcc('V','T','1','5')
+#endif
+#ifndef V4L2_PIX_FMT_P012
+#define V4L2_PIX_FMT_P012 v4l2_fourcc('P','0','1','2')
+#endif
+#ifndef V4L2_PIX_FMT_P012M
+#define V4L2_PIX_FMT_P012M v4l2_fourcc('P','M','1','2')
+#endif
+#ifndef V4L2_PIX_FMT_RGBA1010102
+#define V4L2_PIX_FMT_RGBA1010102 v4l2_fourcc('R','A','3','0')
+#endif
+#ifndef V4L2_PIX_FMT_RGBX1010102
+#define V4L2_PIX_FMT_RGBX1010102 v4l2_fourcc('R','X','3','0')
+#endif
+#ifndef V4L2_PIX_FMT_RV30
+#define V4L2_PIX_FMT_RV30 v4l2_fourcc('R','V','3','0')
+#endif
+#ifndef V4L2_PIX_FMT_RV40
+#define V4L2_PIX_FMT_RV40 v4l2_fourcc('R','V','4','0')
+#endif
+#ifndef V4L2_PIX_FMT_SPK
+#define V4L2_PIX_FMT_SPK v4l2_fourcc('S','P','K','0')
+#endif
+#ifndef V4L2_PIX_FMT_Y012
+#define V4L2_PIX_FMT_Y012 v4l2_fourcc('Y','0','1','2')
+#endif
+#ifndef V4L2_PIX_FMT_Y210
+#define V4L2_PIX_FMT_Y210 v4l2_fourcc('Y','2','1','0')
+#endif
+#ifndef V4L2_PIX_FMT_Y212
+#define V4L2_PIX_FMT_Y212 v4l2_fourcc('Y','2','1','2')
+#endif
+#ifndef V4L2_PIX_FMT_Y216
+#define V4L2_PIX_FMT_Y216 v4l2_fourcc('Y','2','1','6')
+#endif
+#ifndef V4L2_PIX_FMT_YUV48_12
+#define V4L2_PIX_FMT_YUV48_12 v4l2_fourcc('Y','3','1','2')
+#endif
+
 #define PRINT_DEFINE_LU(f, n) fprintf(f, "pub const %s: u64 = %lu;\n", #n, (unsigned long)n)
 #define PRINT_DEFINE_U(f, n) fprintf(f, "pub const %s: u32 = %u;\n", #n, (unsigned int)n)
 
@@ -307,6 +378,11 @@ int main(void)
   PRINT_DEFINE_LU(file, VIDIOC_ENUM_FRAMESIZES);
   PRINT_DEFINE_LU(file, VIDIOC_ENUM_FRAMEINTERVALS);
 
+  PRINT_DEFINE_LU(file, VIDIOC_QUERYCTRL);
+  PRINT_DEFINE_LU(file, VIDIOC_QUERYMENU);
+  PRINT_DEFINE_LU(file, VIDIOC_G_CTRL);
+  PRINT_DEFINE_LU(file, VIDIOC_S_CTRL);
+
   fclose(file);
   return 0;
 }
@@ -0,0 +1,86 @@
+//! Capture from a device without displaying anything and print the capture
+//! statistics once a second.
+//!
+//! Usage: capture-stats [DEVICE] [SECONDS] [BUFFERS] [fifo|low-latency|no-drop]
+
+use std::{
+    fmt::Display,
+    process,
+    time::{Duration, Instant},
+};
+use videorama::{
+    v4l2::{self, CaptureConfig, QueueMode, HISTOGRAM_BUCKET},
+    V4l2VideoDevice,
+};
+
+fn fail(message: impl Display) -> ! {
+    log::error!("{message}");
+    process::exit(1);
+}
+
+fn main() {
+    // Only what went wrong, the statistics are the output. RUST_LOG=debug
+    // shows more.
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+    let mut args = std::env::args().skip(1);
+    let device_path = args.next().unwrap_or_else(|| "/dev/video0".to_string());
+    let seconds: u64 = args.next().map_or(10, |s| {
+        s.parse()
+            .unwrap_or_else(|_| fail("SECONDS must be a number"))
+    });
+
+    let mut config = CaptureConfig::default();
+    if let Some(buffers) = args.next() {
+        config.buffer_count = buffers
+            .parse()
+            .unwrap_or_else(|_| fail("BUFFERS must be a number"));
+    }
+    if let Some(mode) = args.next() {
+        config.queue_mode = match mode.as_str() {
+            "fifo" => QueueMode::Fifo,
+            "low-latency" => QueueMode::LowLatency,
+            "no-drop" => QueueMode::NoDrop,
+            _ => fail(format!("Unknown queue mode {mode}")),
+        };
+    }
+
+    let v4l2_device = V4l2VideoDevice::with_config(&device_path, config)
+        .unwrap_or_else(|e| fail(format!("Can't open {device_path}: {e}")));
+    let stats = v4l2_device.stats();
+    println!(
+        "{:?} with {} buffers",
+        config.queue_mode,
+        v4l2_device.buffer_count()
+    );
+
+    let start = Instant::now();
+    let mut last_print = start;
+
+    while start.elapsed() < Duration::from_secs(seconds) {
+        // Unplugging the camera ends the run early, with what was captured
+        // up to then
+        match v4l2_device.get_frame() {
+            Ok(_frame) => {}
+            Err(e) if v4l2::is_disconnected(&e) => {
+                log::warn!("Lost {device_path}: {e}");
+                break;
+            }
+            Err(e) => fail(format!("Failed to get a frame: {e}")),
+        }
+
+        if last_print.elapsed() >= Duration::from_secs(1) {
+            println!("{}", stats.snapshot());
+            last_print = Instant::now();
+        }
+    }
+
+    let snapshot = stats.snapshot();
+    println!("{} frames: {snapshot}", snapshot.frames);
+    println!("Frame interval histogram:");
+    for (bucket, count) in snapshot.interval_histogram.iter().enumerate() {
+        if *count > 0 {
+            let from = HISTOGRAM_BUCKET * bucket as u32;
+            println!("{:>8}: {count}", format!("{from:?}"));
+        }
+    }
+}
@@ -0,0 +1,306 @@
+//! Conversion of captured frames to RGBA.
+
+mod bayer;
+mod color;
+mod deep;
+mod image;
+mod jpeg;
+mod mono;
+mod packed;
+mod parallel;
+mod planar;
+mod rgb;
+mod scale;
+mod simd;
+mod transform;
+mod yuv;
+
+use std::{error::Error, fmt};
+
+use crate::v4l2::{FrameFormat, PixelFormat};
+use bayer::BayerRows;
+use deep::DeepRows;
+use mono::MonoRows;
+use packed::Packed422Rows;
+use planar::PlanarRows;
+use rgb::RgbRows;
+
+pub use bayer::{Bayer, BayerPacking, CfaPattern, Demosaic, RawOptions};
+pub use color::{
+    ColorOverride, ColorSpec, FixedYuvToRgb, Matrix, Range, RgbToYuv, Transfer, YuvToRgb,
+};
+pub use deep::{DeepLayout, DeepYuv, HdrOptions};
+pub use image::{Mono16Image, Rgba16Image, RgbaImage};
+pub use jpeg::with_default_huffman_tables;
+pub use mono::{Colormap, Mono, MonoOptions, MonoPacking, ToneMap};
+pub use packed::Packed422;
+pub use parallel::ParallelConverter;
+pub use planar::Planar;
+pub use rgb::{Channel, RgbLayout};
+pub use scale::{resize_frame, resize_plane, resize_rgba, Filter};
+pub use simd::Kernel;
+pub use transform::{transform_rgba, Rotation, Transform};
+pub use yuv::YuvView;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConvertError {
+    /// There is no converter for this pixel format
+    UnsupportedFormat(PixelFormat),
+    /// The frame couldn't be decoded, usually a corrupted or truncated MJPEG
+    /// frame. Worth skipping rather than giving up on the stream.
+    CorruptFrame(String),
+}
+
+impl fmt::Display for ConvertError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConvertError::UnsupportedFormat(format) => write!(f, "can't convert {format}"),
+            ConvertError::CorruptFrame(reason) => write!(f, "corrupt frame: {reason}"),
+        }
+    }
+}
+
+impl Error for ConvertError {}
+
+/// Whether `frame_to_rgba` can convert frames in `format`.
+pub fn is_supported(format: PixelFormat) -> bool {
+    Packed422::from_pixel_format(format).is_some()
+        || Planar::from_pixel_format(format).is_some()
+        || RgbLayout::from_pixel_format(format).is_some()
+        || Bayer::from_pixel_format(format).is_some()
+        || Mono::from_pixel_format(format).is_some()
+        || DeepYuv::from_pixel_format(format).is_some()
+        || jpeg::is_jpeg(format)
+}
+
+/// Convert a frame into `dst`, resizing it to fit, with the fastest kernel the
+/// CPU supports. `planes` holds the data of each of the `format.num_planes`
+/// planes, see `V4l2Frame::planes`. Y'CbCr is interpreted according to `spec`
+/// rather than `format.color`, so the caller gets to override it. A
+/// `bytes_per_line` of 0 means tightly packed rows.
+///
+/// (M)JPEG frames are decoded whole, at the size in the bitstream, and a
+/// corrupted one is a `ConvertError::CorruptFrame`. They are always full
+/// range BT.601, so `spec` doesn't apply to them. Raw Bayer frames get the
+/// default `RawOptions`, see `bayer_to_rgba` to choose them, and greyscale
+/// and depth frames `MonoOptions::for_format`, see `mono_to_rgba`. 10 bit
+/// and deeper Y'CbCr is tone mapped to SDR with the default `HdrOptions` if
+/// `spec` says it's PQ or HLG, see `deep_to_rgba`.
+pub fn frame_to_rgba(
+    planes: &[&[u8]],
+    format: &FrameFormat,
+    spec: ColorSpec,
+    dst: &mut RgbaImage,
+) -> Result<(), ConvertError> {
+    frame_to_rgba_with(Kernel::detect(), planes, format, spec, dst)
+}
+
+/// `frame_to_rgba` with a particular kernel, which falls back to the scalar
+/// one if the CPU doesn't support it.
+pub fn frame_to_rgba_with(
+    kernel: Kernel,
+    planes: &[&[u8]],
+    format: &FrameFormat,
+    spec: ColorSpec,
+    dst: &mut RgbaImage,
+) -> Result<(), ConvertError> {
+    let options = Options {
+        spec,
+        ..Options::default()
+    };
+    convert(&SingleThreaded, kernel, planes, format, &options, dst)
+}
+
+/// Demosaic and process a raw Bayer frame into `dst`, resizing it to fit.
+pub fn bayer_to_rgba(
+    planes: &[&[u8]],
+    format: &FrameFormat,
+    options: &RawOptions,
+    dst: &mut RgbaImage,
+) -> Result<(), ConvertError> {
+    if Bayer::from_pixel_format(format.pixel_format).is_none() {
+        return Err(ConvertError::UnsupportedFormat(format.pixel_format));
+    }
+    let options = Options {
+        raw: *options,
+        ..Options::default()
+    };
+    convert(
+        &SingleThreaded,
+        Kernel::Scalar,
+        planes,
+        format,
+        &options,
+        dst,
+    )
+}
+
+/// Tone map and colour a greyscale or depth frame into `dst`, resizing it to
+/// fit.
+pub fn mono_to_rgba(
+    planes: &[&[u8]],
+    format: &FrameFormat,
+    options: &MonoOptions,
+    dst: &mut RgbaImage,
+) -> Result<(), ConvertError> {
+    if Mono::from_pixel_format(format.pixel_format).is_none() {
+        return Err(ConvertError::UnsupportedFormat(format.pixel_format));
+    }
+    let options = Options {
+        mono: Some(*options),
+        ..Options::default()
+    };
+    convert(
+        &SingleThreaded,
+        Kernel::Scalar,
+        planes,
+        format,
+        &options,
+        dst,
+    )
+}
+
+/// The samples of a greyscale or depth frame as they are, widened to 16 bits,
+/// into `dst`, resizing it to fit.
+pub fn frame_to_mono16(
+    planes: &[&[u8]],
+    format: &FrameFormat,
+    dst: &mut Mono16Image,
+) -> Result<(), ConvertError> {
+    let Some(mono) = Mono::from_pixel_format(format.pixel_format) else {
+        return Err(ConvertError::UnsupportedFormat(format.pixel_format));
+    };
+    let src = planes.first().copied().unwrap_or_default();
+    mono.unpack_frame(src, format, dst);
+    Ok(())
+}
+
+/// Convert a 10 bit or deeper Y'CbCr frame into `dst`, resizing it to fit,
+/// tone mapping it if `spec` says it's HDR and `options` ask for it.
+pub fn deep_to_rgba(
+    planes: &[&[u8]],
+    format: &FrameFormat,
+    spec: ColorSpec,
+    options: &HdrOptions,
+    dst: &mut RgbaImage,
+) -> Result<(), ConvertError> {
+    if DeepYuv::from_pixel_format(format.pixel_format).is_none() {
+        return Err(ConvertError::UnsupportedFormat(format.pixel_format));
+    }
+    let options = Options {
+        spec,
+        hdr: *options,
+        ..Options::default()
+    };
+    convert(
+        &SingleThreaded,
+        Kernel::Scalar,
+        planes,
+        format,
+        &options,
+        dst,
+    )
+}
+
+/// `deep_to_rgba` keeping 16 bits per channel, for recording. Rows past the
+/// end of a short frame are left as they were.
+pub fn deep_to_rgba16(
+    planes: &[&[u8]],
+    format: &FrameFormat,
+    spec: ColorSpec,
+    options: &HdrOptions,
+    dst: &mut Rgba16Image,
+) -> Result<(), ConvertError> {
+    let Some(deep) = DeepYuv::from_pixel_format(format.pixel_format) else {
+        return Err(ConvertError::UnsupportedFormat(format.pixel_format));
+    };
+    dst.resize(format.width, format.height);
+    if format.width > 0 {
+        DeepRows::new(deep, planes, format, spec, options).convert_16(dst);
+    }
+    Ok(())
+}
+
+// What a conversion can be told besides the frame
+#[derive(Default)]
+struct Options {
+    spec: ColorSpec,
+    raw: RawOptions,
+    hdr: HdrOptions,
+    // `MonoOptions::for_format` if not given
+    mono: Option<MonoOptions>,
+}
+
+// Converts any band of rows of one frame
+trait Rows: Sync {
+    /// Convert the rows in `dst`, the first of which is row `first_row`.
+    fn convert(&self, first_row: usize, dst: &mut [u8]);
+}
+
+// How the rows of a frame get spread over threads
+trait Run {
+    fn run<R: Rows>(&self, rows: &R, dst: &mut RgbaImage);
+}
+
+struct SingleThreaded;
+
+impl Run for SingleThreaded {
+    fn run<R: Rows>(&self, rows: &R, dst: &mut RgbaImage) {
+        rows.convert(0, dst.data_mut());
+    }
+}
+
+fn convert(
+    runner: &impl Run,
+    kernel: Kernel,
+    planes: &[&[u8]],
+    format: &FrameFormat,
+    options: &Options,
+    dst: &mut RgbaImage,
+) -> Result<(), ConvertError> {
+    let spec = options.spec;
+    let kernel = if kernel.is_supported() {
+        kernel
+    } else {
+        Kernel::Scalar
+    };
+
+    if !is_supported(format.pixel_format) {
+        return Err(ConvertError::UnsupportedFormat(format.pixel_format));
+    }
+    if jpeg::is_jpeg(format.pixel_format) {
+        let src = planes.first().copied().unwrap_or_default();
+        return jpeg::decode(src, format, dst);
+    }
+    dst.resize(format.width, format.height);
+    if format.width == 0 || format.height == 0 {
+        return Ok(());
+    }
+
+    let pixel_format = format.pixel_format;
+    if let Some(order) = Packed422::from_pixel_format(pixel_format) {
+        let src = planes.first().copied().unwrap_or_default();
+        runner.run(&Packed422Rows::new(kernel, order, src, format, spec), dst);
+    } else if let Some(layout) = Planar::from_pixel_format(pixel_format) {
+        runner.run(&PlanarRows::new(layout, planes, format, spec), dst);
+    } else if let Some(layout) = RgbLayout::from_pixel_format(pixel_format) {
+        let src = planes.first().copied().unwrap_or_default();
+        runner.run(&RgbRows::new(layout, src, format), dst);
+    } else if let Some(bayer) = Bayer::from_pixel_format(pixel_format) {
+        let src = planes.first().copied().unwrap_or_default();
+        runner.run(&BayerRows::new(bayer, src, format, &options.raw), dst);
+    } else if let Some(mono) = Mono::from_pixel_format(pixel_format) {
+        let src = planes.first().copied().unwrap_or_default();
+        let mono_options = options.mono.unwrap_or(MonoOptions::for_format(mono));
+        MonoRows::with(mono, src, format, &mono_options, |rows| {
+            runner.run(rows, dst)
+        });
+    } else if let Some(deep) = DeepYuv::from_pixel_format(pixel_format) {
+        runner.run(
+            &DeepRows::new(deep, planes, format, spec, &options.hdr),
+            dst,
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,654 @@
+use std::{cell::RefCell, sync::OnceLock};
+
+use super::Rows;
+use crate::v4l2::{FrameFormat, PixelFormat};
+
+const R: usize = 0;
+const G: usize = 1;
+const B: usize = 2;
+
+/// The colours of the top left 2x2 cell of a Bayer mosaic, left to right and
+/// top to bottom.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CfaPattern {
+    Bggr,
+    Gbrg,
+    Grbg,
+    Rggb,
+}
+
+impl CfaPattern {
+    /// The colour of the sample at `x`, `y`: 0 for red, 1 for green and 2 for
+    /// blue.
+    pub fn color_at(self, x: usize, y: usize) -> usize {
+        let cell = match self {
+            CfaPattern::Bggr => [B, G, G, R],
+            CfaPattern::Gbrg => [G, B, R, G],
+            CfaPattern::Grbg => [G, R, B, G],
+            CfaPattern::Rggb => [R, G, G, B],
+        };
+        cell[(y & 1) * 2 + (x & 1)]
+    }
+}
+
+/// How the samples of a Bayer format are stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BayerPacking {
+    /// A byte per sample
+    Bytes,
+    /// A little endian 16 bit word per sample, in the low bits
+    Words,
+    /// MIPI CSI-2 packing: the high 8 bits of each sample in a byte of its
+    /// own, then the low bits of the group (4 samples for 10 and 14 bit, 2
+    /// for 12 bit) packed together
+    Mipi,
+}
+
+/// Layout of a raw Bayer format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Bayer {
+    pub pattern: CfaPattern,
+    pub bits: u32,
+    pub packing: BayerPacking,
+}
+
+#[rustfmt::skip]
+const FORMATS: [(PixelFormat, CfaPattern, u32, BayerPacking); 32] = {
+    use BayerPacking::*;
+    use CfaPattern::*;
+    [
+        (PixelFormat::SBGGR8, Bggr, 8, Bytes),
+        (PixelFormat::SGBRG8, Gbrg, 8, Bytes),
+        (PixelFormat::SGRBG8, Grbg, 8, Bytes),
+        (PixelFormat::SRGGB8, Rggb, 8, Bytes),
+        (PixelFormat::SBGGR10, Bggr, 10, Words),
+        (PixelFormat::SGBRG10, Gbrg, 10, Words),
+        (PixelFormat::SGRBG10, Grbg, 10, Words),
+        (PixelFormat::SRGGB10, Rggb, 10, Words),
+        (PixelFormat::SBGGR12, Bggr, 12, Words),
+        (PixelFormat::SGBRG12, Gbrg, 12, Words),
+        (PixelFormat::SGRBG12, Grbg, 12, Words),
+        (PixelFormat::SRGGB12, Rggb, 12, Words),
+        (PixelFormat::SBGGR14, Bggr, 14, Words),
+        (PixelFormat::SGBRG14, Gbrg, 14, Words),
+        (PixelFormat::SGRBG14, Grbg, 14, Words),
+        (PixelFormat::SRGGB14, Rggb, 14, Words),
+        (PixelFormat::SBGGR16, Bggr, 16, Words),
+        (PixelFormat::SGBRG16, Gbrg, 16, Words),
+        (PixelFormat::SGRBG16, Grbg, 16, Words),
+        (PixelFormat::SRGGB16, Rggb, 16, Words),
+        (PixelFormat::SBGGR10P, Bggr, 10, Mipi),
+        (PixelFormat::SGBRG10P, Gbrg, 10, Mipi),
+        (PixelFormat::SGRBG10P, Grbg, 10, Mipi),
+        (PixelFormat::SRGGB10P, Rggb, 10, Mipi),
+        (PixelFormat::SBGGR12P, Bggr, 12, Mipi),
+        (PixelFormat::SGBRG12P, Gbrg, 12, Mipi),
+        (PixelFormat::SGRBG12P, Grbg, 12, Mipi),
+        (PixelFormat::SRGGB12P, Rggb, 12, Mipi),
+        (PixelFormat::SBGGR14P, Bggr, 14, Mipi),
+        (PixelFormat::SGBRG14P, Gbrg, 14, Mipi),
+        (PixelFormat::SGRBG14P, Grbg, 14, Mipi),
+        (PixelFormat::SRGGB14P, Rggb, 14, Mipi),
+    ]
+};
+
+impl Bayer {
+    pub fn from_pixel_format(format: PixelFormat) -> Option<Self> {
+        FORMATS
+            .iter()
+            .find(|(f, ..)| *f == format)
+            .map(|&(_, pattern, bits, packing)| Self {
+                pattern,
+                bits,
+                packing,
+            })
+    }
+
+    /// The largest sample value.
+    pub fn white_level(&self) -> u16 {
+        ((1u32 << self.bits) - 1) as u16
+    }
+
+    /// Bytes taken by a row of `width` samples, without any padding.
+    pub fn row_bytes(&self, width: usize) -> usize {
+        packed_row_bytes(self.bits, self.packing, width)
+    }
+
+    /// Unpack a row of `dst.len()` samples from `src`, which has to hold
+    /// `row_bytes(dst.len())` bytes.
+    pub fn unpack_row(&self, src: &[u8], dst: &mut [u16]) {
+        unpack_samples(self.bits, self.packing, src, dst)
+    }
+}
+
+// Samples per group and bytes per group for MIPI packing
+fn mipi_group(bits: u32) -> (usize, usize) {
+    match bits {
+        10 => (4, 5),
+        12 => (2, 3),
+        _ => (4, 7),
+    }
+}
+
+// Shared with the greyscale formats, which are packed the same way
+pub(super) fn packed_row_bytes(bits: u32, packing: BayerPacking, width: usize) -> usize {
+    match packing {
+        BayerPacking::Bytes => width,
+        BayerPacking::Words => width * 2,
+        BayerPacking::Mipi => {
+            let (samples, bytes) = mipi_group(bits);
+            width.div_ceil(samples) * bytes
+        }
+    }
+}
+
+pub(super) fn unpack_samples(bits: u32, packing: BayerPacking, src: &[u8], dst: &mut [u16]) {
+    match packing {
+        BayerPacking::Bytes => {
+            for (d, s) in dst.iter_mut().zip(src) {
+                *d = *s as u16;
+            }
+        }
+        BayerPacking::Words => {
+            let mask = ((1u32 << bits) - 1) as u16;
+            for (d, s) in dst.iter_mut().zip(src.chunks_exact(2)) {
+                *d = u16::from_le_bytes([s[0], s[1]]) & mask;
+            }
+        }
+        BayerPacking::Mipi => {
+            let (samples, bytes) = mipi_group(bits);
+            let low_bits = bits - 8;
+            let low_mask = (1 << low_bits) - 1;
+            for (src, dst) in src.chunks_exact(bytes).zip(dst.chunks_mut(samples)) {
+                // The low bits of the whole group, little endian
+                let low = src[samples..]
+                    .iter()
+                    .rev()
+                    .fold(0u32, |acc, b| acc << 8 | *b as u32);
+                for (i, d) in dst.iter_mut().enumerate() {
+                    let low = (low >> (low_bits as usize * i)) & low_mask;
+                    *d = ((src[i] as u32) << low_bits | low) as u16;
+                }
+            }
+        }
+    }
+}
+
+/// How the missing two colours of each sample get filled in.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Demosaic {
+    /// Copy them from the same 2x2 cell. Fast and blocky.
+    Nearest,
+    /// Average the nearest samples of each colour.
+    Bilinear,
+    /// Bilinear corrected with the gradient of the sample's own colour
+    /// (Malvar, He and Cutler, 2004). Much less colour fringing on edges.
+    #[default]
+    MalvarHeCutler,
+}
+
+/// What to do with raw Bayer samples on the way to RGB.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RawOptions {
+    pub demosaic: Demosaic,
+    /// Subtracted from every sample, in the units of the format's bit depth
+    pub black_level: u16,
+    /// Red, green and blue gains, applied before demosaicing
+    pub white_balance: [f32; 3],
+    /// Camera RGB to linear sRGB, one row per output channel
+    pub color_matrix: [[f32; 3]; 3],
+    /// Encode the linear result with the sRGB transfer function
+    pub srgb_gamma: bool,
+}
+
+impl Default for RawOptions {
+    fn default() -> Self {
+        Self {
+            demosaic: Demosaic::default(),
+            black_level: 0,
+            white_balance: [1.0; 3],
+            color_matrix: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+            srgb_gamma: true,
+        }
+    }
+}
+
+// Linear light in 1/4095 steps to 8 bits, either straight or sRGB encoded
+fn output_lut(srgb: bool) -> &'static [u8; 4096] {
+    static LINEAR: OnceLock<[u8; 4096]> = OnceLock::new();
+    static SRGB: OnceLock<[u8; 4096]> = OnceLock::new();
+    let (lut, transfer): (_, fn(f32) -> f32) = if srgb {
+        (&SRGB, |l| {
+            if l <= 0.003_130_8 {
+                l * 12.92
+            } else {
+                1.055 * l.powf(1.0 / 2.4) - 0.055
+            }
+        })
+    } else {
+        (&LINEAR, |l| l)
+    };
+    lut.get_or_init(|| std::array::from_fn(|i| (transfer(i as f32 / 4095.0) * 255.0 + 0.5) as u8))
+}
+
+// Mirrors around the first and last sample, which keeps the colour of the
+// mosaic as long as there are at least 3 samples.
+fn reflect(i: isize, len: usize) -> usize {
+    let last = len as isize - 1;
+    let i = if i < 0 {
+        -i
+    } else if i > last {
+        2 * last - i
+    } else {
+        i
+    };
+    i.clamp(0, last) as usize
+}
+
+// Border on each side of a row, enough for the 5x5 kernels
+const PAD: usize = 2;
+
+// The rows around the one being demosaiced, unpacked and normalised, each
+// with PAD reflected samples on either side. Row `y` lives in slot `y % 5`,
+// which never collides as a window only spans 5 consecutive rows.
+#[derive(Default)]
+struct Window {
+    rows: [Vec<f32>; 5],
+    loaded: [Option<usize>; 5],
+    samples: Vec<u16>,
+    // The demosaiced row
+    rgb: Vec<[f32; 3]>,
+}
+
+impl Window {
+    // Empty and sized for rows of `width`, which only allocates when they
+    // get wider
+    fn reset(&mut self, width: usize) {
+        for row in &mut self.rows {
+            row.resize(width + 2 * PAD, 0.0);
+        }
+        self.loaded = [None; 5];
+        self.samples.resize(width, 0);
+        self.rgb.resize(width, [0.0; 3]);
+    }
+}
+
+thread_local! {
+    // Each converting thread keeps its window from band to band and frame to
+    // frame
+    static WINDOW: RefCell<Window> = RefCell::default();
+}
+
+pub(super) struct BayerRows<'a> {
+    bayer: Bayer,
+    options: RawOptions,
+    src: &'a [u8],
+    width: usize,
+    height: usize,
+    stride: usize,
+    // Per colour, what a sample minus the black level gets multiplied by
+    scale: [f32; 3],
+}
+
+impl<'a> BayerRows<'a> {
+    pub(super) fn new(
+        bayer: Bayer,
+        src: &'a [u8],
+        format: &FrameFormat,
+        options: &RawOptions,
+    ) -> Self {
+        let stride = if format.bytes_per_line == 0 {
+            bayer.row_bytes(format.width)
+        } else {
+            format.bytes_per_line
+        };
+        let range = (bayer.white_level().saturating_sub(options.black_level) as f32).max(1.0);
+        Self {
+            bayer,
+            options: *options,
+            src,
+            width: format.width,
+            height: format.height,
+            stride,
+            scale: options.white_balance.map(|gain| gain / range),
+        }
+    }
+
+    fn source_row(&self, y: usize) -> Option<&'a [u8]> {
+        self.src
+            .get(y * self.stride..)?
+            .get(..self.bayer.row_bytes(self.width))
+    }
+
+    fn load(&self, window: &mut Window, y: usize) -> usize {
+        let slot = y % 5;
+        if window.loaded[slot] == Some(y) {
+            return slot;
+        }
+        window.loaded[slot] = Some(y);
+
+        let row = &mut window.rows[slot];
+        match self.source_row(y) {
+            Some(src) => self.bayer.unpack_row(src, &mut window.samples),
+            // Past the end of a short frame
+            None => window.samples.fill(0),
+        }
+        let black = self.options.black_level;
+        for (x, (dst, sample)) in row[PAD..].iter_mut().zip(&window.samples).enumerate() {
+            let color = self.bayer.pattern.color_at(x, y);
+            // Clipped after the gains so blown highlights stay white
+            *dst = (sample.saturating_sub(black) as f32 * self.scale[color]).min(1.0);
+        }
+        for i in 0..PAD {
+            let left = reflect(i as isize - PAD as isize, self.width);
+            let right = reflect((self.width + i) as isize, self.width);
+            row[i] = row[PAD + left];
+            row[PAD + self.width + i] = row[PAD + right];
+        }
+        slot
+    }
+
+    // Demosaic row `y` into linear RGB. `rows` are the rows from `y - 2` to
+    // `y + 2`.
+    fn demosaic_row(&self, rows: [&[f32]; 5], y: usize, out: &mut [[f32; 3]]) {
+        let pattern = self.bayer.pattern;
+        // A row only ever has two colours, alternating
+        let colors = [pattern.color_at(0, y), pattern.color_at(1, y)];
+        let [up2, up, row, down, down2] = rows;
+        // The other row of this sample's 2x2 cell
+        let pair = if y & 1 == 0 { down } else { up };
+        // PAD is even, so `i ^ 1` is still the other sample of the cell
+        let samples = out.iter_mut().enumerate().map(|(x, rgb)| (x + PAD, rgb));
+
+        match self.options.demosaic {
+            Demosaic::Nearest => {
+                for (i, rgb) in samples {
+                    let color = colors[i & 1];
+                    rgb[color] = row[i];
+                    if color == G {
+                        rgb[colors[!i & 1]] = row[i ^ 1];
+                        rgb[B - colors[!i & 1]] = pair[i];
+                    } else {
+                        // The green on this row rather than the one below
+                        rgb[G] = row[i ^ 1];
+                        rgb[B - color] = pair[i ^ 1];
+                    }
+                }
+            }
+            Demosaic::Bilinear => {
+                for (i, rgb) in samples {
+                    let color = colors[i & 1];
+                    rgb[color] = row[i];
+                    if color == G {
+                        let across = colors[!i & 1];
+                        rgb[across] = (row[i - 1] + row[i + 1]) / 2.0;
+                        rgb[B - across] = (up[i] + down[i]) / 2.0;
+                    } else {
+                        let cross = row[i - 1] + row[i + 1] + up[i] + down[i];
+                        let diagonal = up[i - 1] + up[i + 1] + down[i - 1] + down[i + 1];
+                        rgb[G] = cross / 4.0;
+                        rgb[B - color] = diagonal / 4.0;
+                    }
+                }
+            }
+            Demosaic::MalvarHeCutler => {
+                for (i, rgb) in samples {
+                    let color = colors[i & 1];
+                    let centre = row[i];
+                    let diagonal = up[i - 1] + up[i + 1] + down[i - 1] + down[i + 1];
+                    let far_h = row[i - 2] + row[i + 2];
+                    let far_v = up2[i] + down2[i];
+                    rgb[color] = centre;
+                    if color == G {
+                        let across = colors[!i & 1];
+                        rgb[across] =
+                            (5.0 * centre + 4.0 * (row[i - 1] + row[i + 1]) - far_h - diagonal
+                                + 0.5 * far_v)
+                                / 8.0;
+                        rgb[B - across] =
+                            (5.0 * centre + 4.0 * (up[i] + down[i]) - far_v - diagonal
+                                + 0.5 * far_h)
+                                / 8.0;
+                    } else {
+                        let cross = row[i - 1] + row[i + 1] + up[i] + down[i];
+                        rgb[G] = (4.0 * centre + 2.0 * cross - far_h - far_v) / 8.0;
+                        rgb[B - color] =
+                            (6.0 * centre + 2.0 * diagonal - 1.5 * (far_h + far_v)) / 8.0;
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Rows for BayerRows<'_> {
+    fn convert(&self, first_row: usize, dst: &mut [u8]) {
+        let lut = output_lut(self.options.srgb_gamma);
+        let m = &self.options.color_matrix;
+
+        WINDOW.with_borrow_mut(|window| {
+            window.reset(self.width);
+            for (y, dst_row) in (first_row..).zip(dst.chunks_exact_mut(self.width * 4)) {
+                // A short frame leaves the rows past its end as they were
+                if self.source_row(y).is_none() {
+                    break;
+                }
+                let slots = [-2, -1, 0, 1, 2].map(|dy| {
+                    let row = reflect(y as isize + dy, self.height);
+                    self.load(window, row)
+                });
+                let rows = slots.map(|slot| window.rows[slot].as_slice());
+                self.demosaic_row(rows, y, &mut window.rgb);
+
+                for (rgb, rgba) in window.rgb.iter().zip(dst_row.chunks_exact_mut(4)) {
+                    for (out, m) in rgba.iter_mut().zip(m) {
+                        let v = (m[0] * rgb[0] + m[1] * rgb[1] + m[2] * rgb[2]).clamp(0.0, 1.0);
+                        *out = lut[(v * 4095.0 + 0.5) as usize & 4095];
+                    }
+                    rgba[3] = 255;
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::convert::{bayer_to_rgba, RgbaImage};
+
+    const PATTERNS: [(PixelFormat, CfaPattern); 4] = [
+        (PixelFormat::SBGGR8, CfaPattern::Bggr),
+        (PixelFormat::SGBRG8, CfaPattern::Gbrg),
+        (PixelFormat::SGRBG8, CfaPattern::Grbg),
+        (PixelFormat::SRGGB8, CfaPattern::Rggb),
+    ];
+
+    const DEMOSAICS: [Demosaic; 3] = [
+        Demosaic::Nearest,
+        Demosaic::Bilinear,
+        Demosaic::MalvarHeCutler,
+    ];
+
+    // What the sensor would see of a picture of a single colour
+    fn mosaic(pattern: CfaPattern, width: usize, height: usize, rgb: [u8; 3]) -> Vec<u8> {
+        (0..width * height)
+            .map(|i| rgb[pattern.color_at(i % width, i / width)])
+            .collect()
+    }
+
+    fn raw(demosaic: Demosaic) -> RawOptions {
+        RawOptions {
+            demosaic,
+            srgb_gamma: false,
+            ..Default::default()
+        }
+    }
+
+    fn convert(
+        pixel_format: PixelFormat,
+        data: &[u8],
+        (width, height): (usize, usize),
+        options: &RawOptions,
+    ) -> RgbaImage {
+        let format = FrameFormat {
+            pixel_format,
+            width,
+            height,
+            ..Default::default()
+        };
+        let mut dst = RgbaImage::new();
+        bayer_to_rgba(&[data], &format, options, &mut dst).unwrap();
+        dst
+    }
+
+    fn assert_all(image: &RgbaImage, want: [u8; 4], tolerance: u8, what: &str) {
+        for (i, pixel) in image.data().chunks_exact(4).enumerate() {
+            let off = (0..4).map(|c| pixel[c].abs_diff(want[c])).max().unwrap();
+            assert!(
+                off <= tolerance,
+                "{what}, pixel {i}: {pixel:?}, want {want:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn cfa_orders() {
+        for (pixel_format, pattern) in PATTERNS {
+            assert_eq!(
+                Bayer::from_pixel_format(pixel_format).unwrap().pattern,
+                pattern
+            );
+        }
+        let cells = [
+            (CfaPattern::Bggr, [B, G, G, R]),
+            (CfaPattern::Gbrg, [G, B, R, G]),
+            (CfaPattern::Grbg, [G, R, B, G]),
+            (CfaPattern::Rggb, [R, G, G, B]),
+        ];
+        for (pattern, cell) in cells {
+            for (i, color) in cell.into_iter().enumerate() {
+                // The cell repeats every two samples either way
+                let (x, y) = (i % 2, i / 2);
+                assert_eq!(pattern.color_at(x, y), color, "{pattern:?}");
+                assert_eq!(pattern.color_at(x + 6, y + 4), color, "{pattern:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn unpacks_every_packing() {
+        let cases: [(PixelFormat, &[u8], &[u16]); 6] = [
+            (PixelFormat::SRGGB8, &[0, 1, 254, 255], &[0, 1, 254, 255]),
+            // Bits above the depth are junk
+            (
+                PixelFormat::SRGGB10,
+                &[0xff, 0x03, 0x00, 0xfc, 0x55, 0x01],
+                &[0x3ff, 0x000, 0x155],
+            ),
+            (
+                PixelFormat::SRGGB16,
+                &[0x34, 0x12, 0xff, 0xff],
+                &[0x1234, 0xffff],
+            ),
+            (
+                PixelFormat::SRGGB10P,
+                &[0xff, 0x00, 0x55, 0xaa, 0x93],
+                &[0x3ff, 0x000, 0x155, 0x2aa],
+            ),
+            (PixelFormat::SRGGB12P, &[0xab, 0x12, 0x3c], &[0xabc, 0x123]),
+            (
+                PixelFormat::SRGGB14P,
+                &[0xff, 0x00, 0x80, 0x48, 0x7f, 0x00, 0xd0],
+                &[0x3fff, 0x0001, 0x2000, 0x1234],
+            ),
+        ];
+        for (pixel_format, src, want) in cases {
+            let bayer = Bayer::from_pixel_format(pixel_format).unwrap();
+            assert_eq!(bayer.row_bytes(want.len()), src.len(), "{pixel_format}");
+            let mut got = vec![0; want.len()];
+            bayer.unpack_row(src, &mut got);
+            assert_eq!(got, want, "{pixel_format}");
+        }
+    }
+
+    #[test]
+    fn demosaics_flat_colours_in_every_order() {
+        let colors = [
+            [200, 100, 50],
+            [0, 255, 0],
+            [255, 0, 0],
+            [0, 0, 255],
+            [90, 90, 90],
+        ];
+        for (pixel_format, pattern) in PATTERNS {
+            for demosaic in DEMOSAICS {
+                for [r, g, b] in colors {
+                    // Odd sizes, so the borders get reflected on every side
+                    let data = mosaic(pattern, 9, 7, [r, g, b]);
+                    let image = convert(pixel_format, &data, (9, 7), &raw(demosaic));
+                    let what = format!("{pattern:?} {demosaic:?}");
+                    assert_all(&image, [r, g, b, 255], 1, &what);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn demosaics_edges_without_overshoot_in_flat_areas() {
+        // Left half black, right half white
+        for (pixel_format, pattern) in PATTERNS {
+            for demosaic in DEMOSAICS {
+                let data: Vec<u8> = (0..16 * 8)
+                    .map(|i| if i % 16 < 8 { 0 } else { 255 })
+                    .collect();
+                let image = convert(pixel_format, &data, (16, 8), &raw(demosaic));
+                let what = format!("{pattern:?} {demosaic:?}");
+                for y in 0..8 {
+                    // Far enough from the edge for every kernel
+                    let pixel = |x: usize| &image.data()[(y * 16 + x) * 4..][..3];
+                    assert_eq!(pixel(2), [0, 0, 0], "{what}");
+                    assert_eq!(pixel(13), [255, 255, 255], "{what}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn applies_black_level_and_white_balance() {
+        // A 10 bit grey card at 528 over a black level of 64, under light
+        // that makes red half as bright and blue a quarter
+        let samples = mosaic(CfaPattern::Rggb, 8, 6, [0, 1, 2])
+            .iter()
+            .map(|&color| [296u16, 528, 180][color as usize])
+            .flat_map(u16::to_le_bytes)
+            .collect::<Vec<u8>>();
+        let options = RawOptions {
+            black_level: 64,
+            white_balance: [2.0, 1.0, 4.0],
+            ..raw(Demosaic::Bilinear)
+        };
+        let image = convert(PixelFormat::SRGGB10, &samples, (8, 6), &options);
+        // (528 - 64) / (1023 - 64) of full scale
+        assert_all(&image, [123, 123, 123, 255], 1, "grey card");
+    }
+
+    #[test]
+    fn applies_colour_matrix_and_gamma() {
+        let data = mosaic(CfaPattern::Grbg, 6, 6, [255, 128, 0]);
+        let swap = RawOptions {
+            color_matrix: [[0.0, 0.0, 1.0], [0.0, 1.0, 0.0], [1.0, 0.0, 0.0]],
+            ..raw(Demosaic::Nearest)
+        };
+        let image = convert(PixelFormat::SGRBG8, &data, (6, 6), &swap);
+        assert_all(&image, [0, 128, 255, 255], 0, "swapped");
+
+        // Half of full scale is 188 in sRGB
+        let srgb = RawOptions {
+            srgb_gamma: true,
+            ..swap
+        };
+        let image = convert(PixelFormat::SGRBG8, &data, (6, 6), &srgb);
+        assert_all(&image, [0, 188, 255, 255], 1, "sRGB");
+    }
+}
@@ -0,0 +1,434 @@
+use std::fmt;
+
+/// The YCbCr matrix, i.e. the luma weights of red and blue.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Matrix {
+    /// SD video and most webcams at low resolutions
+    #[default]
+    Bt601,
+    /// HD video
+    Bt709,
+    /// UHD and HDR video
+    Bt2020,
+    Smpte240m,
+}
+
+impl Matrix {
+    pub const ALL: [Matrix; 4] = [
+        Matrix::Bt601,
+        Matrix::Bt709,
+        Matrix::Bt2020,
+        Matrix::Smpte240m,
+    ];
+
+    /// `(Kr, Kb)`, the contribution of red and blue to luma.
+    pub fn coefficients(self) -> (f32, f32) {
+        match self {
+            Matrix::Bt601 => (0.299, 0.114),
+            Matrix::Bt709 => (0.2126, 0.0722),
+            Matrix::Bt2020 => (0.2627, 0.0593),
+            Matrix::Smpte240m => (0.212, 0.087),
+        }
+    }
+}
+
+impl fmt::Display for Matrix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Matrix::Bt601 => "BT.601",
+            Matrix::Bt709 => "BT.709",
+            Matrix::Bt2020 => "BT.2020",
+            Matrix::Smpte240m => "SMPTE 240M",
+        })
+    }
+}
+
+/// Quantization range of the Y'CbCr values.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Range {
+    /// Y' in 16..=235, Cb and Cr in 16..=240
+    #[default]
+    Limited,
+    /// Everything in 0..=255, as used by JPEG
+    Full,
+}
+
+impl Range {
+    pub const ALL: [Range; 2] = [Range::Limited, Range::Full];
+}
+
+impl fmt::Display for Range {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Range::Limited => "limited",
+            Range::Full => "full",
+        })
+    }
+}
+
+/// Transfer function of the R'G'B' values, i.e. how they map to light.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Transfer {
+    /// BT.709, sRGB and the other SDR gamma curves, shown as they are
+    #[default]
+    Sdr,
+    /// SMPTE ST 2084, the HDR10 curve, absolute up to 10000 nits
+    Pq,
+    /// Hybrid log-gamma from BT.2100, relative to the display's peak
+    Hlg,
+}
+
+impl Transfer {
+    pub const ALL: [Transfer; 3] = [Transfer::Sdr, Transfer::Pq, Transfer::Hlg];
+}
+
+impl fmt::Display for Transfer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Transfer::Sdr => "SDR",
+            Transfer::Pq => "PQ",
+            Transfer::Hlg => "HLG",
+        })
+    }
+}
+
+/// How the Y'CbCr values of a frame are to be interpreted.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ColorSpec {
+    pub matrix: Matrix,
+    pub range: Range,
+    /// Only the 10 bit and deeper formats are tone mapped according to this,
+    /// 8 bit is always taken as SDR
+    pub transfer: Transfer,
+}
+
+/// Replaces whatever the driver reported, for devices that get it wrong.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ColorOverride {
+    pub matrix: Option<Matrix>,
+    pub range: Option<Range>,
+    pub transfer: Option<Transfer>,
+}
+
+impl ColorOverride {
+    pub fn apply(self, spec: ColorSpec) -> ColorSpec {
+        ColorSpec {
+            matrix: self.matrix.unwrap_or(spec.matrix),
+            range: self.range.unwrap_or(spec.range),
+            transfer: self.transfer.unwrap_or(spec.transfer),
+        }
+    }
+}
+
+/// Y'CbCr to R'G'B' conversion factors for one `ColorSpec`, worked out once
+/// per frame rather than per pixel.
+#[derive(Debug, Clone, Copy)]
+pub struct YuvToRgb {
+    y_offset: f32,
+    y_scale: f32,
+    // Cb/Cr -> R, G and B, already scaled for the range
+    r_cr: f32,
+    g_cb: f32,
+    g_cr: f32,
+    b_cb: f32,
+}
+
+impl YuvToRgb {
+    pub fn new(spec: ColorSpec) -> Self {
+        let (kr, kb) = spec.matrix.coefficients();
+        let kg = 1.0 - kr - kb;
+
+        // Scale from code values to Y' in 0..=1 and Cb/Cr in -0.5..=0.5
+        let (y_offset, y_scale, c_scale) = match spec.range {
+            Range::Limited => (16.0, 255.0 / 219.0, 255.0 / 224.0),
+            Range::Full => (0.0, 1.0, 1.0),
+        };
+
+        Self {
+            y_offset,
+            y_scale,
+            r_cr: 2.0 * (1.0 - kr) * c_scale,
+            g_cb: -2.0 * kb * (1.0 - kb) / kg * c_scale,
+            g_cr: -2.0 * kr * (1.0 - kr) / kg * c_scale,
+            b_cb: 2.0 * (1.0 - kb) * c_scale,
+        }
+    }
+
+    #[inline]
+    pub fn convert(&self, y: u8, u: u8, v: u8) -> [u8; 3] {
+        let y = (y as f32 - self.y_offset) * self.y_scale;
+        let u = u as f32 - 128.0;
+        let v = v as f32 - 128.0;
+
+        let r = y + self.r_cr * v;
+        let g = y + self.g_cb * u + self.g_cr * v;
+        let b = y + self.b_cb * u;
+
+        // `as` saturates, rounding is up to us
+        [r.round() as u8, g.round() as u8, b.round() as u8]
+    }
+}
+
+/// Fractional bits of the `FixedYuvToRgb` factors. 13 keeps the biggest one
+/// (BT.2020 Cb -> B in limited range) inside an `i16`, which the SIMD
+/// kernels need.
+pub(crate) const FIXED_BITS: i32 = 13;
+pub(crate) const FIXED_ROUND: i32 = 1 << (FIXED_BITS - 1);
+
+/// `YuvToRgb` in fixed point. The SIMD kernels do exactly this arithmetic, so
+/// every path gives the same bytes; results are within one code value of the
+/// floating point version.
+#[derive(Debug, Clone, Copy)]
+pub struct FixedYuvToRgb {
+    pub(crate) y_offset: i16,
+    pub(crate) y_scale: i16,
+    pub(crate) r_cr: i16,
+    pub(crate) g_cb: i16,
+    pub(crate) g_cr: i16,
+    pub(crate) b_cb: i16,
+}
+
+impl FixedYuvToRgb {
+    pub fn new(spec: ColorSpec) -> Self {
+        let float = YuvToRgb::new(spec);
+        let fixed = |x: f32| (x * (1 << FIXED_BITS) as f32).round() as i16;
+
+        Self {
+            y_offset: float.y_offset as i16,
+            y_scale: fixed(float.y_scale),
+            r_cr: fixed(float.r_cr),
+            g_cb: fixed(float.g_cb),
+            g_cr: fixed(float.g_cr),
+            b_cb: fixed(float.b_cb),
+        }
+    }
+
+    #[inline]
+    pub fn convert(&self, y: u8, u: u8, v: u8) -> [u8; 3] {
+        let y = (y as i32 - self.y_offset as i32) * self.y_scale as i32 + FIXED_ROUND;
+        let u = u as i32 - 128;
+        let v = v as i32 - 128;
+
+        let r = (y + self.r_cr as i32 * v) >> FIXED_BITS;
+        let g = (y + self.g_cb as i32 * u + self.g_cr as i32 * v) >> FIXED_BITS;
+        let b = (y + self.b_cb as i32 * u) >> FIXED_BITS;
+
+        [
+            r.clamp(0, 255) as u8,
+            g.clamp(0, 255) as u8,
+            b.clamp(0, 255) as u8,
+        ]
+    }
+}
+
+/// R'G'B' to Y'CbCr, the other way round from `YuvToRgb`.
+#[derive(Debug, Clone, Copy)]
+pub struct RgbToYuv {
+    kr: f32,
+    kb: f32,
+    y_offset: f32,
+    // Code values per unit of Y' and of Cb/Cr
+    y_scale: f32,
+    c_scale: f32,
+}
+
+impl RgbToYuv {
+    pub fn new(spec: ColorSpec) -> Self {
+        let (kr, kb) = spec.matrix.coefficients();
+        let (y_offset, y_scale, c_scale) = match spec.range {
+            Range::Limited => (16.0, 219.0, 224.0),
+            Range::Full => (0.0, 255.0, 255.0),
+        };
+
+        Self {
+            kr,
+            kb,
+            y_offset,
+            y_scale: y_scale / 255.0,
+            c_scale: c_scale / 255.0,
+        }
+    }
+
+    #[inline]
+    pub fn convert(&self, r: u8, g: u8, b: u8) -> [u8; 3] {
+        let (r, g, b) = (r as f32, g as f32, b as f32);
+        let y = self.kr * r + (1.0 - self.kr - self.kb) * g + self.kb * b;
+        let u = (b - y) / (2.0 * (1.0 - self.kb));
+        let v = (r - y) / (2.0 * (1.0 - self.kr));
+
+        [
+            (y * self.y_scale + self.y_offset).round() as u8,
+            (u * self.c_scale + 128.0).round() as u8,
+            (v * self.c_scale + 128.0).round() as u8,
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 100% colour bars, white to black
+    const BARS: [[u8; 3]; 8] = [
+        [255, 255, 255],
+        [255, 255, 0],
+        [0, 255, 255],
+        [0, 255, 0],
+        [255, 0, 255],
+        [255, 0, 0],
+        [0, 0, 255],
+        [0, 0, 0],
+    ];
+
+    // Y'CbCr of `BARS`, as published for BT.601 and BT.709 limited range,
+    // and worked out from the same equations for the rest
+    const REFERENCE: [(Matrix, Range, [[u8; 3]; 8]); 6] = [
+        (
+            Matrix::Bt601,
+            Range::Limited,
+            [
+                [235, 128, 128],
+                [210, 16, 146],
+                [170, 166, 16],
+                [145, 54, 34],
+                [106, 202, 222],
+                [81, 90, 240],
+                [41, 240, 110],
+                [16, 128, 128],
+            ],
+        ),
+        (
+            Matrix::Bt601,
+            Range::Full,
+            [
+                [255, 128, 128],
+                [226, 0, 149],
+                [179, 171, 0],
+                [150, 44, 21],
+                [105, 212, 235],
+                [76, 85, 255],
+                [29, 255, 107],
+                [0, 128, 128],
+            ],
+        ),
+        (
+            Matrix::Bt709,
+            Range::Limited,
+            [
+                [235, 128, 128],
+                [219, 16, 138],
+                [188, 154, 16],
+                [173, 42, 26],
+                [78, 214, 230],
+                [63, 102, 240],
+                [32, 240, 118],
+                [16, 128, 128],
+            ],
+        ),
+        (
+            Matrix::Bt709,
+            Range::Full,
+            [
+                [255, 128, 128],
+                [237, 0, 140],
+                [201, 157, 0],
+                [182, 30, 12],
+                [73, 226, 244],
+                [54, 99, 255],
+                [18, 255, 116],
+                [0, 128, 128],
+            ],
+        ),
+        (
+            Matrix::Bt2020,
+            Range::Limited,
+            [
+                [235, 128, 128],
+                [222, 16, 137],
+                [177, 159, 16],
+                [164, 47, 25],
+                [87, 209, 231],
+                [74, 97, 240],
+                [29, 240, 119],
+                [16, 128, 128],
+            ],
+        ),
+        (
+            Matrix::Bt2020,
+            Range::Full,
+            [
+                [255, 128, 128],
+                [240, 0, 138],
+                [188, 164, 0],
+                [173, 36, 11],
+                [82, 220, 245],
+                [67, 92, 255],
+                [15, 255, 118],
+                [0, 128, 128],
+            ],
+        ),
+    ];
+
+    fn assert_close(got: [u8; 3], want: [u8; 3], tolerance: u8, what: String) {
+        let off = (0..3).map(|i| got[i].abs_diff(want[i])).max().unwrap();
+        assert!(off <= tolerance, "{what}: got {got:?}, want {want:?}");
+    }
+
+    #[test]
+    fn decodes_colour_bars() {
+        for (matrix, range, yuv) in REFERENCE {
+            let spec = ColorSpec {
+                matrix,
+                range,
+                ..Default::default()
+            };
+            let float = YuvToRgb::new(spec);
+            let fixed = FixedYuvToRgb::new(spec);
+            for (&[y, u, v], &rgb) in yuv.iter().zip(&BARS) {
+                // Rounding of the reference values, and chroma that had to
+                // be clipped at 255 in full range
+                let what = format!("{matrix} {range} {y} {u} {v}");
+                assert_close(float.convert(y, u, v), rgb, 2, what.clone());
+                assert_close(fixed.convert(y, u, v), rgb, 2, what);
+            }
+        }
+    }
+
+    #[test]
+    fn encodes_colour_bars() {
+        for (matrix, range, yuv) in REFERENCE {
+            let spec = ColorSpec {
+                matrix,
+                range,
+                ..Default::default()
+            };
+            let encode = RgbToYuv::new(spec);
+            for (&want, &[r, g, b]) in yuv.iter().zip(&BARS) {
+                let what = format!("{matrix} {range} {r} {g} {b}");
+                assert_close(encode.convert(r, g, b), want, 1, what);
+            }
+        }
+    }
+
+    #[test]
+    fn fixed_point_is_within_one_of_float() {
+        for matrix in Matrix::ALL {
+            for range in Range::ALL {
+                let spec = ColorSpec {
+                    matrix,
+                    range,
+                    ..Default::default()
+                };
+                let (float, fixed) = (YuvToRgb::new(spec), FixedYuvToRgb::new(spec));
+                for y in (0..=255).step_by(5) {
+                    for u in (0..=255).step_by(15) {
+                        for v in (0..=255).step_by(15) {
+                            let what = format!("{matrix} {range} {y} {u} {v}");
+                            assert_close(fixed.convert(y, u, v), float.convert(y, u, v), 1, what);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,616 @@
+use std::{cell::RefCell, sync::OnceLock};
+
+use super::{ColorSpec, Range, Rgba16Image, Rows, Transfer};
+use crate::v4l2::{FrameFormat, PixelFormat};
+
+/// How the samples of a 10 bit or deeper Y'CbCr format are arranged. Every
+/// sample is a little endian 16 bit word with the data in its high bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DeepLayout {
+    /// A Y' plane and an interleaved CbCr plane at half the width and height,
+    /// as in P010
+    SemiPlanar420,
+    /// Y'0 Cb Y'1 Cr for every two pixels, as in Y210
+    Packed422,
+    /// Y' Cb Cr for every pixel, as in YUV48_12
+    Packed444,
+}
+
+/// Layout of a deep colour Y'CbCr format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DeepYuv {
+    pub layout: DeepLayout,
+    pub bits: u32,
+}
+
+#[rustfmt::skip]
+const FORMATS: [(PixelFormat, DeepLayout, u32); 7] = {
+    use DeepLayout::*;
+    [
+        (PixelFormat::P010, SemiPlanar420, 10),
+        (PixelFormat::P012, SemiPlanar420, 12),
+        (PixelFormat::P012M, SemiPlanar420, 12),
+        (PixelFormat::Y210, Packed422, 10),
+        (PixelFormat::Y212, Packed422, 12),
+        (PixelFormat::Y216, Packed422, 16),
+        (PixelFormat::YUV48_12, Packed444, 12),
+    ]
+};
+
+impl DeepYuv {
+    pub fn from_pixel_format(format: PixelFormat) -> Option<Self> {
+        FORMATS
+            .iter()
+            .find(|(f, ..)| *f == format)
+            .map(|&(_, layout, bits)| Self { layout, bits })
+    }
+
+    /// Bytes taken by a row of `width` pixels of the first plane.
+    pub fn row_bytes(&self, width: usize) -> usize {
+        match self.layout {
+            DeepLayout::SemiPlanar420 => width * 2,
+            DeepLayout::Packed422 => width.div_ceil(2) * 8,
+            DeepLayout::Packed444 => width * 6,
+        }
+    }
+}
+
+/// What to do with HDR frames on the way to an SDR display.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HdrOptions {
+    /// Tone map PQ and HLG down to SDR. Without it the signal is shown as it
+    /// is, which looks flat and washed out.
+    pub tone_map: bool,
+    /// How bright SDR white is taken to be, in nits. BT.2408 puts HDR
+    /// reference white at 203.
+    pub sdr_white: f32,
+    /// The brightest the content gets, in nits. Anything up to it is rolled
+    /// off smoothly, anything above clips.
+    pub peak: f32,
+}
+
+impl Default for HdrOptions {
+    fn default() -> Self {
+        Self {
+            tone_map: true,
+            sdr_white: 203.0,
+            peak: 1000.0,
+        }
+    }
+}
+
+// Tone mapping leaves everything below this alone and squeezes the rest of
+// the way up to the peak into what's left
+const KNEE: f32 = 0.75;
+
+// BT.2020 to BT.709 primaries, for linear light
+#[rustfmt::skip]
+const BT2020_TO_BT709: [[f32; 3]; 3] = [
+    [1.6605, -0.5876, -0.0728],
+    [-0.1246, 1.1329, -0.0083],
+    [-0.0182, -0.1006, 1.1187],
+];
+
+const CURVE_STEPS: usize = 4096;
+
+// A function on 0..=1 sampled finely enough to interpolate linearly
+struct Curve([f32; CURVE_STEPS + 1]);
+
+impl Curve {
+    fn new(f: impl Fn(f64) -> f64) -> Self {
+        Self(std::array::from_fn(|i| {
+            f(i as f64 / CURVE_STEPS as f64) as f32
+        }))
+    }
+
+    #[inline]
+    fn eval(&self, x: f32) -> f32 {
+        let x = x.clamp(0.0, 1.0) * CURVE_STEPS as f32;
+        let i = (x as usize).min(CURVE_STEPS - 1);
+        let t = x - i as f32;
+        self.0[i] + (self.0[i + 1] - self.0[i]) * t
+    }
+}
+
+// ST 2084 signal to nits
+fn pq_eotf() -> &'static Curve {
+    static CURVE: OnceLock<Curve> = OnceLock::new();
+    CURVE.get_or_init(|| {
+        let (m1, m2) = (2610.0 / 16384.0, 2523.0 / 4096.0 * 128.0);
+        let (c1, c2, c3) = (
+            3424.0 / 4096.0,
+            2413.0 / 4096.0 * 32.0,
+            2392.0 / 4096.0 * 32.0,
+        );
+        Curve::new(|e: f64| {
+            let p = e.powf(1.0 / m2);
+            10000.0 * ((p - c1).max(0.0) / (c2 - c3 * p)).powf(1.0 / m1)
+        })
+    })
+}
+
+// HLG signal to scene light, 0..=1
+fn hlg_inverse_oetf() -> &'static Curve {
+    static CURVE: OnceLock<Curve> = OnceLock::new();
+    CURVE.get_or_init(|| {
+        let (a, b, c) = (0.17883277, 0.28466892, 0.55991073);
+        Curve::new(|e: f64| {
+            if e <= 0.5 {
+                e * e / 3.0
+            } else {
+                (((e - c) / a).exp() + b) / 12.0
+            }
+        })
+    })
+}
+
+fn srgb_oetf() -> &'static Curve {
+    static CURVE: OnceLock<Curve> = OnceLock::new();
+    CURVE.get_or_init(|| {
+        Curve::new(|l: f64| {
+            if l <= 0.0031308 {
+                12.92 * l
+            } else {
+                1.055 * l.powf(1.0 / 2.4) - 0.055
+            }
+        })
+    })
+}
+
+// Y'CbCr codes to R'G'B' ready for an SDR display, 0..=1
+struct DeepToRgb {
+    y_offset: f32,
+    y_scale: f32,
+    c_offset: f32,
+    c_scale: f32,
+    r_cr: f32,
+    g_cb: f32,
+    g_cr: f32,
+    b_cb: f32,
+    transfer: Transfer,
+    sdr_white: f32,
+    // Where the peak lands past the knee, squared
+    peak_squared: f32,
+}
+
+impl DeepToRgb {
+    fn new(bits: u32, spec: ColorSpec, options: &HdrOptions) -> Self {
+        let (kr, kb) = spec.matrix.coefficients();
+        let kg = 1.0 - kr - kb;
+        let unit = (1 << (bits - 8)) as f32;
+        let (y_offset, y_scale, c_scale) = match spec.range {
+            Range::Limited => (16.0 * unit, 1.0 / (219.0 * unit), 1.0 / (224.0 * unit)),
+            Range::Full => {
+                let max = ((1u32 << bits) - 1) as f32;
+                (0.0, 1.0 / max, 1.0 / max)
+            }
+        };
+        let peak = (options.peak / options.sdr_white - KNEE) / (1.0 - KNEE);
+
+        Self {
+            y_offset,
+            y_scale,
+            c_offset: 128.0 * unit,
+            c_scale,
+            r_cr: 2.0 * (1.0 - kr),
+            g_cb: -2.0 * kb * (1.0 - kb) / kg,
+            g_cr: -2.0 * kr * (1.0 - kr) / kg,
+            b_cb: 2.0 * (1.0 - kb),
+            transfer: if options.tone_map {
+                spec.transfer
+            } else {
+                Transfer::Sdr
+            },
+            sdr_white: options.sdr_white,
+            peak_squared: peak.max(1.0).powi(2),
+        }
+    }
+
+    #[inline]
+    fn convert(&self, [y, u, v]: [u16; 3]) -> [f32; 3] {
+        let y = (y as f32 - self.y_offset) * self.y_scale;
+        let u = (u as f32 - self.c_offset) * self.c_scale;
+        let v = (v as f32 - self.c_offset) * self.c_scale;
+        let rgb = [
+            y + self.r_cr * v,
+            y + self.g_cb * u + self.g_cr * v,
+            y + self.b_cb * u,
+        ];
+
+        // Nits of each channel
+        let light = match self.transfer {
+            Transfer::Sdr => return rgb.map(|c| c.clamp(0.0, 1.0)),
+            Transfer::Pq => rgb.map(|c| pq_eotf().eval(c)),
+            Transfer::Hlg => {
+                // The BT.2100 OOTF for a 1000 nit display
+                let [r, g, b] = rgb.map(|c| hlg_inverse_oetf().eval(c));
+                let luma = 0.2627 * r + 0.6780 * g + 0.0593 * b;
+                let gain = 1000.0 * luma.powf(0.2);
+                [r * gain, g * gain, b * gain]
+            }
+        };
+        let light = light.map(|c| c / self.sdr_white);
+        let rgb = BT2020_TO_BT709.map(|row| {
+            let c = row[0] * light[0] + row[1] * light[1] + row[2] * light[2];
+            c.max(0.0)
+        });
+
+        // Roll off the brightest channel past the knee, scaling the others
+        // along so the hue stays
+        let max = rgb[0].max(rgb[1]).max(rgb[2]);
+        let scale = if max > KNEE {
+            let x = (max - KNEE) / (1.0 - KNEE);
+            let rolled = x * (1.0 + x / self.peak_squared) / (1.0 + x);
+            (KNEE + (1.0 - KNEE) * rolled.min(1.0)) / max
+        } else {
+            1.0
+        };
+        rgb.map(|c| srgb_oetf().eval(c * scale))
+    }
+}
+
+// The samples and pixels of a row
+type Row = (Vec<[u16; 3]>, Vec<[f32; 3]>);
+
+thread_local! {
+    // Kept by each converting thread from band to band and frame to frame
+    static ROW: RefCell<Row> = RefCell::default();
+}
+
+pub(super) struct DeepRows<'a> {
+    deep: DeepYuv,
+    to_rgb: DeepToRgb,
+    width: usize,
+    y: &'a [u8],
+    y_stride: usize,
+    // The CbCr plane of the semi-planar formats
+    c: &'a [u8],
+    c_stride: usize,
+}
+
+fn word(src: &[u8], i: usize) -> u16 {
+    u16::from_le_bytes([src[i * 2], src[i * 2 + 1]])
+}
+
+impl<'a> DeepRows<'a> {
+    pub(super) fn new(
+        deep: DeepYuv,
+        planes: &[&'a [u8]],
+        format: &FrameFormat,
+        spec: ColorSpec,
+        options: &HdrOptions,
+    ) -> Self {
+        let or = |stride: usize, default: usize| if stride == 0 { default } else { stride };
+        let plane = |i: usize| planes.get(i).copied().unwrap_or_default();
+        let y_stride = or(format.bytes_per_line, deep.row_bytes(format.width));
+        let chroma_bytes = format.width.div_ceil(2) * 4;
+
+        // As with NV12, either a plane of its own or right after the luma
+        let (c, c_stride) = if format.num_planes > 1 {
+            (plane(1), or(format.planes[1].bytes_per_line, chroma_bytes))
+        } else {
+            let after_y = plane(0).get(y_stride * format.height..);
+            (
+                after_y.unwrap_or_default(),
+                or(format.bytes_per_line, chroma_bytes),
+            )
+        };
+
+        Self {
+            deep,
+            to_rgb: DeepToRgb::new(deep.bits, spec, options),
+            width: format.width,
+            y: plane(0),
+            y_stride,
+            c,
+            c_stride,
+        }
+    }
+
+    // Y', Cb and Cr of every pixel of a row, or false if the frame is too
+    // short to have it
+    fn read_row(&self, row: usize, out: &mut [[u16; 3]]) -> bool {
+        let shift = 16 - self.deep.bits;
+        let width = self.width;
+        let y = self.y.get(row * self.y_stride..).unwrap_or_default();
+        let Some(y) = y.get(..self.deep.row_bytes(width)) else {
+            return false;
+        };
+
+        match self.deep.layout {
+            DeepLayout::SemiPlanar420 => {
+                let c = self.c.get(row / 2 * self.c_stride..).unwrap_or_default();
+                let Some(c) = c.get(..width.div_ceil(2) * 4) else {
+                    return false;
+                };
+                for (x, out) in out.iter_mut().enumerate() {
+                    let i = x / 2 * 2;
+                    *out = [word(y, x), word(c, i), word(c, i + 1)];
+                }
+            }
+            DeepLayout::Packed422 => {
+                for (x, out) in out.iter_mut().enumerate() {
+                    let i = x / 2 * 4;
+                    *out = [word(y, i + x % 2 * 2), word(y, i + 1), word(y, i + 3)];
+                }
+            }
+            DeepLayout::Packed444 => {
+                for (x, out) in out.iter_mut().enumerate() {
+                    *out = [word(y, x * 3), word(y, x * 3 + 1), word(y, x * 3 + 2)];
+                }
+            }
+        }
+        for sample in out.iter_mut() {
+            *sample = sample.map(|s| s >> shift);
+        }
+        true
+    }
+
+    // The display ready pixels of each row in turn, starting at `first_row`,
+    // until `rows` or the end of a short frame
+    fn for_each_row(&self, first_row: usize, rows: usize, mut f: impl FnMut(usize, &[[f32; 3]])) {
+        ROW.with_borrow_mut(|(samples, rgb)| {
+            samples.resize(self.width, [0; 3]);
+            rgb.resize(self.width, [0.0; 3]);
+            for row in 0..rows {
+                if !self.read_row(first_row + row, samples) {
+                    break;
+                }
+                for (rgb, sample) in rgb.iter_mut().zip(samples.iter()) {
+                    *rgb = self.to_rgb.convert(*sample);
+                }
+                f(row, rgb);
+            }
+        });
+    }
+
+    pub(super) fn convert_16(&self, dst: &mut Rgba16Image) {
+        let rows = dst.height();
+        let mut dst_rows = dst.rows_mut();
+        self.for_each_row(0, rows, |_, rgb| {
+            let Some(dst_row) = dst_rows.next() else {
+                return;
+            };
+            for ([r, g, b], rgba) in rgb.iter().zip(dst_row.chunks_exact_mut(4)) {
+                let to_16 = |c: f32| (c * 65535.0 + 0.5) as u16;
+                rgba.copy_from_slice(&[to_16(*r), to_16(*g), to_16(*b), u16::MAX]);
+            }
+        });
+    }
+}
+
+impl Rows for DeepRows<'_> {
+    fn convert(&self, first_row: usize, dst: &mut [u8]) {
+        let row_bytes = self.width * 4;
+        self.for_each_row(first_row, dst.len() / row_bytes, |row, rgb| {
+            let dst_row = &mut dst[row * row_bytes..][..row_bytes];
+            for ([r, g, b], rgba) in rgb.iter().zip(dst_row.chunks_exact_mut(4)) {
+                let to_8 = |c: f32| (c * 255.0 + 0.5) as u8;
+                rgba.copy_from_slice(&[to_8(*r), to_8(*g), to_8(*b), 255]);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::convert::{deep_to_rgba, deep_to_rgba16, HdrOptions, Matrix, RgbaImage};
+
+    // A row of Y210 going up by a 10 bit step at a time from limited range
+    // black, with neutral chroma
+    fn y210_ramp(width: usize) -> (Vec<u8>, FrameFormat) {
+        let words: Vec<u16> = (0..width / 2)
+            .flat_map(|pair| [64 + pair as u16 * 2, 512, 65 + pair as u16 * 2, 512])
+            .map(|sample| sample << 6)
+            .collect();
+        let format = FrameFormat {
+            pixel_format: PixelFormat::Y210,
+            width,
+            height: 1,
+            ..Default::default()
+        };
+        (words.iter().flat_map(|w| w.to_le_bytes()).collect(), format)
+    }
+
+    // The Y', Cb and Cr codes of the first row of a frame holding `words`,
+    // each with its code in the high bits and junk in the low ones
+    fn codes(
+        pixel_format: PixelFormat,
+        width: usize,
+        height: usize,
+        words: &[u16],
+    ) -> Vec<[u16; 3]> {
+        let deep = DeepYuv::from_pixel_format(pixel_format).unwrap();
+        let junk = (1 << (16 - deep.bits)) - 1;
+        let data: Vec<u8> = words
+            .iter()
+            .flat_map(|w| (w << (16 - deep.bits) | junk & 0x5555).to_le_bytes())
+            .collect();
+        let format = FrameFormat {
+            pixel_format,
+            width,
+            height,
+            ..Default::default()
+        };
+        let rows = DeepRows::new(
+            deep,
+            &[&data],
+            &format,
+            ColorSpec::default(),
+            &HdrOptions::default(),
+        );
+        let mut out = vec![[0; 3]; width];
+        assert!(rows.read_row(0, &mut out));
+        out
+    }
+
+    #[test]
+    fn unpacks_every_layout() {
+        // Two rows of Y' then a row of Cb Cr
+        let semi_planar = [0x2ab, 0x155, 0x0ff, 0x100, 0x0c0, 0x3f0];
+        assert_eq!(
+            codes(PixelFormat::P010, 2, 2, &semi_planar),
+            [[0x2ab, 0x0c0, 0x3f0], [0x155, 0x0c0, 0x3f0]]
+        );
+        let semi_planar = [0xabc, 0x123, 0x0ff, 0x100, 0x456, 0xf0f];
+        assert_eq!(
+            codes(PixelFormat::P012, 2, 2, &semi_planar),
+            [[0xabc, 0x456, 0xf0f], [0x123, 0x456, 0xf0f]]
+        );
+        // Y'0 Cb Y'1 Cr
+        assert_eq!(
+            codes(PixelFormat::Y210, 2, 1, &[0x2ab, 0x0c0, 0x155, 0x3f0]),
+            [[0x2ab, 0x0c0, 0x3f0], [0x155, 0x0c0, 0x3f0]]
+        );
+        assert_eq!(
+            codes(PixelFormat::Y212, 2, 1, &[0xabc, 0x456, 0x123, 0xf0f]),
+            [[0xabc, 0x456, 0xf0f], [0x123, 0x456, 0xf0f]]
+        );
+        assert_eq!(
+            codes(PixelFormat::Y216, 2, 1, &[0xabcd, 0x4567, 0x1234, 0xf0f0]),
+            [[0xabcd, 0x4567, 0xf0f0], [0x1234, 0x4567, 0xf0f0]]
+        );
+        // Y' Cb Cr
+        assert_eq!(
+            codes(
+                PixelFormat::YUV48_12,
+                2,
+                1,
+                &[0xabc, 0x456, 0xf0f, 0x123, 0x789, 0x0f0]
+            ),
+            [[0xabc, 0x456, 0xf0f], [0x123, 0x789, 0x0f0]]
+        );
+    }
+
+    fn assert_close(got: [f32; 3], want: [f32; 3]) {
+        for (g, w) in got.iter().zip(want) {
+            assert!((g - w).abs() < 0.01, "{got:?}, not {want:?}");
+        }
+    }
+
+    #[test]
+    fn bt2020_primaries() {
+        let spec = ColorSpec {
+            matrix: Matrix::Bt2020,
+            ..Default::default()
+        };
+        let to_rgb = DeepToRgb::new(10, spec, &HdrOptions::default());
+        // Encoded by hand with BT.2020's Kr and Kb, in 10 bit limited range
+        let (kr, kb) = (0.2627, 0.0593);
+        for rgb in [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0], [0.5; 3]] {
+            let [r, g, b] = rgb;
+            let y = kr * r + (1.0 - kr - kb) * g + kb * b;
+            let cb = (b - y) / (2.0 * (1.0 - kb));
+            let cr = (r - y) / (2.0 * (1.0 - kr));
+            let code = |offset: f32, scale: f32, v: f32| (offset + scale * v).round() as u16;
+            let yuv = [
+                code(64.0, 876.0, y),
+                code(512.0, 896.0, cb),
+                code(512.0, 896.0, cr),
+            ];
+            assert_close(to_rgb.convert(yuv), rgb);
+        }
+    }
+
+    #[test]
+    fn tone_maps_to_sdr() {
+        let options = HdrOptions::default();
+        let spec = |transfer| ColorSpec {
+            matrix: Matrix::Bt2020,
+            transfer,
+            ..Default::default()
+        };
+        // 100 nit grey in PQ is half of the 203 nit SDR white, which sRGB
+        // encodes as 0.730
+        let pq = DeepToRgb::new(10, spec(Transfer::Pq), &options);
+        assert_close(pq.convert([509, 512, 512]), [0.730; 3]);
+        // Half the HLG signal is a 12th of the scene light, 50.7 nits on a
+        // 1000 nit display, so 0.537
+        let hlg = DeepToRgb::new(10, spec(Transfer::Hlg), &options);
+        assert_close(hlg.convert([502, 512, 512]), [0.537; 3]);
+        // Without tone mapping the signal shows as it is
+        let options = HdrOptions {
+            tone_map: false,
+            ..options
+        };
+        let flat = DeepToRgb::new(10, spec(Transfer::Hlg), &options);
+        assert_close(flat.convert([502, 512, 512]), [0.5; 3]);
+    }
+
+    #[test]
+    fn sixteen_bit_output_keeps_the_precision() {
+        let (data, format) = y210_ramp(64);
+        let spec = ColorSpec {
+            matrix: Matrix::Bt2020,
+            ..Default::default()
+        };
+        let options = HdrOptions::default();
+
+        let mut rgba = RgbaImage::new();
+        deep_to_rgba(&[&data], &format, spec, &options, &mut rgba).unwrap();
+        let mut rgba16 = Rgba16Image::new();
+        deep_to_rgba16(&[&data], &format, spec, &options, &mut rgba16).unwrap();
+        assert_eq!((rgba16.width(), rgba16.height()), (64, 1));
+
+        let pixels16: Vec<&[u16]> = rgba16.data().chunks_exact(4).collect();
+        for (pixel, pixel16) in rgba.data().chunks_exact(4).zip(&pixels16) {
+            for channel in 0..3 {
+                let narrowed = ((pixel16[channel] as u32 + 128) >> 8).min(255) as u8;
+                assert!(
+                    narrowed.abs_diff(pixel[channel]) <= 1,
+                    "{pixel:?} {pixel16:?}"
+                );
+            }
+            assert_eq!(pixel16[3], u16::MAX);
+        }
+        // Every 10 bit step shows, where 8 bits would have to merge some
+        for pair in pixels16.windows(2) {
+            assert!(pair[1][1] > pair[0][1], "{:?} then {:?}", pair[0], pair[1]);
+        }
+    }
+
+    #[test]
+    fn sixteen_bit_output_keeps_the_code() {
+        // Full range grey 2053 of 4095 is 32856 of 65535, where 8 bits would
+        // have had to round it to 128 * 257 = 32896
+        let format = FrameFormat {
+            pixel_format: PixelFormat::YUV48_12,
+            width: 1,
+            height: 1,
+            ..Default::default()
+        };
+        let spec = ColorSpec {
+            range: Range::Full,
+            ..Default::default()
+        };
+        let data: Vec<u8> = [2053u16 << 4, 2048 << 4, 2048 << 4]
+            .iter()
+            .flat_map(|w| w.to_le_bytes())
+            .collect();
+        let mut rgba16 = Rgba16Image::new();
+        deep_to_rgba16(&[&data], &format, spec, &HdrOptions::default(), &mut rgba16).unwrap();
+        let want = (2053.0 / 4095.0 * 65535.0_f32).round() as u16;
+        for &channel in &rgba16.data()[..3] {
+            assert!(channel.abs_diff(want) <= 1, "{channel}, not {want}");
+        }
+    }
+
+    #[test]
+    fn sixteen_bit_output_only_takes_deep_formats() {
+        let format = FrameFormat {
+            pixel_format: PixelFormat::YUYV,
+            width: 2,
+            height: 1,
+            ..Default::default()
+        };
+        let result = deep_to_rgba16(
+            &[&[0; 4]],
+            &format,
+            ColorSpec::default(),
+            &HdrOptions::default(),
+            &mut Rgba16Image::new(),
+        );
+        assert!(result.is_err());
+    }
+}
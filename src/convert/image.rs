@@ -0,0 +1,130 @@
+/// Tightly packed RGBA8 pixels. Meant to be kept around and converted into
+/// frame after frame, it only reallocates when the frame size grows.
+#[derive(Debug, Clone, Default)]
+pub struct RgbaImage {
+    width: usize,
+    height: usize,
+    data: Vec<u8>,
+}
+
+impl RgbaImage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+
+    /// Keeps the old pixels where they still fit, new ones are transparent black.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        self.width = width;
+        self.height = height;
+        self.data.resize(width * height * 4, 0);
+    }
+
+    pub fn rows_mut(&mut self) -> std::slice::ChunksExactMut<'_, u8> {
+        self.data.chunks_exact_mut((self.width * 4).max(1))
+    }
+}
+
+/// Tightly packed 16 bit samples of a greyscale or depth frame, as they came
+/// from the camera, for measuring rather than showing.
+#[derive(Debug, Clone, Default)]
+pub struct Mono16Image {
+    width: usize,
+    height: usize,
+    data: Vec<u16>,
+}
+
+impl Mono16Image {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn data(&self) -> &[u16] {
+        &self.data
+    }
+
+    /// The sample at `x`, `y`, if that's inside the image.
+    pub fn get(&self, x: usize, y: usize) -> Option<u16> {
+        if x < self.width {
+            self.data.get(y * self.width + x).copied()
+        } else {
+            None
+        }
+    }
+
+    /// Keeps the old samples where they still fit, new ones are 0.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        self.width = width;
+        self.height = height;
+        self.data.resize(width * height, 0);
+    }
+
+    pub fn rows_mut(&mut self) -> std::slice::ChunksExactMut<'_, u16> {
+        self.data.chunks_exact_mut(self.width.max(1))
+    }
+}
+
+/// Tightly packed RGBA pixels of 16 bits per channel, for keeping the
+/// precision of 10 bit and deeper frames when recording.
+#[derive(Debug, Clone, Default)]
+pub struct Rgba16Image {
+    width: usize,
+    height: usize,
+    data: Vec<u16>,
+}
+
+impl Rgba16Image {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn data(&self) -> &[u16] {
+        &self.data
+    }
+
+    pub fn data_mut(&mut self) -> &mut [u16] {
+        &mut self.data
+    }
+
+    /// Keeps the old pixels where they still fit, new ones are transparent black.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        self.width = width;
+        self.height = height;
+        self.data.resize(width * height * 4, 0);
+    }
+
+    pub fn rows_mut(&mut self) -> std::slice::ChunksExactMut<'_, u16> {
+        self.data.chunks_exact_mut((self.width * 4).max(1))
+    }
+}
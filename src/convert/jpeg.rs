@@ -0,0 +1,290 @@
+use std::borrow::Cow;
+
+use zune_jpeg::zune_core::{colorspace::ColorSpace, options::DecoderOptions};
+
+use super::{ConvertError, RgbaImage};
+use crate::v4l2::{FrameFormat, PixelFormat};
+
+const SOI: u8 = 0xd8;
+const SOS: u8 = 0xda;
+const DHT: u8 = 0xc4;
+const EOI: u8 = 0xd9;
+
+/// The Huffman tables from section K.3 of the JPEG standard as one DHT
+/// segment. UVC cameras leave them out of MJPEG frames and expect the decoder
+/// to use these, as the OpenDML AVI spec says.
+#[rustfmt::skip]
+const DEFAULT_DHT: [u8; 420] = [
+    0xff, 0xc4, 0x01, 0xa2,
+    // Luma DC
+    0x00,
+    0x00, 0x01, 0x05, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b,
+    // Chroma DC
+    0x01,
+    0x00, 0x03, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b,
+    // Luma AC
+    0x10,
+    0x00, 0x02, 0x01, 0x03, 0x03, 0x02, 0x04, 0x03, 0x05, 0x05, 0x04, 0x04, 0x00, 0x00, 0x01, 0x7d,
+    0x01, 0x02, 0x03, 0x00, 0x04, 0x11, 0x05, 0x12, 0x21, 0x31, 0x41, 0x06, 0x13, 0x51, 0x61, 0x07,
+    0x22, 0x71, 0x14, 0x32, 0x81, 0x91, 0xa1, 0x08, 0x23, 0x42, 0xb1, 0xc1, 0x15, 0x52, 0xd1, 0xf0,
+    0x24, 0x33, 0x62, 0x72, 0x82, 0x09, 0x0a, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x25, 0x26, 0x27, 0x28,
+    0x29, 0x2a, 0x34, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3a, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48, 0x49,
+    0x4a, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5a, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68, 0x69,
+    0x6a, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7a, 0x83, 0x84, 0x85, 0x86, 0x87, 0x88, 0x89,
+    0x8a, 0x92, 0x93, 0x94, 0x95, 0x96, 0x97, 0x98, 0x99, 0x9a, 0xa2, 0xa3, 0xa4, 0xa5, 0xa6, 0xa7,
+    0xa8, 0xa9, 0xaa, 0xb2, 0xb3, 0xb4, 0xb5, 0xb6, 0xb7, 0xb8, 0xb9, 0xba, 0xc2, 0xc3, 0xc4, 0xc5,
+    0xc6, 0xc7, 0xc8, 0xc9, 0xca, 0xd2, 0xd3, 0xd4, 0xd5, 0xd6, 0xd7, 0xd8, 0xd9, 0xda, 0xe1, 0xe2,
+    0xe3, 0xe4, 0xe5, 0xe6, 0xe7, 0xe8, 0xe9, 0xea, 0xf1, 0xf2, 0xf3, 0xf4, 0xf5, 0xf6, 0xf7, 0xf8,
+    0xf9, 0xfa,
+    // Chroma AC
+    0x11,
+    0x00, 0x02, 0x01, 0x02, 0x04, 0x04, 0x03, 0x04, 0x07, 0x05, 0x04, 0x04, 0x00, 0x01, 0x02, 0x77,
+    0x00, 0x01, 0x02, 0x03, 0x11, 0x04, 0x05, 0x21, 0x31, 0x06, 0x12, 0x41, 0x51, 0x07, 0x61, 0x71,
+    0x13, 0x22, 0x32, 0x81, 0x08, 0x14, 0x42, 0x91, 0xa1, 0xb1, 0xc1, 0x09, 0x23, 0x33, 0x52, 0xf0,
+    0x15, 0x62, 0x72, 0xd1, 0x0a, 0x16, 0x24, 0x34, 0xe1, 0x25, 0xf1, 0x17, 0x18, 0x19, 0x1a, 0x26,
+    0x27, 0x28, 0x29, 0x2a, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3a, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48,
+    0x49, 0x4a, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5a, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68,
+    0x69, 0x6a, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7a, 0x82, 0x83, 0x84, 0x85, 0x86, 0x87,
+    0x88, 0x89, 0x8a, 0x92, 0x93, 0x94, 0x95, 0x96, 0x97, 0x98, 0x99, 0x9a, 0xa2, 0xa3, 0xa4, 0xa5,
+    0xa6, 0xa7, 0xa8, 0xa9, 0xaa, 0xb2, 0xb3, 0xb4, 0xb5, 0xb6, 0xb7, 0xb8, 0xb9, 0xba, 0xc2, 0xc3,
+    0xc4, 0xc5, 0xc6, 0xc7, 0xc8, 0xc9, 0xca, 0xd2, 0xd3, 0xd4, 0xd5, 0xd6, 0xd7, 0xd8, 0xd9, 0xda,
+    0xe2, 0xe3, 0xe4, 0xe5, 0xe6, 0xe7, 0xe8, 0xe9, 0xea, 0xf2, 0xf3, 0xf4, 0xf5, 0xf6, 0xf7, 0xf8,
+    0xf9, 0xfa,
+];
+
+pub(crate) fn is_jpeg(format: PixelFormat) -> bool {
+    matches!(format, PixelFormat::MJPEG | PixelFormat::JPEG)
+}
+
+/// Where the default Huffman tables have to go, if `src` has none of its own:
+/// right before the first start of scan.
+fn missing_dht_at(src: &[u8]) -> Option<usize> {
+    if src.get(..2) != Some(&[0xff, SOI]) {
+        return None;
+    }
+
+    let mut pos = 2;
+    loop {
+        if *src.get(pos)? != 0xff {
+            // Garbage between segments, the decoder will complain about it
+            return None;
+        }
+        // Markers may be padded with any number of 0xff
+        let mut marker_pos = pos + 1;
+        while *src.get(marker_pos)? == 0xff {
+            marker_pos += 1;
+        }
+
+        match src[marker_pos] {
+            SOS => return Some(pos),
+            DHT => return None,
+            // Restart markers and TEM have no length
+            0xd0..=0xd7 | 0x01 => pos = marker_pos + 1,
+            _ => {
+                let len = src.get(marker_pos + 1..marker_pos + 3)?;
+                pos = marker_pos + 1 + u16::from_be_bytes([len[0], len[1]]) as usize;
+            }
+        }
+    }
+}
+
+// A frame cut short by the USB transfer has no end of image marker. 0xff is
+// always escaped in the entropy coded data, so this can't be fooled by it.
+fn has_eoi(src: &[u8]) -> bool {
+    // Searching from the back, as some cameras pad frames after the marker
+    src.windows(2).rev().any(|pair| pair == [0xff, EOI])
+}
+
+/// `src` with the default Huffman tables inserted if it needs them.
+pub fn with_default_huffman_tables(src: &[u8]) -> Cow<'_, [u8]> {
+    match missing_dht_at(src) {
+        Some(pos) => {
+            let mut jpeg = Vec::with_capacity(src.len() + DEFAULT_DHT.len());
+            jpeg.extend_from_slice(&src[..pos]);
+            jpeg.extend_from_slice(&DEFAULT_DHT);
+            jpeg.extend_from_slice(&src[pos..]);
+            Cow::Owned(jpeg)
+        }
+        None => Cow::Borrowed(src),
+    }
+}
+
+/// Decode a (M)JPEG frame into `dst`, which takes the size of the image
+/// rather than of `format`.
+pub(crate) fn decode(
+    src: &[u8],
+    format: &FrameFormat,
+    dst: &mut RgbaImage,
+) -> Result<(), ConvertError> {
+    // The decoder would happily fill the rest of a truncated frame with grey
+    if !has_eoi(src) {
+        return Err(ConvertError::CorruptFrame("truncated frame".into()));
+    }
+    let jpeg = with_default_huffman_tables(src);
+
+    let mut options = DecoderOptions::default().jpeg_set_out_colorspace(ColorSpace::RGBA);
+    // A corrupted header shouldn't make us allocate gigabytes
+    if format.width > 0 && format.height > 0 {
+        options = options
+            .set_max_width(format.width)
+            .set_max_height(format.height);
+    }
+
+    let mut decoder = zune_jpeg::JpegDecoder::new_with_options(jpeg.as_ref(), options);
+    let corrupt = |e| ConvertError::CorruptFrame(format!("{e:?}"));
+    decoder.decode_headers().map_err(corrupt)?;
+    let (width, height) = decoder.dimensions().unwrap_or_default();
+
+    dst.resize(width, height);
+    match decoder.get_output_colorspace() {
+        Some(ColorSpace::RGBA) => decoder.decode_into(dst.data_mut()).map_err(corrupt),
+        // Greyscale JPEGs only decode to greyscale. Decode into the front of
+        // `dst` and spread it out from the back, so nothing gets overwritten
+        // before it's read.
+        Some(ColorSpace::Luma) => {
+            let data = dst.data_mut();
+            decoder
+                .decode_into(&mut data[..width * height])
+                .map_err(corrupt)?;
+            for i in (0..width * height).rev() {
+                let y = data[i];
+                data[i * 4..i * 4 + 4].copy_from_slice(&[y, y, y, 255]);
+            }
+            Ok(())
+        }
+        other => Err(ConvertError::CorruptFrame(format!(
+            "can't decode to RGBA from {other:?}"
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 32x16 colour bars as encoded by a regular encoder, and the same with
+    // the Huffman tables left out the way UVC cameras send it
+    const BARS: &[u8] = include_bytes!("testdata/bars.jpg");
+    const BARS_NO_DHT: &[u8] = include_bytes!("testdata/bars_no_dht.jpg");
+    // A 16x16 greyscale ramp
+    const RAMP_GREY: &[u8] = include_bytes!("testdata/ramp_grey.jpg");
+    // Bars cut off two thirds of the way through
+    const TRUNCATED: &[u8] = include_bytes!("testdata/truncated.jpg");
+    // Bars claiming to have five components
+    const CORRUPT_SOF: &[u8] = include_bytes!("testdata/corrupt_sof.jpg");
+    // Noise between a start and an end of image
+    const GARBAGE: &[u8] = include_bytes!("testdata/garbage.jpg");
+
+    const BAR_COLORS: [[u8; 3]; 8] = [
+        [255, 255, 255],
+        [255, 255, 0],
+        [0, 255, 255],
+        [0, 255, 0],
+        [255, 0, 255],
+        [255, 0, 0],
+        [0, 0, 255],
+        [0, 0, 0],
+    ];
+
+    fn mjpeg(width: usize, height: usize) -> FrameFormat {
+        FrameFormat {
+            pixel_format: PixelFormat::MJPEG,
+            width,
+            height,
+            ..Default::default()
+        }
+    }
+
+    fn assert_bars(image: &RgbaImage) {
+        assert_eq!((image.width(), image.height()), (32, 16));
+        for y in [0, 8, 15] {
+            for (bar, color) in BAR_COLORS.iter().enumerate() {
+                // The middle of the bar, away from chroma bleeding over the edges
+                let x = bar * 4 + 2;
+                let pixel = &image.data()[(y * 32 + x) * 4..][..4];
+                for channel in 0..3 {
+                    assert!(
+                        pixel[channel].abs_diff(color[channel]) <= 24,
+                        "bar {bar} row {y}: {pixel:?}"
+                    );
+                }
+                assert_eq!(pixel[3], 255);
+            }
+        }
+    }
+
+    #[test]
+    fn decodes_bars() {
+        let mut dst = RgbaImage::new();
+        decode(BARS, &mjpeg(32, 16), &mut dst).unwrap();
+        assert_bars(&dst);
+    }
+
+    #[test]
+    fn fills_in_missing_huffman_tables() {
+        assert!(missing_dht_at(BARS).is_none());
+        assert!(missing_dht_at(BARS_NO_DHT).is_some());
+
+        let (mut with, mut without) = (RgbaImage::new(), RgbaImage::new());
+        decode(BARS, &mjpeg(32, 16), &mut with).unwrap();
+        decode(BARS_NO_DHT, &mjpeg(32, 16), &mut without).unwrap();
+        assert_bars(&without);
+        assert!(with.data() == without.data());
+    }
+
+    #[test]
+    fn decodes_greyscale() {
+        let mut dst = RgbaImage::new();
+        decode(RAMP_GREY, &mjpeg(16, 16), &mut dst).unwrap();
+        assert_eq!((dst.width(), dst.height()), (16, 16));
+        for (x, pixel) in dst.data()[..16 * 4].chunks_exact(4).enumerate() {
+            assert!(pixel[0].abs_diff((x * 16) as u8) <= 8, "{x}: {pixel:?}");
+            assert_eq!((pixel[0], pixel[0], 255), (pixel[1], pixel[2], pixel[3]));
+        }
+    }
+
+    #[test]
+    fn rejects_broken_frames() {
+        let format = mjpeg(32, 16);
+        for (name, src) in [
+            ("empty", &[][..]),
+            ("truncated", TRUNCATED),
+            ("corrupt SOF", CORRUPT_SOF),
+            ("garbage", GARBAGE),
+        ] {
+            let result = decode(src, &format, &mut RgbaImage::new());
+            assert!(
+                matches!(result, Err(ConvertError::CorruptFrame(_))),
+                "{name}: {result:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_frames_bigger_than_the_format() {
+        let result = decode(BARS, &mjpeg(16, 16), &mut RgbaImage::new());
+        assert!(matches!(result, Err(ConvertError::CorruptFrame(_))));
+    }
+
+    #[test]
+    fn never_panics_on_damaged_frames() {
+        let format = mjpeg(32, 16);
+        let mut dst = RgbaImage::new();
+        for src in [BARS, BARS_NO_DHT, RAMP_GREY] {
+            // Every length it could have been cut off at, with the end of
+            // image marker put back so it gets past the truncation check
+            for len in 0..src.len() {
+                let cut = [&src[..len], &[0xff, EOI]].concat();
+                let _ = decode(&cut, &format, &mut dst);
+            }
+            // And every byte flipped in turn
+            for i in 0..src.len() {
+                let mut flipped = src.to_vec();
+                flipped[i] ^= 0xa5;
+                let _ = decode(&flipped, &format, &mut dst);
+            }
+        }
+    }
+}
@@ -0,0 +1,544 @@
+use std::{cell::RefCell, sync::OnceLock};
+
+use super::{
+    bayer::{packed_row_bytes, unpack_samples},
+    BayerPacking, Mono16Image, Rows,
+};
+use crate::v4l2::{FrameFormat, PixelFormat};
+
+/// How the samples of a greyscale format are stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MonoPacking {
+    /// A byte per sample
+    Bytes,
+    /// A little endian 16 bit word per sample, in the low bits
+    Words,
+    /// A big endian 16 bit word per sample
+    BigEndianWords,
+    /// MIPI CSI-2 packing, the same as the packed Bayer formats
+    Mipi,
+    /// Samples packed one after the other, most significant bit first
+    BitPacked,
+}
+
+/// Layout of a greyscale or depth format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Mono {
+    pub bits: u32,
+    pub packing: MonoPacking,
+    /// The samples are distances rather than brightness, 0 meaning no data
+    pub depth: bool,
+}
+
+#[rustfmt::skip]
+const FORMATS: [(PixelFormat, u32, MonoPacking, bool); 9] = {
+    use MonoPacking::*;
+    [
+        (PixelFormat::GREY, 8, Bytes, false),
+        (PixelFormat::Y10, 10, Words, false),
+        (PixelFormat::Y12, 12, Words, false),
+        (PixelFormat::Y14, 14, Words, false),
+        (PixelFormat::Y16, 16, Words, false),
+        (PixelFormat::Y16_BE, 16, BigEndianWords, false),
+        (PixelFormat::Y10P, 10, Mipi, false),
+        (PixelFormat::Y10BPACK, 10, BitPacked, false),
+        (PixelFormat::Z16, 16, Words, true),
+    ]
+};
+
+impl Mono {
+    pub fn from_pixel_format(format: PixelFormat) -> Option<Self> {
+        FORMATS
+            .iter()
+            .find(|(f, ..)| *f == format)
+            .map(|&(_, bits, packing, depth)| Self {
+                bits,
+                packing,
+                depth,
+            })
+    }
+
+    /// The largest sample value.
+    pub fn white_level(&self) -> u16 {
+        ((1u32 << self.bits) - 1) as u16
+    }
+
+    /// Bytes taken by a row of `width` samples, without any padding.
+    pub fn row_bytes(&self, width: usize) -> usize {
+        match self.packing {
+            MonoPacking::Bytes => width,
+            MonoPacking::Words | MonoPacking::BigEndianWords => width * 2,
+            MonoPacking::Mipi => packed_row_bytes(self.bits, BayerPacking::Mipi, width),
+            MonoPacking::BitPacked => (width * self.bits as usize).div_ceil(8),
+        }
+    }
+
+    /// Unpack a row of `dst.len()` samples from `src`, which has to hold
+    /// `row_bytes(dst.len())` bytes.
+    pub fn unpack_row(&self, src: &[u8], dst: &mut [u16]) {
+        match self.packing {
+            MonoPacking::Bytes => unpack_samples(self.bits, BayerPacking::Bytes, src, dst),
+            MonoPacking::Words => unpack_samples(self.bits, BayerPacking::Words, src, dst),
+            MonoPacking::Mipi => unpack_samples(self.bits, BayerPacking::Mipi, src, dst),
+            MonoPacking::BigEndianWords => {
+                for (d, s) in dst.iter_mut().zip(src.chunks_exact(2)) {
+                    *d = u16::from_be_bytes([s[0], s[1]]);
+                }
+            }
+            MonoPacking::BitPacked => {
+                let mut bits = 0u32;
+                let mut pending = 0;
+                let mut src = src.iter();
+                for d in dst {
+                    while pending < self.bits {
+                        bits = bits << 8 | *src.next().unwrap_or(&0) as u32;
+                        pending += 8;
+                    }
+                    pending -= self.bits;
+                    *d = ((bits >> pending) & self.white_level() as u32) as u16;
+                }
+            }
+        }
+    }
+
+    fn stride(&self, format: &FrameFormat) -> usize {
+        if format.bytes_per_line == 0 {
+            self.row_bytes(format.width)
+        } else {
+            format.bytes_per_line
+        }
+    }
+
+    /// Unpack a whole frame into `dst`, resizing it to fit. Rows past the end
+    /// of a short frame are left as they were.
+    pub fn unpack_frame(&self, src: &[u8], format: &FrameFormat, dst: &mut Mono16Image) {
+        dst.resize(format.width, format.height);
+        let (stride, row_bytes) = (self.stride(format), self.row_bytes(format.width));
+        for (row, dst_row) in dst.rows_mut().enumerate() {
+            match src.get(row * stride..).and_then(|src| src.get(..row_bytes)) {
+                Some(src) => self.unpack_row(src, dst_row),
+                None => break,
+            }
+        }
+    }
+}
+
+/// How sample values are spread over the colormap.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum ToneMap {
+    /// The whole range of the format, from 0 to its white level
+    #[default]
+    Full,
+    /// The `width` values centred on `level`. Anything outside gets clamped to
+    /// the ends of the colormap.
+    Window { level: u16, width: u16 },
+    /// Stretched over each frame's own samples, between the `low` and `high`
+    /// fractions of them, so a few hot or dead pixels don't flatten the rest
+    Auto { low: f32, high: f32 },
+}
+
+impl ToneMap {
+    /// Auto stretch ignoring the darkest and brightest 1%.
+    pub const AUTO: Self = ToneMap::Auto {
+        low: 0.01,
+        high: 0.99,
+    };
+}
+
+/// What colour each value gets once tone mapped.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Colormap {
+    #[default]
+    Grey,
+    Jet,
+    Turbo,
+    Inferno,
+}
+
+impl Colormap {
+    pub const ALL: [Colormap; 4] = [
+        Colormap::Grey,
+        Colormap::Jet,
+        Colormap::Turbo,
+        Colormap::Inferno,
+    ];
+
+    /// The colour of `t`, from 0 to 1.
+    pub fn color(self, t: f32) -> [u8; 3] {
+        let t = t.clamp(0.0, 1.0);
+        let rgb = match self {
+            Colormap::Grey => [t; 3],
+            Colormap::Jet => [3.0, 2.0, 1.0].map(|c| (1.5 - (4.0 * t - c).abs()).clamp(0.0, 1.0)),
+            // Polynomial fits of the originals
+            Colormap::Turbo => polynomial(&TURBO, t),
+            Colormap::Inferno => polynomial(&INFERNO, t),
+        };
+        rgb.map(|c| (c.clamp(0.0, 1.0) * 255.0 + 0.5) as u8)
+    }
+
+    /// 256 entries of RGBA8, from black (or its equivalent) up.
+    pub fn table(self) -> &'static [[u8; 4]; 256] {
+        static TABLES: [OnceLock<[[u8; 4]; 256]>; 4] = [const { OnceLock::new() }; 4];
+        TABLES[self as usize].get_or_init(|| {
+            std::array::from_fn(|i| {
+                let [r, g, b] = self.color(i as f32 / 255.0);
+                [r, g, b, 255]
+            })
+        })
+    }
+}
+
+// Coefficients for red, green and blue from t^0 up. Turbo is the fit
+// published with it, inferno the one from Matt Zucker's shadertoy.
+#[rustfmt::skip]
+#[allow(clippy::excessive_precision)]
+const TURBO: [[f32; 3]; 6] = [
+    [0.13572138, 0.09140261, 0.10667330],
+    [4.61539260, 2.19418839, 12.64194608],
+    [-42.66032258, 4.84296658, -60.58204836],
+    [132.13108234, -14.18503333, 110.36276771],
+    [-152.94239396, 4.27729857, -89.90310912],
+    [59.28637943, 2.82956604, 27.34824973],
+];
+
+#[rustfmt::skip]
+#[allow(clippy::excessive_precision)]
+const INFERNO: [[f32; 3]; 7] = [
+    [0.00021894037, 0.0016510046, -0.019480898],
+    [0.10651342, 0.56395644, 3.9327124],
+    [11.602493, -3.9728540, -15.942394],
+    [-41.703996, 17.436399, 44.354145],
+    [77.162936, -33.402359, -81.807309],
+    [-71.319428, 32.626064, 73.209520],
+    [25.131126, -12.242669, -23.070325],
+];
+
+fn polynomial(coefficients: &[[f32; 3]], t: f32) -> [f32; 3] {
+    let mut rgb = [0.0; 3];
+    for c in coefficients.iter().rev() {
+        for (v, c) in rgb.iter_mut().zip(c) {
+            *v = *v * t + c;
+        }
+    }
+    rgb
+}
+
+/// How greyscale and depth samples are turned into colours.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct MonoOptions {
+    pub tone_map: ToneMap,
+    pub colormap: Colormap,
+    /// Show samples of 0 as black and leave them out of the auto stretch,
+    /// for depth cameras that use it for "no data"
+    pub zero_is_invalid: bool,
+}
+
+impl MonoOptions {
+    /// What `frame_to_rgba` uses: plain greyscale, except for depth which
+    /// gets auto stretched over turbo.
+    pub fn for_format(mono: Mono) -> Self {
+        if mono.depth {
+            Self {
+                tone_map: ToneMap::AUTO,
+                colormap: Colormap::Turbo,
+                zero_is_invalid: true,
+            }
+        } else {
+            Self::default()
+        }
+    }
+}
+
+// Finest the auto stretch histogram gets, in bits
+const HISTOGRAM_BITS: u32 = 12;
+// Only every so many rows and columns of a frame larger than
+// HISTOGRAM_STEP * 64 square go into the histogram
+const HISTOGRAM_STEP: usize = 4;
+
+// The colormap entry of each sample value, for the white level and tone map
+// range it was last worked out for, and the auto stretch histogram. Kept
+// from frame to frame by the thread starting the conversions, as they only
+// change along with the format or the settings, or the picture when auto
+// stretching.
+#[derive(Default)]
+struct Tables {
+    index: Vec<u8>,
+    mapped: Option<(u32, u32, u32)>,
+    histogram: Vec<usize>,
+}
+
+thread_local! {
+    static TABLES: RefCell<Tables> = RefCell::default();
+    // A row of samples, for each converting thread
+    static SAMPLES: RefCell<Vec<u16>> = RefCell::default();
+}
+
+pub(super) struct MonoRows<'a> {
+    mono: Mono,
+    // Colormap entry of each sample value
+    index: &'a [u8],
+    colormap: &'static [[u8; 4]; 256],
+    zero_is_invalid: bool,
+    src: &'a [u8],
+    width: usize,
+    height: usize,
+    stride: usize,
+}
+
+impl MonoRows<'_> {
+    /// Hands the rows of the frame in `src` to `f`.
+    pub(super) fn with<R>(
+        mono: Mono,
+        src: &[u8],
+        format: &FrameFormat,
+        options: &MonoOptions,
+        f: impl FnOnce(&MonoRows) -> R,
+    ) -> R {
+        TABLES.with_borrow_mut(|tables| {
+            let mut rows = MonoRows {
+                mono,
+                index: &[],
+                colormap: options.colormap.table(),
+                zero_is_invalid: options.zero_is_invalid,
+                src,
+                width: format.width,
+                height: format.height,
+                stride: mono.stride(format),
+            };
+
+            let white = mono.white_level() as u32;
+            let (low, high) = match options.tone_map {
+                ToneMap::Full => (0, white),
+                ToneMap::Window { level, width } => {
+                    let low = (level as u32).saturating_sub(width as u32 / 2);
+                    (low, low + width as u32)
+                }
+                ToneMap::Auto { low, high } => rows.percentiles(low, high, &mut tables.histogram),
+            };
+            if tables.mapped != Some((white, low, high)) {
+                let range = high.saturating_sub(low).max(1);
+                tables.index.clear();
+                tables.index.extend(
+                    (0..=white).map(|v| {
+                        ((v.saturating_sub(low) * 255 + range / 2) / range).min(255) as u8
+                    }),
+                );
+                tables.mapped = Some((white, low, high));
+            }
+            rows.index = &tables.index;
+            f(&rows)
+        })
+    }
+
+    fn row(&self, row: usize, samples: &mut [u16]) -> bool {
+        let row_bytes = self.mono.row_bytes(self.width);
+        match self
+            .src
+            .get(row * self.stride..)
+            .and_then(|src| src.get(..row_bytes))
+        {
+            Some(src) => {
+                self.mono.unpack_row(src, samples);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // The sample values at the `low` and `high` fractions of a sparse
+    // histogram of the frame
+    fn percentiles(&self, low: f32, high: f32, histogram: &mut Vec<usize>) -> (u32, u32) {
+        let shift = self.mono.bits.saturating_sub(HISTOGRAM_BITS);
+        histogram.clear();
+        histogram.resize(1 << (self.mono.bits - shift), 0);
+        let step = if self.width * self.height >= (HISTOGRAM_STEP * 64).pow(2) {
+            HISTOGRAM_STEP
+        } else {
+            1
+        };
+        SAMPLES.with_borrow_mut(|samples| {
+            samples.resize(self.width, 0);
+            for row in (0..self.height).step_by(step) {
+                if !self.row(row, samples) {
+                    break;
+                }
+                for &v in samples.iter().step_by(step) {
+                    if v != 0 || !self.zero_is_invalid {
+                        histogram[(v >> shift) as usize] += 1;
+                    }
+                }
+            }
+        });
+
+        let total: usize = histogram.iter().sum();
+        if total == 0 {
+            return (0, self.mono.white_level() as u32);
+        }
+        let bin_at = |fraction: f32| {
+            let target = (fraction.clamp(0.0, 1.0) * total as f32) as usize;
+            let mut seen = 0;
+            histogram
+                .iter()
+                .position(|count| {
+                    seen += count;
+                    seen > target
+                })
+                .unwrap_or(histogram.len() - 1) as u32
+        };
+        let low = bin_at(low) << shift;
+        let high = ((bin_at(high) + 1) << shift) - 1;
+        (low, high.max(low + 1))
+    }
+}
+
+impl Rows for MonoRows<'_> {
+    fn convert(&self, first_row: usize, dst: &mut [u8]) {
+        SAMPLES.with_borrow_mut(|samples| {
+            samples.resize(self.width, 0);
+            for (row, dst_row) in (first_row..).zip(dst.chunks_exact_mut(self.width * 4)) {
+                // A short frame leaves the rows past its end as they were
+                if !self.row(row, samples) {
+                    break;
+                }
+                for (v, rgba) in samples.iter().zip(dst_row.chunks_exact_mut(4)) {
+                    if *v == 0 && self.zero_is_invalid {
+                        rgba.copy_from_slice(&[0, 0, 0, 255]);
+                    } else {
+                        rgba.copy_from_slice(&self.colormap[self.index[*v as usize] as usize]);
+                    }
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::convert::{frame_to_mono16, mono_to_rgba, RgbaImage};
+
+    fn unpack(pixel_format: PixelFormat, src: &[u8], samples: usize) -> Vec<u16> {
+        let mono = Mono::from_pixel_format(pixel_format).unwrap();
+        assert_eq!(mono.row_bytes(samples), src.len(), "{pixel_format}");
+        let mut dst = vec![0; samples];
+        mono.unpack_row(src, &mut dst);
+        dst
+    }
+
+    #[test]
+    fn unpacks_every_packing() {
+        // Anything above the format's bits is ignored
+        assert_eq!(
+            unpack(PixelFormat::Y10, &[0xff, 0x03, 0x34, 0x12], 2),
+            [1023, 0x234]
+        );
+        assert_eq!(
+            unpack(PixelFormat::Y12, &[0xff, 0x0f, 0x34, 0x12], 2),
+            [4095, 0x234]
+        );
+        assert_eq!(unpack(PixelFormat::Y16, &[0x34, 0x12], 1), [0x1234]);
+        assert_eq!(unpack(PixelFormat::Y16_BE, &[0x12, 0x34], 1), [0x1234]);
+
+        let samples = [0x3ff, 0x000, 0x155, 0x2aa];
+        // The high 8 bits of each, then the low 2 of all 4 from the bottom up
+        assert_eq!(
+            unpack(
+                PixelFormat::Y10P,
+                &[0xff, 0x00, 0x55, 0xaa, 0b10_01_00_11],
+                4
+            ),
+            samples
+        );
+        // 1111111111 0000000000 0101010101 1010101010
+        assert_eq!(
+            unpack(PixelFormat::Y10BPACK, &[0xff, 0xc0, 0x05, 0x56, 0xaa], 4),
+            samples
+        );
+    }
+
+    // A row of Y12 holding `samples`, tone mapped to grey levels
+    fn grey(samples: &[u16], tone_map: ToneMap) -> Vec<u8> {
+        let format = FrameFormat {
+            pixel_format: PixelFormat::Y12,
+            width: samples.len(),
+            height: 1,
+            ..Default::default()
+        };
+        let data: Vec<u8> = samples.iter().flat_map(|v| v.to_le_bytes()).collect();
+        let options = MonoOptions {
+            tone_map,
+            ..Default::default()
+        };
+        let mut image = RgbaImage::new();
+        mono_to_rgba(&[&data], &format, &options, &mut image).unwrap();
+        image.data().chunks_exact(4).map(|rgba| rgba[0]).collect()
+    }
+
+    #[test]
+    fn window_and_level() {
+        let window = ToneMap::Window {
+            level: 2048,
+            width: 256,
+        };
+        assert_eq!(
+            grey(&[0, 1920, 2048, 2176, 4095], window),
+            [0, 0, 128, 255, 255]
+        );
+        assert_eq!(grey(&[0, 2048, 4095], ToneMap::Full), [0, 128, 255]);
+    }
+
+    #[test]
+    fn auto_stretch_leaves_out_the_extremes() {
+        // 1001 to 1198, a dead pixel and a hot one. 1% of 200 in from either
+        // end is 1002 and 1198.
+        let mut samples: Vec<u16> = (1001..=1198).collect();
+        samples.extend([0, 4095]);
+        let got = grey(&samples, ToneMap::AUTO);
+        let at = |v: u16| got[samples.iter().position(|&s| s == v).unwrap()];
+        assert_eq!([at(0), at(1001), at(1002)], [0, 0, 0]);
+        assert_eq!(at(1100), 128);
+        assert_eq!([at(1198), at(4095)], [255, 255]);
+
+        // The next frame gets stretched over its own samples
+        let brighter: Vec<u16> = samples
+            .iter()
+            .map(|&v| if v == 0 || v == 4095 { v } else { v + 1000 })
+            .collect();
+        assert_eq!(grey(&brighter, ToneMap::AUTO), got);
+    }
+
+    #[test]
+    fn colormap_ends() {
+        assert_eq!(Colormap::Grey.color(0.0), [0, 0, 0]);
+        assert_eq!(Colormap::Grey.color(1.0), [255, 255, 255]);
+        // Dark blue to dark red
+        assert_eq!(Colormap::Jet.color(0.0), [0, 0, 128]);
+        assert_eq!(Colormap::Jet.color(1.0), [128, 0, 0]);
+        // As far as the polynomial fits go
+        assert_eq!(Colormap::Turbo.color(0.0), [35, 23, 27]);
+        assert_eq!(Colormap::Turbo.color(1.0), [144, 13, 0]);
+        assert_eq!(Colormap::Inferno.color(0.0), [0, 0, 0]);
+        assert_eq!(Colormap::Inferno.color(1.0), [250, 255, 167]);
+        // And the tables are the same
+        for colormap in Colormap::ALL {
+            let [r, g, b] = colormap.color(1.0);
+            assert_eq!(colormap.table()[255], [r, g, b, 255]);
+        }
+    }
+
+    #[test]
+    fn mono16_keeps_the_samples() {
+        let format = FrameFormat {
+            pixel_format: PixelFormat::Y10,
+            width: 3,
+            height: 2,
+            ..Default::default()
+        };
+        let samples = [0, 1, 511, 512, 1022, 1023];
+        let data: Vec<u8> = samples.iter().flat_map(|v: &u16| v.to_le_bytes()).collect();
+        let mut image = Mono16Image::new();
+        frame_to_mono16(&[&data], &format, &mut image).unwrap();
+        assert_eq!((image.width(), image.height()), (3, 2));
+        assert_eq!(image.data(), samples);
+    }
+}
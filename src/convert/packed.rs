@@ -0,0 +1,190 @@
+use super::{ColorSpec, FixedYuvToRgb, Kernel, Rows};
+use crate::v4l2::{FrameFormat, PixelFormat};
+
+/// Where Y'0, Cb, Y'1 and Cr sit in the 4 bytes holding two pixels of a
+/// packed 4:2:2 format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Packed422 {
+    pub y0: usize,
+    pub u: usize,
+    pub y1: usize,
+    pub v: usize,
+}
+
+impl Packed422 {
+    pub const YUYV: Self = Self::new(0, 1, 2, 3);
+    pub const UYVY: Self = Self::new(1, 0, 3, 2);
+    pub const YVYU: Self = Self::new(0, 3, 2, 1);
+    pub const VYUY: Self = Self::new(1, 2, 3, 0);
+    pub const YYUV: Self = Self::new(0, 2, 1, 3);
+
+    const fn new(y0: usize, u: usize, y1: usize, v: usize) -> Self {
+        Self { y0, u, y1, v }
+    }
+
+    pub fn from_pixel_format(format: PixelFormat) -> Option<Self> {
+        Some(match format {
+            PixelFormat::YUYV => Self::YUYV,
+            PixelFormat::UYVY => Self::UYVY,
+            PixelFormat::YVYU => Self::YVYU,
+            PixelFormat::VYUY => Self::VYUY,
+            PixelFormat::YYUV => Self::YYUV,
+            _ => return None,
+        })
+    }
+}
+
+pub(super) struct Packed422Rows<'a> {
+    kernel: Kernel,
+    order: Packed422,
+    coeffs: FixedYuvToRgb,
+    src: &'a [u8],
+    width: usize,
+    stride: usize,
+}
+
+impl<'a> Packed422Rows<'a> {
+    pub(super) fn new(
+        kernel: Kernel,
+        order: Packed422,
+        src: &'a [u8],
+        format: &FrameFormat,
+        spec: ColorSpec,
+    ) -> Self {
+        let row_bytes = format.width.div_ceil(2) * 4;
+
+        Self {
+            kernel,
+            order,
+            coeffs: FixedYuvToRgb::new(spec),
+            src,
+            width: format.width,
+            stride: match format.bytes_per_line {
+                0 => row_bytes,
+                stride => stride,
+            },
+        }
+    }
+}
+
+impl Rows for Packed422Rows<'_> {
+    fn convert(&self, first_row: usize, dst: &mut [u8]) {
+        let row_bytes = self.width.div_ceil(2) * 4;
+        let src = self.src.get(first_row * self.stride..).unwrap_or_default();
+
+        // A short frame leaves the rows past its end as they were
+        for (src_row, dst_row) in src
+            .chunks(self.stride)
+            .zip(dst.chunks_exact_mut(self.width * 4))
+        {
+            if src_row.len() < row_bytes {
+                break;
+            }
+            self.kernel
+                .packed422_row(self.order, &src_row[..row_bytes], dst_row, &self.coeffs);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::convert::{frame_to_rgba, Matrix, Range, RgbToYuv, RgbaImage};
+
+    const HEIGHT: usize = 9;
+
+    fn picture(width: usize) -> Vec<[u8; 3]> {
+        (0..width * HEIGHT)
+            .map(|i| {
+                let (x, y) = (i % width, i / width);
+                [(x * 4) as u8, (y * 25) as u8, (255 - x * 2 - y) as u8]
+            })
+            .collect()
+    }
+
+    // Two pixels to every 4 bytes, sharing the average of their Cb and Cr.
+    // An odd width leaves the last Y'1 unused.
+    fn encode(
+        picture: &[[u8; 3]],
+        width: usize,
+        order: Packed422,
+        spec: ColorSpec,
+        stride: usize,
+    ) -> Vec<u8> {
+        let encoder = RgbToYuv::new(spec);
+        let mut data = vec![0; stride * HEIGHT];
+        for (row, out) in picture
+            .chunks_exact(width)
+            .zip(data.chunks_exact_mut(stride))
+        {
+            let yuv: Vec<[u8; 3]> = row
+                .iter()
+                .map(|&[r, g, b]| encoder.convert(r, g, b))
+                .collect();
+            for (pair, out) in yuv.chunks(2).zip(out.chunks_exact_mut(4)) {
+                let average = |c: usize| {
+                    let sum: u32 = pair.iter().map(|p| p[c] as u32).sum();
+                    let count = pair.len() as u32;
+                    ((sum + count / 2) / count) as u8
+                };
+                out[order.y0] = pair[0][0];
+                out[order.y1] = pair.get(1).map_or(16, |p| p[0]);
+                out[order.u] = average(1);
+                out[order.v] = average(2);
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn round_trips_through_every_order() {
+        let formats = [
+            PixelFormat::YUYV,
+            PixelFormat::UYVY,
+            PixelFormat::YVYU,
+            PixelFormat::VYUY,
+            PixelFormat::YYUV,
+        ];
+        for width in [64, 63, 1] {
+            let picture = picture(width);
+            let want: Vec<u8> = picture
+                .iter()
+                .flat_map(|&[r, g, b]| [r, g, b, 255])
+                .collect();
+            for pixel_format in formats {
+                for (matrix, range) in [
+                    (Matrix::Bt601, Range::Limited),
+                    (Matrix::Bt709, Range::Full),
+                ] {
+                    // Tightly packed and with padding after each row
+                    for padding in [0, 32] {
+                        let order = Packed422::from_pixel_format(pixel_format).unwrap();
+                        let spec = ColorSpec {
+                            matrix,
+                            range,
+                            ..Default::default()
+                        };
+                        let stride = width.div_ceil(2) * 4 + padding;
+                        let data = encode(&picture, width, order, spec, stride);
+                        let format = FrameFormat {
+                            pixel_format,
+                            width,
+                            height: HEIGHT,
+                            bytes_per_line: stride,
+                            ..Default::default()
+                        };
+
+                        let mut got = RgbaImage::new();
+                        frame_to_rgba(&[&data], &format, spec, &mut got).unwrap();
+                        assert_eq!(got.data().len(), want.len());
+                        let what = format!("{pixel_format} {width} wide {matrix} {range}");
+                        for (i, (&got, &want)) in got.data().iter().zip(&want).enumerate() {
+                            let error = got.abs_diff(want);
+                            assert!(error <= 4, "{what}: byte {i} off by {error}");
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
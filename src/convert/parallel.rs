@@ -0,0 +1,189 @@
+use rayon::{prelude::*, ThreadPool, ThreadPoolBuilder};
+
+use super::{
+    Bayer, ColorSpec, ConvertError, DeepYuv, HdrOptions, Kernel, Mono, MonoOptions, Options,
+    RawOptions, RgbaImage, Rows, Run,
+};
+use crate::v4l2::FrameFormat;
+
+// Bands per thread, so a thread that got descheduled doesn't hold up the frame
+const BANDS_PER_THREAD: usize = 4;
+
+/// Converts frames on a thread pool, each thread taking bands of rows.
+///
+/// Every row is converted exactly as the single threaded functions would, so
+/// the output is the same byte for byte.
+pub struct ParallelConverter {
+    pool: ThreadPool,
+    kernel: Kernel,
+}
+
+impl ParallelConverter {
+    /// A pool of `threads` threads, 0 meaning one per CPU.
+    pub fn new(threads: usize) -> Result<Self, rayon::ThreadPoolBuildError> {
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .thread_name(|i| format!("convert-{i}"))
+            .build()?;
+
+        Ok(Self {
+            pool,
+            kernel: Kernel::detect(),
+        })
+    }
+
+    /// Use `kernel` rather than the fastest one the CPU supports.
+    pub fn with_kernel(mut self, kernel: Kernel) -> Self {
+        self.kernel = kernel;
+        self
+    }
+
+    pub fn threads(&self) -> usize {
+        self.pool.current_num_threads()
+    }
+
+    /// Same as `convert::frame_to_rgba`.
+    pub fn frame_to_rgba(
+        &self,
+        planes: &[&[u8]],
+        format: &FrameFormat,
+        spec: ColorSpec,
+        dst: &mut RgbaImage,
+    ) -> Result<(), ConvertError> {
+        let options = Options {
+            spec,
+            ..Options::default()
+        };
+        super::convert(self, self.kernel, planes, format, &options, dst)
+    }
+
+    /// Same as `convert::bayer_to_rgba`.
+    pub fn bayer_to_rgba(
+        &self,
+        planes: &[&[u8]],
+        format: &FrameFormat,
+        options: &RawOptions,
+        dst: &mut RgbaImage,
+    ) -> Result<(), ConvertError> {
+        if Bayer::from_pixel_format(format.pixel_format).is_none() {
+            return Err(ConvertError::UnsupportedFormat(format.pixel_format));
+        }
+        let options = Options {
+            raw: *options,
+            ..Options::default()
+        };
+        super::convert(self, self.kernel, planes, format, &options, dst)
+    }
+
+    /// Same as `convert::deep_to_rgba`.
+    pub fn deep_to_rgba(
+        &self,
+        planes: &[&[u8]],
+        format: &FrameFormat,
+        spec: ColorSpec,
+        options: &HdrOptions,
+        dst: &mut RgbaImage,
+    ) -> Result<(), ConvertError> {
+        if DeepYuv::from_pixel_format(format.pixel_format).is_none() {
+            return Err(ConvertError::UnsupportedFormat(format.pixel_format));
+        }
+        let options = Options {
+            spec,
+            hdr: *options,
+            ..Options::default()
+        };
+        super::convert(self, self.kernel, planes, format, &options, dst)
+    }
+
+    /// Same as `convert::mono_to_rgba`.
+    pub fn mono_to_rgba(
+        &self,
+        planes: &[&[u8]],
+        format: &FrameFormat,
+        options: &MonoOptions,
+        dst: &mut RgbaImage,
+    ) -> Result<(), ConvertError> {
+        if Mono::from_pixel_format(format.pixel_format).is_none() {
+            return Err(ConvertError::UnsupportedFormat(format.pixel_format));
+        }
+        let options = Options {
+            mono: Some(*options),
+            ..Options::default()
+        };
+        super::convert(self, self.kernel, planes, format, &options, dst)
+    }
+}
+
+impl Run for ParallelConverter {
+    fn run<R: Rows>(&self, rows: &R, dst: &mut RgbaImage) {
+        let bands = self.threads() * BANDS_PER_THREAD;
+        let band_rows = dst.height().div_ceil(bands);
+        let band_len = band_rows * dst.width() * 4;
+
+        self.pool.install(|| {
+            dst.data_mut()
+                .par_chunks_mut(band_len)
+                .enumerate()
+                .for_each(|(band, dst)| rows.convert(band * band_rows, dst));
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::v4l2::PixelFormat;
+
+    // Odd sizes leave a short last band, and a one or two row band for the
+    // demosaic to look past
+    const SIZES: [(usize, usize); 5] = [(2, 1), (6, 3), (34, 7), (64, 33), (98, 61)];
+
+    const FORMATS: [PixelFormat; 9] = [
+        PixelFormat::YUYV,
+        PixelFormat::NV12,
+        PixelFormat::YUV420,
+        PixelFormat::YUV422P,
+        PixelFormat::RGB24,
+        PixelFormat::SRGGB8,
+        PixelFormat::SGRBG10P,
+        PixelFormat::GREY,
+        PixelFormat::P010,
+    ];
+
+    #[test]
+    fn any_number_of_threads_gives_the_same_frame() {
+        let one = ParallelConverter::new(1).unwrap();
+        let many = ParallelConverter::new(7).unwrap();
+        assert_eq!(many.threads(), 7);
+
+        for pixel_format in FORMATS {
+            for (width, height) in SIZES {
+                let format = FrameFormat {
+                    pixel_format,
+                    width,
+                    height,
+                    ..Default::default()
+                };
+                // More than any of the formats needs
+                let data: Vec<u8> = (0..width * height * 8)
+                    .map(|i| (i * 7 + i / 5) as u8)
+                    .collect();
+                let spec = ColorSpec::default();
+
+                let mut want = RgbaImage::new();
+                crate::convert::frame_to_rgba(&[&data], &format, spec, &mut want).unwrap();
+                for converter in [&one, &many, &many] {
+                    let mut got = RgbaImage::new();
+                    converter
+                        .frame_to_rgba(&[&data], &format, spec, &mut got)
+                        .unwrap();
+                    assert!(
+                        got.data() == want.data(),
+                        "{pixel_format} {width}x{height} on {} threads",
+                        converter.threads()
+                    );
+                }
+            }
+        }
+    }
+}
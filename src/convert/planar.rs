@@ -0,0 +1,369 @@
+use super::{ColorSpec, FixedYuvToRgb, Rows};
+use crate::v4l2::{FrameFormat, PixelFormat};
+
+/// Layout of a planar or semi-planar 4:2:0 or 4:2:2 Y'CbCr format. Chroma is
+/// always halved horizontally.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Planar {
+    /// 2 for 4:2:0, 1 for 4:2:2
+    pub vertical_subsampling: usize,
+    /// Cb and Cr interleaved in one plane, as in NV12, rather than one plane each
+    pub semi_planar: bool,
+    /// Cr before Cb, as in NV21 and YV12
+    pub cr_first: bool,
+}
+
+impl Planar {
+    const fn new(vertical_subsampling: usize, semi_planar: bool, cr_first: bool) -> Self {
+        Self {
+            vertical_subsampling,
+            semi_planar,
+            cr_first,
+        }
+    }
+
+    /// The `*M` variants have the same layout, just with every plane in its
+    /// own buffer.
+    pub fn from_pixel_format(format: PixelFormat) -> Option<Self> {
+        Some(match format {
+            PixelFormat::NV12 | PixelFormat::NV12M => Self::new(2, true, false),
+            PixelFormat::NV21 | PixelFormat::NV21M => Self::new(2, true, true),
+            PixelFormat::NV16 | PixelFormat::NV16M => Self::new(1, true, false),
+            PixelFormat::YUV420 | PixelFormat::YUV420M => Self::new(2, false, false),
+            PixelFormat::YVU420 | PixelFormat::YVU420M => Self::new(2, false, true),
+            PixelFormat::YUV422P | PixelFormat::YUV422M => Self::new(1, false, false),
+            _ => return None,
+        })
+    }
+}
+
+// One plane, or Cb or Cr within a semi-planar one
+#[derive(Clone, Copy)]
+pub(super) struct Component<'a> {
+    data: &'a [u8],
+    stride: usize,
+}
+
+impl<'a> Component<'a> {
+    pub(super) fn row(&self, row: usize) -> &'a [u8] {
+        self.data.get(row * self.stride..).unwrap_or_default()
+    }
+}
+
+pub(super) struct PlanarRows<'a> {
+    layout: Planar,
+    coeffs: FixedYuvToRgb,
+    width: usize,
+    y: Component<'a>,
+    u: Component<'a>,
+    v: Component<'a>,
+}
+
+impl<'a> PlanarRows<'a> {
+    pub(super) fn new(
+        layout: Planar,
+        planes: &[&'a [u8]],
+        format: &FrameFormat,
+        spec: ColorSpec,
+    ) -> Self {
+        let [y, u, v] = layout.components(planes, format);
+        Self {
+            layout,
+            coeffs: FixedYuvToRgb::new(spec),
+            width: format.width,
+            y,
+            u,
+            v,
+        }
+    }
+}
+
+impl Planar {
+    /// Y', Cb and Cr of a frame. A semi-planar chroma component starts at its
+    /// first byte, with the samples two bytes apart.
+    pub(super) fn components<'a>(
+        self,
+        planes: &[&'a [u8]],
+        format: &FrameFormat,
+    ) -> [Component<'a>; 3] {
+        let chroma_width = format.width.div_ceil(2);
+        let chroma_height = format.height.div_ceil(self.vertical_subsampling);
+        // Bytes in a row of the plane holding chroma
+        let chroma_bytes = if self.semi_planar {
+            chroma_width * 2
+        } else {
+            chroma_width
+        };
+        let or = |stride: usize, default: usize| if stride == 0 { default } else { stride };
+        let plane = |i: usize| planes.get(i).copied().unwrap_or_default();
+
+        let y_stride = or(format.bytes_per_line, format.width);
+        let y = Component {
+            data: plane(0),
+            stride: y_stride,
+        };
+
+        // Either one buffer per plane, or all of them one after the other in
+        // the first, with the chroma lines as long as the luma ones for
+        // semi-planar and half as long for planar.
+        let (first, second) = if format.num_planes > 1 {
+            let first = Component {
+                data: plane(1),
+                stride: or(format.planes[1].bytes_per_line, chroma_bytes),
+            };
+            let second = Component {
+                data: plane(2),
+                stride: or(format.planes[2].bytes_per_line, chroma_bytes),
+            };
+            (first, second)
+        } else {
+            let stride = if self.semi_planar {
+                or(format.bytes_per_line, chroma_bytes)
+            } else {
+                or(format.bytes_per_line / 2, chroma_bytes)
+            };
+            let after_y = plane(0).get(y_stride * format.height..).unwrap_or_default();
+            let after_first = after_y.get(stride * chroma_height..).unwrap_or_default();
+
+            let first = Component {
+                data: after_y,
+                stride,
+            };
+            let second = Component {
+                data: after_first,
+                stride,
+            };
+            (first, second)
+        };
+
+        let (u, v) = if self.semi_planar {
+            // Both in the first, every other byte
+            let skip = |c: Component<'a>, n: usize| Component {
+                data: c.data.get(n..).unwrap_or_default(),
+                ..c
+            };
+            (skip(first, 0), skip(first, 1))
+        } else {
+            (first, second)
+        };
+        let (u, v) = if self.cr_first { (v, u) } else { (u, v) };
+        [y, u, v]
+    }
+}
+
+impl Rows for PlanarRows<'_> {
+    fn convert(&self, first_row: usize, dst: &mut [u8]) {
+        let chroma_step = if self.layout.semi_planar { 2 } else { 1 };
+        // Bytes needed from a chroma row, from its first sample to its last
+        let chroma_len = (self.width.div_ceil(2) - 1) * chroma_step + 1;
+
+        for (row, dst_row) in (first_row..).zip(dst.chunks_exact_mut(self.width * 4)) {
+            let chroma_row = row / self.layout.vertical_subsampling;
+            let y = self.y.row(row);
+            let u = self.u.row(chroma_row);
+            let v = self.v.row(chroma_row);
+
+            // A short frame leaves the rows past its end as they were
+            if y.len() < self.width || u.len() < chroma_len || v.len() < chroma_len {
+                break;
+            }
+            planar_row(&y[..self.width], u, v, chroma_step, dst_row, &self.coeffs);
+        }
+    }
+}
+
+fn planar_row(y: &[u8], u: &[u8], v: &[u8], chroma_step: usize, dst: &mut [u8], c: &FixedYuvToRgb) {
+    for (x, (y, rgba)) in y.iter().zip(dst.chunks_exact_mut(4)).enumerate() {
+        let i = x / 2 * chroma_step;
+        let [r, g, b] = c.convert(*y, u[i], v[i]);
+        rgba.copy_from_slice(&[r, g, b, 255]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::convert::{frame_to_rgba, Matrix, Range, RgbToYuv, RgbaImage};
+    use crate::v4l2::PlaneFormat;
+
+    // Odd, so the last chroma sample covers a single column and row
+    const WIDTH: usize = 63;
+    const HEIGHT: usize = 47;
+
+    // Smooth enough that chroma subsampling doesn't lose much
+    fn picture() -> Vec<[u8; 3]> {
+        (0..WIDTH * HEIGHT)
+            .map(|i| {
+                let (x, y) = (i % WIDTH, i / WIDTH);
+                [(x * 4) as u8, (y * 5) as u8, (255 - x * 2 - y) as u8]
+            })
+            .collect()
+    }
+
+    // Y' per pixel and Cb/Cr averaged over each block of pixels sharing them
+    fn encode(picture: &[[u8; 3]], layout: Planar, spec: ColorSpec) -> [Vec<u8>; 3] {
+        let encoder = RgbToYuv::new(spec);
+        let yuv: Vec<[u8; 3]> = picture
+            .iter()
+            .map(|&[r, g, b]| encoder.convert(r, g, b))
+            .collect();
+
+        let luma = yuv.iter().map(|p| p[0]).collect();
+        let (chroma_width, chroma_height) = (
+            WIDTH.div_ceil(2),
+            HEIGHT.div_ceil(layout.vertical_subsampling),
+        );
+        let mut cb = Vec::new();
+        let mut cr = Vec::new();
+        for cy in 0..chroma_height {
+            for cx in 0..chroma_width {
+                let (mut sum, mut count) = ([0u32; 2], 0);
+                for y in cy * layout.vertical_subsampling
+                    ..HEIGHT.min((cy + 1) * layout.vertical_subsampling)
+                {
+                    for x in cx * 2..WIDTH.min(cx * 2 + 2) {
+                        let p = yuv[y * WIDTH + x];
+                        sum[0] += p[1] as u32;
+                        sum[1] += p[2] as u32;
+                        count += 1;
+                    }
+                }
+                cb.push(((sum[0] + count / 2) / count) as u8);
+                cr.push(((sum[1] + count / 2) / count) as u8);
+            }
+        }
+        [luma, cb, cr]
+    }
+
+    // Rows of `width` bytes from `data`, `padding` bytes of junk apart
+    fn pad(data: &[u8], width: usize, padding: usize) -> Vec<u8> {
+        data.chunks(width)
+            .flat_map(|row| [row, &[0xee; 64][..padding]].concat())
+            .collect()
+    }
+
+    // The planes in the layout's order with `padding` bytes after every row,
+    // either each on its own or all of them in one buffer, along with the
+    // format describing them
+    fn pack(
+        pixel_format: PixelFormat,
+        [luma, cb, cr]: [Vec<u8>; 3],
+        separate: bool,
+        padding: usize,
+    ) -> (Vec<Vec<u8>>, FrameFormat) {
+        let layout = Planar::from_pixel_format(pixel_format).unwrap();
+        let (first, second) = if layout.cr_first { (cr, cb) } else { (cb, cr) };
+        let chroma: Vec<Vec<u8>> = if layout.semi_planar {
+            vec![first
+                .iter()
+                .zip(&second)
+                .flat_map(|(&a, &b)| [a, b])
+                .collect()]
+        } else {
+            vec![first, second]
+        };
+        let chroma_bytes = WIDTH.div_ceil(2) * if layout.semi_planar { 2 } else { 1 };
+
+        let mut format = FrameFormat {
+            pixel_format,
+            width: WIDTH,
+            height: HEIGHT,
+            ..Default::default()
+        };
+        let stride = |width: usize| if padding == 0 { 0 } else { width + padding };
+        format.bytes_per_line = stride(WIDTH);
+        if !separate {
+            // In the one buffer the chroma rows are as long as the luma ones
+            // for semi-planar and half as long for planar
+            let chroma_padding = match layout.semi_planar {
+                true => format.bytes_per_line.saturating_sub(chroma_bytes),
+                false => (format.bytes_per_line / 2).saturating_sub(chroma_bytes),
+            };
+            let mut data = pad(&luma, WIDTH, padding);
+            for plane in &chroma {
+                data.extend(pad(plane, chroma_bytes, chroma_padding));
+            }
+            format.num_planes = 1;
+            format.planes[0] = PlaneFormat {
+                bytes_per_line: format.bytes_per_line,
+                size: data.len(),
+            };
+            return (vec![data], format);
+        }
+
+        let planes: Vec<Vec<u8>> = [pad(&luma, WIDTH, padding)]
+            .into_iter()
+            .chain(chroma.iter().map(|plane| pad(plane, chroma_bytes, padding)))
+            .collect();
+        format.num_planes = planes.len();
+        for (i, (plane, data)) in format.planes.iter_mut().zip(&planes).enumerate() {
+            *plane = PlaneFormat {
+                bytes_per_line: stride(if i == 0 { WIDTH } else { chroma_bytes }),
+                size: data.len(),
+            };
+        }
+        (planes, format)
+    }
+
+    fn psnr_and_max_error(a: &[u8], b: &[u8]) -> (f64, u8) {
+        let mut squares = 0.0;
+        let mut max = 0;
+        for (i, (&a, &b)) in a.iter().zip(b).enumerate() {
+            // Leave out alpha
+            if i % 4 != 3 {
+                squares += (a as f64 - b as f64).powi(2);
+                max = max.max(a.abs_diff(b));
+            }
+        }
+        let mse = squares / (a.len() / 4 * 3) as f64;
+        (10.0 * (255.0 * 255.0 / mse).log10(), max)
+    }
+
+    #[test]
+    fn round_trips_through_every_layout() {
+        let picture = picture();
+        let want: Vec<u8> = picture
+            .iter()
+            .flat_map(|&[r, g, b]| [r, g, b, 255])
+            .collect();
+
+        let formats = [
+            (PixelFormat::NV12, false),
+            (PixelFormat::NV21, false),
+            (PixelFormat::NV16, false),
+            (PixelFormat::YUV420, false),
+            (PixelFormat::YVU420, false),
+            (PixelFormat::YUV422P, false),
+            (PixelFormat::NV12M, true),
+            (PixelFormat::NV21M, true),
+            (PixelFormat::NV16M, true),
+            (PixelFormat::YUV420M, true),
+            (PixelFormat::YVU420M, true),
+            (PixelFormat::YUV422M, true),
+        ];
+        // Tightly packed, and with padding after every row that also moves
+        // the chroma planes along in a single buffer
+        for (pixel_format, separate) in formats {
+            for matrix in [Matrix::Bt601, Matrix::Bt709, Matrix::Bt2020] {
+                for (range, padding) in Range::ALL.into_iter().flat_map(|r| [(r, 0), (r, 32)]) {
+                    let layout = Planar::from_pixel_format(pixel_format).unwrap();
+                    let spec = ColorSpec {
+                        matrix,
+                        range,
+                        ..Default::default()
+                    };
+                    let yuv = encode(&picture, layout, spec);
+                    let (planes, format) = pack(pixel_format, yuv, separate, padding);
+                    let planes: Vec<&[u8]> = planes.iter().map(Vec::as_slice).collect();
+
+                    let mut got = RgbaImage::new();
+                    frame_to_rgba(&planes, &format, spec, &mut got).unwrap();
+                    let (psnr, max_error) = psnr_and_max_error(got.data(), &want);
+                    let what = format!("{pixel_format} {matrix} {range} padded by {padding}");
+                    assert!(psnr > 38.0, "{what}: PSNR {psnr:.1} dB");
+                    assert!(max_error <= 8, "{what}: off by up to {max_error}");
+                }
+            }
+        }
+    }
+}
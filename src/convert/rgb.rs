@@ -0,0 +1,366 @@
+use super::Rows;
+use crate::v4l2::{FrameFormat, PixelFormat};
+
+/// Where a colour channel sits in a pixel, read as one integer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Channel {
+    pub shift: u8,
+    /// 0 for a missing channel, which for alpha means opaque
+    pub bits: u8,
+}
+
+/// Layout of a packed RGB format. Each pixel is read as a `bytes` wide
+/// integer and the channels are taken out of it with shifts and masks. For the
+/// 24 and 32 bit formats that means a little endian read of the bytes, so the
+/// first byte in memory is the one at shift 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RgbLayout {
+    pub bytes: usize,
+    pub big_endian: bool,
+    /// Red, green, blue and alpha
+    pub channels: [Channel; 4],
+}
+
+const fn c(shift: u8, bits: u8) -> Channel {
+    Channel { shift, bits }
+}
+
+const NONE: Channel = c(0, 0);
+
+const fn le(bytes: usize, r: Channel, g: Channel, b: Channel, a: Channel) -> RgbLayout {
+    RgbLayout {
+        bytes,
+        big_endian: false,
+        channels: [r, g, b, a],
+    }
+}
+
+const fn be(bytes: usize, r: Channel, g: Channel, b: Channel, a: Channel) -> RgbLayout {
+    RgbLayout {
+        big_endian: true,
+        ..le(bytes, r, g, b, a)
+    }
+}
+
+#[rustfmt::skip]
+const FORMATS: [(PixelFormat, RgbLayout); 42] = [
+    (PixelFormat::RGB332, le(1, c(5, 3), c(2, 3), c(0, 2), NONE)),
+
+    // The newer 16 bit names list the channels from the top bit of the little
+    // endian word down
+    (PixelFormat::RGB444, le(2, c(8, 4), c(4, 4), c(0, 4), NONE)),
+    (PixelFormat::ARGB444, le(2, c(8, 4), c(4, 4), c(0, 4), c(12, 4))),
+    (PixelFormat::XRGB444, le(2, c(8, 4), c(4, 4), c(0, 4), NONE)),
+    (PixelFormat::RGBA444, le(2, c(12, 4), c(8, 4), c(4, 4), c(0, 4))),
+    (PixelFormat::RGBX444, le(2, c(12, 4), c(8, 4), c(4, 4), NONE)),
+    (PixelFormat::ABGR444, le(2, c(0, 4), c(4, 4), c(8, 4), c(12, 4))),
+    (PixelFormat::XBGR444, le(2, c(0, 4), c(4, 4), c(8, 4), NONE)),
+    (PixelFormat::BGRA444, le(2, c(4, 4), c(8, 4), c(12, 4), c(0, 4))),
+    (PixelFormat::BGRX444, le(2, c(4, 4), c(8, 4), c(12, 4), NONE)),
+
+    (PixelFormat::RGB555, le(2, c(10, 5), c(5, 5), c(0, 5), NONE)),
+    (PixelFormat::ARGB555, le(2, c(10, 5), c(5, 5), c(0, 5), c(15, 1))),
+    (PixelFormat::XRGB555, le(2, c(10, 5), c(5, 5), c(0, 5), NONE)),
+    (PixelFormat::RGBA555, le(2, c(11, 5), c(6, 5), c(1, 5), c(0, 1))),
+    (PixelFormat::RGBX555, le(2, c(11, 5), c(6, 5), c(1, 5), NONE)),
+    (PixelFormat::ABGR555, le(2, c(0, 5), c(5, 5), c(10, 5), c(15, 1))),
+    (PixelFormat::XBGR555, le(2, c(0, 5), c(5, 5), c(10, 5), NONE)),
+    (PixelFormat::BGRA555, le(2, c(1, 5), c(6, 5), c(11, 5), c(0, 1))),
+    (PixelFormat::BGRX555, le(2, c(1, 5), c(6, 5), c(11, 5), NONE)),
+    (PixelFormat::RGB565, le(2, c(11, 5), c(5, 6), c(0, 5), NONE)),
+    (PixelFormat::RGB555X, be(2, c(10, 5), c(5, 5), c(0, 5), NONE)),
+    (PixelFormat::ARGB555X, be(2, c(10, 5), c(5, 5), c(0, 5), c(15, 1))),
+    (PixelFormat::XRGB555X, be(2, c(10, 5), c(5, 5), c(0, 5), NONE)),
+    (PixelFormat::RGB565X, be(2, c(11, 5), c(5, 6), c(0, 5), NONE)),
+
+    // 6 bits each from the top of the first byte
+    (PixelFormat::BGR666, be(4, c(14, 6), c(20, 6), c(26, 6), NONE)),
+
+    (PixelFormat::BGR24, le(3, c(16, 8), c(8, 8), c(0, 8), NONE)),
+    (PixelFormat::RGB24, le(3, c(0, 8), c(8, 8), c(16, 8), NONE)),
+    // The deprecated BGR32 and RGB32 don't say what their fourth byte is
+    (PixelFormat::BGR32, le(4, c(16, 8), c(8, 8), c(0, 8), NONE)),
+    (PixelFormat::ABGR32, le(4, c(16, 8), c(8, 8), c(0, 8), c(24, 8))),
+    (PixelFormat::XBGR32, le(4, c(16, 8), c(8, 8), c(0, 8), NONE)),
+    (PixelFormat::BGRA32, le(4, c(24, 8), c(16, 8), c(8, 8), c(0, 8))),
+    (PixelFormat::BGRX32, le(4, c(24, 8), c(16, 8), c(8, 8), NONE)),
+    (PixelFormat::RGB32, le(4, c(8, 8), c(16, 8), c(24, 8), NONE)),
+    (PixelFormat::RGBA32, le(4, c(0, 8), c(8, 8), c(16, 8), c(24, 8))),
+    (PixelFormat::RGBX32, le(4, c(0, 8), c(8, 8), c(16, 8), NONE)),
+    (PixelFormat::ARGB32, le(4, c(8, 8), c(16, 8), c(24, 8), c(0, 8))),
+    (PixelFormat::XRGB32, le(4, c(8, 8), c(16, 8), c(24, 8), NONE)),
+
+    (PixelFormat::RGBX1010102, le(4, c(22, 10), c(12, 10), c(2, 10), NONE)),
+    (PixelFormat::RGBA1010102, le(4, c(22, 10), c(12, 10), c(2, 10), c(0, 2))),
+    (PixelFormat::ARGB2101010, le(4, c(20, 10), c(10, 10), c(0, 10), c(30, 2))),
+
+    // 16 bit little endian components with the data in the high bits
+    (PixelFormat::BGR48_12, le(6, c(36, 12), c(20, 12), c(4, 12), NONE)),
+    (PixelFormat::ABGR64_12, le(8, c(36, 12), c(20, 12), c(4, 12), c(52, 12))),
+];
+
+impl RgbLayout {
+    pub fn from_pixel_format(format: PixelFormat) -> Option<Self> {
+        FORMATS
+            .iter()
+            .find(|(f, _)| *f == format)
+            .map(|(_, layout)| *layout)
+    }
+
+    /// The pixel at the start of `src`, which has to hold `bytes` bytes.
+    pub fn read(&self, src: &[u8]) -> u64 {
+        let bytes = &src[..self.bytes];
+        if self.big_endian {
+            bytes.iter().fold(0, |word, b| word << 8 | *b as u64)
+        } else {
+            bytes.iter().rev().fold(0, |word, b| word << 8 | *b as u64)
+        }
+    }
+
+    // The byte of each channel, if every channel is a whole byte
+    fn byte_offsets(&self) -> Option<[Option<usize>; 4]> {
+        let mut offsets = [None; 4];
+        for (offset, channel) in offsets.iter_mut().zip(self.channels) {
+            match channel.bits {
+                0 => {}
+                8 if channel.shift % 8 == 0 && !self.big_endian => {
+                    *offset = Some(channel.shift as usize / 8)
+                }
+                _ => return None,
+            }
+        }
+        Some(offsets)
+    }
+
+    /// Convert one pixel to RGBA8.
+    pub fn to_rgba(&self, word: u64) -> [u8; 4] {
+        self.channels.map(|Channel { shift, bits }| {
+            if bits == 0 {
+                return 255;
+            }
+            to_8_bits((word >> shift) & ((1 << bits) - 1), bits)
+        })
+    }
+}
+
+fn to_8_bits(value: u64, bits: u8) -> u8 {
+    if bits >= 8 {
+        (value >> (bits - 8)) as u8
+    } else {
+        // Scaled so the maximum is 255 rather than just shifted
+        let max = (1 << bits) - 1;
+        ((value * 255 + max / 2) / max) as u8
+    }
+}
+
+// `RgbLayout::to_rgba` for one channel, with the scaling looked up
+struct Extract {
+    shift: u32,
+    mask: u64,
+    // How far to shift channels wider than 8 bits down
+    down: u32,
+    // A missing channel always comes out as scale[0], which is opaque
+    scale: [u8; 256],
+}
+
+impl Extract {
+    fn new(Channel { shift, bits }: Channel) -> Self {
+        let mut scale = [255; 256];
+        if bits > 0 {
+            let bits = bits.min(8);
+            for (value, out) in scale.iter_mut().enumerate().take(1 << bits) {
+                *out = to_8_bits(value as u64, bits);
+            }
+        }
+        Self {
+            shift: shift as u32,
+            mask: (1 << bits) - 1,
+            down: bits.saturating_sub(8) as u32,
+            scale,
+        }
+    }
+
+    fn get(&self, word: u64) -> u8 {
+        self.scale[(((word >> self.shift) & self.mask) >> self.down) as usize & 255]
+    }
+}
+
+pub(super) struct RgbRows<'a> {
+    layout: RgbLayout,
+    channels: [Extract; 4],
+    // Where each channel's byte is, when they are all whole bytes
+    bytes: Option<[Option<usize>; 4]>,
+    src: &'a [u8],
+    width: usize,
+    stride: usize,
+}
+
+impl<'a> RgbRows<'a> {
+    pub(super) fn new(layout: RgbLayout, src: &'a [u8], format: &FrameFormat) -> Self {
+        let stride = if format.bytes_per_line == 0 {
+            format.width * layout.bytes
+        } else {
+            format.bytes_per_line
+        };
+        Self {
+            layout,
+            channels: layout.channels.map(Extract::new),
+            bytes: layout.byte_offsets(),
+            src,
+            width: format.width,
+            stride,
+        }
+    }
+
+    // With the pixel size known at compile time the reads become plain loads
+    fn convert_rows<const BYTES: usize>(&self, first_row: usize, dst: &mut [u8]) {
+        for (row, dst_row) in (first_row..).zip(dst.chunks_exact_mut(self.width * 4)) {
+            let src = self.src.get(row * self.stride..).unwrap_or_default();
+            // A short frame leaves the rows past its end as they were
+            if src.len() < self.width * BYTES {
+                break;
+            }
+
+            let pixels = src.chunks_exact(BYTES).zip(dst_row.chunks_exact_mut(4));
+            if let Some(offsets) = self.bytes {
+                // A missing channel reads the 255 just past the pixel
+                let [r, g, b, a] = offsets.map(|offset| offset.unwrap_or(BYTES));
+                let mut padded = [255; 9];
+                for (pixel, rgba) in pixels {
+                    padded[..BYTES].copy_from_slice(pixel);
+                    rgba.copy_from_slice(&[padded[r], padded[g], padded[b], padded[a]]);
+                }
+                continue;
+            }
+            for (pixel, rgba) in pixels {
+                let mut word = [0; 8];
+                let word = if self.layout.big_endian {
+                    word[8 - BYTES..].copy_from_slice(pixel);
+                    u64::from_be_bytes(word)
+                } else {
+                    word[..BYTES].copy_from_slice(pixel);
+                    u64::from_le_bytes(word)
+                };
+                let [r, g, b, a] = &self.channels;
+                rgba.copy_from_slice(&[r.get(word), g.get(word), b.get(word), a.get(word)]);
+            }
+        }
+    }
+}
+
+impl Rows for RgbRows<'_> {
+    fn convert(&self, first_row: usize, dst: &mut [u8]) {
+        match self.layout.bytes {
+            1 => self.convert_rows::<1>(first_row, dst),
+            2 => self.convert_rows::<2>(first_row, dst),
+            3 => self.convert_rows::<3>(first_row, dst),
+            4 => self.convert_rows::<4>(first_row, dst),
+            6 => self.convert_rows::<6>(first_row, dst),
+            _ => self.convert_rows::<8>(first_row, dst),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::convert::{frame_to_rgba, ColorSpec, RgbaImage};
+
+    // One pixel in memory order, as the V4L2 documentation lays them out,
+    // and what it should come out as. Red is 0x11, green 0x22, blue 0x33,
+    // alpha 0x44 and padding 0x99 wherever a channel is a byte of its own.
+    // The bits of the smaller ones are grouped by channel.
+    #[rustfmt::skip]
+    #[allow(clippy::unusual_byte_groupings)]
+    const PIXELS: &[(PixelFormat, &[u8], [u8; 4])] = &[
+        (PixelFormat::RGB24, &[0x11, 0x22, 0x33], [0x11, 0x22, 0x33, 255]),
+        (PixelFormat::BGR24, &[0x33, 0x22, 0x11], [0x11, 0x22, 0x33, 255]),
+        (PixelFormat::BGR32, &[0x33, 0x22, 0x11, 0x99], [0x11, 0x22, 0x33, 255]),
+        (PixelFormat::ABGR32, &[0x33, 0x22, 0x11, 0x44], [0x11, 0x22, 0x33, 0x44]),
+        (PixelFormat::XBGR32, &[0x33, 0x22, 0x11, 0x99], [0x11, 0x22, 0x33, 255]),
+        (PixelFormat::BGRA32, &[0x44, 0x33, 0x22, 0x11], [0x11, 0x22, 0x33, 0x44]),
+        (PixelFormat::BGRX32, &[0x99, 0x33, 0x22, 0x11], [0x11, 0x22, 0x33, 255]),
+        (PixelFormat::RGB32, &[0x99, 0x11, 0x22, 0x33], [0x11, 0x22, 0x33, 255]),
+        (PixelFormat::RGBA32, &[0x11, 0x22, 0x33, 0x44], [0x11, 0x22, 0x33, 0x44]),
+        (PixelFormat::RGBX32, &[0x11, 0x22, 0x33, 0x99], [0x11, 0x22, 0x33, 255]),
+        (PixelFormat::ARGB32, &[0x44, 0x11, 0x22, 0x33], [0x11, 0x22, 0x33, 0x44]),
+        (PixelFormat::XRGB32, &[0x99, 0x11, 0x22, 0x33], [0x11, 0x22, 0x33, 255]),
+        // rrrgggbb
+        (PixelFormat::RGB332, &[0b111_010_01], [255, 73, 85, 255]),
+        // Little endian rrrrrggg gggbbbbb, and big endian for the X variant
+        (PixelFormat::RGB565, &[0b000_11111, 0b11111_000], [255, 0, 255, 255]),
+        (PixelFormat::RGB565X, &[0b11111_000, 0b000_11111], [255, 0, 255, 255]),
+        // Little endian arrrrrgg gggbbbbb
+        (PixelFormat::ARGB555, &[0b111_00000, 0b1_00000_11], [0, 255, 0, 255]),
+        (PixelFormat::ARGB555, &[0b000_11111, 0b0_00000_00], [0, 0, 255, 0]),
+        (PixelFormat::XRGB555, &[0b000_00000, 0b1_11111_00], [255, 0, 0, 255]),
+        (PixelFormat::ARGB555X, &[0b1_11111_00, 0b000_00000], [255, 0, 0, 255]),
+        (PixelFormat::RGB555, &[0b000_00001, 0b0_11111_10], [255, 132, 8, 255]),
+        (PixelFormat::RGB555X, &[0b0_11111_10, 0b000_00001], [255, 132, 8, 255]),
+        (PixelFormat::XRGB555X, &[0b0_11111_10, 0b000_00001], [255, 132, 8, 255]),
+        // Little endian rrrrrggg ggbbbbba
+        (PixelFormat::RGBA555, &[0b00_00001_0, 0b11111_100], [255, 132, 8, 0]),
+        (PixelFormat::RGBX555, &[0b00_00001_0, 0b11111_100], [255, 132, 8, 255]),
+        // Little endian abbbbbgg gggrrrrr
+        (PixelFormat::ABGR555, &[0b000_11111, 0b1_00001_10], [255, 132, 8, 255]),
+        (PixelFormat::XBGR555, &[0b000_11111, 0b0_00001_10], [255, 132, 8, 255]),
+        // Little endian bbbbbggg ggrrrrra
+        (PixelFormat::BGRA555, &[0b00_11111_0, 0b00001_100], [255, 132, 8, 0]),
+        (PixelFormat::BGRX555, &[0b00_11111_0, 0b00001_100], [255, 132, 8, 255]),
+        // Little endian aaaarrrr ggggbbbb
+        (PixelFormat::ARGB444, &[0x0f, 0x80], [0, 0, 255, 136]),
+        (PixelFormat::RGBA444, &[0x0f, 0xf0], [255, 0, 0, 255]),
+        // The rest with red 1, green 2, blue 3, alpha 4 and padding 9
+        (PixelFormat::RGB444, &[0x23, 0x91], [17, 34, 51, 255]),
+        (PixelFormat::XRGB444, &[0x23, 0x91], [17, 34, 51, 255]),
+        (PixelFormat::RGBX444, &[0x39, 0x12], [17, 34, 51, 255]),
+        (PixelFormat::ABGR444, &[0x21, 0x43], [17, 34, 51, 68]),
+        (PixelFormat::XBGR444, &[0x21, 0x93], [17, 34, 51, 255]),
+        (PixelFormat::BGRA444, &[0x14, 0x32], [17, 34, 51, 68]),
+        (PixelFormat::BGRX444, &[0x19, 0x32], [17, 34, 51, 255]),
+        // Big endian bbbbbbgg ggggrrrr rrxxxxxx xxxxxxxx
+        (PixelFormat::BGR666, &[0b111111_10, 0b0000_0000, 0b01_000000, 0x00], [4, 130, 255, 255]),
+        // Little endian rrrrrrrr rrgggggg ggggbbbb bbbbbbxx
+        (PixelFormat::RGBX1010102, &[0x00, 0x00, 0xc0, 0xff], [255, 0, 0, 255]),
+        (PixelFormat::RGBA1010102, &[0xfe, 0x0f, 0x00, 0x00], [0, 0, 255, 170]),
+        // Little endian aabbbbbb bbbbgggg ggggggrr rrrrrrrr
+        (PixelFormat::ARGB2101010, &[0x00, 0xfc, 0x0f, 0xc0], [0, 255, 0, 255]),
+        // Little endian 16 bit blue, green and red, with 12 bits at the top
+        (PixelFormat::BGR48_12, &[0xf0, 0xff, 0x00, 0x00, 0x00, 0x80], [0x80, 0, 255, 255]),
+        (PixelFormat::ABGR64_12, &[0xf0, 0xff, 0x00, 0x00, 0x00, 0x80, 0x40, 0x44], [0x80, 0, 255, 0x44]),
+    ];
+
+    #[test]
+    fn every_format_is_checked() {
+        for (pixel_format, _) in FORMATS {
+            assert!(
+                PIXELS.iter().any(|&(f, _, _)| f == pixel_format),
+                "no pixel for {pixel_format}"
+            );
+        }
+    }
+
+    #[test]
+    fn channel_order() {
+        for &(pixel_format, pixel, want) in PIXELS {
+            let layout = RgbLayout::from_pixel_format(pixel_format).unwrap();
+            assert_eq!(layout.bytes, pixel.len(), "{pixel_format}");
+            assert_eq!(layout.to_rgba(layout.read(pixel)), want, "{pixel_format}");
+
+            // And through the row converter, which has its own fast paths
+            let (width, height) = (3, 2);
+            let format = FrameFormat {
+                pixel_format,
+                width,
+                height,
+                ..Default::default()
+            };
+            let data = pixel.repeat(width * height);
+            let mut image = RgbaImage::new();
+            frame_to_rgba(&[&data], &format, ColorSpec::default(), &mut image).unwrap();
+            for got in image.data().chunks_exact(4) {
+                assert_eq!(got, want, "{pixel_format}");
+            }
+        }
+    }
+}
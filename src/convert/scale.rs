@@ -0,0 +1,750 @@
+use std::{cell::RefCell, f64::consts::PI, fmt, rc::Rc};
+
+use super::{ConvertError, Packed422, Planar, RgbaImage};
+use crate::v4l2::{FrameFormat, PixelFormat};
+
+/// How pixels are resampled when scaling.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Filter {
+    /// The source pixel under the centre of each output pixel
+    Nearest,
+    /// Linear interpolation between the 2x2 nearest, widened when shrinking
+    /// so every source pixel still counts
+    Bilinear,
+    /// The average of the source pixels each output pixel covers, weighted by
+    /// how much of them it covers. The one for thumbnails.
+    #[default]
+    Area,
+    /// Windowed sinc with 3 lobes. Sharpest, at the cost of some ringing.
+    Lanczos3,
+}
+
+impl Filter {
+    pub const ALL: [Filter; 4] = [
+        Filter::Nearest,
+        Filter::Bilinear,
+        Filter::Area,
+        Filter::Lanczos3,
+    ];
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Filter::Nearest => "nearest",
+            Filter::Bilinear => "bilinear",
+            Filter::Area => "area",
+            Filter::Lanczos3 => "Lanczos",
+        })
+    }
+}
+
+// Weights are fixed point with this many fractional bits
+const PRECISION: u32 = 14;
+
+// For each output pixel, which source pixels it takes and by how much
+struct Weights {
+    taps: usize,
+    // First source pixel of each output pixel
+    starts: Vec<usize>,
+    // `taps` weights per output pixel, summing to 1 << PRECISION
+    weights: Vec<i32>,
+}
+
+fn sinc(x: f64) -> f64 {
+    if x == 0.0 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+impl Weights {
+    fn new(filter: Filter, src: usize, dst: usize) -> Self {
+        let scale = src as f64 / dst as f64;
+        // Shrinking stretches the filter over the source, so nothing gets
+        // skipped
+        let stretch = scale.max(1.0);
+        let (radius, kernel): (f64, fn(f64) -> f64) = match filter {
+            Filter::Lanczos3 => (3.0, |x| sinc(x) * sinc(x / 3.0)),
+            _ => (1.0, |x| 1.0 - x.abs()),
+        };
+
+        let mut ranges = Vec::with_capacity(dst);
+        for i in 0..dst {
+            let center = (i as f64 + 0.5) * scale;
+            let row: Vec<(usize, f64)> = match filter {
+                Filter::Nearest => vec![((center as usize).min(src - 1), 1.0)],
+                // How much of each source pixel the output pixel covers
+                Filter::Area => {
+                    let (from, to) = (i as f64 * scale, (i + 1) as f64 * scale);
+                    (from.floor() as usize..(to.ceil() as usize).min(src))
+                        .map(|j| (j, to.min(j as f64 + 1.0) - from.max(j as f64)))
+                        .collect()
+                }
+                Filter::Bilinear | Filter::Lanczos3 => {
+                    let support = radius * stretch;
+                    let first = (center - support).floor().max(0.0) as usize;
+                    let last = ((center + support).ceil() as usize).min(src);
+                    (first..last)
+                        .map(|j| (j as f64 + 0.5 - center) / stretch)
+                        .map(|x| if x.abs() < radius { kernel(x) } else { 0.0 })
+                        .enumerate()
+                        .map(|(t, w)| (first + t, w))
+                        .collect()
+                }
+            };
+            // Only what's nonzero, so the edges don't cost any extra taps
+            let first = row.iter().position(|(_, w)| *w != 0.0).unwrap_or(0);
+            let last = row.iter().rposition(|(_, w)| *w != 0.0).unwrap_or(0);
+            ranges.push(row[first..=last].to_vec());
+        }
+        let taps = ranges.iter().map(Vec::len).max().unwrap_or(1);
+
+        let mut starts = Vec::with_capacity(dst);
+        let mut weights = vec![0; dst * taps];
+        for (row, out) in ranges.iter().zip(weights.chunks_exact_mut(taps)) {
+            // Near the edges part of the filter falls outside the source,
+            // which is made up for by scaling up the rest
+            let sum: f64 = row.iter().map(|(_, w)| w).sum();
+            let start = row[0].0.min(src - taps.min(src));
+            starts.push(start);
+            let one = (1 << PRECISION) as f64;
+            for &(j, w) in row {
+                out[j - start] = (w / sum * one).round() as i32;
+            }
+            // Rounding mustn't make flat areas any lighter or darker
+            let error = (1 << PRECISION) - out.iter().sum::<i32>();
+            let largest = (0..taps).max_by_key(|&t| out[t]).unwrap_or(0);
+            out[largest] += error;
+        }
+
+        Self {
+            taps,
+            starts,
+            weights,
+        }
+    }
+
+    fn get(&self, i: usize) -> (usize, &[i32]) {
+        (
+            self.starts[i],
+            &self.weights[i * self.taps..(i + 1) * self.taps],
+        )
+    }
+}
+
+// How many sets of weights each thread keeps, enough for the luma and chroma
+// of a frame both ways
+const CACHED_WEIGHTS: usize = 8;
+
+// What each thread keeps from one resize to the next, as the sizes seldom
+// change from frame to frame
+#[derive(Default)]
+struct Scratch {
+    // Most recently used last
+    weights: Vec<((Filter, usize, usize), Rc<Weights>)>,
+    // The rows once scaled across
+    narrowed: Vec<u8>,
+    // The column sums of a row on the way down
+    sums: Vec<i32>,
+}
+
+thread_local! {
+    static SCRATCH: RefCell<Scratch> = RefCell::default();
+    // Packed 4:2:2 split up into Y', Cb and Cr, before and after scaling
+    static PLANES: RefCell<[Vec<u8>; 6]> = RefCell::default();
+}
+
+impl Scratch {
+    fn weights(&mut self, filter: Filter, src: usize, dst: usize) -> Rc<Weights> {
+        let key = (filter, src, dst);
+        let weights = match self.weights.iter().position(|(k, _)| *k == key) {
+            Some(i) => self.weights.remove(i).1,
+            None => Rc::new(Weights::new(filter, src, dst)),
+        };
+        if self.weights.len() == CACHED_WEIGHTS {
+            self.weights.remove(0);
+        }
+        self.weights.push((key, weights.clone()));
+        weights
+    }
+}
+
+fn to_u8(sum: i32) -> u8 {
+    ((sum + (1 << (PRECISION - 1))) >> PRECISION).clamp(0, 255) as u8
+}
+
+// Each row of `src` across to `dst_width`
+fn horizontal<const CHANNELS: usize>(
+    weights: &Weights,
+    src: &[u8],
+    src_stride: usize,
+    rows: usize,
+    dst: &mut [u8],
+    dst_width: usize,
+) {
+    let dst_stride = dst_width * CHANNELS;
+    for (row, dst_row) in dst.chunks_exact_mut(dst_stride).take(rows).enumerate() {
+        let src_row = &src[row * src_stride..];
+        for (x, out) in dst_row.chunks_exact_mut(CHANNELS).enumerate() {
+            let (start, taps) = weights.get(x);
+            let pixels = src_row[start * CHANNELS..].chunks_exact(CHANNELS);
+            let mut sums = [0i32; CHANNELS];
+            for (pixel, w) in pixels.zip(taps) {
+                for (sum, v) in sums.iter_mut().zip(pixel) {
+                    *sum += *v as i32 * w;
+                }
+            }
+            for (out, sum) in out.iter_mut().zip(sums) {
+                *out = to_u8(sum);
+            }
+        }
+    }
+}
+
+// Each column of `src` down to as many rows as `weights` has
+fn vertical(
+    weights: &Weights,
+    src: &[u8],
+    row_bytes: usize,
+    dst: &mut [u8],
+    dst_stride: usize,
+    sums: &mut Vec<i32>,
+) {
+    sums.resize(row_bytes, 0);
+    for (y, dst_row) in dst
+        .chunks_mut(dst_stride)
+        .enumerate()
+        .take(weights.starts.len())
+    {
+        let (start, taps) = weights.get(y);
+        sums.fill(0);
+        for (t, w) in taps.iter().enumerate() {
+            let src_row = &src[(start + t) * row_bytes..][..row_bytes];
+            for (sum, v) in sums.iter_mut().zip(src_row) {
+                *sum += *v as i32 * w;
+            }
+        }
+        for (out, sum) in dst_row.iter_mut().zip(sums.iter()) {
+            *out = to_u8(*sum);
+        }
+    }
+}
+
+/// Scale one plane of `channels` interleaved 8 bit components, 1 to 4 of
+/// them, each of which is filtered on its own. Strides are in bytes.
+///
+/// # Panics
+///
+/// If `src` or `dst` is too short for its size and stride.
+#[allow(clippy::too_many_arguments)]
+pub fn resize_plane(
+    src: &[u8],
+    src_width: usize,
+    src_height: usize,
+    src_stride: usize,
+    channels: usize,
+    dst: &mut [u8],
+    dst_width: usize,
+    dst_height: usize,
+    dst_stride: usize,
+    filter: Filter,
+) {
+    if src_width == 0 || src_height == 0 || dst_width == 0 || dst_height == 0 {
+        return;
+    }
+    assert!(src.len() >= src_stride * (src_height - 1) + src_width * channels);
+    assert!(dst.len() >= dst_stride * (dst_height - 1) + dst_width * channels);
+
+    SCRATCH.with_borrow_mut(|scratch| {
+        let across = scratch.weights(filter, src_width, dst_width);
+        let down = scratch.weights(filter, src_height, dst_height);
+
+        // Across first, then down through the narrowed rows
+        let row_bytes = dst_width * channels;
+        scratch.narrowed.resize(row_bytes * src_height, 0);
+        let pass = match channels {
+            1 => horizontal::<1>,
+            2 => horizontal::<2>,
+            3 => horizontal::<3>,
+            _ => horizontal::<4>,
+        };
+        pass(
+            &across,
+            src,
+            src_stride,
+            src_height,
+            &mut scratch.narrowed,
+            dst_width,
+        );
+        vertical(
+            &down,
+            &scratch.narrowed,
+            row_bytes,
+            dst,
+            dst_stride,
+            &mut scratch.sums,
+        );
+    })
+}
+
+/// Scale `src` into `dst`, which gets resized to `width` by `height`.
+pub fn resize_rgba(
+    src: &RgbaImage,
+    dst: &mut RgbaImage,
+    width: usize,
+    height: usize,
+    filter: Filter,
+) {
+    dst.resize(width, height);
+    resize_plane(
+        src.data(),
+        src.width(),
+        src.height(),
+        src.width() * 4,
+        4,
+        dst.data_mut(),
+        width,
+        height,
+        width * 4,
+        filter,
+    );
+}
+
+/// Scale a packed 4:2:2, planar or semi-planar Y'CbCr or a `GREY` frame to
+/// `width` by `height`, one plane at a time. The result goes into `dst` in
+/// the same pixel format, tightly packed with all the planes in the one
+/// buffer, and its format is returned.
+pub fn resize_frame(
+    planes: &[&[u8]],
+    format: &FrameFormat,
+    width: usize,
+    height: usize,
+    filter: Filter,
+    dst: &mut Vec<u8>,
+) -> Result<FrameFormat, ConvertError> {
+    let planar = Planar::from_pixel_format(format.pixel_format);
+    let packed = Packed422::from_pixel_format(format.pixel_format);
+    if planar.is_none() && packed.is_none() && format.pixel_format != PixelFormat::GREY {
+        return Err(ConvertError::UnsupportedFormat(format.pixel_format));
+    }
+    let or = |stride: usize, default: usize| if stride == 0 { default } else { stride };
+    let plane = |i: usize| planes.get(i).copied().unwrap_or_default();
+    // A short frame would have the planes in the wrong place
+    let too_short = || ConvertError::CorruptFrame("frame too short to scale".into());
+
+    let y_stride = or(format.bytes_per_line, format.width);
+    let luma_size = y_stride * format.height;
+    let mut scaled = FrameFormat {
+        width,
+        height,
+        bytes_per_line: 0,
+        num_planes: 1,
+        ..*format
+    };
+    scaled.planes = Default::default();
+
+    if let Some(order) = packed {
+        resize_packed422(order, plane(0), format, width, height, filter, dst)?;
+        return Ok(scaled);
+    }
+
+    let Some(layout) = planar else {
+        dst.resize(width * height, 0);
+        let src = plane(0).get(..luma_size).ok_or_else(too_short)?;
+        resize_plane(
+            src,
+            format.width,
+            format.height,
+            y_stride,
+            1,
+            dst,
+            width,
+            height,
+            width,
+            filter,
+        );
+        return Ok(scaled);
+    };
+
+    // As in `PlanarRows::new`
+    let chroma = |width: usize, height: usize| {
+        (
+            width.div_ceil(2),
+            height.div_ceil(layout.vertical_subsampling),
+        )
+    };
+    let (src_cw, src_ch) = chroma(format.width, format.height);
+    let (dst_cw, dst_ch) = chroma(width, height);
+    let (components, chroma_planes) = if layout.semi_planar { (2, 1) } else { (1, 2) };
+    let src_chroma_stride = if format.num_planes > 1 {
+        None
+    } else if layout.semi_planar {
+        Some(or(format.bytes_per_line, src_cw * 2))
+    } else {
+        Some(or(format.bytes_per_line / 2, src_cw))
+    };
+
+    let dst_luma = width * height;
+    let dst_chroma = dst_cw * components * dst_ch;
+    dst.resize(dst_luma + dst_chroma * chroma_planes, 0);
+    let (dst_y, dst_c) = dst.split_at_mut(dst_luma);
+
+    let src_y = plane(0).get(..luma_size).ok_or_else(too_short)?;
+    resize_plane(
+        src_y,
+        format.width,
+        format.height,
+        y_stride,
+        1,
+        dst_y,
+        width,
+        height,
+        width,
+        filter,
+    );
+
+    for (i, dst_c) in dst_c.chunks_exact_mut(dst_chroma).enumerate() {
+        let (src, stride) = match src_chroma_stride {
+            Some(stride) => {
+                let offset = luma_size + i * stride * src_ch;
+                (plane(0).get(offset..).unwrap_or_default(), stride)
+            }
+            None => {
+                let stride = or(format.planes[i + 1].bytes_per_line, src_cw * components);
+                (plane(i + 1), stride)
+            }
+        };
+        if src.len() < stride * (src_ch - 1) + src_cw * components {
+            return Err(too_short());
+        }
+        resize_plane(
+            src,
+            src_cw,
+            src_ch,
+            stride,
+            components,
+            dst_c,
+            dst_cw,
+            dst_ch,
+            dst_cw * components,
+            filter,
+        );
+    }
+    Ok(scaled)
+}
+
+// Packed 4:2:2 is split up into planes, scaled as planar 4:2:2 would be and
+// packed again. An odd width leaves the last Y'1 of every row unused.
+fn resize_packed422(
+    order: Packed422,
+    src: &[u8],
+    format: &FrameFormat,
+    width: usize,
+    height: usize,
+    filter: Filter,
+    dst: &mut Vec<u8>,
+) -> Result<(), ConvertError> {
+    let (src_pairs, dst_pairs) = (format.width.div_ceil(2), width.div_ceil(2));
+    dst.resize(dst_pairs * 4 * height, 0);
+    if format.width == 0 || format.height == 0 || width == 0 || height == 0 {
+        return Ok(());
+    }
+    let stride = if format.bytes_per_line == 0 {
+        src_pairs * 4
+    } else {
+        format.bytes_per_line
+    };
+    if src.len() < stride * (format.height - 1) + src_pairs * 4 {
+        return Err(ConvertError::CorruptFrame(
+            "frame too short to scale".into(),
+        ));
+    }
+
+    PLANES.with_borrow_mut(|planes| {
+        let (src_planes, dst_planes) = planes.split_at_mut(3);
+        for (plane, len) in src_planes.iter_mut().zip([2, 1, 1]) {
+            plane.resize(src_pairs * len * format.height, 0);
+        }
+        for (y, row) in src.chunks(stride).take(format.height).enumerate() {
+            for (x, pair) in row.chunks_exact(4).take(src_pairs).enumerate() {
+                let i = y * src_pairs + x;
+                src_planes[0][i * 2] = pair[order.y0];
+                src_planes[0][i * 2 + 1] = pair[order.y1];
+                src_planes[1][i] = pair[order.u];
+                src_planes[2][i] = pair[order.v];
+            }
+        }
+
+        let sizes = [
+            (format.width, src_pairs * 2, width, dst_pairs * 2),
+            (src_pairs, src_pairs, dst_pairs, dst_pairs),
+            (src_pairs, src_pairs, dst_pairs, dst_pairs),
+        ];
+        for ((src, dst), (src_width, src_stride, dst_width, dst_stride)) in
+            src_planes.iter().zip(dst_planes.iter_mut()).zip(sizes)
+        {
+            dst.resize(dst_stride * height, 0);
+            resize_plane(
+                src,
+                src_width,
+                format.height,
+                src_stride,
+                1,
+                dst,
+                dst_width,
+                height,
+                dst_stride,
+                filter,
+            );
+        }
+
+        let [luma, cb, cr] = [&dst_planes[0], &dst_planes[1], &dst_planes[2]];
+        for (i, pair) in dst.chunks_exact_mut(4).enumerate() {
+            pair[order.y0] = luma[i * 2];
+            pair[order.y1] = luma[i * 2 + 1];
+            pair[order.u] = cb[i];
+            pair[order.v] = cr[i];
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resize_row(src: &[u8], width: usize, filter: Filter) -> Vec<u8> {
+        let mut dst = vec![0; width];
+        resize_plane(
+            src,
+            src.len(),
+            1,
+            src.len(),
+            1,
+            &mut dst,
+            width,
+            1,
+            width,
+            filter,
+        );
+        dst
+    }
+
+    fn format(pixel_format: PixelFormat, width: usize, height: usize) -> FrameFormat {
+        FrameFormat {
+            pixel_format,
+            width,
+            height,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn known_rows() {
+        let cases: [(&[u8], usize, Filter, &[u8]); 7] = [
+            (&[10, 20, 30, 50], 2, Filter::Nearest, &[20, 50]),
+            (&[10, 20, 30, 50], 2, Filter::Area, &[15, 40]),
+            // The triangle widened to two source pixels either side
+            (&[10, 20, 30, 50], 2, Filter::Bilinear, &[17, 37]),
+            (&[10, 20, 30, 50], 1, Filter::Area, &[28]),
+            (&[0, 100], 4, Filter::Nearest, &[0, 0, 100, 100]),
+            (&[0, 100], 4, Filter::Bilinear, &[0, 25, 75, 100]),
+            (&[0, 90, 30], 2, Filter::Area, &[30, 50]),
+        ];
+        for (src, width, filter, want) in cases {
+            assert_eq!(
+                resize_row(src, width, filter),
+                want,
+                "{src:?} to {width} {filter}"
+            );
+        }
+    }
+
+    #[test]
+    fn same_size_is_a_copy() {
+        let src: Vec<u8> = (0..13 * 7).map(|i| (i * 37 % 256) as u8).collect();
+        for filter in Filter::ALL {
+            let mut dst = vec![0; src.len()];
+            resize_plane(&src, 13, 7, 13, 1, &mut dst, 13, 7, 13, filter);
+            assert_eq!(dst, src, "{filter}");
+        }
+    }
+
+    #[test]
+    fn flat_stays_flat() {
+        // Lanczos rings, but not on a flat picture
+        let src = [77u8, 140, 3, 255].repeat(9 * 5);
+        for filter in Filter::ALL {
+            for (width, height) in [(1, 1), (4, 3), (9, 5), (23, 11)] {
+                let mut dst = vec![0; width * height * 4];
+                resize_plane(
+                    &src,
+                    9,
+                    5,
+                    36,
+                    4,
+                    &mut dst,
+                    width,
+                    height,
+                    width * 4,
+                    filter,
+                );
+                for pixel in dst.chunks_exact(4) {
+                    assert_eq!(pixel, [77, 140, 3, 255], "{filter} {width}x{height}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn honours_strides() {
+        // Two rows of two pixels, padded to 5 bytes
+        let src = [10, 30, 99, 99, 99, 50, 70, 99, 99, 99];
+        let mut dst = [0; 6];
+        resize_plane(&src, 2, 2, 5, 1, &mut dst, 1, 1, 6, Filter::Area);
+        assert_eq!(dst, [40, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn resizes_rgba() {
+        let mut src = RgbaImage::new();
+        src.resize(2, 2);
+        src.data_mut().copy_from_slice(&[
+            0, 0, 0, 255, 100, 0, 0, 255, //
+            0, 200, 0, 255, 0, 0, 40, 255,
+        ]);
+        let mut dst = RgbaImage::new();
+        resize_rgba(&src, &mut dst, 1, 1, Filter::Area);
+        assert_eq!((dst.width(), dst.height()), (1, 1));
+        assert_eq!(dst.data(), [25, 50, 10, 255]);
+    }
+
+    #[test]
+    fn resizes_grey_and_planar_frames() {
+        // 4x2 of luma 10, 20, 30, 50 on every row
+        let grey = [10, 20, 30, 50].repeat(2);
+        let mut dst = Vec::new();
+        let scaled = resize_frame(
+            &[&grey],
+            &format(PixelFormat::GREY, 4, 2),
+            2,
+            1,
+            Filter::Area,
+            &mut dst,
+        )
+        .unwrap();
+        assert_eq!((scaled.width, scaled.height), (2, 1));
+        assert_eq!(dst, [15, 40]);
+
+        // The same luma with one Cb/Cr pair for the lot
+        let mut nv12 = grey.clone();
+        nv12.extend([60, 200, 60, 200]);
+        let scaled = resize_frame(
+            &[&nv12],
+            &format(PixelFormat::NV12, 4, 2),
+            2,
+            1,
+            Filter::Area,
+            &mut dst,
+        )
+        .unwrap();
+        assert_eq!(scaled.pixel_format, PixelFormat::NV12);
+        assert_eq!(dst, [15, 40, 60, 200]);
+
+        // And as three planes of their own
+        let (cb, cr) = ([60, 60], [200, 200]);
+        let mut yuv420m = format(PixelFormat::YUV420M, 4, 2);
+        yuv420m.num_planes = 3;
+        resize_frame(&[&grey, &cb, &cr], &yuv420m, 2, 1, Filter::Area, &mut dst).unwrap();
+        assert_eq!(dst, [15, 40, 60, 200]);
+    }
+
+    #[test]
+    fn resizes_packed_422_in_every_order() {
+        let orders = [
+            (PixelFormat::YUYV, Packed422::YUYV),
+            (PixelFormat::UYVY, Packed422::UYVY),
+            (PixelFormat::YVYU, Packed422::YVYU),
+            (PixelFormat::VYUY, Packed422::VYUY),
+            (PixelFormat::YYUV, Packed422::YYUV),
+        ];
+        let pack = |order: Packed422, luma: &[u8], cb: &[u8], cr: &[u8]| {
+            let mut packed = vec![0; cb.len() * 4];
+            for (i, pair) in packed.chunks_exact_mut(4).enumerate() {
+                pair[order.y0] = luma[i * 2];
+                pair[order.y1] = luma[i * 2 + 1];
+                pair[order.u] = cb[i];
+                pair[order.v] = cr[i];
+            }
+            packed
+        };
+
+        for (pixel_format, order) in orders {
+            // 4x2 with a luma ramp and chroma changing from left to right
+            let src = pack(
+                order,
+                &[10, 20, 30, 50, 10, 20, 30, 50],
+                &[40, 80, 40, 80],
+                &[200, 100, 200, 100],
+            );
+            let mut dst = Vec::new();
+            let scaled = resize_frame(
+                &[&src],
+                &format(pixel_format, 4, 2),
+                2,
+                1,
+                Filter::Area,
+                &mut dst,
+            )
+            .unwrap();
+            assert_eq!(scaled.pixel_format, pixel_format);
+            assert_eq!((scaled.width, scaled.height), (2, 1));
+            assert_eq!(dst, pack(order, &[15, 40], &[60], &[150]), "{pixel_format}");
+
+            // Growing a flat frame to an odd width keeps it flat
+            let flat = pack(order, &[90; 8], &[30; 4], &[220; 4]);
+            resize_frame(
+                &[&flat],
+                &format(pixel_format, 4, 2),
+                5,
+                3,
+                Filter::Lanczos3,
+                &mut dst,
+            )
+            .unwrap();
+            assert_eq!(dst.len(), 3 * 4 * 3);
+            for (i, pair) in dst.chunks_exact(4).enumerate() {
+                let last = i % 3 == 2;
+                assert_eq!(pair[order.y0], 90, "{pixel_format}");
+                assert!(last || pair[order.y1] == 90, "{pixel_format}");
+                assert_eq!((pair[order.u], pair[order.v]), (30, 220), "{pixel_format}");
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_what_it_cant_scale() {
+        let mut dst = Vec::new();
+        let rgb = format(PixelFormat::RGB24, 4, 2);
+        assert_eq!(
+            resize_frame(&[&[0; 24]], &rgb, 2, 1, Filter::Area, &mut dst),
+            Err(ConvertError::UnsupportedFormat(PixelFormat::RGB24))
+        );
+        for pixel_format in [PixelFormat::GREY, PixelFormat::NV12, PixelFormat::YUYV] {
+            let result = resize_frame(
+                &[&[0; 5]],
+                &format(pixel_format, 4, 2),
+                2,
+                1,
+                Filter::Area,
+                &mut dst,
+            );
+            assert!(
+                matches!(result, Err(ConvertError::CorruptFrame(_))),
+                "{pixel_format}"
+            );
+        }
+    }
+}
@@ -0,0 +1,470 @@
+use std::fmt;
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64"))]
+use super::color::{FIXED_BITS, FIXED_ROUND};
+use super::{color::FixedYuvToRgb, Packed422};
+
+/// Implementation used for the pixel loops. All of them give bit-identical
+/// results, they only differ in speed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Kernel {
+    Scalar,
+    Sse2,
+    Avx2,
+    Neon,
+}
+
+impl Kernel {
+    pub const ALL: [Kernel; 4] = [Kernel::Scalar, Kernel::Sse2, Kernel::Avx2, Kernel::Neon];
+
+    /// The fastest kernel the CPU we're running on supports.
+    pub fn detect() -> Self {
+        [Kernel::Avx2, Kernel::Neon, Kernel::Sse2]
+            .into_iter()
+            .find(|kernel| kernel.is_supported())
+            .unwrap_or(Kernel::Scalar)
+    }
+
+    pub fn is_supported(self) -> bool {
+        match self {
+            Kernel::Scalar => true,
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Kernel::Sse2 => is_x86_feature_detected!("sse2"),
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Kernel::Avx2 => is_x86_feature_detected!("avx2"),
+            #[cfg(target_arch = "aarch64")]
+            Kernel::Neon => std::arch::is_aarch64_feature_detected!("neon"),
+            #[allow(unreachable_patterns)]
+            _ => false,
+        }
+    }
+
+    /// Convert one row of packed 4:2:2, `dst` holding the RGBA of
+    /// `dst.len() / 4` pixels. Must only be called on a supported kernel.
+    pub(crate) fn packed422_row(
+        self,
+        order: Packed422,
+        src: &[u8],
+        dst: &mut [u8],
+        c: &FixedYuvToRgb,
+    ) {
+        debug_assert!(self.is_supported());
+
+        // The vector loops do whole blocks, the scalar one the rest of the row.
+        // Safety: the caller checked the CPU has the instructions.
+        let done = match self {
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Kernel::Sse2 if x86::handles(order) => unsafe {
+                x86::packed422_row_sse2(order, src, dst, c)
+            },
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Kernel::Avx2 if x86::handles(order) => unsafe {
+                x86::packed422_row_avx2(order, src, dst, c)
+            },
+            #[cfg(target_arch = "aarch64")]
+            Kernel::Neon => unsafe { neon::packed422_row_neon(order, src, dst, c) },
+            _ => 0,
+        };
+
+        packed422_row_scalar(order, &src[done * 2..], &mut dst[done * 4..], c);
+    }
+}
+
+impl fmt::Display for Kernel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Kernel::Scalar => "scalar",
+            Kernel::Sse2 => "SSE2",
+            Kernel::Avx2 => "AVX2",
+            Kernel::Neon => "NEON",
+        })
+    }
+}
+
+fn packed422_row_scalar(order: Packed422, src: &[u8], dst: &mut [u8], c: &FixedYuvToRgb) {
+    // The last chunk is a single pixel for odd widths
+    for (packed, rgba) in src.chunks_exact(4).zip(dst.chunks_mut(8)) {
+        let [y0, u, y1, v] = [order.y0, order.u, order.y1, order.v].map(|i| packed[i]);
+
+        let [r, g, b] = c.convert(y0, u, v);
+        rgba[..4].copy_from_slice(&[r, g, b, 255]);
+        if rgba.len() == 8 {
+            let [r, g, b] = c.convert(y1, u, v);
+            rgba[4..].copy_from_slice(&[r, g, b, 255]);
+        }
+    }
+}
+
+// The x86 kernels interleave Y' with Cb or Cr in 16 bit lanes and multiply-add
+// each pair with `pmaddwd`, which gives the same 32 bit sums as the scalar
+// code. The saturating packs afterwards are the clamp to 0..=255.
+//
+// They handle the orders where Y' alternates with chroma, i.e. everything but
+// YYUV: Y' is either the low or the high byte of every 16 bit lane and Cb
+// comes either first or second.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod x86 {
+    #[cfg(target_arch = "x86")]
+    use std::arch::x86::*;
+    #[cfg(target_arch = "x86_64")]
+    use std::arch::x86_64::*;
+
+    use super::{FixedYuvToRgb, Packed422, FIXED_BITS, FIXED_ROUND};
+
+    pub(super) fn handles(order: Packed422) -> bool {
+        order.y1 == order.y0 + 2
+    }
+
+    // Bit shifts bringing Y' and chroma to the low byte of their 16 bit lanes
+    fn shifts(order: Packed422) -> (i32, i32) {
+        if order.y0 == 0 {
+            (0, 8)
+        } else {
+            (8, 0)
+        }
+    }
+
+    // `a` for the even and `b` for the odd 16 bit lane of every 32 bit lane
+    fn pair(a: i16, b: i16) -> i32 {
+        (a as u16 as i32) | ((b as i32) << 16)
+    }
+
+    #[target_feature(enable = "sse2")]
+    #[inline]
+    unsafe fn narrow_sse2(lo: __m128i, hi: __m128i) -> __m128i {
+        let lo = _mm_srai_epi32::<FIXED_BITS>(lo);
+        let hi = _mm_srai_epi32::<FIXED_BITS>(hi);
+        let rgb = _mm_packs_epi32(lo, hi);
+        _mm_packus_epi16(rgb, rgb)
+    }
+
+    /// Converts the pixels of the row up to a multiple of 8, returns how many.
+    #[target_feature(enable = "sse2")]
+    pub(super) unsafe fn packed422_row_sse2(
+        order: Packed422,
+        src: &[u8],
+        dst: &mut [u8],
+        c: &FixedYuvToRgb,
+    ) -> usize {
+        let pixels = (src.len() / 2).min(dst.len() / 4) & !7;
+
+        let (y_shift, c_shift) = shifts(order);
+        let y_shift = _mm_cvtsi32_si128(y_shift);
+        let c_shift = _mm_cvtsi32_si128(c_shift);
+        let low_bytes = _mm_set1_epi16(0xff);
+        let y_offset = _mm_set1_epi16(c.y_offset);
+        let chroma_offset = _mm_set1_epi16(128);
+        let one = _mm_set1_epi16(1);
+        let round = _mm_set1_epi32(FIXED_ROUND);
+        let alpha = _mm_set1_epi8(-1);
+
+        let r_coeffs = _mm_set1_epi32(pair(c.y_scale, c.r_cr));
+        let g_coeffs = _mm_set1_epi32(pair(c.y_scale, c.g_cb));
+        // Paired with (Cr, 1), so the rounding comes for free
+        let g_cr_coeffs = _mm_set1_epi32(pair(c.g_cr, FIXED_ROUND as i16));
+        let b_coeffs = _mm_set1_epi32(pair(c.y_scale, c.b_cb));
+
+        for x in (0..pixels).step_by(8) {
+            let packed = _mm_loadu_si128(src.as_ptr().add(x * 2).cast());
+
+            let y = _mm_and_si128(_mm_srl_epi16(packed, y_shift), low_bytes);
+            let y = _mm_sub_epi16(y, y_offset);
+            // Cb0 Cr0 Cb1 Cr1 ..., spread to one Cb and one Cr per pixel
+            let uv = _mm_and_si128(_mm_srl_epi16(packed, c_shift), low_bytes);
+            let uv = _mm_sub_epi16(uv, chroma_offset);
+            let u = _mm_shufflehi_epi16::<0b10_10_00_00>(_mm_shufflelo_epi16::<0b10_10_00_00>(uv));
+            let v = _mm_shufflehi_epi16::<0b11_11_01_01>(_mm_shufflelo_epi16::<0b11_11_01_01>(uv));
+            let (u, v) = if order.u < order.v { (u, v) } else { (v, u) };
+
+            let yu_lo = _mm_unpacklo_epi16(y, u);
+            let yu_hi = _mm_unpackhi_epi16(y, u);
+            let yv_lo = _mm_unpacklo_epi16(y, v);
+            let yv_hi = _mm_unpackhi_epi16(y, v);
+            let v1_lo = _mm_unpacklo_epi16(v, one);
+            let v1_hi = _mm_unpackhi_epi16(v, one);
+
+            let r = narrow_sse2(
+                _mm_add_epi32(_mm_madd_epi16(yv_lo, r_coeffs), round),
+                _mm_add_epi32(_mm_madd_epi16(yv_hi, r_coeffs), round),
+            );
+            let g = narrow_sse2(
+                _mm_add_epi32(
+                    _mm_madd_epi16(yu_lo, g_coeffs),
+                    _mm_madd_epi16(v1_lo, g_cr_coeffs),
+                ),
+                _mm_add_epi32(
+                    _mm_madd_epi16(yu_hi, g_coeffs),
+                    _mm_madd_epi16(v1_hi, g_cr_coeffs),
+                ),
+            );
+            let b = narrow_sse2(
+                _mm_add_epi32(_mm_madd_epi16(yu_lo, b_coeffs), round),
+                _mm_add_epi32(_mm_madd_epi16(yu_hi, b_coeffs), round),
+            );
+
+            let rg = _mm_unpacklo_epi8(r, g);
+            let ba = _mm_unpacklo_epi8(b, alpha);
+            let out = dst.as_mut_ptr().add(x * 4);
+            _mm_storeu_si128(out.cast(), _mm_unpacklo_epi16(rg, ba));
+            _mm_storeu_si128(out.add(16).cast(), _mm_unpackhi_epi16(rg, ba));
+        }
+
+        pixels
+    }
+
+    #[target_feature(enable = "avx2")]
+    #[inline]
+    unsafe fn narrow_avx2(lo: __m256i, hi: __m256i) -> __m256i {
+        let lo = _mm256_srai_epi32::<FIXED_BITS>(lo);
+        let hi = _mm256_srai_epi32::<FIXED_BITS>(hi);
+        let rgb = _mm256_packs_epi32(lo, hi);
+        _mm256_packus_epi16(rgb, rgb)
+    }
+
+    /// Same as the SSE2 version on both 128 bit halves, 16 pixels at a time.
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn packed422_row_avx2(
+        order: Packed422,
+        src: &[u8],
+        dst: &mut [u8],
+        c: &FixedYuvToRgb,
+    ) -> usize {
+        let pixels = (src.len() / 2).min(dst.len() / 4) & !15;
+
+        let (y_shift, c_shift) = shifts(order);
+        let y_shift = _mm_cvtsi32_si128(y_shift);
+        let c_shift = _mm_cvtsi32_si128(c_shift);
+        let low_bytes = _mm256_set1_epi16(0xff);
+        let y_offset = _mm256_set1_epi16(c.y_offset);
+        let chroma_offset = _mm256_set1_epi16(128);
+        let one = _mm256_set1_epi16(1);
+        let round = _mm256_set1_epi32(FIXED_ROUND);
+        let alpha = _mm256_set1_epi8(-1);
+
+        let r_coeffs = _mm256_set1_epi32(pair(c.y_scale, c.r_cr));
+        let g_coeffs = _mm256_set1_epi32(pair(c.y_scale, c.g_cb));
+        let g_cr_coeffs = _mm256_set1_epi32(pair(c.g_cr, FIXED_ROUND as i16));
+        let b_coeffs = _mm256_set1_epi32(pair(c.y_scale, c.b_cb));
+
+        for x in (0..pixels).step_by(16) {
+            let packed = _mm256_loadu_si256(src.as_ptr().add(x * 2).cast());
+
+            let y = _mm256_and_si256(_mm256_srl_epi16(packed, y_shift), low_bytes);
+            let y = _mm256_sub_epi16(y, y_offset);
+            let uv = _mm256_and_si256(_mm256_srl_epi16(packed, c_shift), low_bytes);
+            let uv = _mm256_sub_epi16(uv, chroma_offset);
+            let u = _mm256_shufflehi_epi16::<0b10_10_00_00>(
+                _mm256_shufflelo_epi16::<0b10_10_00_00>(uv),
+            );
+            let v = _mm256_shufflehi_epi16::<0b11_11_01_01>(
+                _mm256_shufflelo_epi16::<0b11_11_01_01>(uv),
+            );
+            let (u, v) = if order.u < order.v { (u, v) } else { (v, u) };
+
+            let yu_lo = _mm256_unpacklo_epi16(y, u);
+            let yu_hi = _mm256_unpackhi_epi16(y, u);
+            let yv_lo = _mm256_unpacklo_epi16(y, v);
+            let yv_hi = _mm256_unpackhi_epi16(y, v);
+            let v1_lo = _mm256_unpacklo_epi16(v, one);
+            let v1_hi = _mm256_unpackhi_epi16(v, one);
+
+            let r = narrow_avx2(
+                _mm256_add_epi32(_mm256_madd_epi16(yv_lo, r_coeffs), round),
+                _mm256_add_epi32(_mm256_madd_epi16(yv_hi, r_coeffs), round),
+            );
+            let g = narrow_avx2(
+                _mm256_add_epi32(
+                    _mm256_madd_epi16(yu_lo, g_coeffs),
+                    _mm256_madd_epi16(v1_lo, g_cr_coeffs),
+                ),
+                _mm256_add_epi32(
+                    _mm256_madd_epi16(yu_hi, g_coeffs),
+                    _mm256_madd_epi16(v1_hi, g_cr_coeffs),
+                ),
+            );
+            let b = narrow_avx2(
+                _mm256_add_epi32(_mm256_madd_epi16(yu_lo, b_coeffs), round),
+                _mm256_add_epi32(_mm256_madd_epi16(yu_hi, b_coeffs), round),
+            );
+
+            // Pixels 0-3 and 8-11 in `lo`, 4-7 and 12-15 in `hi`
+            let rg = _mm256_unpacklo_epi8(r, g);
+            let ba = _mm256_unpacklo_epi8(b, alpha);
+            let lo = _mm256_unpacklo_epi16(rg, ba);
+            let hi = _mm256_unpackhi_epi16(rg, ba);
+
+            let out = dst.as_mut_ptr().add(x * 4);
+            _mm256_storeu_si256(out.cast(), _mm256_permute2x128_si256::<0x20>(lo, hi));
+            _mm256_storeu_si256(
+                out.add(32).cast(),
+                _mm256_permute2x128_si256::<0x31>(lo, hi),
+            );
+        }
+
+        pixels
+    }
+}
+
+// `vld4` splits the macropixels into their four bytes, whichever order they
+// are in, so the even and odd pixels are worked out separately and zipped
+// back together on the way out.
+#[cfg(target_arch = "aarch64")]
+mod neon {
+    use std::arch::aarch64::*;
+
+    use super::{FixedYuvToRgb, Packed422, FIXED_BITS, FIXED_ROUND};
+
+    #[target_feature(enable = "neon")]
+    #[inline]
+    unsafe fn widen(x: uint8x8_t, offset: i16) -> int16x8_t {
+        vsubq_s16(vreinterpretq_s16_u16(vmovl_u8(x)), vdupq_n_s16(offset))
+    }
+
+    // (y * y_scale + a * ka + b * kb + round) >> FIXED_BITS, saturated to u8
+    #[target_feature(enable = "neon")]
+    #[inline]
+    unsafe fn channel(
+        y: int16x8_t,
+        y_scale: i16,
+        a: int16x8_t,
+        ka: i16,
+        b: int16x8_t,
+        kb: i16,
+    ) -> uint8x8_t {
+        let round = vdupq_n_s32(FIXED_ROUND);
+
+        let mut lo = vaddq_s32(vmull_n_s16(vget_low_s16(y), y_scale), round);
+        lo = vmlal_n_s16(lo, vget_low_s16(a), ka);
+        lo = vmlal_n_s16(lo, vget_low_s16(b), kb);
+        let mut hi = vaddq_s32(vmull_n_s16(vget_high_s16(y), y_scale), round);
+        hi = vmlal_n_s16(hi, vget_high_s16(a), ka);
+        hi = vmlal_n_s16(hi, vget_high_s16(b), kb);
+
+        let rgb = vcombine_s16(
+            vqmovn_s32(vshrq_n_s32::<FIXED_BITS>(lo)),
+            vqmovn_s32(vshrq_n_s32::<FIXED_BITS>(hi)),
+        );
+        vqmovun_s16(rgb)
+    }
+
+    #[target_feature(enable = "neon")]
+    #[inline]
+    unsafe fn rgb(
+        y: int16x8_t,
+        u: int16x8_t,
+        v: int16x8_t,
+        c: &FixedYuvToRgb,
+    ) -> (uint8x8_t, uint8x8_t, uint8x8_t) {
+        (
+            channel(y, c.y_scale, v, c.r_cr, u, 0),
+            channel(y, c.y_scale, u, c.g_cb, v, c.g_cr),
+            channel(y, c.y_scale, u, c.b_cb, v, 0),
+        )
+    }
+
+    /// Converts the pixels of the row up to a multiple of 16, returns how many.
+    #[target_feature(enable = "neon")]
+    pub(super) unsafe fn packed422_row_neon(
+        order: Packed422,
+        src: &[u8],
+        dst: &mut [u8],
+        c: &FixedYuvToRgb,
+    ) -> usize {
+        let pixels = (src.len() / 2).min(dst.len() / 4) & !15;
+        let alpha = vdup_n_u8(255);
+
+        for x in (0..pixels).step_by(16) {
+            let bytes = vld4_u8(src.as_ptr().add(x * 2));
+            let bytes = [bytes.0, bytes.1, bytes.2, bytes.3];
+
+            let u = widen(bytes[order.u], 128);
+            let v = widen(bytes[order.v], 128);
+            let (r_even, g_even, b_even) = rgb(widen(bytes[order.y0], c.y_offset), u, v, c);
+            let (r_odd, g_odd, b_odd) = rgb(widen(bytes[order.y1], c.y_offset), u, v, c);
+
+            let r = vzip_u8(r_even, r_odd);
+            let g = vzip_u8(g_even, g_odd);
+            let b = vzip_u8(b_even, b_odd);
+
+            let out = dst.as_mut_ptr().add(x * 4);
+            vst4_u8(out, uint8x8x4_t(r.0, g.0, b.0, alpha));
+            vst4_u8(out.add(32), uint8x8x4_t(r.1, g.1, b.1, alpha));
+        }
+
+        pixels
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::convert::{ColorSpec, Matrix, Range};
+
+    const ORDERS: [Packed422; 5] = [
+        Packed422::YUYV,
+        Packed422::UYVY,
+        Packed422::YVYU,
+        Packed422::VYUY,
+        Packed422::YYUV,
+    ];
+
+    // Rows of random bytes and of the values most likely to over- or
+    // underflow, for every width up to a few blocks of the widest kernel
+    fn rows() -> Vec<Vec<u8>> {
+        let mut seed = 0x2545_f491_4f6c_dd1d_u64;
+        let mut random = move || {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed as u8
+        };
+        let edges = [0, 1, 15, 16, 128, 235, 240, 254, 255];
+
+        let mut rows = Vec::new();
+        for width in 1..=100_usize {
+            // Odd widths still come as whole pairs in packed 4:2:2
+            let len = width.next_multiple_of(2) * 2;
+            rows.push((0..len).map(|_| random()).collect());
+            rows.push((0..len).map(|i| edges[i % edges.len()]).collect());
+            rows.push(
+                (0..len)
+                    .map(|i| edges[(i * 7 + width) % edges.len()])
+                    .collect(),
+            );
+        }
+        rows
+    }
+
+    #[test]
+    fn kernels_match_scalar() {
+        let rows = rows();
+        for kernel in Kernel::ALL
+            .into_iter()
+            .filter(|kernel| kernel.is_supported())
+        {
+            for matrix in Matrix::ALL {
+                for range in Range::ALL {
+                    let c = FixedYuvToRgb::new(ColorSpec {
+                        matrix,
+                        range,
+                        ..Default::default()
+                    });
+                    for order in ORDERS {
+                        for src in &rows {
+                            for width in [src.len() / 2 - 1, src.len() / 2] {
+                                let mut want = vec![0; width * 4];
+                                let mut got = vec![0; width * 4];
+                                packed422_row_scalar(order, src, &mut want, &c);
+                                kernel.packed422_row(order, src, &mut got, &c);
+                                assert!(
+                                    got == want,
+                                    "{kernel} {matrix} {range} {order:?}, width {width}"
+                                );
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
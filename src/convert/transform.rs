@@ -0,0 +1,184 @@
+use std::fmt;
+
+use super::RgbaImage;
+
+/// Clockwise rotation.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Rotation {
+    #[default]
+    None,
+    Cw90,
+    Cw180,
+    Cw270,
+}
+
+impl Rotation {
+    pub const ALL: [Rotation; 4] = [
+        Rotation::None,
+        Rotation::Cw90,
+        Rotation::Cw180,
+        Rotation::Cw270,
+    ];
+}
+
+impl fmt::Display for Rotation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Rotation::None => "0°",
+            Rotation::Cw90 => "90°",
+            Rotation::Cw180 => "180°",
+            Rotation::Cw270 => "270°",
+        })
+    }
+}
+
+/// Flips followed by a rotation, the order a camera that can flip its sensor
+/// readout would do them in.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Transform {
+    /// Mirror left to right
+    pub hflip: bool,
+    /// Upside down
+    pub vflip: bool,
+    pub rotation: Rotation,
+}
+
+impl Transform {
+    pub fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// The same transform with a 180° rotation turned into both flips, so
+    /// that as much of it as possible can be left to a device that flips.
+    pub fn normalized(self) -> Self {
+        let (half_turn, rotation) = match self.rotation {
+            Rotation::Cw180 => (true, Rotation::None),
+            Rotation::Cw270 => (true, Rotation::Cw90),
+            rotation => (false, rotation),
+        };
+        Self {
+            hflip: self.hflip ^ half_turn,
+            vflip: self.vflip ^ half_turn,
+            rotation,
+        }
+    }
+
+    /// Where a point of a frame ends up once it's transformed, both in
+    /// fractions of the width and height.
+    pub fn map_point(&self, x: f32, y: f32) -> (f32, f32) {
+        let x = if self.hflip { 1.0 - x } else { x };
+        let y = if self.vflip { 1.0 - y } else { y };
+        match self.rotation {
+            Rotation::None => (x, y),
+            Rotation::Cw90 => (1.0 - y, x),
+            Rotation::Cw180 => (1.0 - x, 1.0 - y),
+            Rotation::Cw270 => (y, 1.0 - x),
+        }
+    }
+
+    /// Where a point of a transformed frame was before, the other way round
+    /// from `map_point`.
+    pub fn unmap_point(&self, x: f32, y: f32) -> (f32, f32) {
+        let (x, y) = match self.rotation {
+            Rotation::None => (x, y),
+            Rotation::Cw90 => (y, 1.0 - x),
+            Rotation::Cw180 => (1.0 - x, 1.0 - y),
+            Rotation::Cw270 => (1.0 - y, x),
+        };
+        let x = if self.hflip { 1.0 - x } else { x };
+        let y = if self.vflip { 1.0 - y } else { y };
+        (x, y)
+    }
+
+    /// The size of a `width` by `height` frame once transformed.
+    pub fn size(&self, width: usize, height: usize) -> (usize, usize) {
+        match self.rotation {
+            Rotation::Cw90 | Rotation::Cw270 => (height, width),
+            Rotation::None | Rotation::Cw180 => (width, height),
+        }
+    }
+}
+
+/// Transform `src` into `dst`, resizing it to fit.
+pub fn transform_rgba(src: &RgbaImage, transform: Transform, dst: &mut RgbaImage) {
+    let (width, height) = (src.width(), src.height());
+    let (dst_width, dst_height) = transform.size(width, height);
+    dst.resize(dst_width, dst_height);
+    if width == 0 || height == 0 {
+        return;
+    }
+
+    // Where the top left of `dst` is in `src`, and where a step right and a
+    // step down in `dst` go
+    let (w, h) = (width - 1, height - 1);
+    let (mut corner, mut right, mut down) = match transform.rotation {
+        Rotation::None => ((0, 0), (1, 0), (0, 1)),
+        Rotation::Cw90 => ((0, h), (0, -1), (1, 0)),
+        Rotation::Cw180 => ((w, h), (-1, 0), (0, -1)),
+        Rotation::Cw270 => ((w, 0), (0, 1), (-1, 0)),
+    };
+    // Flipping happens first, so on the source side
+    if transform.hflip {
+        corner.0 = w - corner.0;
+        right.0 = -right.0;
+        down.0 = -down.0;
+    }
+    if transform.vflip {
+        corner.1 = h - corner.1;
+        right.1 = -right.1;
+        down.1 = -down.1;
+    }
+    let step = |(x, y): (isize, isize)| x + y * width as isize;
+    let (right, down) = (step(right), step(down));
+
+    let src = src.data();
+    let start = (corner.1 * width + corner.0) as isize;
+    for (row, dst_row) in dst.rows_mut().enumerate() {
+        let mut i = start + row as isize * down;
+        for rgba in dst_row.chunks_exact_mut(4) {
+            let at = i as usize * 4;
+            rgba.copy_from_slice(&src[at..at + 4]);
+            i += right;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn points_go_where_the_pixels_do() {
+        let (width, height) = (5, 3);
+        let mut src = RgbaImage::new();
+        src.resize(width, height);
+        for (i, rgba) in src.data_mut().chunks_exact_mut(4).enumerate() {
+            rgba.copy_from_slice(&[i as u8, 0, 0, 255]);
+        }
+        let mut dst = RgbaImage::new();
+
+        for rotation in Rotation::ALL {
+            for (hflip, vflip) in [(false, false), (true, false), (false, true), (true, true)] {
+                let transform = Transform {
+                    hflip,
+                    vflip,
+                    rotation,
+                };
+                transform_rgba(&src, transform, &mut dst);
+                for i in 0..width * height {
+                    // The middle of the pixel
+                    let x = ((i % width) as f32 + 0.5) / width as f32;
+                    let y = ((i / width) as f32 + 0.5) / height as f32;
+                    let (mapped_x, mapped_y) = transform.map_point(x, y);
+                    let to_x = (mapped_x * dst.width() as f32) as usize;
+                    let to_y = (mapped_y * dst.height() as f32) as usize;
+                    let at = (to_y * dst.width() + to_x) * 4;
+                    assert_eq!(dst.data()[at] as usize, i, "{transform:?}");
+
+                    let (back_x, back_y) = transform.unmap_point(mapped_x, mapped_y);
+                    assert!((back_x - x).abs() < 1e-6 && (back_y - y).abs() < 1e-6);
+                }
+            }
+        }
+    }
+}
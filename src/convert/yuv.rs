@@ -0,0 +1,127 @@
+use super::planar::Component;
+use super::{Packed422, Planar};
+use crate::v4l2::FrameFormat;
+
+/// The samples of an 8 bit packed 4:2:2 or planar Y'CbCr frame, read
+/// straight from the captured data, for the things that want Y'CbCr rather
+/// than RGB.
+#[derive(Clone, Copy)]
+pub struct YuvView<'a> {
+    width: usize,
+    height: usize,
+    layout: Layout<'a>,
+}
+
+#[derive(Clone, Copy)]
+enum Layout<'a> {
+    Packed {
+        order: Packed422,
+        data: &'a [u8],
+        stride: usize,
+    },
+    Planar {
+        vertical_subsampling: usize,
+        // Bytes from one chroma sample to the next
+        chroma_step: usize,
+        y: Component<'a>,
+        u: Component<'a>,
+        v: Component<'a>,
+    },
+}
+
+impl<'a> YuvView<'a> {
+    /// `planes` as for `frame_to_rgba`. None if `format` is anything but
+    /// 8 bit 4:2:2 or 4:2:0 Y'CbCr.
+    pub fn new(planes: &[&'a [u8]], format: &FrameFormat) -> Option<Self> {
+        let layout = if let Some(order) = Packed422::from_pixel_format(format.pixel_format) {
+            let stride = match format.bytes_per_line {
+                0 => format.width.div_ceil(2) * 4,
+                stride => stride,
+            };
+            Layout::Packed {
+                order,
+                data: planes.first().copied().unwrap_or_default(),
+                stride,
+            }
+        } else {
+            let planar = Planar::from_pixel_format(format.pixel_format)?;
+            let [y, u, v] = planar.components(planes, format);
+            Layout::Planar {
+                vertical_subsampling: planar.vertical_subsampling,
+                chroma_step: if planar.semi_planar { 2 } else { 1 },
+                y,
+                u,
+                v,
+            }
+        };
+
+        Some(Self {
+            width: format.width,
+            height: format.height,
+            layout,
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Y', Cb and Cr at `x`, `y`. None outside the frame, or if the data
+    /// runs out before it.
+    #[inline]
+    pub fn get(&self, x: usize, y: usize) -> Option<[u8; 3]> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        match self.layout {
+            Layout::Packed {
+                order,
+                data,
+                stride,
+            } => {
+                let start = y * stride + x / 2 * 4;
+                let pair = data.get(start..start + 4)?;
+                let luma = [order.y0, order.y1][x % 2];
+                Some([pair[luma], pair[order.u], pair[order.v]])
+            }
+            Layout::Planar {
+                vertical_subsampling,
+                chroma_step,
+                y: luma,
+                u,
+                v,
+            } => {
+                let chroma_row = y / vertical_subsampling;
+                let i = x / 2 * chroma_step;
+                Some([
+                    *luma.row(y).get(x)?,
+                    *u.row(chroma_row).get(i)?,
+                    *v.row(chroma_row).get(i)?,
+                ])
+            }
+        }
+    }
+
+    /// Just the Y' of `get`, which is all some things need.
+    #[inline]
+    pub fn luma(&self, x: usize, y: usize) -> Option<u8> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        match self.layout {
+            Layout::Packed {
+                order,
+                data,
+                stride,
+            } => {
+                let offset = [order.y0, order.y1][x % 2];
+                data.get(y * stride + x / 2 * 4 + offset).copied()
+            }
+            Layout::Planar { y: luma, .. } => luma.row(y).get(x).copied(),
+        }
+    }
+}
@@ -0,0 +1,9 @@
+//! Webcam capture on Linux through V4L2.
+
+pub mod convert;
+pub mod motion;
+pub mod pipeline;
+pub mod scopes;
+pub mod v4l2;
+
+pub use v4l2::{ExhaustionPolicy, PixelFormat, V4l2Frame, V4l2VideoDevice};
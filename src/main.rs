@@ -1,19 +1,78 @@
-use eframe::egui::{self, Color32, ColorImage, TextureHandle, TextureOptions};
+use eframe::egui::{self, ColorImage, TextureHandle, TextureOptions};
 use std::{
-    iter::repeat,
-    sync::mpsc::{self, Receiver, Sender},
+    path::Path,
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex, PoisonError,
+    },
+    time::{Duration, Instant},
+};
+use videorama::{
+    convert, motion,
+    pipeline::{self, FrameProcessor, Pipeline},
+    scopes, v4l2,
 };
-
-mod v4l2;
 
 const DEVICE_NAME: &str = "/dev/video0";
 
 struct WebcamUi {
-    frame_rx: Receiver<TextureHandle>,
+    gui_event_rx: Receiver<GuiEvent>,
+    /// For work the UI hands off to other threads to report back with
+    gui_event_tx: Sender<GuiEvent>,
     ui_action_tx: Sender<UiAction>,
+    pipeline_tx: Sender<PipelineAction>,
+    color_override: convert::ColorOverride,
+    filter: convert::Filter,
+    max_size: (usize, usize),
+    transform: convert::Transform,
+    /// Where the camera is headed, digitally or not
+    ptz: pipeline::Ptz,
+    ptz_presets: Vec<(String, pipeline::Ptz)>,
+    ptz_preset_name: String,
+    /// The camera's own controls, with what they're set to
+    controls: Vec<(v4l2::ControlInfo, i32)>,
+    adjustments: pipeline::Adjustments,
+    lut_path: String,
+    lut_error: Option<String>,
+    loading_lut: bool,
+    /// Of every LUT loaded, in the order they were
+    lut_names: Vec<String>,
+    lut: Option<usize>,
+    interpolation: pipeline::Interpolation,
+    stages: Vec<pipeline::StageTiming>,
+    overlays: pipeline::Overlays,
+    burn_in_overlays: bool,
+    key_enabled: bool,
+    key: pipeline::ChromaKey,
+    key_background: KeyBackground,
+    key_color: [u8; 3],
+    key_image_path: String,
+    key_camera_path: String,
+    key_error: Option<String>,
+    starting_key_camera: bool,
+    detect_motion: bool,
+    motion_settings: motion::MotionSettings,
+    mask_tool: MaskTool,
+    /// Where the zone being drawn started
+    drag_start: Option<egui::Pos2>,
+    in_motion: bool,
+    /// What's moving, in fractions of the frame as captured
+    motion_regions: Vec<egui::Rect>,
+    /// How the picture comes out of the frame as captured, see `GuiEvent::View`
+    view: (pipeline::Ptz, convert::Transform),
+    /// The latest motion events, newest last
+    motion_log: Vec<String>,
+    started: Instant,
+    shown_scopes: ShownScopes,
+    /// Of the scopes shown, in the order they are
+    scope_textures: Vec<(&'static str, TextureHandle)>,
     last_texture: Option<TextureHandle>,
+    connected: bool,
+    stats: v4l2::CaptureStats,
     selected_size: usize,
     available_frame_sizes: Vec<(u32, u32)>,
+    pixel_format: v4l2::PixelFormat,
+    available_pixel_formats: Vec<v4l2::PixelFormat>,
 }
 
 impl WebcamUi {
@@ -22,49 +81,404 @@ impl WebcamUi {
         // Restore app state using cc.storage (requires the "persistence" feature).
         // Use the cc.gl (a glow::Context) to create graphics shaders and buffers that you can use
         // for e.g. egui::PaintCallback.
-        let v4l2_device = v4l2::V4l2VideoDevice::new(&DEVICE_NAME);
-        v4l2_device.print_formats();
+        // A preview only cares about the newest frame, and the camera should
+        // keep streaming even if conversion falls behind.
+        let config = v4l2::CaptureConfig {
+            queue_mode: v4l2::QueueMode::LowLatency,
+            exhaustion_policy: v4l2::ExhaustionPolicy::DropNewest,
+            ..Default::default()
+        };
+        let v4l2_device = match v4l2::V4l2VideoDevice::with_config(&DEVICE_NAME, config) {
+            Ok(v4l2_device) => v4l2_device,
+            Err(e) => {
+                log::error!("Can't open {DEVICE_NAME}: {e}");
+                std::process::exit(1);
+            }
+        };
+        print_formats(&v4l2_device);
 
-        let available_frame_sizes = v4l2_device.get_frame_sizes();
-        println!("Available frame sizes: {:?}", available_frame_sizes);
+        let stats = v4l2_device.stats();
+        let available_frame_sizes = frame_sizes(&v4l2_device);
+        log::info!("Available frame sizes: {:?}", available_frame_sizes);
+        let format = v4l2_device.format();
+        let controls = camera_controls(&v4l2_device);
+        let available_pixel_formats = v4l2_device
+            .formats()
+            .unwrap_or_else(|e| {
+                log::warn!("Can't list pixel formats: {e}");
+                Vec::new()
+            })
+            .into_iter()
+            .filter(|format| convert::is_supported(*format))
+            .collect();
 
         // v4l2_device.set_frame_size(1);
 
-        let (frame_tx, frame_rx) = mpsc::channel();
+        let (gui_event_tx, gui_event_rx) = mpsc::channel();
+        let (v4l2_frame_tx, v4l2_frame_rx) = mpsc::channel();
         let (ui_action_tx, ui_action_rx) = mpsc::channel();
+        let (pipeline_tx, pipeline_rx) = mpsc::channel();
 
         let ctx = cc.egui_ctx.clone();
+        let capture_gui_event_tx = gui_event_tx.clone();
+        let capture_ctx = ctx.clone();
+        let capture_pipeline_tx = pipeline_tx.clone();
 
-        std::thread::spawn(move || feed_gui(ctx, v4l2_device, frame_tx, ui_action_rx));
+        std::thread::spawn(move || {
+            capture(
+                capture_ctx,
+                v4l2_device,
+                v4l2_frame_tx,
+                capture_gui_event_tx,
+                ui_action_rx,
+                capture_pipeline_tx,
+            )
+        });
+        let feed_gui_event_tx = gui_event_tx.clone();
+        std::thread::spawn(move || feed_gui(ctx, v4l2_frame_rx, feed_gui_event_tx, pipeline_rx));
 
         WebcamUi {
-            frame_rx,
+            gui_event_rx,
+            gui_event_tx,
             ui_action_tx,
-            // @FIXME get correct initial size
-            selected_size: 0,
+            pipeline_tx,
+            color_override: Default::default(),
+            filter: Default::default(),
+            max_size: (0, 0),
+            transform: Default::default(),
+            ptz: Default::default(),
+            ptz_presets: Vec::new(),
+            ptz_preset_name: String::new(),
+            controls,
+            adjustments: Default::default(),
+            lut_path: String::new(),
+            lut_error: None,
+            loading_lut: false,
+            lut_names: Vec::new(),
+            lut: None,
+            interpolation: Default::default(),
+            stages: Vec::new(),
+            overlays: Default::default(),
+            burn_in_overlays: false,
+            key_enabled: false,
+            key: Default::default(),
+            key_background: KeyBackground::Color,
+            key_color: [0, 0, 0],
+            key_image_path: String::new(),
+            key_camera_path: "/dev/video2".into(),
+            key_error: None,
+            starting_key_camera: false,
+            detect_motion: false,
+            motion_settings: Default::default(),
+            mask_tool: MaskTool::Off,
+            drag_start: None,
+            in_motion: false,
+            motion_regions: Vec::new(),
+            view: Default::default(),
+            motion_log: Vec::new(),
+            started: Instant::now(),
+            shown_scopes: Default::default(),
+            scope_textures: Vec::new(),
+            connected: true,
+            stats,
+            selected_size: size_index(&available_frame_sizes, &format),
             available_frame_sizes,
+            pixel_format: format.pixel_format,
+            available_pixel_formats,
             last_texture: None,
         }
     }
 }
 
+impl WebcamUi {
+    fn send_motion_settings(&self) {
+        self.pipeline_tx
+            .send(PipelineAction::Motion(
+                self.detect_motion,
+                self.motion_settings.clone(),
+            ))
+            .unwrap();
+    }
+
+    fn log_motion(&mut self, event: motion::MotionEvent) {
+        let seconds = |at: Instant| at.saturating_duration_since(self.started).as_secs_f64();
+        let line = match event {
+            motion::MotionEvent::Started { at, regions } => {
+                self.in_motion = true;
+                format!("{:.1} s: motion in {} places", seconds(at), regions.len())
+            }
+            motion::MotionEvent::Ended { at, since, area } => {
+                self.in_motion = false;
+                format!(
+                    "{:.1} s: still again after {:.1} s, {}x{} at {},{} moved",
+                    seconds(at),
+                    at.saturating_duration_since(since).as_secs_f64(),
+                    area.width,
+                    area.height,
+                    area.x,
+                    area.y
+                )
+            }
+        };
+        self.motion_log.push(line);
+        if self.motion_log.len() > MOTION_LOG_LINES {
+            self.motion_log.remove(0);
+        }
+    }
+
+    fn send_ptz(&self, duration: Duration) {
+        self.ui_action_tx
+            .send(UiAction::SetPtz(self.ptz, duration))
+            .unwrap();
+    }
+
+    // Zooming with the wheel over the picture, and panning and tilting by
+    // grabbing it
+    fn steer_ptz(&mut self, ui: &egui::Ui, response: &egui::Response) {
+        let mut ptz = self.ptz;
+        if response.hovered() {
+            let scroll = ui.input(|input| input.smooth_scroll_delta.y);
+            ptz.zoom *= (scroll / 200.0).exp();
+        }
+        // So the picture follows the pointer. Without zoom there's no room to
+        // move into, unless the camera pans and tilts by itself.
+        let room = (ptz.zoom - 1.0).max(0.25);
+        let drag = response.drag_delta() / response.rect.size();
+        ptz.pan -= drag.x * 2.0 / room;
+        ptz.tilt -= drag.y * 2.0 / room;
+        let ptz = ptz.clamped();
+        if ptz != self.ptz {
+            self.ptz = ptz;
+            self.send_ptz(Duration::ZERO);
+        }
+    }
+
+    // Motion zones and what's moving over the picture, and drawing new zones
+    // on it. All in fractions of the frame as captured, which is what motion
+    // is looked for in, so they go through the same zoom and rotation as the
+    // picture.
+    fn draw_zones(&mut self, ui: &egui::Ui, response: &egui::Response) {
+        let rect = response.rect;
+        let (ptz, transform) = self.view;
+        let (left, top, width, height) = ptz.view();
+        let to_screen = |zone: egui::Rect| {
+            let corner = |pos: egui::Pos2| {
+                let (x, y) = ((pos.x - left) / width, (pos.y - top) / height);
+                let (x, y) = transform.map_point(x, y);
+                rect.lerp_inside(egui::vec2(x, y))
+            };
+            egui::Rect::from_two_pos(corner(zone.min), corner(zone.max))
+        };
+        let to_picture = |pos: egui::Pos2| {
+            let on_screen = (pos - rect.min) / rect.size();
+            let (x, y) = transform.unmap_point(on_screen.x, on_screen.y);
+            egui::pos2(left + x * width, top + y * height)
+        };
+        // Zones can go past the part that's zoomed in on
+        let painter = ui.painter_at(rect);
+
+        if self.mask_tool != MaskTool::Off {
+            if response.drag_started() {
+                self.drag_start = response.interact_pointer_pos();
+            }
+            if let (Some(start), Some(end)) = (self.drag_start, response.interact_pointer_pos()) {
+                let zone = egui::Rect::from_two_pos(to_picture(start), to_picture(end)).intersect(
+                    egui::Rect::from_min_max(egui::Pos2::ZERO, egui::pos2(1.0, 1.0)),
+                );
+                if response.drag_stopped() {
+                    self.drag_start = None;
+                    self.motion_settings.zones.push(motion::Zone {
+                        left: zone.min.x,
+                        top: zone.min.y,
+                        right: zone.max.x,
+                        bottom: zone.max.y,
+                        exclude: self.mask_tool == MaskTool::Exclude,
+                    });
+                    self.send_motion_settings();
+                } else {
+                    let stroke = egui::Stroke::new(1.0, egui::Color32::WHITE);
+                    painter.rect_stroke(to_screen(zone), 0.0, stroke);
+                }
+            }
+        }
+
+        for zone in &self.motion_settings.zones {
+            let color = if zone.exclude {
+                egui::Color32::RED
+            } else {
+                egui::Color32::GREEN
+            };
+            let zone = egui::Rect::from_min_max(
+                egui::pos2(zone.left, zone.top),
+                egui::pos2(zone.right, zone.bottom),
+            );
+            painter.rect_stroke(to_screen(zone), 0.0, egui::Stroke::new(1.0, color));
+        }
+        if self.detect_motion {
+            let stroke = egui::Stroke::new(2.0, egui::Color32::YELLOW);
+            for region in &self.motion_regions {
+                painter.rect_stroke(to_screen(*region), 0.0, stroke);
+            }
+        }
+    }
+}
+
+fn print_formats(v4l2_device: &v4l2::V4l2VideoDevice) {
+    for format in v4l2_device.formats().unwrap_or_default() {
+        log::info!("PIXEL_FORMAT: {format}");
+        match v4l2_device.frame_sizes(format) {
+            Ok(sizes) => log::info!("Frame sizes: {sizes:?}"),
+            Err(e) => log::warn!("Can't list frame sizes: {e}"),
+        }
+    }
+}
+
+// Of the current pixel format, none if the device won't say
+fn frame_sizes(v4l2_device: &v4l2::V4l2VideoDevice) -> Vec<(u32, u32)> {
+    v4l2_device.get_frame_sizes().unwrap_or_else(|e| {
+        log::warn!("Can't list frame sizes: {e}");
+        Vec::new()
+    })
+}
+
+// Along with what they're set to, none if the device won't say
+fn camera_controls(v4l2_device: &v4l2::V4l2VideoDevice) -> Vec<(v4l2::ControlInfo, i32)> {
+    let controls = v4l2_device.controls().unwrap_or_else(|e| {
+        log::warn!("Can't list controls: {e}");
+        Vec::new()
+    });
+    controls
+        .into_iter()
+        .map(|info| {
+            // Write-only ones can't be read back
+            let value = v4l2_device.control(info.id).unwrap_or(info.default_value);
+            (info, value)
+        })
+        .collect()
+}
+
 fn size_to_str(size: &(u32, u32)) -> String {
     format!("{}x{}", size.0, size.1)
 }
 
+fn size_index(sizes: &[(u32, u32)], format: &v4l2::FrameFormat) -> usize {
+    sizes
+        .iter()
+        .position(|&(width, height)| {
+            (width as usize, height as usize) == (format.width, format.height)
+        })
+        .unwrap_or(0)
+}
+
+// Combo box with a `none` entry for `None`. Returns whether the value changed.
+fn option_combo<T: Copy + PartialEq + std::fmt::Display>(
+    ui: &mut egui::Ui,
+    label: &str,
+    none: &str,
+    value: &mut Option<T>,
+    options: &[T],
+) -> bool {
+    let prev = *value;
+    egui::ComboBox::from_label(label)
+        .selected_text(value.map_or(none.to_string(), |v| v.to_string()))
+        .show_ui(ui, |ui| {
+            ui.selectable_value(value, None, none);
+            for option in options {
+                ui.selectable_value(value, Some(*option), option.to_string());
+            }
+        });
+    *value != prev
+}
+
 impl eframe::App for WebcamUi {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        while let Ok(v) = self.frame_rx.try_recv() {
-            self.last_texture = Some(v);
+        while let Ok(event) = self.gui_event_rx.try_recv() {
+            match event {
+                GuiEvent::Frame(texture) => self.last_texture = Some(texture),
+                GuiEvent::Stages(stages) => self.stages = stages,
+                GuiEvent::Controls(controls) => self.controls = controls,
+                GuiEvent::LutLoaded(loaded) => {
+                    self.loading_lut = false;
+                    match loaded {
+                        Ok((name, lut)) => {
+                            self.lut_names.push(name);
+                            self.pipeline_tx.send(PipelineAction::AddLut(lut)).unwrap();
+                            // Most likely it was loaded to be looked at
+                            self.lut = Some(self.lut_names.len() - 1);
+                            self.lut_error = None;
+                        }
+                        Err(e) => self.lut_error = Some(e),
+                    }
+                }
+                GuiEvent::Scopes(textures) => self.scope_textures = textures,
+                GuiEvent::Motion(events, regions) => {
+                    for event in events {
+                        self.log_motion(event);
+                    }
+                    self.motion_regions = regions;
+                }
+                GuiEvent::KeyCamera(started) => {
+                    self.starting_key_camera = false;
+                    match started {
+                        // Unless another background got picked meanwhile,
+                        // in which case dropping it stops the camera
+                        Ok(picture) if self.key_background == KeyBackground::Camera => {
+                            let background = pipeline::Background::Live(picture);
+                            self.pipeline_tx
+                                .send(PipelineAction::KeyBackground(background))
+                                .unwrap();
+                            self.key_error = None;
+                        }
+                        Ok(_) => {}
+                        Err(e) => self.key_error = Some(e),
+                    }
+                }
+                GuiEvent::View(ptz, transform) => self.view = (ptz, transform),
+                GuiEvent::Disconnected => self.connected = false,
+                GuiEvent::Connected => self.connected = true,
+                GuiEvent::FormatChanged(format, sizes) => {
+                    self.pixel_format = format.pixel_format;
+                    self.selected_size = size_index(&sizes, &format);
+                    self.available_frame_sizes = sizes;
+                }
+            }
+        }
+
+        if self.shown_scopes.any() && !self.scope_textures.is_empty() {
+            egui::SidePanel::right("scopes").show(ctx, |ui| {
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    for (name, texture) in &self.scope_textures {
+                        ui.label(*name);
+                        let image = egui::Image::new((texture.id(), texture.size_vec2()));
+                        ui.add(image.max_width(ui.available_width()));
+                    }
+                });
+            });
         }
 
         egui::CentralPanel::default().show(ctx, |ui| {
+            let prev_pixel_format = self.pixel_format;
+            egui::ComboBox::from_label("Pixel format")
+                .selected_text(self.pixel_format.to_string())
+                .show_ui(ui, |ui| {
+                    for format in &self.available_pixel_formats {
+                        ui.selectable_value(&mut self.pixel_format, *format, format.to_string());
+                    }
+                });
+            if self.pixel_format != prev_pixel_format {
+                self.ui_action_tx
+                    .send(UiAction::ChangePixelFormat(self.pixel_format))
+                    .unwrap();
+            }
+
             let prev_selected_size = self.selected_size;
             egui::ComboBox::from_label("Frame sizes")
-                .selected_text(format!(
-                    "{:?}",
-                    self.available_frame_sizes[self.selected_size]
-                ))
+                .selected_text(
+                    self.available_frame_sizes
+                        .get(self.selected_size)
+                        .map(size_to_str)
+                        .unwrap_or_default(),
+                )
                 .show_ui(ui, |ui| {
                     for i in 0..self.available_frame_sizes.len() {
                         if ui
@@ -81,13 +495,457 @@ impl eframe::App for WebcamUi {
                 });
 
             if self.selected_size != prev_selected_size {
+                if let Some(&(width, height)) = self.available_frame_sizes.get(self.selected_size) {
+                    self.ui_action_tx
+                        .send(UiAction::ChangeSize(width, height))
+                        .unwrap();
+                }
+            }
+
+            let matrix_changed = option_combo(
+                ui,
+                "Colour matrix",
+                "Auto",
+                &mut self.color_override.matrix,
+                &convert::Matrix::ALL,
+            );
+            let range_changed = option_combo(
+                ui,
+                "Range",
+                "Auto",
+                &mut self.color_override.range,
+                &convert::Range::ALL,
+            );
+            let transfer_changed = option_combo(
+                ui,
+                "Transfer",
+                "Auto",
+                &mut self.color_override.transfer,
+                &convert::Transfer::ALL,
+            );
+            if matrix_changed || range_changed || transfer_changed {
+                self.pipeline_tx
+                    .send(PipelineAction::ColorOverride(self.color_override))
+                    .unwrap();
+            }
+
+            let prev_transform = self.transform;
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_label("Rotation")
+                    .selected_text(self.transform.rotation.to_string())
+                    .show_ui(ui, |ui| {
+                        for rotation in convert::Rotation::ALL {
+                            let text = rotation.to_string();
+                            ui.selectable_value(&mut self.transform.rotation, rotation, text);
+                        }
+                    });
+                ui.checkbox(&mut self.transform.hflip, "Mirror");
+                ui.checkbox(&mut self.transform.vflip, "Upside down");
+            });
+            if self.transform != prev_transform {
                 self.ui_action_tx
-                    .send(UiAction::ChangeSize(self.selected_size))
+                    .send(UiAction::SetTransform(self.transform))
+                    .unwrap();
+            }
+
+            let prev_ptz = self.ptz;
+            // Moves from the sliders happen right away
+            let mut ptz_duration = Duration::ZERO;
+            ui.collapsing("Pan, tilt and zoom", |ui| {
+                let zoom = egui::Slider::new(&mut self.ptz.zoom, 1.0..=pipeline::Ptz::MAX_ZOOM)
+                    .logarithmic(true)
+                    .text("Zoom");
+                ui.add(zoom);
+                ui.add(egui::Slider::new(&mut self.ptz.pan, -1.0..=1.0).text("Pan"));
+                ui.add(egui::Slider::new(&mut self.ptz.tilt, -1.0..=1.0).text("Tilt"));
+                ui.label("Or scroll and drag on the picture, unless that draws motion zones");
+                ui.horizontal(|ui| {
+                    if ui.button("Reset").clicked() {
+                        self.ptz = Default::default();
+                        ptz_duration = PRESET_MOVE;
+                    }
+                    ui.text_edit_singleline(&mut self.ptz_preset_name);
+                    if ui.button("Save preset").clicked() && !self.ptz_preset_name.is_empty() {
+                        let name = self.ptz_preset_name.clone();
+                        // Saving under a name that's taken moves that preset
+                        match self
+                            .ptz_presets
+                            .iter_mut()
+                            .find(|(taken, _)| *taken == name)
+                        {
+                            Some((_, preset)) => *preset = self.ptz,
+                            None => self.ptz_presets.push((name, self.ptz)),
+                        }
+                    }
+                });
+                let mut removed = None;
+                for (i, (name, preset)) in self.ptz_presets.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        if ui.button(name).clicked() {
+                            self.ptz = *preset;
+                            ptz_duration = PRESET_MOVE;
+                        }
+                        if ui.small_button("✖").clicked() {
+                            removed = Some(i);
+                        }
+                    });
+                }
+                if let Some(i) = removed {
+                    self.ptz_presets.remove(i);
+                }
+            });
+            if self.ptz != prev_ptz {
+                self.send_ptz(ptz_duration);
+            }
+
+            ui.collapsing("Camera", |ui| {
+                if self.controls.is_empty() {
+                    ui.label("The camera has no controls");
+                }
+                for (info, value) in &mut self.controls {
+                    let prev_value = *value;
+                    ui.add_enabled_ui(!info.read_only && !info.inactive, |ui| match info.kind {
+                        v4l2::ControlKind::Integer => {
+                            let slider = egui::Slider::new(value, info.minimum..=info.maximum)
+                                .step_by(info.step.max(1) as f64)
+                                .text(&info.name);
+                            ui.add(slider);
+                        }
+                        v4l2::ControlKind::Boolean => {
+                            let mut on = *value != 0;
+                            ui.checkbox(&mut on, &info.name);
+                            *value = on as i32;
+                        }
+                        v4l2::ControlKind::Menu => {
+                            let choice = info.menu.iter().find(|(index, _)| index == value);
+                            egui::ComboBox::new(info.id, &info.name)
+                                .selected_text(choice.map_or("", |(_, name)| name))
+                                .show_ui(ui, |ui| {
+                                    for (index, name) in &info.menu {
+                                        ui.selectable_value(value, *index, name);
+                                    }
+                                });
+                        }
+                    });
+                    if *value != prev_value {
+                        self.ui_action_tx
+                            .send(UiAction::SetControl(info.id, *value))
+                            .unwrap();
+                    }
+                }
+                if ui.button("Defaults").clicked() {
+                    for (info, value) in &mut self.controls {
+                        if !info.read_only && *value != info.default_value {
+                            *value = info.default_value;
+                            self.ui_action_tx
+                                .send(UiAction::SetControl(info.id, *value))
+                                .unwrap();
+                        }
+                    }
+                }
+            });
+
+            // Right by the camera's own, for cameras that don't have them
+            let prev_adjustments = self.adjustments;
+            ui.collapsing("Picture", |ui| {
+                let adjustments = &mut self.adjustments;
+                ui.add(
+                    egui::Slider::new(&mut adjustments.brightness, -0.5..=0.5).text("Brightness"),
+                );
+                ui.add(egui::Slider::new(&mut adjustments.contrast, 0.0..=3.0).text("Contrast"));
+                ui.add(
+                    egui::Slider::new(&mut adjustments.gamma, 0.2..=5.0)
+                        .logarithmic(true)
+                        .text("Gamma"),
+                );
+                ui.add(
+                    egui::Slider::new(&mut adjustments.saturation, 0.0..=3.0).text("Saturation"),
+                );
+                ui.add(egui::Slider::new(&mut adjustments.hue, -180.0..=180.0).text("Hue"));
+                for (gain, name) in adjustments.gains.iter_mut().zip(["Red", "Green", "Blue"]) {
+                    ui.add(egui::Slider::new(gain, 0.0..=4.0).text(format!("{name} gain")));
+                }
+                if ui.button("Reset").clicked() {
+                    *adjustments = Default::default();
+                }
+            });
+            if self.adjustments != prev_adjustments {
+                self.pipeline_tx
+                    .send(PipelineAction::Adjust(self.adjustments))
+                    .unwrap();
+            }
+
+            let prev_lut = (self.lut, self.interpolation);
+            ui.collapsing("Grading", |ui| {
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut self.lut_path);
+                    let load = egui::Button::new("Load .cube");
+                    if ui.add_enabled(!self.loading_lut, load).clicked() {
+                        // A 256³ one takes a while to read
+                        self.loading_lut = true;
+                        let path = self.lut_path.clone();
+                        let tx = self.gui_event_tx.clone();
+                        let ctx = ui.ctx().clone();
+                        std::thread::spawn(move || {
+                            let _ = tx.send(GuiEvent::LutLoaded(load_lut(&path)));
+                            ctx.request_repaint();
+                        });
+                    }
+                    if self.loading_lut {
+                        ui.spinner();
+                    }
+                });
+                if let Some(error) = &self.lut_error {
+                    ui.colored_label(egui::Color32::RED, error);
+                }
+
+                let name = |lut: Option<usize>| lut.map_or("None", |i| &self.lut_names[i]);
+                egui::ComboBox::from_label("LUT")
+                    .selected_text(name(self.lut))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut self.lut, None, "None");
+                        for (i, lut_name) in self.lut_names.iter().enumerate() {
+                            ui.selectable_value(&mut self.lut, Some(i), lut_name);
+                        }
+                    });
+                egui::ComboBox::from_label("Interpolation")
+                    .selected_text(self.interpolation.to_string())
+                    .show_ui(ui, |ui| {
+                        for interpolation in pipeline::Interpolation::ALL {
+                            let text = interpolation.to_string();
+                            ui.selectable_value(&mut self.interpolation, interpolation, text);
+                        }
+                    });
+            });
+            if (self.lut, self.interpolation) != prev_lut {
+                self.pipeline_tx
+                    .send(PipelineAction::SelectLut(self.lut, self.interpolation))
+                    .unwrap();
+            }
+
+            let prev_filter = self.filter;
+            egui::ComboBox::from_label("Preview scaling")
+                .selected_text(self.filter.to_string())
+                .show_ui(ui, |ui| {
+                    for filter in convert::Filter::ALL {
+                        ui.selectable_value(&mut self.filter, filter, filter.to_string());
+                    }
+                });
+
+            let prev_overlays = (self.overlays, self.burn_in_overlays);
+            ui.collapsing("Exposure", |ui| {
+                let overlays = &mut self.overlays;
+                ui.horizontal(|ui| {
+                    let mut zebra = overlays.zebra.is_some();
+                    ui.checkbox(&mut zebra, "Zebras");
+                    let mut level = overlays.zebra.unwrap_or(95.0);
+                    ui.add_enabled(
+                        zebra,
+                        egui::Slider::new(&mut level, 50.0..=109.0).text("IRE"),
+                    );
+                    overlays.zebra = zebra.then_some(level);
+                });
+                ui.checkbox(&mut overlays.false_color, "False colour");
+                ui.horizontal(|ui| {
+                    let mut peaking = overlays.peaking.is_some();
+                    ui.checkbox(&mut peaking, "Focus peaking");
+                    let mut settings = overlays.peaking.unwrap_or_default();
+                    ui.add_enabled_ui(peaking, |ui| {
+                        ui.color_edit_button_srgb(&mut settings.color);
+                        ui.add(
+                            egui::Slider::new(&mut settings.threshold, 8..=255).text("Threshold"),
+                        );
+                    });
+                    overlays.peaking = peaking.then_some(settings);
+                });
+                ui.checkbox(&mut self.burn_in_overlays, "Show in recordings too");
+            });
+            if (self.overlays, self.burn_in_overlays) != prev_overlays {
+                self.pipeline_tx
+                    .send(PipelineAction::Overlays(
+                        self.overlays,
+                        self.burn_in_overlays,
+                    ))
                     .unwrap();
             }
 
-            if let Some(texture) = &self.last_texture {
-                ui.image((texture.id(), texture.size_vec2()));
+            let prev_key = (self.key_enabled, self.key);
+            let prev_key_background = (self.key_background, self.key_color);
+            ui.collapsing("Chroma key", |ui| {
+                let key = &mut self.key;
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.key_enabled, "Key out");
+                    ui.color_edit_button_srgb(&mut key.color);
+                });
+                ui.add(egui::Slider::new(&mut key.tolerance, 0.0..=1.0).text("Tolerance"));
+                ui.add(egui::Slider::new(&mut key.softness, 0.0..=0.5).text("Softness"));
+                ui.add(egui::Slider::new(&mut key.spill, 0.0..=1.0).text("Spill suppression"));
+                ui.add(egui::Slider::new(&mut key.spill_range, 0.0..=1.0).text("Spill range"));
+
+                ui.horizontal(|ui| {
+                    ui.label("Background");
+                    ui.selectable_value(&mut self.key_background, KeyBackground::Color, "Colour");
+                    ui.selectable_value(&mut self.key_background, KeyBackground::Image, "Image");
+                    ui.selectable_value(&mut self.key_background, KeyBackground::Camera, "Camera");
+                });
+                match self.key_background {
+                    KeyBackground::Color => {
+                        ui.color_edit_button_srgb(&mut self.key_color);
+                    }
+                    KeyBackground::Image => {
+                        ui.horizontal(|ui| {
+                            ui.text_edit_singleline(&mut self.key_image_path);
+                            if ui.button("Load JPEG").clicked() {
+                                self.key_error = match load_jpeg(&self.key_image_path) {
+                                    Ok(image) => {
+                                        let background = pipeline::Background::Image(image);
+                                        self.pipeline_tx
+                                            .send(PipelineAction::KeyBackground(background))
+                                            .unwrap();
+                                        None
+                                    }
+                                    Err(e) => Some(e.to_string()),
+                                };
+                            }
+                        });
+                    }
+                    KeyBackground::Camera => {
+                        ui.horizontal(|ui| {
+                            ui.text_edit_singleline(&mut self.key_camera_path);
+                            let start = egui::Button::new("Start");
+                            if ui.add_enabled(!self.starting_key_camera, start).clicked() {
+                                // Opening it can take a while, or hang on a
+                                // camera that's on its way out
+                                self.starting_key_camera = true;
+                                let path = self.key_camera_path.clone();
+                                let tx = self.gui_event_tx.clone();
+                                let ctx = ui.ctx().clone();
+                                std::thread::spawn(move || start_key_camera(&path, tx, ctx));
+                            }
+                            if self.starting_key_camera {
+                                ui.spinner();
+                            }
+                        });
+                    }
+                }
+                if let Some(error) = &self.key_error {
+                    ui.colored_label(egui::Color32::RED, error);
+                }
+            });
+            if (self.key_enabled, self.key) != prev_key {
+                self.pipeline_tx
+                    .send(PipelineAction::Key(self.key_enabled, self.key))
+                    .unwrap();
+            }
+            // Images and cameras only once they're loaded or started
+            let color_picked = self.key_background == KeyBackground::Color
+                && (self.key_background, self.key_color) != prev_key_background;
+            if color_picked {
+                let background = pipeline::Background::Color(self.key_color);
+                self.pipeline_tx
+                    .send(PipelineAction::KeyBackground(background))
+                    .unwrap();
+            }
+
+            let prev_motion = (self.detect_motion, self.motion_settings.clone());
+            ui.collapsing("Motion", |ui| {
+                let settings = &mut self.motion_settings;
+                ui.checkbox(&mut self.detect_motion, "Detect motion");
+                ui.add(egui::Slider::new(&mut settings.sensitivity, 0.0..=1.0).text("Sensitivity"));
+                let mut min_area = settings.min_area * 100.0;
+                let slider = egui::Slider::new(&mut min_area, 0.01..=20.0)
+                    .logarithmic(true)
+                    .text("Smallest area, %");
+                // Only when it's moved, the round trip through percent isn't exact
+                if ui.add(slider).changed() {
+                    settings.min_area = min_area / 100.0;
+                }
+                ui.horizontal(|ui| {
+                    ui.label("Drag on the picture to");
+                    ui.selectable_value(&mut self.mask_tool, MaskTool::Off, "pan and tilt");
+                    ui.selectable_value(&mut self.mask_tool, MaskTool::Include, "watch");
+                    ui.selectable_value(&mut self.mask_tool, MaskTool::Exclude, "ignore");
+                    if ui.button("Clear zones").clicked() {
+                        settings.zones.clear();
+                    }
+                });
+                ui.label(if self.in_motion { "Motion" } else { "Still" });
+                for line in &self.motion_log {
+                    ui.label(line);
+                }
+            });
+            let (prev_detect_motion, prev_motion_settings) = prev_motion;
+            if self.detect_motion != prev_detect_motion
+                || self.motion_settings != prev_motion_settings
+            {
+                self.send_motion_settings();
+            }
+
+            let prev_shown_scopes = self.shown_scopes;
+            ui.collapsing("Scopes", |ui| {
+                let shown = &mut self.shown_scopes;
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut shown.histogram, "Histogram");
+                    ui.checkbox(&mut shown.waveform, "Waveform");
+                    ui.checkbox(&mut shown.parade, "RGB parade");
+                    ui.checkbox(&mut shown.vectorscope, "Vectorscope");
+                });
+            });
+            if self.shown_scopes != prev_shown_scopes {
+                self.pipeline_tx
+                    .send(PipelineAction::Scopes(self.shown_scopes))
+                    .unwrap();
+            }
+
+            ui.collapsing("Processing", |ui| {
+                for (i, stage) in self.stages.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        let mut enabled = stage.enabled;
+                        if ui.checkbox(&mut enabled, stage.name).changed() {
+                            let action = PipelineAction::Enable(stage.name, enabled);
+                            self.pipeline_tx.send(action).unwrap();
+                        }
+                        if ui.add_enabled(i > 0, egui::Button::new("⏶")).clicked() {
+                            let action = PipelineAction::Move(stage.name, i - 1);
+                            self.pipeline_tx.send(action).unwrap();
+                        }
+                        let last = i + 1 == self.stages.len();
+                        if ui.add_enabled(!last, egui::Button::new("⏷")).clicked() {
+                            let action = PipelineAction::Move(stage.name, i + 1);
+                            self.pipeline_tx.send(action).unwrap();
+                        }
+                        if stage.enabled {
+                            ui.label(format!(
+                                "{:.1} ms, {:.1} ms on average",
+                                stage.last.as_secs_f64() * 1000.0,
+                                stage.average.as_secs_f64() * 1000.0
+                            ));
+                        }
+                    });
+                }
+            });
+
+            ui.label(self.stats.snapshot().to_string());
+
+            // Frames get shrunk to whatever room is left for them
+            let room = ui.available_size();
+            let max_size = (room.x.max(1.0) as usize, room.y.max(1.0) as usize);
+            if self.filter != prev_filter || max_size != self.max_size {
+                self.max_size = max_size;
+                self.pipeline_tx
+                    .send(PipelineAction::Scale(self.filter, max_size))
+                    .unwrap();
+            }
+
+            if !self.connected {
+                ui.heading("Camera disconnected, waiting for it to come back...");
+            } else if let Some(texture) = &self.last_texture {
+                let image = egui::Image::new((texture.id(), texture.size_vec2()));
+                let response = ui.add(image.sense(egui::Sense::drag()));
+                if self.mask_tool == MaskTool::Off {
+                    self.steer_ptz(ui, &response);
+                }
+                self.draw_zones(ui, &response);
             }
 
             ui.heading("Hello World!");
@@ -95,84 +953,691 @@ impl eframe::App for WebcamUi {
     }
 }
 
+/// Where the chroma key background comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyBackground {
+    Color,
+    Image,
+    Camera,
+}
+
+// Motion events kept for showing
+const MOTION_LOG_LINES: usize = 10;
+
+// How long it takes to get to a preset
+const PRESET_MOVE: Duration = Duration::from_secs(1);
+
+/// What dragging over the picture does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaskTool {
+    /// Pans and tilts, like the mouse wheel zooms
+    Off,
+    /// Draws a zone to look for motion in
+    Include,
+    /// Draws a zone to never look for motion in
+    Exclude,
+}
+
+/// Which scopes go in the side panel.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ShownScopes {
+    pub histogram: bool,
+    pub waveform: bool,
+    pub parade: bool,
+    pub vectorscope: bool,
+}
+
+impl ShownScopes {
+    fn any(self) -> bool {
+        self.histogram || self.waveform || self.parade || self.vectorscope
+    }
+}
+
 pub enum UiAction {
-    ChangeSize(usize),
+    /// Width and height rather than an index, which a format change queued
+    /// up in front of it could make point at another size or none at all
+    ChangeSize(u32, u32),
+    ChangePixelFormat(v4l2::PixelFormat),
+    SetTransform(convert::Transform),
+    /// Where to point, and how long to take getting there
+    SetPtz(pipeline::Ptz, Duration),
+    /// Set one of the camera's controls, by id
+    SetControl(u32, i32),
 }
 
-fn feed_gui(
+/// Changes to how `feed_gui` processes frames.
+pub enum PipelineAction {
+    Enable(&'static str, bool),
+    /// Move a stage to another place in the pipeline
+    Move(&'static str, usize),
+    ColorOverride(convert::ColorOverride),
+    Transform(convert::Transform),
+    /// What's left for the `PtzStage` after the camera did what it could
+    Ptz(pipeline::Ptz),
+    Adjust(pipeline::Adjustments),
+    AddLut(pipeline::CubeLut),
+    /// Index of the LUT to grade with, in the order they were added
+    SelectLut(Option<usize>, pipeline::Interpolation),
+    /// Filter and the size to fit the preview in
+    Scale(convert::Filter, (usize, usize)),
+    Scopes(ShownScopes),
+    /// And whether they go in the picture rather than just the preview
+    Overlays(pipeline::Overlays, bool),
+    /// Whether to look for it at all, and how
+    Motion(bool, motion::MotionSettings),
+    /// Whether to key at all, and how
+    Key(bool, pipeline::ChromaKey),
+    KeyBackground(pipeline::Background),
+}
+
+pub enum GuiEvent {
+    Frame(TextureHandle),
+    /// Every processing stage, in order
+    Stages(Vec<pipeline::StageTiming>),
+    /// The camera's controls and what they're set to, whenever that may
+    /// have changed
+    Controls(Vec<(v4l2::ControlInfo, i32)>),
+    /// A LUT read from a file, named after its title or the file
+    LutLoaded(Result<(String, pipeline::CubeLut), String>),
+    /// Every scope asked for, by name
+    Scopes(Vec<(&'static str, TextureHandle)>),
+    /// Any motion that started or ended, and where things are moving, in
+    /// fractions of the frame as captured
+    Motion(Vec<motion::MotionEvent>, Vec<egui::Rect>),
+    /// The camera for the chroma key background running, or why it isn't
+    KeyCamera(Result<Arc<Mutex<convert::RgbaImage>>, String>),
+    /// The zoom and then the transform that make the picture out of the
+    /// frame as captured, as far as they're done in software, for drawing
+    /// on it
+    View(pipeline::Ptz, convert::Transform),
+    Disconnected,
+    Connected,
+    /// The new format and the frame sizes it comes in
+    FormatChanged(v4l2::FrameFormat, Vec<(u32, u32)>),
+}
+
+fn capture(
     ctx: egui::Context,
     mut v4l2_device: v4l2::V4l2VideoDevice,
-    tx: Sender<TextureHandle>,
+    tx: Sender<v4l2::V4l2Frame>,
+    gui_event_tx: Sender<GuiEvent>,
     rx: Receiver<UiAction>,
+    pipeline_tx: Sender<PipelineAction>,
 ) {
-    loop {
-        if let Ok(ui_action) = rx.try_recv() {
+    // Taken while the device is still there, so we can bring it back after an unplug
+    let mut device_state = v4l2_device.state();
+    let mut ptz = pipeline::Ptz::default();
+    let mut ptz_move: Option<pipeline::PtzMove> = None;
+    let mut ptz_controls = v4l2_device.ptz_controls();
+    // Whether the last try at getting a frame failed
+    let mut failing = false;
+
+    'capture: loop {
+        // Setting one control can change others, like automatic exposure
+        // turning off the exposure time
+        let mut controls_set = false;
+        // Dragging over the picture sends lots of these
+        while let Ok(ui_action) = rx.try_recv() {
             match ui_action {
-                UiAction::ChangeSize(idx) => {
-                    println!("Trying to change size to index {idx}");
-                    v4l2_device.set_frame_size(idx);
+                UiAction::ChangeSize(width, height) => {
+                    log::info!("Trying to change size to {width}x{height}");
+                    if let Err(e) = v4l2_device.set_size(width, height) {
+                        log::error!("Failed to change size: {e}");
+                    }
+                    device_state = v4l2_device.state();
+                }
+                UiAction::ChangePixelFormat(format) => {
+                    log::info!("Trying to change pixel format to {format}");
+                    if let Err(e) = v4l2_device.set_pixel_format(format) {
+                        log::error!("Failed to change pixel format: {e}");
+                    }
+                    device_state = v4l2_device.state();
+
+                    // Sizes differ between formats, and the driver may have
+                    // picked another one or even another format
+                    let event =
+                        GuiEvent::FormatChanged(v4l2_device.format(), frame_sizes(&v4l2_device));
+                    if gui_event_tx.send(event).is_err() {
+                        break 'capture;
+                    }
+                    ctx.request_repaint();
+                }
+                UiAction::SetTransform(transform) => {
+                    // Whatever flipping the device can do saves us a pass
+                    // over every frame
+                    let transform = transform.normalized();
+                    let (hflip, vflip) = v4l2_device.set_flip(transform.hflip, transform.vflip);
+                    device_state = v4l2_device.state();
+                    let software = convert::Transform {
+                        hflip,
+                        vflip,
+                        ..transform
+                    };
+                    log::info!("Transforming {transform:?}, {software:?} of it in software");
+                    if pipeline_tx
+                        .send(PipelineAction::Transform(software))
+                        .is_err()
+                    {
+                        break 'capture;
+                    }
+                }
+                UiAction::SetPtz(to, duration) => {
+                    ptz_move = Some(pipeline::PtzMove::new(ptz, to, Instant::now(), duration));
+                }
+                UiAction::SetControl(id, value) => {
+                    if let Err(e) = v4l2_device.set_control(id, value) {
+                        log::error!("Failed to set control {id:#x} to {value}: {e}");
+                    }
+                    controls_set = true;
+                }
+            }
+        }
+        if controls_set {
+            device_state = v4l2_device.state();
+            // Zoom, pan and tilt may no longer be where they were last set to
+            ptz_controls = v4l2_device.ptz_controls();
+            let event = GuiEvent::Controls(camera_controls(&v4l2_device));
+            if gui_event_tx.send(event).is_err() {
+                break;
+            }
+            ctx.request_repaint();
+        }
+
+        // Along with every frame until it's there
+        if let Some(moving) = ptz_move {
+            let now = Instant::now();
+            ptz = moving.at(now);
+            // Evenly across the device's zoom range, see `set_ptz`, so it
+            // won't be as close as the same zoom in software would be
+            let zoom = (ptz.zoom - 1.0) / (pipeline::Ptz::MAX_ZOOM - 1.0);
+            // Panning and tilting in software takes a software zoom to have
+            // room to move in, so the device only zooms if it does both
+            let (_, can_pan, can_tilt) = ptz_controls.available();
+            let device_zoom = if can_pan && can_tilt { zoom } else { 0.0 };
+            let (zoom_left, pan_left, tilt_left) =
+                v4l2_device.set_ptz(&mut ptz_controls, device_zoom, ptz.pan, ptz.tilt);
+            let software = pipeline::Ptz {
+                zoom: if zoom_left || !(can_pan && can_tilt) {
+                    ptz.zoom
+                } else {
+                    1.0
+                },
+                pan: if pan_left { ptz.pan } else { 0.0 },
+                tilt: if tilt_left { ptz.tilt } else { 0.0 },
+            };
+            if pipeline_tx.send(PipelineAction::Ptz(software)).is_err() {
+                break;
+            }
+            if moving.is_done(now) {
+                ptz_move = None;
+                device_state = v4l2_device.state();
+            }
+        }
+
+        match v4l2_device.get_frame() {
+            Ok(v4l2_frame) => {
+                failing = false;
+                if tx.send(v4l2_frame).is_err() {
+                    break;
+                }
+            }
+            Err(e) if !v4l2::is_disconnected(&e) => {
+                // Say so once, and don't spin if it keeps failing
+                if !failing {
+                    log::error!("Failed to get a frame: {e}");
+                    failing = true;
+                }
+                std::thread::sleep(Duration::from_millis(100));
+            }
+            Err(e) => {
+                log::error!("Lost camera: {e}");
+                if gui_event_tx.send(GuiEvent::Disconnected).is_err() {
+                    break;
+                }
+                ctx.request_repaint();
+
+                // Let go of it before opening it again
+                let stats = v4l2_device.stats();
+                drop(v4l2_device);
+                v4l2_device = wait_for_device(&device_state);
+                ptz_controls = v4l2_device.ptz_controls();
+                failing = false;
+                v4l2_device.set_stats(stats);
+                log::info!("Camera is back at {:?}", device_state.path);
+
+                if gui_event_tx.send(GuiEvent::Connected).is_err() {
+                    break;
+                }
+                // It may have come back another camera altogether
+                let event = GuiEvent::Controls(camera_controls(&v4l2_device));
+                if gui_event_tx.send(event).is_err() {
+                    break;
                 }
             }
         }
+    }
+}
 
-        let v4l2_frame = v4l2_device.get_frame();
+fn wait_for_device(device_state: &v4l2::DeviceState) -> v4l2::V4l2VideoDevice {
+    // Without inotify it's down to trying once a second
+    let mut watcher = match v4l2::DeviceWatcher::new(&"/dev") {
+        Ok(watcher) => Some(watcher),
+        Err(e) => {
+            log::warn!("Can't watch /dev, polling instead: {e}");
+            None
+        }
+    };
+    // A by-id link could point at any of the nodes that show up
+    let any_node = device_state.path.parent() != Some(Path::new("/dev"));
 
-        // YUYV encoded
-        let data = v4l2_frame.data();
+    loop {
+        // It may already be back before the watcher was set up
+        if let Ok(v4l2_device) = v4l2::V4l2VideoDevice::reopen(device_state) {
+            return v4l2_device;
+        }
 
-        let ys = data.iter().step_by(2);
-        let us = data
-            .iter()
-            .skip(1)
-            .step_by(4)
-            .flat_map(|u| repeat(u).take(2));
-        let vs = data
-            .iter()
-            .skip(3)
-            .step_by(4)
-            .flat_map(|u| repeat(u).take(2));
+        // udev fixes up permissions and by-id links a little after the node
+        // shows up, so don't only retry on events but also once a second.
+        let next_try = Instant::now() + Duration::from_secs(1);
+        loop {
+            let left = next_try.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                break;
+            }
+            let Some(watching) = &mut watcher else {
+                std::thread::sleep(left);
+                break;
+            };
+            match watching.wait(Some(left)) {
+                Ok(events) => {
+                    let ours = events.iter().any(|event| match event {
+                        v4l2::HotplugEvent::Added(path) => any_node || *path == device_state.path,
+                        v4l2::HotplugEvent::Removed(_) => false,
+                    });
+                    if ours {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    log::warn!("Stopped watching /dev, polling instead: {e}");
+                    watcher = None;
+                }
+            }
+        }
+    }
+}
 
-        let color_data: Vec<Color32> = ys
-            .zip(us)
-            .zip(vs)
-            .map(|((y, u), v)| {
-                let y = *y as f32 - 16.;
-                let u = *u as f32 - 128.;
-                let v = *v as f32 - 128.;
+// The end of the pipeline, where frames get handed to the UI
+struct Display {
+    ctx: egui::Context,
+    tx: Sender<GuiEvent>,
+}
 
-                let r = 1.164 * y + 1.596 * v;
-                let g = 1.164 * y - 0.392 * u - 0.813 * v;
-                let b = 1.164 * y + 2.017 * u;
+impl Display {
+    const NAME: &'static str = "display";
+}
 
-                egui::Color32::from_rgb(r as u8, g as u8, b as u8)
-            })
-            .collect();
+impl FrameProcessor for Display {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
 
-        // Just get black & white data
-        // let color_data: Vec<Color32> = data
-        //     .iter()
-        //     // Discard U and V data
-        //     .step_by(2)
-        //     .map(|y| egui::Color32::from_gray(*y))
-        //     .collect();
-
-        let image = ColorImage {
-            size: [v4l2_frame.width(), v4l2_frame.height()],
-            pixels: color_data,
+    fn process(
+        &mut self,
+        frame: &mut pipeline::Frame,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let rgba = if frame.preview.data().is_empty() {
+            &frame.rgba
+        } else {
+            &frame.preview
         };
+        // Nothing converted it
+        if rgba.data().is_empty() {
+            return Ok(());
+        }
+        let image = ColorImage::from_rgba_unmultiplied([rgba.width(), rgba.height()], rgba.data());
+        let texture = self
+            .ctx
+            .load_texture("our only image", image, TextureOptions::LINEAR);
+        self.tx
+            .send(GuiEvent::Frame(texture))
+            .map_err(|_| "the UI is gone")?;
+        Ok(())
+    }
+}
+
+// How often the stage timings get sent to the UI
+const TIMINGS_INTERVAL: Duration = Duration::from_millis(250);
+
+fn feed_gui(
+    ctx: egui::Context,
+    rx: Receiver<v4l2::V4l2Frame>,
+    tx: Sender<GuiEvent>,
+    pipeline_rx: Receiver<PipelineAction>,
+) {
+    let converter = match convert::ParallelConverter::new(0) {
+        Ok(converter) => converter,
+        Err(e) => {
+            log::error!("Can't start the conversion threads: {e}");
+            std::process::exit(1);
+        }
+    };
+    let display = Display {
+        ctx: ctx.clone(),
+        tx: tx.clone(),
+    };
+    let mut pipeline = Pipeline::new()
+        .with(pipeline::ConvertStage::new(converter))
+        .with(pipeline::KeyStage::default())
+        .with(pipeline::ScopeStage::new())
+        .with(pipeline::MotionStage::default())
+        .with(pipeline::PtzStage::default())
+        .with(pipeline::AdjustStage::default())
+        .with(pipeline::LutStage::default())
+        .with(pipeline::TransformStage::default())
+        .with(pipeline::OverlayStage::default())
+        .with(pipeline::ScaleStage::new(Default::default(), (0, 0)))
+        .with(display);
+    // Until asked for, the GPU does the scaling
+    pipeline.set_enabled(pipeline::ScaleStage::NAME, false);
+    // Nobody's looking at them yet
+    pipeline.set_enabled(pipeline::ScopeStage::NAME, false);
+    pipeline.set_enabled(pipeline::MotionStage::NAME, false);
+    pipeline.set_enabled(pipeline::KeyStage::NAME, false);
+    let mut shown_scopes = ShownScopes::default();
+    let mut scope_images = ScopeImages::default();
+    let mut last_error = None;
+    let mut timings_sent: Option<Instant> = None;
+    let mut view_sent = Default::default();
+
+    while let Ok(mut v4l2_frame) = rx.recv() {
+        // Only the newest frame is worth showing
+        while let Ok(newer) = rx.try_recv() {
+            v4l2_frame = newer;
+        }
+        let detecting_motion = pipeline.is_enabled(pipeline::MotionStage::NAME);
+        while let Ok(action) = pipeline_rx.try_recv() {
+            match action {
+                PipelineAction::Enable(name, enabled) => {
+                    pipeline.set_enabled(name, enabled);
+                }
+                PipelineAction::Move(name, index) => {
+                    pipeline.move_stage(name, index);
+                }
+                PipelineAction::ColorOverride(color_override) => {
+                    if let Some(stage) = pipeline.stage_mut::<pipeline::ConvertStage>() {
+                        stage.color_override = color_override;
+                    }
+                    if let Some(stage) = pipeline.stage_mut::<pipeline::ScopeStage>() {
+                        stage.color_override = color_override;
+                    }
+                    if let Some(stage) = pipeline.stage_mut::<pipeline::KeyStage>() {
+                        stage.color_override = color_override;
+                    }
+                }
+                PipelineAction::Transform(transform) => {
+                    if let Some(stage) = pipeline.stage_mut::<pipeline::TransformStage>() {
+                        stage.transform = transform;
+                    }
+                }
+                PipelineAction::Ptz(ptz) => {
+                    if let Some(stage) = pipeline.stage_mut::<pipeline::PtzStage>() {
+                        stage.ptz = ptz;
+                    }
+                }
+                PipelineAction::Adjust(adjustments) => {
+                    if let Some(stage) = pipeline.stage_mut::<pipeline::AdjustStage>() {
+                        stage.adjustments = adjustments;
+                    }
+                }
+                PipelineAction::AddLut(lut) => {
+                    if let Some(stage) = pipeline.stage_mut::<pipeline::LutStage>() {
+                        stage.add(lut);
+                    }
+                }
+                PipelineAction::SelectLut(lut, interpolation) => {
+                    if let Some(stage) = pipeline.stage_mut::<pipeline::LutStage>() {
+                        stage.selected = lut;
+                        stage.interpolation = interpolation;
+                    }
+                }
+                PipelineAction::Scale(filter, max_size) => {
+                    if let Some(stage) = pipeline.stage_mut::<pipeline::ScaleStage>() {
+                        stage.filter = filter;
+                        stage.max_size = max_size;
+                    }
+                }
+                PipelineAction::Overlays(overlays, burn_in) => {
+                    if let Some(stage) = pipeline.stage_mut::<pipeline::OverlayStage>() {
+                        stage.overlays = overlays;
+                        stage.burn_in = burn_in;
+                    }
+                }
+                PipelineAction::Key(enabled, key) => {
+                    pipeline.set_enabled(pipeline::KeyStage::NAME, enabled);
+                    if let Some(stage) = pipeline.stage_mut::<pipeline::KeyStage>() {
+                        stage.key = key;
+                    }
+                }
+                PipelineAction::KeyBackground(background) => {
+                    if let Some(stage) = pipeline.stage_mut::<pipeline::KeyStage>() {
+                        stage.set_background(background);
+                    }
+                }
+                PipelineAction::Motion(enabled, settings) => {
+                    pipeline.set_enabled(pipeline::MotionStage::NAME, enabled);
+                    if let Some(stage) = pipeline.stage_mut::<pipeline::MotionStage>() {
+                        stage.detector.settings = settings;
+                    }
+                }
+                PipelineAction::Scopes(shown) => {
+                    shown_scopes = shown;
+                    pipeline.set_enabled(pipeline::ScopeStage::NAME, shown.any());
+                }
+            }
+        }
+        // Otherwise the UI would be left waiting for the end of whatever was
+        // moving
+        if detecting_motion && !pipeline.is_enabled(pipeline::MotionStage::NAME) {
+            if let Some(stage) = pipeline.stage_mut::<pipeline::MotionStage>() {
+                stage.stop(Instant::now());
+                if tx
+                    .send(GuiEvent::Motion(stage.take_events(), Vec::new()))
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        }
+        let view = picture_view(&mut pipeline);
+        if view != view_sent {
+            view_sent = view;
+            if tx.send(GuiEvent::View(view.0, view.1)).is_err() {
+                break;
+            }
+        }
 
-        let texture = ctx.load_texture("our only image", image, TextureOptions::LINEAR);
-        tx.send(texture).unwrap();
+        match pipeline.process(v4l2_frame) {
+            Ok(()) => last_error = None,
+            Err(e) if e.stage == Display::NAME => break,
+            Err(e) => {
+                // Once is enough, it's going to be the same for every frame
+                let message = e.to_string();
+                if last_error.as_ref() != Some(&message) {
+                    log::error!("Can't show frame: {message}");
+                    last_error = Some(message);
+                }
+            }
+        }
+        if pipeline.is_enabled(pipeline::MotionStage::NAME) {
+            if let Some(stage) = pipeline.stage_mut::<pipeline::MotionStage>() {
+                let (width, height) = stage.detector.frame_size();
+                let size = egui::vec2(width.max(1) as f32, height.max(1) as f32);
+                let regions = stage.detector.blobs().iter().map(|blob| {
+                    let min = egui::pos2(blob.x as f32, blob.y as f32);
+                    let blob_size = egui::vec2(blob.width as f32, blob.height as f32);
+                    egui::Rect::from_min_size((min.to_vec2() / size).to_pos2(), blob_size / size)
+                });
+                let regions = regions.collect();
+                let event = GuiEvent::Motion(stage.take_events(), regions);
+                if tx.send(event).is_err() {
+                    break;
+                }
+            }
+        }
+        if pipeline.is_enabled(pipeline::ScopeStage::NAME) {
+            if let Some(stage) = pipeline.stage_mut::<pipeline::ScopeStage>() {
+                let textures = scope_images.upload(&ctx, stage.scopes(), shown_scopes);
+                if tx.send(GuiEvent::Scopes(textures)).is_err() {
+                    break;
+                }
+            }
+        }
+        // Nobody reads them at the frame rate
+        if timings_sent.is_none_or(|sent| sent.elapsed() >= TIMINGS_INTERVAL) {
+            timings_sent = Some(Instant::now());
+            if tx.send(GuiEvent::Stages(pipeline.timings())).is_err() {
+                break;
+            }
+        }
         ctx.request_repaint();
     }
 }
 
+// The software zoom and transform the pipeline does, none for a stage that's
+// off. They go in that order, as long as the stages weren't moved around.
+fn picture_view(pipeline: &mut Pipeline) -> (pipeline::Ptz, convert::Transform) {
+    let mut ptz = pipeline::Ptz::default();
+    if pipeline.is_enabled(pipeline::PtzStage::NAME) {
+        if let Some(stage) = pipeline.stage_mut::<pipeline::PtzStage>() {
+            ptz = stage.ptz;
+        }
+    }
+    let mut transform = convert::Transform::default();
+    if pipeline.is_enabled(pipeline::TransformStage::NAME) {
+        if let Some(stage) = pipeline.stage_mut::<pipeline::TransformStage>() {
+            transform = stage.transform;
+        }
+    }
+    (ptz, transform)
+}
+
+fn load_lut(path: &str) -> Result<(String, pipeline::CubeLut), String> {
+    let lut = pipeline::CubeLut::load(path).map_err(|e| e.to_string())?;
+    let file_name = Path::new(path).file_stem().unwrap_or_default();
+    let name = lut.title.clone().filter(|title| !title.is_empty());
+    Ok((
+        name.unwrap_or_else(|| file_name.to_string_lossy().into()),
+        lut,
+    ))
+}
+
+fn load_jpeg(path: &str) -> Result<convert::RgbaImage, Box<dyn std::error::Error>> {
+    let data = std::fs::read(path)?;
+    let format = v4l2::FrameFormat {
+        pixel_format: v4l2::PixelFormat::JPEG,
+        ..Default::default()
+    };
+    let mut image = convert::RgbaImage::new();
+    convert::frame_to_rgba(&[&data], &format, format.color, &mut image)?;
+    Ok(image)
+}
+
+// Keeps `picture` showing what another camera sees, for as long as anything
+// else still has a hold of it
+// Opens the camera at `path` and keeps capturing from it for as long as the
+// picture it's sent is kept
+fn start_key_camera(path: &str, tx: Sender<GuiEvent>, ctx: egui::Context) {
+    let device = match v4l2::V4l2VideoDevice::new(&path) {
+        Ok(device) => device,
+        Err(e) => {
+            let _ = tx.send(GuiEvent::KeyCamera(Err(e.to_string())));
+            ctx.request_repaint();
+            return;
+        }
+    };
+    let picture = Arc::new(Mutex::new(convert::RgbaImage::new()));
+    if tx.send(GuiEvent::KeyCamera(Ok(picture.clone()))).is_err() {
+        return;
+    }
+    ctx.request_repaint();
+    capture_background(device, picture);
+}
+
+fn capture_background(device: v4l2::V4l2VideoDevice, picture: Arc<Mutex<convert::RgbaImage>>) {
+    let mut rgba = convert::RgbaImage::new();
+    while Arc::strong_count(&picture) > 1 {
+        let frame = match device.get_frame() {
+            Ok(frame) => frame,
+            Err(e) => {
+                log::error!("Lost the background camera: {e}");
+                break;
+            }
+        };
+        let format = frame.format();
+        match convert::frame_to_rgba(&frame.planes(), format, format.color, &mut rgba) {
+            Ok(()) => {
+                let mut shared = picture.lock().unwrap_or_else(PoisonError::into_inner);
+                std::mem::swap(&mut *shared, &mut rgba);
+            }
+            Err(e) => log::error!("Can't show the background camera: {e}"),
+        }
+    }
+}
+
+// What the scopes get rendered into, kept from frame to frame
+#[derive(Default)]
+struct ScopeImages {
+    histogram: convert::RgbaImage,
+    waveform: convert::RgbaImage,
+    parade: convert::RgbaImage,
+    vectorscope: convert::RgbaImage,
+}
+
+impl ScopeImages {
+    fn upload(
+        &mut self,
+        ctx: &egui::Context,
+        scopes: &scopes::Scopes,
+        shown: ShownScopes,
+    ) -> Vec<(&'static str, TextureHandle)> {
+        let mut textures = Vec::new();
+        let mut upload = |name: &'static str, image: &convert::RgbaImage| {
+            let size = [image.width(), image.height()];
+            let image = ColorImage::from_rgba_unmultiplied(size, image.data());
+            textures.push((name, ctx.load_texture(name, image, TextureOptions::LINEAR)));
+        };
+        if shown.histogram {
+            scopes.render_histogram(128, &mut self.histogram);
+            upload("Histogram", &self.histogram);
+        }
+        if shown.waveform {
+            scopes.render_waveform(&mut self.waveform);
+            upload("Waveform", &self.waveform);
+        }
+        if shown.parade {
+            scopes.render_parade(&mut self.parade);
+            upload("RGB parade", &self.parade);
+        }
+        if shown.vectorscope {
+            scopes.render_vectorscope(&mut self.vectorscope);
+            upload("Vectorscope", &self.vectorscope);
+        }
+        textures
+    }
+}
+
 fn main() {
+    // Everything but debugging output, unless RUST_LOG says otherwise
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
     let native_options = eframe::NativeOptions::default();
-    eframe::run_native(
+    let result = eframe::run_native(
         "Web Cam",
         native_options,
         Box::new(|cc| Box::new(WebcamUi::new(cc))),
-    )
-    .unwrap();
+    );
+    if let Err(e) = result {
+        log::error!("Can't open a window: {e}");
+        std::process::exit(1);
+    }
 }
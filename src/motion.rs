@@ -0,0 +1,581 @@
+//! Motion detection: a downscaled luma picture compared against a
+//! background that slowly follows the scene.
+
+use std::time::{Duration, Instant};
+
+/// Most cells across the grid motion gets looked for in.
+pub const MAX_COLUMNS: usize = 160;
+
+// Pixels averaged across and down a cell, at most
+const CELL_SAMPLES: usize = 4;
+
+// Changes over more of the picture than this are the lights or the camera's
+// exposure, not something moving
+const LIGHTING_CHANGE: f32 = 0.6;
+
+// A gap this long between frames and the background is no good anymore
+const STALE_AFTER: Duration = Duration::from_secs(1);
+
+/// A rectangle of the picture, in fractions of its width and height, that
+/// motion is looked for in, or never looked for in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Zone {
+    pub left: f32,
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+    /// Motion in here never counts. Otherwise only motion inside one of the
+    /// zones does, if there are any that aren't excluded.
+    pub exclude: bool,
+}
+
+impl Zone {
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        (self.left..self.right).contains(&x) && (self.top..self.bottom).contains(&y)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MotionSettings {
+    /// From 0 to 1, higher takes smaller changes in brightness for motion
+    pub sensitivity: f32,
+    /// The smallest moving area that counts, as a fraction of the picture
+    pub min_area: f32,
+    /// How much of the way the background moves towards every frame. Higher
+    /// forgets things that stopped moving sooner.
+    pub learning_rate: f32,
+    /// How long things have to be still before motion is over
+    pub hold: Duration,
+    pub zones: Vec<Zone>,
+}
+
+impl Default for MotionSettings {
+    fn default() -> Self {
+        Self {
+            sensitivity: 0.5,
+            min_area: 0.002,
+            learning_rate: 0.02,
+            hold: Duration::from_secs(2),
+            zones: Vec::new(),
+        }
+    }
+}
+
+/// A rectangle of the frame, in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Bounds {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Bounds {
+    /// The smallest rectangle around both.
+    pub fn union(self, other: Bounds) -> Bounds {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        let right = (self.x + self.width).max(other.x + other.width);
+        let bottom = (self.y + self.height).max(other.y + other.height);
+        Bounds {
+            x,
+            y,
+            width: right - x,
+            height: bottom - y,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MotionEvent {
+    Started {
+        at: Instant,
+        /// Around each thing that moved
+        regions: Vec<Bounds>,
+    },
+    Ended {
+        at: Instant,
+        /// When the motion started
+        since: Instant,
+        /// Around everything that moved while it went on
+        area: Bounds,
+    },
+}
+
+// Motion that hasn't ended yet
+struct Ongoing {
+    since: Instant,
+    last_moved: Instant,
+    area: Bounds,
+}
+
+/// Looks for motion frame after frame, see `update`.
+pub struct MotionDetector {
+    pub settings: MotionSettings,
+    frame_size: (usize, usize),
+    // Pixels across and down a cell
+    cell: usize,
+    columns: usize,
+    rows: usize,
+    // Average luma of every cell
+    current: Vec<f32>,
+    background: Vec<f32>,
+    moving: Vec<bool>,
+    blobs: Vec<Bounds>,
+    last_update: Option<Instant>,
+    ongoing: Option<Ongoing>,
+}
+
+impl MotionDetector {
+    pub fn new(settings: MotionSettings) -> Self {
+        Self {
+            settings,
+            frame_size: (0, 0),
+            cell: 1,
+            columns: 0,
+            rows: 0,
+            current: Vec::new(),
+            background: Vec::new(),
+            moving: Vec::new(),
+            blobs: Vec::new(),
+            last_update: None,
+            ongoing: None,
+        }
+    }
+
+    /// Size of the last frame.
+    pub fn frame_size(&self) -> (usize, usize) {
+        self.frame_size
+    }
+
+    /// What's moving in the last frame, big enough to count.
+    pub fn blobs(&self) -> &[Bounds] {
+        &self.blobs
+    }
+
+    pub fn in_motion(&self) -> bool {
+        self.ongoing.is_some()
+    }
+
+    /// Start over with the next frame as the background. Motion that was
+    /// going on ends `at`, and that's returned.
+    pub fn reset(&mut self, at: Instant) -> Option<MotionEvent> {
+        self.background.clear();
+        self.blobs.clear();
+        let ongoing = self.ongoing.take()?;
+        Some(MotionEvent::Ended {
+            at,
+            since: ongoing.since,
+            area: ongoing.area,
+        })
+    }
+
+    /// Look at a `width` by `height` frame that came in `at`, with `luma`
+    /// giving the Y' of its pixels. A frame `luma` runs out in the middle of
+    /// is skipped. Returns whether motion started or ended with it.
+    pub fn update(
+        &mut self,
+        width: usize,
+        height: usize,
+        luma: impl Fn(usize, usize) -> Option<u8>,
+        at: Instant,
+    ) -> Option<MotionEvent> {
+        if width == 0 || height == 0 {
+            return None;
+        }
+        // The new size's areas are no match for the old ones, so whatever
+        // was moving ends here
+        let mut ended = None;
+        if (width, height) != self.frame_size {
+            self.frame_size = (width, height);
+            self.cell = width.div_ceil(MAX_COLUMNS);
+            self.columns = width.div_ceil(self.cell);
+            self.rows = height.div_ceil(self.cell);
+            ended = self.reset(at);
+        }
+        let stale = self
+            .last_update
+            .is_none_or(|last| at.saturating_duration_since(last) > STALE_AFTER);
+        if stale {
+            self.background.clear();
+        }
+        self.last_update = Some(at);
+
+        if !self.downscale(&luma) {
+            return ended;
+        }
+        if self.background.is_empty() {
+            self.background.clone_from(&self.current);
+            self.moving.clear();
+            self.moving.resize(self.current.len(), false);
+            self.blobs.clear();
+        } else {
+            self.compare();
+            self.find_blobs();
+        }
+        ended.or_else(|| self.track(at))
+    }
+
+    // Average the luma of every cell into `current`, false if the frame is
+    // short
+    fn downscale(&mut self, luma: &impl Fn(usize, usize) -> Option<u8>) -> bool {
+        let (width, height) = self.frame_size;
+        let step = self.cell.div_ceil(CELL_SAMPLES);
+        self.current.clear();
+        for row in 0..self.rows {
+            let top = row * self.cell;
+            let bottom = (top + self.cell).min(height);
+            for column in 0..self.columns {
+                let left = column * self.cell;
+                let right = (left + self.cell).min(width);
+                let (mut sum, mut count) = (0u32, 0u32);
+                for y in (top..bottom).step_by(step) {
+                    for x in (left..right).step_by(step) {
+                        let Some(l) = luma(x, y) else {
+                            return false;
+                        };
+                        sum += l as u32;
+                        count += 1;
+                    }
+                }
+                self.current.push(sum as f32 / count as f32);
+            }
+        }
+        true
+    }
+
+    // Mark the cells that differ enough from the background, and move the
+    // background towards the frame
+    fn compare(&mut self) {
+        let settings = &self.settings;
+        let threshold = 2.0 + (1.0 - settings.sensitivity.clamp(0.0, 1.0)) * 40.0;
+        let (width, height) = self.frame_size;
+        let include = settings.zones.iter().any(|zone| !zone.exclude);
+
+        let mut counted = 0;
+        let mut moved = 0;
+        for (i, ((current, background), moving)) in self
+            .current
+            .iter()
+            .zip(&mut self.background)
+            .zip(&mut self.moving)
+            .enumerate()
+        {
+            // Zones go by the middle of the cell
+            let x = ((i % self.columns) as f32 + 0.5) * self.cell as f32 / width as f32;
+            let y = ((i / self.columns) as f32 + 0.5) * self.cell as f32 / height as f32;
+            let zones = settings.zones.iter().filter(|zone| zone.contains(x, y));
+            let (mut inside, mut excluded) = (false, false);
+            for zone in zones {
+                excluded |= zone.exclude;
+                inside |= !zone.exclude;
+            }
+
+            *moving = (current - *background).abs() >= threshold;
+            // Things that stop moving fade into the background, but slower
+            // than the background changes otherwise
+            let rate = if *moving {
+                settings.learning_rate / 10.0
+            } else {
+                settings.learning_rate
+            };
+            *background += (current - *background) * rate.clamp(0.0, 1.0);
+
+            if excluded || (include && !inside) {
+                *moving = false;
+            } else {
+                counted += 1;
+                moved += *moving as usize;
+            }
+        }
+
+        if moved as f32 > counted as f32 * LIGHTING_CHANGE {
+            self.background.clone_from(&self.current);
+            self.moving.fill(false);
+        }
+    }
+
+    // Group the moving cells that touch, and keep the groups that are big
+    // enough
+    fn find_blobs(&mut self) {
+        let (width, height) = self.frame_size;
+        let cell_area = (self.cell * self.cell) as f32 / (width * height) as f32;
+        let mut seen = vec![false; self.moving.len()];
+        let mut stack = Vec::new();
+        self.blobs.clear();
+
+        for start in 0..self.moving.len() {
+            if !self.moving[start] || seen[start] {
+                continue;
+            }
+            seen[start] = true;
+            stack.push(start);
+            let (mut left, mut top, mut right, mut bottom) = (usize::MAX, usize::MAX, 0, 0);
+            let mut cells = 0;
+            while let Some(i) = stack.pop() {
+                let (column, row) = (i % self.columns, i / self.columns);
+                left = left.min(column);
+                right = right.max(column);
+                top = top.min(row);
+                bottom = bottom.max(row);
+                cells += 1;
+
+                let neighbours = [
+                    (column > 0).then(|| i - 1),
+                    (column + 1 < self.columns).then_some(i + 1),
+                    (row > 0).then(|| i - self.columns),
+                    (row + 1 < self.rows).then_some(i + self.columns),
+                ];
+                for neighbour in neighbours.into_iter().flatten() {
+                    if self.moving[neighbour] && !seen[neighbour] {
+                        seen[neighbour] = true;
+                        stack.push(neighbour);
+                    }
+                }
+            }
+
+            if cells as f32 * cell_area >= self.settings.min_area {
+                let x = left * self.cell;
+                let y = top * self.cell;
+                self.blobs.push(Bounds {
+                    x,
+                    y,
+                    width: ((right + 1) * self.cell).min(width) - x,
+                    height: ((bottom + 1) * self.cell).min(height) - y,
+                });
+            }
+        }
+    }
+
+    fn track(&mut self, at: Instant) -> Option<MotionEvent> {
+        let around = self.blobs.iter().copied().reduce(Bounds::union);
+        match (&mut self.ongoing, around) {
+            (None, Some(area)) => {
+                self.ongoing = Some(Ongoing {
+                    since: at,
+                    last_moved: at,
+                    area,
+                });
+                Some(MotionEvent::Started {
+                    at,
+                    regions: self.blobs.clone(),
+                })
+            }
+            (Some(ongoing), Some(area)) => {
+                ongoing.last_moved = at;
+                ongoing.area = ongoing.area.union(area);
+                None
+            }
+            (Some(ongoing), None)
+                if at.saturating_duration_since(ongoing.last_moved) >= self.settings.hold =>
+            {
+                let event = MotionEvent::Ended {
+                    at,
+                    since: ongoing.since,
+                    area: ongoing.area,
+                };
+                self.ongoing = None;
+                Some(event)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Default for MotionDetector {
+    fn default() -> Self {
+        Self::new(MotionSettings::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIDTH: usize = 160;
+    const HEIGHT: usize = 120;
+    const FRAME: Duration = Duration::from_millis(33);
+
+    // A grey scene with a bright square, if any, `size` across at `at`
+    fn scene(square: Option<(usize, usize)>, size: usize) -> impl Fn(usize, usize) -> Option<u8> {
+        move |x, y| {
+            let inside = square.is_some_and(|(left, top)| {
+                (left..left + size).contains(&x) && (top..top + size).contains(&y)
+            });
+            Some(if inside { 200 } else { 60 })
+        }
+    }
+
+    // Runs `frames`, each the square's top left corner or none, and returns
+    // the events with the frame they came with
+    fn run(
+        detector: &mut MotionDetector,
+        start: Instant,
+        frames: impl IntoIterator<Item = Option<(usize, usize)>>,
+        size: usize,
+    ) -> Vec<(usize, MotionEvent)> {
+        let mut events = Vec::new();
+        for (i, square) in frames.into_iter().enumerate() {
+            let at = start + FRAME * i as u32;
+            events.extend(
+                detector
+                    .update(WIDTH, HEIGHT, scene(square, size), at)
+                    .map(|event| (i, event)),
+            );
+        }
+        events
+    }
+
+    // Still at first, then a square that moves right for a second and leaves,
+    // then still for three seconds
+    fn passing_square() -> Vec<Option<(usize, usize)>> {
+        let mut frames = vec![None; 5];
+        frames.extend((0..30).map(|i| Some((10 + i * 4, 50))));
+        frames.extend([None; 90]);
+        frames
+    }
+
+    #[test]
+    fn still_scenes_dont_move() {
+        let mut detector = MotionDetector::default();
+        let events = run(&mut detector, Instant::now(), [None; 60], 20);
+        assert_eq!(events, []);
+        assert!(!detector.in_motion());
+        assert_eq!(detector.blobs(), []);
+    }
+
+    #[test]
+    fn a_moving_square_starts_and_ends_motion() {
+        let mut detector = MotionDetector::default();
+        let start = Instant::now();
+        let events = run(&mut detector, start, passing_square(), 20);
+        assert_eq!(events.len(), 2, "{events:?}");
+
+        let (frame, MotionEvent::Started { at, regions }) = &events[0] else {
+            panic!("{events:?}");
+        };
+        assert_eq!(*frame, 5);
+        assert_eq!(*at, start + FRAME * 5);
+        let square = Bounds {
+            x: 10,
+            y: 50,
+            width: 20,
+            height: 20,
+        };
+        assert_eq!(*regions, [square]);
+
+        // Once nothing moved for `hold`, the last time being just before
+        // it was gone
+        let (frame, MotionEvent::Ended { at, since, area }) = &events[1] else {
+            panic!("{events:?}");
+        };
+        let hold = MotionSettings::default().hold;
+        let moved = start + FRAME * 34;
+        assert!(
+            *at >= moved + hold && *at < moved + hold + FRAME,
+            "frame {frame}"
+        );
+        assert_eq!(*since, start + FRAME * 5);
+        // Everywhere it went
+        let last = 10 + 29 * 4;
+        assert_eq!(
+            *area,
+            Bounds {
+                width: last + 20 - 10,
+                ..square
+            }
+        );
+        assert!(!detector.in_motion());
+    }
+
+    #[test]
+    fn blobs_follow_what_moves() {
+        let mut detector = MotionDetector::default();
+        let frames = passing_square().into_iter().take(20);
+        run(&mut detector, Instant::now(), frames, 20);
+        // Where it is now, and where it just was, as the background only
+        // slowly forgets
+        let blobs = detector.blobs();
+        assert_eq!(blobs.len(), 1, "{blobs:?}");
+        let now = 10 + 14 * 4;
+        assert!(blobs[0].x <= now && blobs[0].x + blobs[0].width == now + 20);
+        assert_eq!((blobs[0].y, blobs[0].height), (50, 20));
+        assert!(detector.in_motion());
+    }
+
+    #[test]
+    fn small_things_dont_count() {
+        let mut detector = MotionDetector::default();
+        // 16 pixels, under 0.2% of the picture
+        let events = run(&mut detector, Instant::now(), passing_square(), 4);
+        assert_eq!(events, []);
+    }
+
+    #[test]
+    fn lighting_changes_dont_count() {
+        let mut detector = MotionDetector::default();
+        let start = Instant::now();
+        for (i, level) in [60, 60, 120, 120, 30].into_iter().enumerate() {
+            let event =
+                detector.update(WIDTH, HEIGHT, |_, _| Some(level), start + FRAME * i as u32);
+            assert_eq!(event, None);
+        }
+    }
+
+    #[test]
+    fn zones() {
+        // Top left, bottom right and the middle row the square goes along
+        let zone = |left, top, right, bottom, exclude| Zone {
+            left,
+            top,
+            right,
+            bottom,
+            exclude,
+        };
+        let cases = [
+            (vec![zone(0.0, 0.0, 0.3, 0.3, false)], false),
+            (vec![zone(0.0, 0.0, 1.0, 1.0, false)], true),
+            (vec![zone(0.0, 0.4, 1.0, 0.6, true)], false),
+            (vec![zone(0.7, 0.7, 1.0, 1.0, true)], true),
+            (
+                vec![
+                    zone(0.0, 0.0, 1.0, 1.0, false),
+                    zone(0.0, 0.4, 1.0, 0.6, true),
+                ],
+                false,
+            ),
+        ];
+        for (zones, moves) in cases {
+            let settings = MotionSettings {
+                zones: zones.clone(),
+                ..Default::default()
+            };
+            let mut detector = MotionDetector::new(settings);
+            let events = run(&mut detector, Instant::now(), passing_square(), 20);
+            assert_eq!(!events.is_empty(), moves, "{zones:?}");
+        }
+    }
+
+    #[test]
+    fn a_new_frame_size_ends_motion() {
+        let mut detector = MotionDetector::default();
+        let start = Instant::now();
+        let frames = passing_square().into_iter().take(10);
+        let events = run(&mut detector, start, frames, 20);
+        assert!(matches!(events[..], [(5, MotionEvent::Started { .. })]));
+
+        let at = start + FRAME * 10;
+        let event = detector.update(WIDTH / 2, HEIGHT / 2, scene(None, 0), at);
+        let Some(MotionEvent::Ended {
+            at: ended, since, ..
+        }) = event
+        else {
+            panic!("{event:?}");
+        };
+        assert_eq!((ended, since), (at, start + FRAME * 5));
+        assert!(!detector.in_motion());
+        assert_eq!(detector.frame_size(), (WIDTH / 2, HEIGHT / 2));
+        // And nothing more to end
+        assert_eq!(detector.reset(at), None);
+    }
+}
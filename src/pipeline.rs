@@ -0,0 +1,489 @@
+//! Chains of processing stages that frames go through after capture.
+
+mod adjust;
+mod cube;
+mod key;
+mod overlay;
+mod ptz;
+mod stages;
+
+use std::{
+    any::Any,
+    error::Error,
+    fmt,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    convert::{Rgba16Image, RgbaImage},
+    v4l2::{FrameFormat, V4l2Frame, MAX_PLANES},
+};
+
+pub use adjust::{AdjustStage, Adjustments};
+pub use cube::{CubeError, CubeLut, CubeTable, Interpolation, LutStage};
+pub use key::{Background, ChromaKey, KeyStage};
+pub use overlay::{false_color, OverlayStage, Overlays, Peaking};
+pub use ptz::{Ptz, PtzMove, PtzStage};
+pub use stages::{ConvertStage, MotionStage, ScaleStage, ScopeStage, TransformStage};
+
+/// The data of a frame as it came in.
+#[derive(Clone)]
+pub enum Captured {
+    Device(V4l2Frame),
+    /// Made up or loaded from somewhere, one `Vec` per plane
+    Data {
+        format: FrameFormat,
+        planes: Vec<Vec<u8>>,
+    },
+}
+
+impl Captured {
+    pub fn format(&self) -> &FrameFormat {
+        match self {
+            Captured::Device(frame) => frame.format(),
+            Captured::Data { format, .. } => format,
+        }
+    }
+
+    /// The data of every plane, empty past `format().num_planes`.
+    pub fn planes(&self) -> [&[u8]; MAX_PLANES] {
+        match self {
+            Captured::Device(frame) => frame.planes(),
+            Captured::Data { planes, .. } => {
+                let mut out = [&[][..]; MAX_PLANES];
+                for (out, plane) in out.iter_mut().zip(planes) {
+                    *out = plane;
+                }
+                out
+            }
+        }
+    }
+}
+
+impl Default for Captured {
+    fn default() -> Self {
+        Captured::Data {
+            format: FrameFormat::default(),
+            planes: Vec::new(),
+        }
+    }
+}
+
+impl From<V4l2Frame> for Captured {
+    fn from(frame: V4l2Frame) -> Self {
+        Captured::Device(frame)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Metadata {
+    /// Frames the pipeline has seen before this one
+    pub sequence: u64,
+    /// When the frame went into the pipeline
+    pub received: Instant,
+}
+
+// BT.709 luma of an sRGB pixel, in 8 bit fixed point
+fn luma(rgba: &[u8]) -> u8 {
+    ((54 * rgba[0] as u32 + 183 * rgba[1] as u32 + 19 * rgba[2] as u32 + 128) >> 8) as u8
+}
+
+/// A frame on its way through a `Pipeline`.
+pub struct Frame {
+    pub captured: Captured,
+    /// The picture so far. Empty until a stage converts the captured data,
+    /// after which stages take turns changing it.
+    pub rgba: RgbaImage,
+    /// What the viewer shows instead of `rgba`, unless it's empty. For things
+    /// like overlays that only help whoever's watching and shouldn't get
+    /// recorded. Stages after the one that fills it still only change `rgba`.
+    pub preview: RgbaImage,
+    /// The picture at 16 bits per channel, for recording 10 bit and deeper
+    /// frames without losing precision. Only filled by a `ConvertStage` with
+    /// `keep_16_bit` set, and no other stage touches it.
+    pub rgba16: Rgba16Image,
+    /// Whether `rgba` is still the captured data converted, pixel for pixel,
+    /// for stages that would rather work on the captured Y'CbCr. The
+    /// `ConvertStage` sets it, and every stage that moves or changes pixels
+    /// in `rgba` clears it, as they can be moved ahead of the others.
+    pub matches_captured: bool,
+    pub meta: Metadata,
+}
+
+impl Frame {
+    pub fn format(&self) -> &FrameFormat {
+        self.captured.format()
+    }
+}
+
+/// Something that gets done to every frame, one stage of a `Pipeline`.
+pub trait FrameProcessor: Any + Send {
+    /// Short and unique within a pipeline, it's how the stage gets found.
+    fn name(&self) -> &'static str;
+
+    /// Failing skips the rest of the pipeline for this frame.
+    fn process(&mut self, frame: &mut Frame) -> Result<(), Box<dyn Error + Send + Sync>>;
+}
+
+/// Why a frame didn't make it through a `Pipeline`.
+#[derive(Debug)]
+pub struct StageError {
+    pub stage: &'static str,
+    pub error: Box<dyn Error + Send + Sync>,
+}
+
+impl fmt::Display for StageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.stage, self.error)
+    }
+}
+
+impl Error for StageError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(self.error.as_ref())
+    }
+}
+
+/// How a stage is set up and how long it has been taking.
+#[derive(Debug, Clone)]
+pub struct StageTiming {
+    pub name: &'static str,
+    pub enabled: bool,
+    /// For the last frame it processed
+    pub last: Duration,
+    /// Over roughly the last `AVERAGE_FRAMES` frames
+    pub average: Duration,
+}
+
+/// How many frames `StageTiming::average` leans on.
+pub const AVERAGE_FRAMES: u32 = 32;
+
+struct Stage {
+    processor: Box<dyn FrameProcessor>,
+    enabled: bool,
+    last: Duration,
+    average: Duration,
+}
+
+/// Stages that every frame goes through in turn. Stages can be switched off
+/// and moved around between frames.
+///
+/// The frame is kept from one call to the next, so its buffers only get
+/// allocated again when the frames grow.
+pub struct Pipeline {
+    stages: Vec<Stage>,
+    frame: Frame,
+    frames: u64,
+}
+
+impl Default for Pipeline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Self {
+            stages: Vec::new(),
+            frame: Frame {
+                captured: Captured::default(),
+                rgba: RgbaImage::new(),
+                preview: RgbaImage::new(),
+                rgba16: Rgba16Image::new(),
+                matches_captured: false,
+                meta: Metadata {
+                    sequence: 0,
+                    received: Instant::now(),
+                },
+            },
+            frames: 0,
+        }
+    }
+
+    /// Add `processor` at the end, enabled.
+    ///
+    /// # Panics
+    ///
+    /// If there already is a stage with the same name.
+    pub fn push(&mut self, processor: impl FrameProcessor) {
+        assert!(
+            self.position(processor.name()).is_none(),
+            "two stages called {}",
+            processor.name()
+        );
+        self.stages.push(Stage {
+            processor: Box::new(processor),
+            enabled: true,
+            last: Duration::ZERO,
+            average: Duration::ZERO,
+        });
+    }
+
+    /// `push` for building a pipeline in one go.
+    pub fn with(mut self, processor: impl FrameProcessor) -> Self {
+        self.push(processor);
+        self
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.stages
+            .iter()
+            .position(|stage| stage.processor.name() == name)
+    }
+
+    /// The stage of type `T`, to change its settings.
+    pub fn stage_mut<T: FrameProcessor>(&mut self) -> Option<&mut T> {
+        self.stages.iter_mut().find_map(|stage| {
+            let processor: &mut dyn Any = stage.processor.as_mut();
+            processor.downcast_mut()
+        })
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Box<dyn FrameProcessor>> {
+        let index = self.position(name)?;
+        Some(self.stages.remove(index).processor)
+    }
+
+    /// Returns false if there is no stage called `name`.
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) -> bool {
+        let Some(index) = self.position(name) else {
+            return false;
+        };
+        self.stages[index].enabled = enabled;
+        true
+    }
+
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.position(name)
+            .is_some_and(|index| self.stages[index].enabled)
+    }
+
+    /// Move the stage called `name` to `index`, or the end if that's past it.
+    /// Returns false if there is no such stage.
+    pub fn move_stage(&mut self, name: &str, index: usize) -> bool {
+        let Some(from) = self.position(name) else {
+            return false;
+        };
+        let stage = self.stages.remove(from);
+        self.stages.insert(index.min(self.stages.len()), stage);
+        true
+    }
+
+    /// Every stage in order.
+    pub fn timings(&self) -> Vec<StageTiming> {
+        self.stages
+            .iter()
+            .map(|stage| StageTiming {
+                name: stage.processor.name(),
+                enabled: stage.enabled,
+                last: stage.last,
+                average: stage.average,
+            })
+            .collect()
+    }
+
+    /// Run `captured` through every enabled stage.
+    pub fn process(&mut self, captured: impl Into<Captured>) -> Result<(), StageError> {
+        let frame = &mut self.frame;
+        frame.captured = captured.into();
+        frame.rgba.resize(0, 0);
+        frame.preview.resize(0, 0);
+        frame.rgba16.resize(0, 0);
+        frame.matches_captured = false;
+        frame.meta = Metadata {
+            sequence: self.frames,
+            received: Instant::now(),
+        };
+        self.frames += 1;
+
+        let result = self
+            .stages
+            .iter_mut()
+            .filter(|stage| stage.enabled)
+            .try_for_each(|stage| {
+                let start = Instant::now();
+                let result = stage.processor.process(frame);
+                stage.last = start.elapsed();
+                // Moving average, with the first frame as the starting point
+                stage.average = if stage.average.is_zero() {
+                    stage.last
+                } else {
+                    (stage.average * (AVERAGE_FRAMES - 1) + stage.last) / AVERAGE_FRAMES
+                };
+                result.map_err(|error| StageError {
+                    stage: stage.processor.name(),
+                    error,
+                })
+            });
+        // Done with it, so a device buffer can go back to the driver
+        frame.captured = Captured::default();
+        result
+    }
+
+    /// The last frame to go through, as far as it got. Only the pictures and
+    /// the metadata are kept, not what was captured.
+    pub fn frame(&self) -> &Frame {
+        &self.frame
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::convert::{Filter, ParallelConverter, Rotation, Transform};
+    use crate::v4l2::PixelFormat;
+
+    type Log = Arc<Mutex<Vec<&'static str>>>;
+    // Of `rgba` and `preview`
+    type Sizes = Arc<Mutex<Vec<((usize, usize), (usize, usize))>>>;
+
+    // Notes down the stages that ran, and the size of the pictures the
+    // display would get
+    struct Probe {
+        name: &'static str,
+        log: Log,
+        sizes: Sizes,
+    }
+
+    impl FrameProcessor for Probe {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        fn process(&mut self, frame: &mut Frame) -> Result<(), Box<dyn Error + Send + Sync>> {
+            self.log.lock().unwrap().push(self.name);
+            let size = |image: &RgbaImage| (image.width(), image.height());
+            let sizes = (size(&frame.rgba), size(&frame.preview));
+            self.sizes.lock().unwrap().push(sizes);
+            Ok(())
+        }
+    }
+
+    struct Fail;
+
+    impl FrameProcessor for Fail {
+        fn name(&self) -> &'static str {
+            "fail"
+        }
+
+        fn process(&mut self, _: &mut Frame) -> Result<(), Box<dyn Error + Send + Sync>> {
+            Err("no good".into())
+        }
+    }
+
+    fn probe(name: &'static str, log: &Log) -> Probe {
+        Probe {
+            name,
+            log: log.clone(),
+            sizes: Default::default(),
+        }
+    }
+
+    // A YUYV gradient
+    fn yuyv(width: usize, height: usize) -> Captured {
+        let format = FrameFormat {
+            pixel_format: PixelFormat::YUYV,
+            width,
+            height,
+            ..Default::default()
+        };
+        let data = (0..width * height * 2)
+            .map(|i| (i % (width * 2) * 255 / (width * 2)) as u8)
+            .collect();
+        Captured::Data {
+            format,
+            planes: vec![data],
+        }
+    }
+
+    #[test]
+    fn convert_transform_scale_display() {
+        let log = Log::default();
+        let display = probe("display", &log);
+        let sizes = display.sizes.clone();
+        let mut pipeline = Pipeline::new()
+            .with(stages::ConvertStage::new(
+                ParallelConverter::new(2).unwrap(),
+            ))
+            .with(stages::TransformStage::new(Transform {
+                rotation: Rotation::Cw90,
+                ..Default::default()
+            }))
+            .with(stages::ScaleStage::new(Filter::Area, (16, 16)))
+            .with(display);
+
+        let names: Vec<_> = pipeline.timings().iter().map(|t| t.name).collect();
+        assert_eq!(names, ["convert", "transform", "scale", "display"]);
+
+        for sequence in 0..3 {
+            pipeline.process(yuyv(64, 32)).unwrap();
+            let frame = pipeline.frame();
+            assert_eq!(frame.meta.sequence, sequence);
+            // Rotated, and only the preview scaled down
+            assert_eq!((frame.rgba.width(), frame.rgba.height()), (32, 64));
+            assert_eq!((frame.preview.width(), frame.preview.height()), (8, 16));
+            // Done with what was captured
+            assert_eq!(frame.format().width, 0);
+        }
+        assert_eq!(*sizes.lock().unwrap(), [((32, 64), (8, 16)); 3]);
+        assert_eq!(*log.lock().unwrap(), ["display"; 3]);
+
+        for timing in pipeline.timings() {
+            assert!(timing.enabled);
+            assert!(timing.average >= timing.last / AVERAGE_FRAMES);
+        }
+        let convert = &pipeline.timings()[0];
+        assert!(!convert.last.is_zero() && !convert.average.is_zero());
+    }
+
+    #[test]
+    fn runs_enabled_stages_in_order() {
+        let log = Log::default();
+        let mut pipeline = Pipeline::new()
+            .with(probe("a", &log))
+            .with(probe("b", &log))
+            .with(probe("c", &log));
+
+        pipeline.process(yuyv(2, 2)).unwrap();
+        assert!(pipeline.move_stage("a", 10));
+        assert!(pipeline.set_enabled("b", false));
+        assert!(!pipeline.is_enabled("b"));
+        pipeline.process(yuyv(2, 2)).unwrap();
+        assert_eq!(*log.lock().unwrap(), ["a", "b", "c", "c", "a"]);
+
+        let timings = pipeline.timings();
+        let names: Vec<_> = timings.iter().map(|t| (t.name, t.enabled)).collect();
+        assert_eq!(names, [("b", false), ("c", true), ("a", true)]);
+
+        assert!(!pipeline.set_enabled("nope", true));
+        assert!(!pipeline.move_stage("nope", 0));
+        assert!(pipeline.remove("b").is_some());
+        assert!(pipeline.stage_mut::<Probe>().is_some());
+    }
+
+    #[test]
+    fn a_failing_stage_stops_the_frame() {
+        let log = Log::default();
+        let mut pipeline = Pipeline::new()
+            .with(probe("before", &log))
+            .with(Fail)
+            .with(probe("after", &log));
+
+        let error = pipeline.process(yuyv(2, 2)).unwrap_err();
+        assert_eq!(error.stage, "fail");
+        assert_eq!(error.to_string(), "fail: no good");
+        assert_eq!(*log.lock().unwrap(), ["before"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "two stages called a")]
+    fn names_are_unique() {
+        let log = Log::default();
+        let _ = Pipeline::new()
+            .with(probe("a", &log))
+            .with(probe("a", &log));
+    }
+}
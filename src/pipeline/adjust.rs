@@ -0,0 +1,191 @@
+use std::error::Error;
+
+use rayon::prelude::*;
+
+use super::{Frame, FrameProcessor};
+use crate::convert::RgbaImage;
+
+/// Picture controls done in software, for cameras whose own are missing or
+/// broken. The defaults leave the picture as it is.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Adjustments {
+    /// Added to every channel, as a fraction of full scale
+    pub brightness: f32,
+    /// Stretch away from mid grey, 1 leaves it
+    pub contrast: f32,
+    /// Above 1 lifts the shadows and midtones, below 1 pushes them down
+    pub gamma: f32,
+    /// 0 is greyscale, 1 leaves it
+    pub saturation: f32,
+    /// Rotation of the hues in degrees
+    pub hue: f32,
+    /// Red, green and blue multipliers, for white balance
+    pub gains: [f32; 3],
+}
+
+impl Default for Adjustments {
+    fn default() -> Self {
+        Self {
+            brightness: 0.0,
+            contrast: 1.0,
+            gamma: 1.0,
+            saturation: 1.0,
+            hue: 0.0,
+            gains: [1.0; 3],
+        }
+    }
+}
+
+impl Adjustments {
+    pub fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+
+    // Saturation and hue as an RGB matrix: into luma and two colour
+    // differences, scale and rotate those, and back out
+    fn color_matrix(&self) -> Option<[[f32; 3]; 3]> {
+        if self.saturation == 1.0 && self.hue == 0.0 {
+            return None;
+        }
+        // BT.709, the weights sRGB was made with
+        let (kr, kb) = (0.2126, 0.0722);
+        let kg = 1.0 - kr - kb;
+        let (sin, cos) = self.hue.to_radians().sin_cos();
+        let (sin, cos) = (sin * self.saturation, cos * self.saturation);
+
+        // Columns of what R, G and B do to luma, B-Y and R-Y
+        let to_diff = [[kr, kg, kb], [-kr, -kg, 1.0 - kb], [1.0 - kr, -kg, -kb]];
+        let mut matrix = [[0.0; 3]; 3];
+        for (input, column) in (0..3).map(|i| to_diff.map(|row| row[i])).enumerate() {
+            let [y, b, r] = column;
+            let (b, r) = (b * cos - r * sin, b * sin + r * cos);
+            let (red, blue) = (y + r, y + b);
+            let green = (y - kr * red - kb * blue) / kg;
+            for (row, out) in matrix.iter_mut().zip([red, green, blue]) {
+                row[input] = out;
+            }
+        }
+        Some(matrix)
+    }
+}
+
+// Fractional bits of the colour matrix
+const MATRIX_BITS: u32 = 12;
+
+// Rows per job when spreading a frame over the threads
+const BAND_ROWS: usize = 32;
+
+// Everything `Adjustments` does, ready to apply
+struct Tables {
+    // Gains, and without a colour matrix everything else too
+    input: [[u8; 256]; 3],
+    // The colour matrix, entry by entry, times every code value
+    products: Option<Box<[[[i32; 256]; 3]; 3]>>,
+    // Brightness, contrast and gamma, after the colour matrix
+    output: [u8; 256],
+}
+
+impl Tables {
+    fn new(adjustments: &Adjustments) -> Self {
+        let tone = |x: f32| {
+            let x = (x - 0.5) * adjustments.contrast + 0.5 + adjustments.brightness;
+            x.clamp(0.0, 1.0).powf(1.0 / adjustments.gamma)
+        };
+        let to_u8 = |x: f32| (x.clamp(0.0, 1.0) * 255.0).round() as u8;
+        let products = adjustments.color_matrix().map(|matrix| {
+            let one = (1 << MATRIX_BITS) as f32;
+            Box::new(matrix.map(|row| {
+                row.map(|m| std::array::from_fn(|v| (m * v as f32 * one).round() as i32))
+            }))
+        });
+
+        let input = adjustments.gains.map(|gain| {
+            std::array::from_fn(|v| {
+                let x = v as f32 / 255.0 * gain;
+                to_u8(if products.is_some() { x } else { tone(x) })
+            })
+        });
+        let output = std::array::from_fn(|v| to_u8(tone(v as f32 / 255.0)));
+
+        Self {
+            input,
+            products,
+            output,
+        }
+    }
+
+    fn apply(&self, image: &mut RgbaImage) {
+        // Each pixel is on its own, so any split will do
+        let band = (image.width() * 4 * BAND_ROWS).max(4);
+        image
+            .data_mut()
+            .par_chunks_mut(band)
+            .for_each(|pixels| self.apply_to(pixels));
+    }
+
+    fn apply_to(&self, pixels: &mut [u8]) {
+        let [r_in, g_in, b_in] = &self.input;
+        let Some(products) = &self.products else {
+            for rgba in pixels.chunks_exact_mut(4) {
+                rgba[0] = r_in[rgba[0] as usize];
+                rgba[1] = g_in[rgba[1] as usize];
+                rgba[2] = b_in[rgba[2] as usize];
+            }
+            return;
+        };
+
+        let round = 1 << (MATRIX_BITS - 1);
+        for rgba in pixels.chunks_exact_mut(4) {
+            let r = r_in[rgba[0] as usize] as usize;
+            let g = g_in[rgba[1] as usize] as usize;
+            let b = b_in[rgba[2] as usize] as usize;
+            for (out, [from_r, from_g, from_b]) in rgba.iter_mut().zip(products.iter()) {
+                let sum = from_r[r] + from_g[g] + from_b[b] + round;
+                *out = self.output[(sum >> MATRIX_BITS).clamp(0, 255) as usize];
+            }
+        }
+    }
+}
+
+/// Applies `Adjustments` through lookup tables, which get rebuilt whenever
+/// the adjustments change.
+#[derive(Default)]
+pub struct AdjustStage {
+    pub adjustments: Adjustments,
+    // Along with the adjustments they were built for
+    tables: Option<(Adjustments, Tables)>,
+}
+
+impl AdjustStage {
+    pub const NAME: &'static str = "adjust";
+
+    pub fn new(adjustments: Adjustments) -> Self {
+        Self {
+            adjustments,
+            tables: None,
+        }
+    }
+}
+
+impl FrameProcessor for AdjustStage {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn process(&mut self, frame: &mut Frame) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if self.adjustments.is_identity() {
+            return Ok(());
+        }
+        let tables = match &mut self.tables {
+            Some((built_for, tables)) if *built_for == self.adjustments => tables,
+            tables => {
+                &mut tables
+                    .insert((self.adjustments, Tables::new(&self.adjustments)))
+                    .1
+            }
+        };
+        tables.apply(&mut frame.rgba);
+        frame.matches_captured = false;
+        Ok(())
+    }
+}
@@ -0,0 +1,655 @@
+use std::{error::Error, fmt, fs, io, path::Path};
+
+use rayon::prelude::*;
+
+use super::{Frame, FrameProcessor};
+use crate::convert::RgbaImage;
+
+/// How a 3D LUT is read between its points.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Interpolation {
+    /// From the 8 surrounding points
+    Trilinear,
+    /// From 4 of them, the corners of the tetrahedron the colour is in. Keeps
+    /// greys grey and is what most grading software does.
+    #[default]
+    Tetrahedral,
+}
+
+impl Interpolation {
+    pub const ALL: [Interpolation; 2] = [Interpolation::Trilinear, Interpolation::Tetrahedral];
+}
+
+impl fmt::Display for Interpolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Interpolation::Trilinear => "trilinear",
+            Interpolation::Tetrahedral => "tetrahedral",
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum CubeError {
+    Io(io::Error),
+    /// A line that doesn't make sense, counting from 1
+    Syntax {
+        line: usize,
+        reason: String,
+    },
+    /// Neither `LUT_1D_SIZE` nor `LUT_3D_SIZE`
+    NoSize,
+    /// More or fewer entries than the sizes call for
+    WrongLength {
+        expected: usize,
+        found: usize,
+    },
+    /// A domain whose minimum isn't below its maximum
+    EmptyDomain,
+}
+
+impl fmt::Display for CubeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CubeError::Io(e) => write!(f, "can't read LUT: {e}"),
+            CubeError::Syntax { line, reason } => write!(f, "line {line}: {reason}"),
+            CubeError::NoSize => write!(f, "no LUT_1D_SIZE or LUT_3D_SIZE"),
+            CubeError::WrongLength { expected, found } => {
+                write!(f, "expected {expected} entries, found {found}")
+            }
+            CubeError::EmptyDomain => write!(f, "domain minimum isn't below the maximum"),
+        }
+    }
+}
+
+impl Error for CubeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CubeError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for CubeError {
+    fn from(e: io::Error) -> Self {
+        CubeError::Io(e)
+    }
+}
+
+// The largest sizes we take, well past anything in the wild
+const MAX_1D_SIZE: usize = 65536;
+const MAX_3D_SIZE: usize = 256;
+
+/// One table of a `.cube` file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CubeTable {
+    pub size: usize,
+    /// Inputs that map to the first and the last entry
+    pub domain: ([f32; 3], [f32; 3]),
+    /// `size` entries for a 1D table, `size`³ with red changing fastest for
+    /// a 3D one
+    pub entries: Vec<[f32; 3]>,
+}
+
+impl CubeTable {
+    // Where `x` falls in the table along channel `c`, in entries
+    fn position(&self, x: f32, c: usize) -> f32 {
+        let (min, max) = (self.domain.0[c], self.domain.1[c]);
+        ((x - min) / (max - min) * (self.size - 1) as f32).clamp(0.0, (self.size - 1) as f32)
+    }
+
+    // How far apart neighbours along red, green and blue are in `entries`
+    fn strides(&self) -> [usize; 3] {
+        [1, self.size, self.size * self.size]
+    }
+
+    // Index of the entry below `position` and how far past it that is
+    fn split(&self, position: f32) -> (usize, f32) {
+        let index = (position as usize).min(self.size - 2);
+        (index, position - index as f32)
+    }
+}
+
+/// A LUT from an Adobe or Resolve `.cube` file: a 1D table, a 3D table, or a
+/// 1D shaper followed by a 3D table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CubeLut {
+    pub title: Option<String>,
+    pub shaper: Option<CubeTable>,
+    pub cube: Option<CubeTable>,
+}
+
+fn parse_floats<const N: usize>(words: &[&str]) -> Result<[f32; N], String> {
+    if words.len() != N {
+        return Err(format!("expected {N} numbers, found {}", words.len()));
+    }
+    let mut out = [0.0; N];
+    for (out, word) in out.iter_mut().zip(words) {
+        *out = word
+            .parse()
+            .ok()
+            .filter(|x: &f32| x.is_finite())
+            .ok_or_else(|| format!("{word:?} isn't a number"))?;
+    }
+    Ok(out)
+}
+
+// `line` up to a `#` that isn't in quotes, as in a `TITLE "Grade #2"`
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '#' if !quoted => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+// Like the keywords in the spec, rather than a typo in the numbers
+fn is_keyword(word: &str) -> bool {
+    word.starts_with(|c: char| c.is_ascii_uppercase())
+        && word
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+}
+
+impl CubeLut {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, CubeError> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, CubeError> {
+        let mut title = None;
+        let mut size_1d = None;
+        let mut size_3d = None;
+        let mut domain = ([0.0; 3], [1.0; 3]);
+        // LUT_1D_INPUT_RANGE and LUT_3D_INPUT_RANGE, from Resolve
+        let mut range_1d = None;
+        let mut range_3d = None;
+        let mut entries = Vec::new();
+
+        for (i, line) in text.lines().enumerate() {
+            let syntax = |reason: String| CubeError::Syntax {
+                line: i + 1,
+                reason,
+            };
+            let line = strip_comment(line).trim();
+            let Some(keyword) = line.split_whitespace().next() else {
+                continue;
+            };
+            let words: Vec<&str> = line.split_whitespace().skip(1).collect();
+            let is_data = keyword.starts_with(|c: char| c.is_ascii_digit() || "-+.".contains(c));
+            if !is_data && !entries.is_empty() {
+                return Err(syntax(format!("{keyword} after the table")));
+            }
+
+            match keyword {
+                _ if is_data => {
+                    let all: Vec<&str> = line.split_whitespace().collect();
+                    entries.push(parse_floats::<3>(&all).map_err(syntax)?);
+                }
+                "TITLE" => {
+                    let quoted = line["TITLE".len()..].trim();
+                    title = Some(quoted.trim_matches('"').to_string());
+                }
+                "LUT_1D_SIZE" | "LUT_3D_SIZE" => {
+                    let [size] = parse_floats::<1>(&words).map_err(&syntax)?;
+                    let max = if keyword == "LUT_1D_SIZE" {
+                        MAX_1D_SIZE
+                    } else {
+                        MAX_3D_SIZE
+                    };
+                    if size.fract() != 0.0 || !(2.0..=max as f32).contains(&size) {
+                        return Err(syntax(format!("size {size} isn't between 2 and {max}")));
+                    }
+                    let size = Some(size as usize);
+                    if keyword == "LUT_1D_SIZE" {
+                        size_1d = size;
+                    } else {
+                        size_3d = size;
+                    }
+                }
+                "DOMAIN_MIN" => domain.0 = parse_floats(&words).map_err(syntax)?,
+                "DOMAIN_MAX" => domain.1 = parse_floats(&words).map_err(syntax)?,
+                "LUT_1D_INPUT_RANGE" | "LUT_3D_INPUT_RANGE" => {
+                    let [min, max] = parse_floats(&words).map_err(syntax)?;
+                    let range = Some(([min; 3], [max; 3]));
+                    if keyword == "LUT_1D_INPUT_RANGE" {
+                        range_1d = range;
+                    } else {
+                        range_3d = range;
+                    }
+                }
+                // Others' extensions, like LUT_IN_VIDEO_RANGE, which don't
+                // change how the table reads
+                _ if is_keyword(keyword) => log::debug!("Skipping {keyword} on line {}", i + 1),
+                _ => return Err(syntax(format!("{keyword:?} isn't a keyword or a number"))),
+            }
+        }
+
+        if size_1d.is_none() && size_3d.is_none() {
+            return Err(CubeError::NoSize);
+        }
+        let empty = |(min, max): &([f32; 3], [f32; 3])| (0..3).any(|c| min[c] >= max[c]);
+        if [Some(domain), range_1d, range_3d]
+            .iter()
+            .flatten()
+            .any(empty)
+        {
+            return Err(CubeError::EmptyDomain);
+        }
+        let length_1d = size_1d.unwrap_or(0);
+        let length_3d = size_3d.map_or(0, |size| size.pow(3));
+        if entries.len() != length_1d + length_3d {
+            return Err(CubeError::WrongLength {
+                expected: length_1d + length_3d,
+                found: entries.len(),
+            });
+        }
+
+        // A shaper comes first
+        let cube_entries = entries.split_off(length_1d);
+        Ok(Self {
+            title,
+            shaper: size_1d.map(|size| CubeTable {
+                size,
+                domain: range_1d.unwrap_or(domain),
+                entries,
+            }),
+            cube: size_3d.map(|size| CubeTable {
+                size,
+                domain: range_3d.unwrap_or(domain),
+                entries: cube_entries,
+            }),
+        })
+    }
+
+    /// A 3D LUT of `size`³ that changes nothing.
+    ///
+    /// # Panics
+    ///
+    /// If `size` is below 2.
+    pub fn identity(size: usize) -> Self {
+        assert!(size >= 2, "a LUT needs at least 2 entries a side");
+        let step = |i: usize| i as f32 / (size - 1) as f32;
+        let entries = (0..size.pow(3))
+            .map(|i| [step(i % size), step(i / size % size), step(i / size / size)])
+            .collect();
+        Self {
+            title: None,
+            shaper: None,
+            cube: Some(CubeTable {
+                size,
+                domain: ([0.0; 3], [1.0; 3]),
+                entries,
+            }),
+        }
+    }
+
+    // Through the shaper, each channel on its own
+    fn shape(&self, rgb: [f32; 3]) -> [f32; 3] {
+        let Some(shaper) = &self.shaper else {
+            return rgb;
+        };
+        std::array::from_fn(|c| {
+            let (i, t) = shaper.split(shaper.position(rgb[c], c));
+            let (a, b) = (shaper.entries[i][c], shaper.entries[i + 1][c]);
+            a + (b - a) * t
+        })
+    }
+
+    /// `rgb` looked up in the LUT.
+    pub fn apply(&self, rgb: [f32; 3], interpolation: Interpolation) -> [f32; 3] {
+        let rgb = self.shape(rgb);
+        let Some(cube) = &self.cube else {
+            return rgb;
+        };
+        let strides = cube.strides();
+        let [(r, fr), (g, fg), (b, fb)] = std::array::from_fn(|c| {
+            let (index, fraction) = cube.split(cube.position(rgb[c], c));
+            (index * strides[c], fraction)
+        });
+        let cell = &cube.entries[r + g + b..];
+        let lerp =
+            |a: [f32; 3], b: [f32; 3], t: f32| std::array::from_fn(|c| a[c] + (b[c] - a[c]) * t);
+        let [sr, sg, sb] = strides;
+
+        match interpolation {
+            Interpolation::Trilinear => {
+                let along_r = |offset: usize| lerp(cell[offset], cell[offset + sr], fr);
+                let g0 = lerp(along_r(0), along_r(sg), fg);
+                let g1 = lerp(along_r(sb), along_r(sg + sb), fg);
+                lerp(g0, g1, fb)
+            }
+            Interpolation::Tetrahedral => {
+                let (offsets, [w0, w1, w2, w3]) = tetrahedron([fr, fg, fb], 1.0, strides);
+                let [c0, c1, c2, c3] = offsets.map(|offset| cell[offset]);
+                std::array::from_fn(|c| c0[c] * w0 + c1[c] * w1 + c2[c] * w2 + c3[c] * w3)
+            }
+        }
+    }
+}
+
+// The tetrahedron of a cell of a 3D table a point with `fractions` into it
+// is in: how far the four corners are from the first corner of the cell in
+// a table with `strides`, and how much each of them counts
+fn tetrahedron<T>(fractions: [T; 3], one: T, strides: [usize; 3]) -> ([usize; 4], [T; 4])
+where
+    T: Copy + PartialOrd + std::ops::Sub<Output = T>,
+{
+    let [r, g, b] = fractions;
+    let [sr, sg, sb] = strides;
+    // From (0, 0, 0) along the largest fraction first, then the next, to
+    // (1, 1, 1)
+    let (first, second, [x, y, z]) = if r > g {
+        if g > b {
+            (sr, sr + sg, [r, g, b])
+        } else if r > b {
+            (sr, sr + sb, [r, b, g])
+        } else {
+            (sb, sr + sb, [b, r, g])
+        }
+    } else if b > g {
+        (sb, sg + sb, [b, g, r])
+    } else if b > r {
+        (sg, sg + sb, [g, b, r])
+    } else {
+        (sg, sr + sg, [g, r, b])
+    };
+    ([0, first, second, sr + sg + sb], [one - x, x - y, y - z, z])
+}
+
+// Fractional bits of the positions inside a cell of a baked LUT
+const FRACTION_BITS: u32 = 12;
+
+// A `CubeLut` ready for 8 bit frames
+enum Baked {
+    // Each channel on its own
+    Curves(Box<[[u8; 256]; 3]>),
+    Cube {
+        strides: [usize; 3],
+        // For every code value of each channel, the offset of the cell it's
+        // in and how far into it, shaper included
+        positions: Box<[[(usize, i32); 256]; 3]>,
+        // The table times 256, so the interpolation can round only once
+        entries: Vec<[i32; 3]>,
+    },
+}
+
+impl Baked {
+    fn new(lut: &CubeLut) -> Self {
+        let input = |v: usize| [v as f32 / 255.0; 3];
+        let Some(cube) = &lut.cube else {
+            let to_u8 = |x: f32| (x.clamp(0.0, 1.0) * 255.0).round() as u8;
+            return Baked::Curves(Box::new(std::array::from_fn(|c| {
+                std::array::from_fn(|v| to_u8(lut.shape(input(v))[c]))
+            })));
+        };
+
+        let strides = cube.strides();
+        let one = (1 << FRACTION_BITS) as f32;
+        let positions = Box::new(std::array::from_fn(|c| {
+            std::array::from_fn(|v| {
+                let (index, fraction) = cube.split(cube.position(lut.shape(input(v))[c], c));
+                (index * strides[c], (fraction * one).round() as i32)
+            })
+        }));
+        // Out of range entries only matter to where the interpolation ends
+        // up between them, and not much past the range keeps it in an i32
+        let entries = cube
+            .entries
+            .iter()
+            .map(|rgb| rgb.map(|x| (x.clamp(-1.0, 2.0) * 255.0 * 256.0).round() as i32))
+            .collect();
+        Baked::Cube {
+            strides,
+            positions,
+            entries,
+        }
+    }
+
+    fn apply(&self, pixels: &mut [u8], interpolation: Interpolation) {
+        let (strides, positions, entries) = match self {
+            Baked::Curves(curves) => {
+                for rgba in pixels.chunks_exact_mut(4) {
+                    for (v, curve) in rgba.iter_mut().zip(curves.iter()) {
+                        *v = curve[*v as usize];
+                    }
+                }
+                return;
+            }
+            Baked::Cube {
+                strides,
+                positions,
+                entries,
+            } => (*strides, positions, entries),
+        };
+
+        let one = 1 << FRACTION_BITS;
+        let half = one >> 1;
+        let lerp = |a: [i32; 3], b: [i32; 3], f: i32| {
+            std::array::from_fn(|c| a[c] + (((b[c] - a[c]) * f + half) >> FRACTION_BITS))
+        };
+        let [sr, sg, sb] = strides;
+        for rgba in pixels.chunks_exact_mut(4) {
+            let [(r, fr), (g, fg), (b, fb)] =
+                std::array::from_fn(|c| positions[c][rgba[c] as usize]);
+            let cell = &entries[r + g + b..];
+
+            // Either way 256 times too big
+            let out: [i32; 3] = match interpolation {
+                Interpolation::Trilinear => {
+                    let along_r = |offset: usize| lerp(cell[offset], cell[offset + sr], fr);
+                    let g0 = lerp(along_r(0), along_r(sg), fg);
+                    let g1 = lerp(along_r(sb), along_r(sg + sb), fg);
+                    lerp(g0, g1, fb)
+                }
+                Interpolation::Tetrahedral => {
+                    let (offsets, [w0, w1, w2, w3]) = tetrahedron([fr, fg, fb], one, strides);
+                    let [c0, c1, c2, c3] = offsets.map(|offset| cell[offset]);
+                    std::array::from_fn(|c| {
+                        let sum = c0[c] * w0 + c1[c] * w1 + c2[c] * w2 + c3[c] * w3;
+                        (sum + half) >> FRACTION_BITS
+                    })
+                }
+            };
+            for (v, out) in rgba.iter_mut().zip(out) {
+                *v = ((out + 128) >> 8).clamp(0, 255) as u8;
+            }
+        }
+    }
+}
+
+// Rows per job when spreading a frame over the threads
+const BAND_ROWS: usize = 32;
+
+/// Grades frames with one of any number of loaded LUTs, or none.
+#[derive(Default)]
+pub struct LutStage {
+    luts: Vec<(CubeLut, Baked)>,
+    /// Index of the LUT in use, in the order they were added
+    pub selected: Option<usize>,
+    pub interpolation: Interpolation,
+}
+
+impl LutStage {
+    pub const NAME: &'static str = "lut";
+
+    /// Add `lut` to the ones to choose from, returning its index.
+    pub fn add(&mut self, lut: CubeLut) -> usize {
+        let baked = Baked::new(&lut);
+        self.luts.push((lut, baked));
+        self.luts.len() - 1
+    }
+
+    pub fn luts(&self) -> impl Iterator<Item = &CubeLut> {
+        self.luts.iter().map(|(lut, _)| lut)
+    }
+
+    /// Grade `image` with the selected LUT.
+    pub fn apply(&self, image: &mut RgbaImage) {
+        let Some((_, baked)) = self.selected.and_then(|i| self.luts.get(i)) else {
+            return;
+        };
+        let band = (image.width() * 4 * BAND_ROWS).max(4);
+        image
+            .data_mut()
+            .par_chunks_mut(band)
+            .for_each(|pixels| baked.apply(pixels, self.interpolation));
+    }
+}
+
+impl FrameProcessor for LutStage {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn process(&mut self, frame: &mut Frame) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if self.selected.is_some_and(|i| i < self.luts.len()) {
+            self.apply(&mut frame.rgba);
+            frame.matches_captured = false;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A 2³ table that changes nothing, as a file
+    const IDENTITY: &str = "\
+TITLE \"Nothing # at all\" # but this is a comment
+# Comment
+LUT_3D_SIZE 2
+DOMAIN_MIN 0 0 0
+DOMAIN_MAX 1 1 1
+
+0 0 0
+1 0 0
+0 1 0
+1 1 0
+0 0 1
+1 0 1
+0 1 1
+1 1 1
+";
+
+    fn syntax_line(text: &str) -> usize {
+        match CubeLut::parse(text) {
+            Err(CubeError::Syntax { line, .. }) => line,
+            other => panic!("{other:?} for {text:?}"),
+        }
+    }
+
+    #[test]
+    fn parses_an_identity_lut() {
+        let lut = CubeLut::parse(IDENTITY).unwrap();
+        assert_eq!(lut.title.as_deref(), Some("Nothing # at all"));
+        assert_eq!(
+            lut,
+            CubeLut {
+                title: lut.title.clone(),
+                ..CubeLut::identity(2)
+            }
+        );
+    }
+
+    #[test]
+    fn identity_changes_nothing() {
+        let lut = CubeLut::identity(17);
+        let levels = [0.0, 0.03, 0.25, 0.5, 0.77, 1.0];
+        for interpolation in Interpolation::ALL {
+            for r in levels {
+                for g in levels {
+                    for b in levels {
+                        let out = lut.apply([r, g, b], interpolation);
+                        for (out, x) in out.into_iter().zip([r, g, b]) {
+                            assert!((out - x).abs() < 1e-5, "{interpolation} {r} {g} {b}");
+                        }
+                    }
+                }
+            }
+        }
+
+        // And baked for 8 bits, down to the last code value
+        let mut image = RgbaImage::new();
+        image.resize(256, 3);
+        for (i, rgba) in image.data_mut().chunks_exact_mut(4).enumerate() {
+            let v = (i % 256) as u8;
+            rgba.copy_from_slice(&[v, v.wrapping_mul(7), v.wrapping_mul(31), 255]);
+        }
+        let original = image.clone();
+        for interpolation in Interpolation::ALL {
+            let mut stage = LutStage::default();
+            stage.selected = Some(stage.add(lut.clone()));
+            stage.interpolation = interpolation;
+            let mut graded = original.clone();
+            stage.apply(&mut graded);
+            assert!(graded.data() == original.data(), "{interpolation}");
+        }
+    }
+
+    #[test]
+    fn skips_unknown_keywords() {
+        let text = IDENTITY.replace("LUT_3D_SIZE 2", "LUT_3D_SIZE 2\nLUT_IN_VIDEO_RANGE");
+        assert_eq!(
+            CubeLut::parse(&text).unwrap().cube,
+            CubeLut::identity(2).cube
+        );
+    }
+
+    #[test]
+    fn shaper_then_cube() {
+        let text = "LUT_1D_SIZE 2\nLUT_3D_SIZE 2\nLUT_1D_INPUT_RANGE 0 2\n0 0 0\n1 1 1\n";
+        let text = text.to_string() + &IDENTITY[IDENTITY.find("\n\n").unwrap()..];
+        let lut = CubeLut::parse(&text).unwrap();
+        let shaper = lut.shaper.as_ref().unwrap();
+        assert_eq!(shaper.domain, ([0.0; 3], [2.0; 3]));
+        let out = lut.apply([1.0, 0.5, 2.0], Interpolation::Trilinear);
+        assert_eq!(out, [0.5, 0.25, 1.0]);
+    }
+
+    #[test]
+    fn rejects_malformed_luts() {
+        assert_eq!(syntax_line("LUT_3D_SIZE 2\n0 0\n"), 2);
+        assert_eq!(syntax_line("LUT_3D_SIZE 2\n0 0 zero\n"), 2);
+        assert_eq!(syntax_line("LUT_3D_SIZE 2\n0 0 NaN\n"), 2);
+        assert_eq!(syntax_line("LUT_3D_SIZE 2\n0 0 0 0\n"), 2);
+        assert_eq!(syntax_line("\nLUT_3D_SIZE 1\n"), 2);
+        assert_eq!(syntax_line("LUT_3D_SIZE 257\n"), 1);
+        assert_eq!(syntax_line("LUT_3D_SIZE 2.5\n"), 1);
+        assert_eq!(syntax_line("LUT_1D_SIZE\n"), 1);
+        assert_eq!(syntax_line("DOMAIN_MIN 0 0\n"), 1);
+        assert_eq!(syntax_line("lut_3d_size 2\n"), 1);
+        assert_eq!(syntax_line("LUT_3D_SIZE 2\n0 0 0\nTITLE \"late\"\n"), 3);
+
+        assert!(matches!(CubeLut::parse(""), Err(CubeError::NoSize)));
+        assert!(matches!(CubeLut::parse("0 0 0\n"), Err(CubeError::NoSize)));
+        let short = IDENTITY.strip_suffix("1 1 1\n").unwrap();
+        assert!(matches!(
+            CubeLut::parse(short),
+            Err(CubeError::WrongLength {
+                expected: 8,
+                found: 7
+            })
+        ));
+        let empty = IDENTITY.replace("DOMAIN_MAX 1 1 1", "DOMAIN_MAX 1 0 1");
+        assert!(matches!(
+            CubeLut::parse(&empty),
+            Err(CubeError::EmptyDomain)
+        ));
+        let empty = IDENTITY.replace("DOMAIN_MIN 0 0 0", "LUT_3D_INPUT_RANGE 1 1");
+        assert!(matches!(
+            CubeLut::parse(&empty),
+            Err(CubeError::EmptyDomain)
+        ));
+        assert!(matches!(
+            CubeLut::load("/nonexistent/grade.cube"),
+            Err(CubeError::Io(_))
+        ));
+    }
+}
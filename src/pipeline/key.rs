@@ -0,0 +1,439 @@
+use std::{
+    error::Error,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use rayon::prelude::*;
+
+use super::{Frame, FrameProcessor};
+use crate::convert::{self, ColorOverride, Filter, FixedYuvToRgb, RgbToYuv, RgbaImage, YuvView};
+
+/// Which colour gets keyed out, and how.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChromaKey {
+    /// The colour of the screen
+    pub color: [u8; 3],
+    /// How far from the screen's chroma a pixel can be and still be keyed
+    /// out completely, from 0 to about 1.4 for opposite colours at full
+    /// saturation
+    pub tolerance: f32,
+    /// How much further from it the pixels fade from keyed out to kept, for
+    /// soft edges
+    pub softness: f32,
+    /// From 0 to 1, how much of the screen's colour gets taken out of what's
+    /// kept, where it reflected off the subject
+    pub spill: f32,
+    /// How much further than the soft edge that goes on, fading out, so
+    /// what's green for real and well clear of the screen's colour stays
+    /// green
+    pub spill_range: f32,
+}
+
+impl Default for ChromaKey {
+    fn default() -> Self {
+        Self {
+            // The usual green screen
+            color: [0, 177, 64],
+            tolerance: 0.2,
+            softness: 0.1,
+            spill: 0.5,
+            spill_range: 0.3,
+        }
+    }
+}
+
+/// What shows through where the key colour was.
+pub enum Background {
+    Color([u8; 3]),
+    /// Stretched to the size of the frames
+    Image(RgbaImage),
+    /// Whatever is in there as each frame comes through, for pictures that
+    /// keep changing, like another camera's. Stretched like `Image`.
+    Live(Arc<Mutex<RgbaImage>>),
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Background::Color([0, 0, 0])
+    }
+}
+
+// Rows per job when spreading a frame over the threads
+const BAND_ROWS: usize = 32;
+
+/// Keys out a colour and puts `background` in its place. Goes by the
+/// captured Y'CbCr while the picture still matches it, see
+/// `Frame::matches_captured`, so is best right after the `ConvertStage`.
+/// Anywhere else it keys the picture as it is by then.
+#[derive(Default)]
+pub struct KeyStage {
+    pub key: ChromaKey,
+    /// Should match the `ConvertStage`'s, for the same RGB
+    pub color_override: ColorOverride,
+    background: Background,
+    // The background at the size of the frames, and which size that was if
+    // it's a `Background::Image`, which only needs scaling once
+    scaled: RgbaImage,
+    scaled_image: Option<(usize, usize)>,
+}
+
+impl KeyStage {
+    pub const NAME: &'static str = "key";
+
+    pub fn new(key: ChromaKey, background: Background) -> Self {
+        Self {
+            key,
+            background,
+            ..Self::default()
+        }
+    }
+
+    pub fn background(&self) -> &Background {
+        &self.background
+    }
+
+    pub fn set_background(&mut self, background: Background) {
+        self.background = background;
+        self.scaled_image = None;
+    }
+}
+
+impl FrameProcessor for KeyStage {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn process(&mut self, frame: &mut Frame) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let (width, height) = (frame.rgba.width(), frame.rgba.height());
+        if width == 0 || height == 0 {
+            return Ok(());
+        }
+        let format = frame.captured.format();
+        let spec = self.color_override.apply(format.color);
+        let planes = frame.captured.planes();
+        let view = YuvView::new(&planes, format).filter(|view| {
+            frame.matches_captured && (view.width(), view.height()) == (width, height)
+        });
+
+        // Borrowed from `self.background` or made into `self.scaled`
+        let live;
+        let background = match &self.background {
+            Background::Color(color) => Fill::Solid(*color),
+            Background::Image(image) => {
+                if self.scaled_image != Some((width, height)) {
+                    convert::resize_rgba(image, &mut self.scaled, width, height, Filter::Bilinear);
+                    self.scaled_image = Some((width, height));
+                }
+                Fill::Picture(self.scaled.data())
+            }
+            Background::Live(shared) => {
+                self.scaled_image = None;
+                // A background camera that went down mid-frame still left
+                // a whole one behind
+                live = shared.lock().unwrap_or_else(PoisonError::into_inner);
+                if live.data().is_empty() {
+                    Fill::Solid([0, 0, 0])
+                } else if (live.width(), live.height()) == (width, height) {
+                    Fill::Picture(live.data())
+                } else {
+                    convert::resize_rgba(&live, &mut self.scaled, width, height, Filter::Bilinear);
+                    Fill::Picture(self.scaled.data())
+                }
+            }
+        };
+
+        let keyer = Keyer::new(&self.key, spec);
+        let band = width * 4 * BAND_ROWS;
+        frame
+            .rgba
+            .data_mut()
+            .par_chunks_mut(band)
+            .enumerate()
+            .for_each(|(i, pixels)| {
+                let first_row = i * BAND_ROWS;
+                for (y, row) in (first_row..).zip(pixels.chunks_exact_mut(width * 4)) {
+                    for (x, rgba) in row.chunks_exact_mut(4).enumerate() {
+                        // Past the end of a short frame there's only what got
+                        // converted
+                        let yuv = view
+                            .and_then(|view| view.get(x, y))
+                            .unwrap_or_else(|| keyer.to_yuv.convert(rgba[0], rgba[1], rgba[2]));
+                        let behind = match background {
+                            Fill::Solid(color) => color,
+                            Fill::Picture(data) => {
+                                let i = (y * width + x) * 4;
+                                [data[i], data[i + 1], data[i + 2]]
+                            }
+                        };
+                        let front = [rgba[0], rgba[1], rgba[2]];
+                        let rgb = keyer.key(yuv, front, behind);
+                        rgba[..3].copy_from_slice(&rgb);
+                    }
+                }
+            });
+        frame.matches_captured = false;
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Fill<'a> {
+    Solid([u8; 3]),
+    /// RGBA at the size of the frame
+    Picture(&'a [u8]),
+}
+
+// `ChromaKey` worked out for one frame
+struct Keyer {
+    to_yuv: RgbToYuv,
+    to_rgb: FixedYuvToRgb,
+    // Cb and Cr of the key colour, around 0
+    chroma: [f32; 2],
+    // Which way the key colour is from grey, if it's not grey
+    direction: Option<[f32; 2]>,
+    tolerance: f32,
+    softness: f32,
+    spill: f32,
+    spill_range: f32,
+}
+
+impl Keyer {
+    fn new(key: &ChromaKey, spec: convert::ColorSpec) -> Self {
+        let to_yuv = RgbToYuv::new(spec);
+        let [r, g, b] = key.color;
+        let [_, cb, cr] = to_yuv.convert(r, g, b);
+        let chroma = [cb as f32 - 128.0, cr as f32 - 128.0];
+        let length = chroma[0].hypot(chroma[1]);
+        Self {
+            to_yuv,
+            to_rgb: FixedYuvToRgb::new(spec),
+            chroma,
+            direction: (length > 0.0).then(|| chroma.map(|c| c / length)),
+            tolerance: key.tolerance,
+            softness: key.softness,
+            spill: key.spill.clamp(0.0, 1.0),
+            spill_range: key.spill_range.max(0.0),
+        }
+    }
+
+    // What the `front` pixel, with Y'CbCr `yuv`, becomes given what's behind
+    // it. The Y'CbCr only decides how much of it is kept and how much spill
+    // to take out, the picture is otherwise left as it was.
+    #[inline]
+    fn key(&self, [y, cb, cr]: [u8; 3], front: [u8; 3], behind: [u8; 3]) -> [u8; 3] {
+        let (mut cb, mut cr) = (cb as f32 - 128.0, cr as f32 - 128.0);
+        let distance = (cb - self.chroma[0]).hypot(cr - self.chroma[1]) / 128.0;
+        // How much of the pixel is kept, easing in and out of the ramp
+        let kept = if self.softness > 0.0 {
+            let t = ((distance - self.tolerance) / self.softness).clamp(0.0, 1.0);
+            t * t * (3.0 - 2.0 * t)
+        } else if distance > self.tolerance {
+            1.0
+        } else {
+            0.0
+        };
+        if kept == 0.0 {
+            return behind;
+        }
+
+        // Take out however much of the chroma goes the key's way, all of it
+        // up to the end of the soft edge and less and less after
+        let edge = self.tolerance + self.softness;
+        let near = (edge + self.spill_range - distance) / self.spill_range.max(f32::EPSILON);
+        let spill = self.spill * near.clamp(0.0, 1.0);
+        let mut front = front;
+        if let (Some([along_cb, along_cr]), true) = (self.direction, spill > 0.0) {
+            let along = cb * along_cb + cr * along_cr;
+            if along > 0.0 {
+                cb -= along_cb * along * spill;
+                cr -= along_cr * along * spill;
+                let code = |c: f32| (c + 128.0).round().clamp(0.0, 255.0) as u8;
+                front = self.to_rgb.convert(y, code(cb), code(cr));
+            }
+        }
+        if kept == 1.0 {
+            return front;
+        }
+        std::array::from_fn(|i| {
+            let mixed = front[i] as f32 * kept + behind[i] as f32 * (1.0 - kept);
+            mixed.round() as u8
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::convert::{ColorSpec, ParallelConverter};
+    use crate::convert::{RgbToYuv, Transform};
+    use crate::pipeline::{Captured, ConvertStage, Pipeline, TransformStage};
+    use crate::v4l2::{FrameFormat, PixelFormat};
+
+    // Whatever shows through
+    const BEHIND: [u8; 3] = [1, 2, 3];
+
+    fn keyer(key: ChromaKey) -> Keyer {
+        Keyer::new(&key, ColorSpec::default())
+    }
+
+    fn key(keyer: &Keyer, rgb @ [r, g, b]: [u8; 3]) -> [u8; 3] {
+        keyer.key(keyer.to_yuv.convert(r, g, b), unkeyed(keyer, rgb), BEHIND)
+    }
+
+    // What the pixel would be without keying, after the round trip
+    fn unkeyed(keyer: &Keyer, [r, g, b]: [u8; 3]) -> [u8; 3] {
+        let [y, cb, cr] = keyer.to_yuv.convert(r, g, b);
+        keyer.to_rgb.convert(y, cb, cr)
+    }
+
+    #[test]
+    fn keys_out_the_screen() {
+        let keyer = keyer(ChromaKey::default());
+        assert_eq!(key(&keyer, [0, 177, 64]), BEHIND);
+        // Darker and lighter, it's the chroma that counts
+        assert_eq!(key(&keyer, [0, 120, 44]), BEHIND);
+        // Far from it, nothing changes
+        for rgb in [[200, 150, 120], [30, 40, 200], [128, 128, 128], [255, 0, 0]] {
+            assert_eq!(key(&keyer, rgb), unkeyed(&keyer, rgb), "{rgb:?}");
+        }
+    }
+
+    #[test]
+    fn soft_edges_mix() {
+        let keyer = keyer(ChromaKey {
+            softness: 0.3,
+            spill: 0.0,
+            ..Default::default()
+        });
+        // Between keyed and kept there's some of both
+        let rgb = [60, 140, 90];
+        let [_, cb, cr] = keyer.to_yuv.convert(60, 140, 90);
+        let distance = (cb as f32 - 128.0 - keyer.chroma[0])
+            .hypot(cr as f32 - 128.0 - keyer.chroma[1])
+            / 128.0;
+        assert!(distance > keyer.tolerance && distance < keyer.tolerance + keyer.softness);
+        let mixed = key(&keyer, rgb);
+        let front = unkeyed(&keyer, rgb);
+        for c in 0..3 {
+            let (low, high) = (front[c].min(BEHIND[c]), front[c].max(BEHIND[c]));
+            assert!((low..=high).contains(&mixed[c]), "{mixed:?}");
+        }
+        assert!(mixed != front && mixed != BEHIND);
+    }
+
+    #[test]
+    fn spill_only_near_the_screen() {
+        // Skin with some of the screen's green on it
+        let spilled = [150, 160, 100];
+        let keyer_with = |spill_range| {
+            keyer(ChromaKey {
+                spill_range,
+                ..Default::default()
+            })
+        };
+        let despilled = key(&keyer_with(0.3), spilled);
+        let front = unkeyed(&keyer_with(0.3), spilled);
+        assert!(
+            despilled[1] < front[1] && despilled[0] > front[0],
+            "{despilled:?}"
+        );
+
+        // A green subject well clear of the screen's keeps its colour with a
+        // short range, and loses some of it with a long one
+        let green = [120, 200, 40];
+        assert_eq!(
+            key(&keyer_with(0.1), green),
+            unkeyed(&keyer_with(0.1), green)
+        );
+        assert!(key(&keyer_with(0.3), green)[1] < unkeyed(&keyer_with(0.3), green)[1]);
+        // And none without any range at all
+        assert_eq!(
+            key(&keyer_with(0.0), green),
+            unkeyed(&keyer_with(0.0), green)
+        );
+    }
+
+    #[test]
+    fn a_poisoned_background_still_shows() {
+        let mut picture = RgbaImage::new();
+        picture.resize(2, 2);
+        picture.data_mut().fill(200);
+        let shared = Arc::new(Mutex::new(picture));
+        let poisoner = shared.clone();
+        let _ = std::thread::spawn(move || {
+            let _held = poisoner.lock().unwrap();
+            panic!("background camera went away");
+        })
+        .join();
+        assert!(shared.is_poisoned());
+
+        let mut pipeline = Pipeline::new()
+            .with(ConvertStage::new(ParallelConverter::new(1).unwrap()))
+            .with(KeyStage::new(
+                ChromaKey::default(),
+                Background::Live(shared),
+            ));
+        let format = FrameFormat {
+            pixel_format: PixelFormat::RGB24,
+            width: 2,
+            height: 2,
+            ..Default::default()
+        };
+        let captured = Captured::Data {
+            format,
+            planes: vec![[0, 177, 64].repeat(4)],
+        };
+        pipeline.process(captured).unwrap();
+        let frame = pipeline.frame();
+        assert!(frame
+            .rgba
+            .data()
+            .chunks_exact(4)
+            .all(|rgba| rgba == [200, 200, 200, 255]));
+    }
+
+    #[test]
+    fn keys_the_picture_as_it_is_after_a_flip() {
+        // Screen on the left, red on the right, flipped before the key
+        let to_yuv = RgbToYuv::new(ColorSpec::default());
+        let pair = |[r, g, b]: [u8; 3]| {
+            let [y, cb, cr] = to_yuv.convert(r, g, b);
+            [y, cb, y, cr]
+        };
+        let row = [pair([0, 177, 64]), pair([200, 60, 50])].concat();
+        let captured = Captured::Data {
+            format: FrameFormat {
+                pixel_format: PixelFormat::YUYV,
+                width: 4,
+                height: 2,
+                ..Default::default()
+            },
+            planes: vec![row.repeat(2)],
+        };
+        let mut pipeline = Pipeline::new()
+            .with(ConvertStage::new(ParallelConverter::new(1).unwrap()))
+            .with(TransformStage::new(Transform {
+                hflip: true,
+                ..Default::default()
+            }))
+            .with(KeyStage::new(
+                ChromaKey::default(),
+                Background::Color([200; 3]),
+            ));
+        pipeline.process(captured).unwrap();
+        let rgba = pipeline.frame().rgba.data();
+        for row in rgba.chunks_exact(4 * 4) {
+            let (left, right) = row.split_at(2 * 4);
+            // The red, now on the left, is kept rather than keyed by what
+            // was captured there
+            assert!(
+                left.chunks_exact(4).all(|p| p[0] > 150 && p[1] < 100),
+                "{left:?}"
+            );
+            assert!(
+                right.chunks_exact(4).all(|p| p == [200, 200, 200, 255]),
+                "{right:?}"
+            );
+        }
+    }
+}
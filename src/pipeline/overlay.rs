@@ -0,0 +1,172 @@
+use std::error::Error;
+
+use rayon::prelude::*;
+
+use super::{luma, Frame, FrameProcessor};
+use crate::convert::RgbaImage;
+
+/// Focus peaking: edges sharp enough to be in focus get painted over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Peaking {
+    pub color: [u8; 3],
+    /// How much luma has to change across a pixel, horizontally and
+    /// vertically together, for it to count as an edge. Lower shows more.
+    pub threshold: u8,
+}
+
+impl Default for Peaking {
+    fn default() -> Self {
+        Self {
+            color: [255, 0, 0],
+            threshold: 64,
+        }
+    }
+}
+
+/// Aids for judging exposure and focus, drawn over the picture.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Overlays {
+    /// Stripes over everything at or above this many IRE
+    pub zebra: Option<f32>,
+    /// Luma shown as bands of colour, see `false_color`
+    pub false_color: bool,
+    pub peaking: Option<Peaking>,
+}
+
+impl Overlays {
+    pub fn is_empty(&self) -> bool {
+        self.zebra.is_none() && !self.false_color && self.peaking.is_none()
+    }
+}
+
+// Up to which IRE each band of false colour goes, and its colour. None keeps
+// the luma as grey. These follow the ones cinema cameras use, with middle
+// grey in green and a stop above it in pink.
+const FALSE_COLORS: [(f32, Option<[u8; 3]>); 9] = [
+    (2.5, Some([128, 0, 160])),
+    (4.0, Some([0, 64, 255])),
+    (38.0, None),
+    (42.0, Some([0, 200, 0])),
+    (52.0, None),
+    (56.0, Some([255, 128, 192])),
+    (97.0, None),
+    (99.0, Some([255, 230, 0])),
+    (f32::INFINITY, Some([255, 0, 0])),
+];
+
+/// The colour `false_color` gives `luma`.
+pub fn false_color(luma: u8) -> [u8; 3] {
+    let ire = luma as f32 / 255.0 * 100.0;
+    let (_, color) = FALSE_COLORS
+        .iter()
+        .find(|(upto, _)| ire < *upto)
+        .unwrap_or(&FALSE_COLORS[FALSE_COLORS.len() - 1]);
+    color.unwrap_or([luma; 3])
+}
+
+// Width of the zebra stripes, across them
+const STRIPE: usize = 4;
+
+// Rows per job when spreading a frame over the threads
+const BAND_ROWS: usize = 32;
+
+/// Draws `Overlays`. They go into `Frame::preview` so that only the viewer
+/// gets them, unless `burn_in` puts them in the picture itself.
+///
+/// Best kept at the end, where it gets the picture as it's going to look,
+/// with only a `ScaleStage` after it.
+#[derive(Default)]
+pub struct OverlayStage {
+    pub overlays: Overlays,
+    pub burn_in: bool,
+    // Of the picture before anything got drawn over it
+    luma: Vec<u8>,
+    // `false_color` for every luma value
+    false_colors: Vec<[u8; 3]>,
+}
+
+impl OverlayStage {
+    pub const NAME: &'static str = "overlays";
+
+    pub fn new(overlays: Overlays) -> Self {
+        Self {
+            overlays,
+            ..Self::default()
+        }
+    }
+
+    fn draw(&mut self, image: &mut RgbaImage, sequence: u64) {
+        let (width, height) = (image.width(), image.height());
+        self.luma.clear();
+        self.luma.extend(image.data().chunks_exact(4).map(luma));
+        if self.overlays.false_color && self.false_colors.is_empty() {
+            self.false_colors = (0..=255).map(false_color).collect();
+        }
+
+        let overlays = self.overlays;
+        let luma = &self.luma;
+        let false_colors = &self.false_colors;
+        let zebra = overlays
+            .zebra
+            .map(|ire| (ire / 100.0 * 255.0).ceil() as i32);
+        // The stripes crawl along, so they don't get taken for the picture
+        let crawl = sequence as usize % (STRIPE * 2);
+
+        let band = (width * 4 * BAND_ROWS).max(4);
+        image
+            .data_mut()
+            .par_chunks_mut(band)
+            .enumerate()
+            .for_each(|(i, pixels)| {
+                for (y, row) in (i * BAND_ROWS..).zip(pixels.chunks_exact_mut(width * 4)) {
+                    let luma_row = &luma[y * width..][..width];
+                    for (x, rgba) in row.chunks_exact_mut(4).enumerate() {
+                        let l = luma_row[x];
+                        if overlays.false_color {
+                            rgba[..3].copy_from_slice(&false_colors[l as usize]);
+                        }
+                        if zebra.is_some_and(|level| l as i32 >= level)
+                            && ((x + y + crawl) / STRIPE).is_multiple_of(2)
+                        {
+                            rgba[..3].fill(0);
+                        }
+                        if let Some(peaking) = overlays.peaking {
+                            // Edges of the picture don't have both neighbours
+                            if x == 0 || y == 0 || x + 1 == width || y + 1 == height {
+                                continue;
+                            }
+                            let at = |x: usize, y: usize| luma[y * width + x] as i32;
+                            let dx = at(x + 1, y) - at(x - 1, y);
+                            let dy = at(x, y + 1) - at(x, y - 1);
+                            if dx.abs() + dy.abs() >= peaking.threshold.max(1) as i32 {
+                                rgba[..3].copy_from_slice(&peaking.color);
+                            }
+                        }
+                    }
+                }
+            });
+    }
+}
+
+impl FrameProcessor for OverlayStage {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn process(&mut self, frame: &mut Frame) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if self.overlays.is_empty() || frame.rgba.data().is_empty() {
+            return Ok(());
+        }
+        let sequence = frame.meta.sequence;
+        if self.burn_in {
+            self.draw(&mut frame.rgba, sequence);
+            frame.matches_captured = false;
+        } else {
+            let preview = &mut frame.preview;
+            preview.resize(frame.rgba.width(), frame.rgba.height());
+            preview.data_mut().copy_from_slice(frame.rgba.data());
+            self.draw(preview, sequence);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,302 @@
+use std::{
+    error::Error,
+    time::{Duration, Instant},
+};
+
+use super::{Frame, FrameProcessor};
+use crate::convert::{self, Filter, RgbaImage};
+
+/// Which part of the picture to show, as a pan/tilt/zoom camera would point.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ptz {
+    /// From 1, showing everything, to `MAX_ZOOM`. At 2 it's half the width
+    /// and half the height.
+    pub zoom: f32,
+    /// From -1 to 1, left to right, as far as the zoom leaves room for
+    pub pan: f32,
+    /// From -1 to 1, top to bottom, likewise
+    pub tilt: f32,
+}
+
+impl Default for Ptz {
+    fn default() -> Self {
+        Self {
+            zoom: 1.0,
+            pan: 0.0,
+            tilt: 0.0,
+        }
+    }
+}
+
+impl Ptz {
+    /// Beyond this there's not much left of the picture.
+    pub const MAX_ZOOM: f32 = 8.0;
+
+    /// With everything in range.
+    pub fn clamped(self) -> Self {
+        Self {
+            zoom: self.zoom.clamp(1.0, Self::MAX_ZOOM),
+            pan: self.pan.clamp(-1.0, 1.0),
+            tilt: self.tilt.clamp(-1.0, 1.0),
+        }
+    }
+
+    /// `t` of the way from `self` to `to`. The zoom goes by ratios, so every
+    /// bit of the way looks like the same amount of zooming.
+    pub fn lerp(self, to: Ptz, t: f32) -> Ptz {
+        let (from, to) = (self.clamped(), to.clamped());
+        Ptz {
+            zoom: from.zoom * (to.zoom / from.zoom).powf(t),
+            pan: from.pan + (to.pan - from.pan) * t,
+            tilt: from.tilt + (to.tilt - from.tilt) * t,
+        }
+    }
+
+    /// The part of the picture it shows, as left, top, width and height in
+    /// fractions of it. `crop` rounds this to whole pixels.
+    pub fn view(&self) -> (f32, f32, f32, f32) {
+        let ptz = self.clamped();
+        let size = 1.0 / ptz.zoom;
+        let start = |at: f32| (1.0 - size) * (at + 1.0) / 2.0;
+        (start(ptz.pan), start(ptz.tilt), size, size)
+    }
+
+    /// The part of a `width` by `height` picture it shows, as left, top,
+    /// width and height in pixels.
+    pub fn crop(&self, width: usize, height: usize) -> (usize, usize, usize, usize) {
+        let ptz = self.clamped();
+        let size = |size: usize| ((size as f32 / ptz.zoom).round() as usize).clamp(1, size.max(1));
+        let (crop_width, crop_height) = (size(width), size(height));
+        let start = |room: usize, at: f32| (room as f32 * (at + 1.0) / 2.0).round() as usize;
+        let left = start(width.saturating_sub(crop_width), ptz.pan);
+        let top = start(height.saturating_sub(crop_height), ptz.tilt);
+        (left, top, crop_width, crop_height)
+    }
+}
+
+/// A move from one `Ptz` to another over `duration`, starting slowly and
+/// slowing down again at the end.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PtzMove {
+    pub from: Ptz,
+    pub to: Ptz,
+    pub start: Instant,
+    pub duration: Duration,
+}
+
+impl PtzMove {
+    pub fn new(from: Ptz, to: Ptz, start: Instant, duration: Duration) -> Self {
+        Self {
+            from,
+            to,
+            start,
+            duration,
+        }
+    }
+
+    /// Where the move is at `now`, `to` once it's done.
+    pub fn at(&self, now: Instant) -> Ptz {
+        if self.is_done(now) {
+            return self.to.clamped();
+        }
+        let t =
+            now.saturating_duration_since(self.start).as_secs_f32() / self.duration.as_secs_f32();
+        let eased = t * t * (3.0 - 2.0 * t);
+        self.from.lerp(self.to, eased)
+    }
+
+    pub fn is_done(&self, now: Instant) -> bool {
+        now.saturating_duration_since(self.start) >= self.duration
+    }
+}
+
+/// Digital pan/tilt/zoom: crops the picture to what `ptz` shows and scales
+/// that back up to the size of the frame.
+///
+/// Goes after the stages that work on the captured data pixel for pixel,
+/// which would no longer line up with the picture.
+pub struct PtzStage {
+    pub ptz: Ptz,
+    pub filter: Filter,
+    buffer: RgbaImage,
+}
+
+impl PtzStage {
+    pub const NAME: &'static str = "ptz";
+
+    pub fn new(filter: Filter) -> Self {
+        Self {
+            ptz: Ptz::default(),
+            filter,
+            buffer: RgbaImage::new(),
+        }
+    }
+}
+
+impl Default for PtzStage {
+    fn default() -> Self {
+        // Area comes out blocky when blowing pictures up
+        Self::new(Filter::Bilinear)
+    }
+}
+
+impl FrameProcessor for PtzStage {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn process(&mut self, frame: &mut Frame) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let (width, height) = (frame.rgba.width(), frame.rgba.height());
+        let (left, top, crop_width, crop_height) = self.ptz.crop(width, height);
+        if (crop_width, crop_height) == (width, height) || width == 0 || height == 0 {
+            return Ok(());
+        }
+        let stride = width * 4;
+        self.buffer.resize(width, height);
+        convert::resize_plane(
+            &frame.rgba.data()[top * stride + left * 4..],
+            crop_width,
+            crop_height,
+            stride,
+            4,
+            self.buffer.data_mut(),
+            width,
+            height,
+            stride,
+            self.filter,
+        );
+        std::mem::swap(&mut frame.rgba, &mut self.buffer);
+        frame.matches_captured = false;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn view_is_crop_in_fractions() {
+        let (width, height) = (1920, 1080);
+        for (zoom, pan, tilt) in [
+            (1.0, 0.0, 0.0),
+            (2.0, -1.0, 1.0),
+            (3.5, 0.3, -0.6),
+            (9.0, 2.0, 0.0),
+        ] {
+            let ptz = Ptz { zoom, pan, tilt };
+            let (left, top, crop_width, crop_height) = ptz.crop(width, height);
+            let (x, y, w, h) = ptz.view();
+            let near = |fraction: f32, pixels: usize, size: usize| {
+                (fraction * size as f32 - pixels as f32).abs() <= 1.0
+            };
+            assert!(
+                near(x, left, width) && near(w, crop_width, width),
+                "{ptz:?}"
+            );
+            assert!(
+                near(y, top, height) && near(h, crop_height, height),
+                "{ptz:?}"
+            );
+        }
+    }
+
+    fn near(a: Ptz, b: Ptz) -> bool {
+        (a.zoom - b.zoom).abs() < 1e-4
+            && (a.pan - b.pan).abs() < 1e-4
+            && (a.tilt - b.tilt).abs() < 1e-4
+    }
+
+    #[test]
+    fn zooms_by_ratios() {
+        let (from, to) = (
+            Ptz::default(),
+            Ptz {
+                zoom: 4.0,
+                pan: 1.0,
+                tilt: -0.5,
+            },
+        );
+        assert!(near(from.lerp(to, 0.0), from));
+        assert!(near(from.lerp(to, 1.0), to));
+        // Halfway from 1 to 4 is 2, not 2.5
+        let half = from.lerp(to, 0.5);
+        assert!(
+            near(
+                half,
+                Ptz {
+                    zoom: 2.0,
+                    pan: 0.5,
+                    tilt: -0.25
+                }
+            ),
+            "{half:?}"
+        );
+        assert!(near(
+            from.lerp(to, 0.25),
+            Ptz {
+                zoom: 2f32.sqrt(),
+                pan: 0.25,
+                tilt: -0.125
+            }
+        ));
+        // Out of range ends are clamped first
+        let wild = Ptz {
+            zoom: 20.0,
+            pan: 3.0,
+            tilt: -3.0,
+        };
+        assert!(near(from.lerp(wild, 1.0), wild.clamped()));
+    }
+
+    #[test]
+    fn moves_from_one_end_to_the_other() {
+        let from = Ptz {
+            zoom: 2.0,
+            pan: -0.5,
+            tilt: 0.25,
+        };
+        let to = Ptz {
+            zoom: 16.0,
+            pan: 0.5,
+            tilt: 2.0,
+        };
+        let start = Instant::now();
+        let duration = Duration::from_millis(800);
+        let ptz_move = PtzMove::new(from, to, start, duration);
+        assert!(near(ptz_move.at(start), from));
+        assert!(!ptz_move.is_done(start));
+        assert_eq!(ptz_move.at(start + duration), to.clamped());
+        assert_eq!(ptz_move.at(start + duration * 2), to.clamped());
+        assert!(ptz_move.is_done(start + duration));
+
+        // Easing in and out is symmetric, so the middle is the middle
+        let middle = ptz_move.at(start + duration / 2);
+        assert!(near(middle, from.lerp(to, 0.5)), "{middle:?}");
+        let early = ptz_move.at(start + duration / 4);
+        let late = ptz_move.at(start + duration * 3 / 4);
+        let (to, from) = (to.clamped(), from.clamped());
+        assert!((early.pan - from.pan - (to.pan - late.pan)).abs() < 1e-4);
+        assert!((early.zoom / from.zoom - to.zoom / late.zoom).abs() < 1e-4);
+    }
+
+    #[test]
+    fn zero_duration_is_done_at_once() {
+        let (from, to) = (
+            Ptz::default(),
+            Ptz {
+                zoom: 3.0,
+                pan: 0.5,
+                tilt: 0.5,
+            },
+        );
+        let start = Instant::now();
+        let ptz_move = PtzMove::new(from, to, start, Duration::ZERO);
+        assert!(ptz_move.is_done(start));
+        assert_eq!(ptz_move.at(start), to);
+        // Even asked about a moment before it started
+        let before = PtzMove::new(from, to, start + Duration::from_secs(1), Duration::ZERO);
+        let ptz = before.at(start);
+        assert!(ptz.zoom.is_finite() && ptz.pan.is_finite() && ptz.tilt.is_finite());
+    }
+}
@@ -0,0 +1,312 @@
+use std::{error::Error, time::Instant};
+
+use super::{luma, Frame, FrameProcessor};
+use crate::convert::{
+    self, ColorOverride, DeepYuv, Filter, HdrOptions, ParallelConverter, RgbaImage, Transform,
+    YuvView,
+};
+use crate::motion::{MotionDetector, MotionEvent};
+use crate::scopes::Scopes;
+
+/// Converts the captured frame to RGBA, see `convert::frame_to_rgba`.
+pub struct ConvertStage {
+    converter: ParallelConverter,
+    pub color_override: ColorOverride,
+    /// Also fill `Frame::rgba16` for 10 bit and deeper Y'CbCr, see
+    /// `convert::deep_to_rgba16`
+    pub keep_16_bit: bool,
+}
+
+impl ConvertStage {
+    pub const NAME: &'static str = "convert";
+
+    pub fn new(converter: ParallelConverter) -> Self {
+        Self {
+            converter,
+            color_override: ColorOverride::default(),
+            keep_16_bit: false,
+        }
+    }
+}
+
+impl FrameProcessor for ConvertStage {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn process(&mut self, frame: &mut Frame) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let format = frame.captured.format();
+        let spec = self.color_override.apply(format.color);
+        let planes = frame.captured.planes();
+        self.converter
+            .frame_to_rgba(&planes, format, spec, &mut frame.rgba)?;
+        frame.matches_captured = true;
+        if self.keep_16_bit && DeepYuv::from_pixel_format(format.pixel_format).is_some() {
+            let options = HdrOptions::default();
+            convert::deep_to_rgba16(&planes, format, spec, &options, &mut frame.rgba16)?;
+        }
+        Ok(())
+    }
+}
+
+/// Fills in `Scopes` for every frame, from the captured data if it's 8 bit
+/// Y'CbCr the picture still matches, and from the picture otherwise. The
+/// frame is left as it is.
+pub struct ScopeStage {
+    /// Should match the `ConvertStage`'s, for the same RGB
+    pub color_override: ColorOverride,
+    scopes: Scopes,
+}
+
+impl ScopeStage {
+    pub const NAME: &'static str = "scopes";
+
+    pub fn new() -> Self {
+        Self {
+            color_override: ColorOverride::default(),
+            scopes: Scopes::new(),
+        }
+    }
+
+    /// Of the last frame through.
+    pub fn scopes(&self) -> &Scopes {
+        &self.scopes
+    }
+}
+
+impl Default for ScopeStage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameProcessor for ScopeStage {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn process(&mut self, frame: &mut Frame) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let format = frame.captured.format();
+        let spec = self.color_override.apply(format.color);
+        let planes = frame.captured.planes();
+        let view = YuvView::new(&planes, format).filter(|_| frame.matches_captured);
+        match view {
+            Some(view) => self.scopes.from_yuv(&view, spec),
+            None => self.scopes.from_rgba(&frame.rgba, spec),
+        }
+        Ok(())
+    }
+}
+
+/// Runs a `MotionDetector` on every frame, on the captured luma if it's 8 bit
+/// Y'CbCr and on the picture otherwise. The frame is left as it is.
+#[derive(Default)]
+pub struct MotionStage {
+    pub detector: MotionDetector,
+    events: Vec<MotionEvent>,
+}
+
+impl MotionStage {
+    pub const NAME: &'static str = "motion";
+
+    pub fn new(detector: MotionDetector) -> Self {
+        Self {
+            detector,
+            events: Vec::new(),
+        }
+    }
+
+    /// What happened since the last call. They pile up until taken.
+    pub fn take_events(&mut self) -> Vec<MotionEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// For when it's no longer going to see frames, like when it's disabled:
+    /// motion that was going on ends `at`, so nobody waits for that forever.
+    pub fn stop(&mut self, at: Instant) {
+        self.events.extend(self.detector.reset(at));
+    }
+}
+
+impl FrameProcessor for MotionStage {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn process(&mut self, frame: &mut Frame) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let at = frame.meta.received;
+        let planes = frame.captured.planes();
+        let event = if let Some(view) = YuvView::new(&planes, frame.captured.format()) {
+            let (width, height) = (view.width(), view.height());
+            self.detector
+                .update(width, height, |x, y| view.luma(x, y), at)
+        } else {
+            let rgba = &frame.rgba;
+            let (width, height) = (rgba.width(), rgba.height());
+            let pixel = |x: usize, y: usize| luma(&rgba.data()[(y * width + x) * 4..]);
+            self.detector
+                .update(width, height, |x, y| Some(pixel(x, y)), at)
+        };
+        self.events.extend(event);
+        Ok(())
+    }
+}
+
+/// Rotates and flips the picture.
+#[derive(Default)]
+pub struct TransformStage {
+    pub transform: Transform,
+    buffer: RgbaImage,
+}
+
+impl TransformStage {
+    pub const NAME: &'static str = "transform";
+
+    pub fn new(transform: Transform) -> Self {
+        Self {
+            transform,
+            buffer: RgbaImage::new(),
+        }
+    }
+}
+
+impl FrameProcessor for TransformStage {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn process(&mut self, frame: &mut Frame) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if !self.transform.is_identity() {
+            convert::transform_rgba(&frame.rgba, self.transform, &mut self.buffer);
+            std::mem::swap(&mut frame.rgba, &mut self.buffer);
+            frame.matches_captured = false;
+        }
+        Ok(())
+    }
+}
+
+/// Shrinks the picture to fit in `max_size` for the viewer, keeping its
+/// aspect ratio. Smaller pictures are left alone.
+///
+/// Only `Frame::preview` gets scaled, made from `rgba` if nothing filled it
+/// yet, so recordings keep the full size. Goes after the `OverlayStage` so
+/// the overlays get scaled along.
+pub struct ScaleStage {
+    pub filter: Filter,
+    pub max_size: (usize, usize),
+    buffer: RgbaImage,
+}
+
+impl ScaleStage {
+    pub const NAME: &'static str = "scale";
+
+    pub fn new(filter: Filter, max_size: (usize, usize)) -> Self {
+        Self {
+            filter,
+            max_size,
+            buffer: RgbaImage::new(),
+        }
+    }
+
+    /// The size a `width` by `height` picture gets scaled to, if it needs it.
+    pub fn fit(&self, width: usize, height: usize) -> Option<(usize, usize)> {
+        let (max_width, max_height) = self.max_size;
+        if width <= max_width && height <= max_height {
+            return None;
+        }
+        let scale = (max_width as f64 / width as f64).min(max_height as f64 / height as f64);
+        let fitted = |size: usize| ((size as f64 * scale).round() as usize).max(1);
+        Some((fitted(width), fitted(height)))
+    }
+}
+
+impl FrameProcessor for ScaleStage {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn process(&mut self, frame: &mut Frame) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let picture = if frame.preview.data().is_empty() {
+            &frame.rgba
+        } else {
+            &frame.preview
+        };
+        if let Some((width, height)) = self.fit(picture.width(), picture.height()) {
+            convert::resize_rgba(picture, &mut self.buffer, width, height, self.filter);
+            std::mem::swap(&mut frame.preview, &mut self.buffer);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::{Captured, Pipeline};
+    use crate::v4l2::{FrameFormat, PixelFormat};
+
+    fn frame(pixel_format: PixelFormat, bytes_per_pixel: usize) -> Captured {
+        let format = FrameFormat {
+            pixel_format,
+            width: 4,
+            height: 2,
+            ..Default::default()
+        };
+        Captured::Data {
+            format,
+            planes: vec![vec![0x80; 4 * 2 * bytes_per_pixel]],
+        }
+    }
+
+    #[test]
+    fn convert_keeps_16_bits_when_asked() {
+        let mut pipeline = Pipeline::new();
+        pipeline.push(ConvertStage::new(ParallelConverter::new(1).unwrap()));
+
+        pipeline.process(frame(PixelFormat::Y210, 4)).unwrap();
+        assert_eq!(pipeline.frame().rgba.width(), 4);
+        assert_eq!(pipeline.frame().rgba16.width(), 0);
+
+        let convert = pipeline.stage_mut::<ConvertStage>().unwrap();
+        convert.keep_16_bit = true;
+        pipeline.process(frame(PixelFormat::Y210, 4)).unwrap();
+        let rgba16 = &pipeline.frame().rgba16;
+        assert_eq!((rgba16.width(), rgba16.height()), (4, 2));
+
+        // Nothing to keep for 8 bit formats
+        pipeline.process(frame(PixelFormat::YUYV, 2)).unwrap();
+        assert_eq!(pipeline.frame().rgba16.width(), 0);
+    }
+
+    #[test]
+    fn stopping_motion_ends_it() {
+        let mut pipeline = Pipeline::new();
+        pipeline.push(MotionStage::default());
+        pipeline.process(frame(PixelFormat::YUYV, 2)).unwrap();
+        let mut moved = frame(PixelFormat::YUYV, 2);
+        if let Captured::Data { planes, .. } = &mut moved {
+            planes[0][0] = 0xf0;
+        }
+        pipeline.process(moved).unwrap();
+
+        let stage = pipeline.stage_mut::<MotionStage>().unwrap();
+        let events = stage.take_events();
+        assert!(
+            matches!(events[..], [MotionEvent::Started { .. }]),
+            "{events:?}"
+        );
+        assert!(stage.detector.in_motion());
+
+        let at = Instant::now();
+        stage.stop(at);
+        let events = stage.take_events();
+        assert!(
+            matches!(events[..], [MotionEvent::Ended { at: ended, .. }] if ended == at),
+            "{events:?}"
+        );
+        assert!(!stage.detector.in_motion());
+        // Already over
+        stage.stop(at);
+        assert_eq!(stage.take_events(), []);
+    }
+}
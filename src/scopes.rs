@@ -0,0 +1,536 @@
+//! Video scopes: histogram, waveform, RGB parade and vectorscope.
+//!
+//! They're worked out from a grid of at most `MAX_COLUMNS` by `MAX_ROWS`
+//! pixels spread over the frame, which is plenty to judge exposure and
+//! colour by and cheap enough to do for every frame.
+
+use crate::convert::{ColorSpec, FixedYuvToRgb, RgbToYuv, RgbaImage, YuvView};
+
+/// Most pixels sampled across a frame.
+pub const MAX_COLUMNS: usize = 512;
+/// Most rows sampled down a frame.
+pub const MAX_ROWS: usize = 288;
+
+// Code values, and so the height of a waveform and the size of the
+// vectorscope
+const LEVELS: usize = 256;
+
+// What the scopes get drawn on
+const BACKGROUND: [u8; 3] = [16, 16, 16];
+const GRATICULE: [u8; 3] = [64, 64, 64];
+
+/// How many sampled pixels have each code value.
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    pub luma: [u32; LEVELS],
+    /// Red, green and blue
+    pub rgb: [[u32; LEVELS]; 3],
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            luma: [0; LEVELS],
+            rgb: [[0; LEVELS]; 3],
+        }
+    }
+}
+
+/// How many sampled pixels of each column have each code value, so with the
+/// picture's columns left to right and its levels bottom to top.
+#[derive(Debug, Clone, Default)]
+pub struct Waveform {
+    columns: usize,
+    // Sampled in each column
+    rows: usize,
+    // A row of columns per level
+    counts: Vec<u32>,
+}
+
+impl Waveform {
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    /// # Panics
+    ///
+    /// If `column` is out of range.
+    pub fn count(&self, column: usize, level: u8) -> u32 {
+        assert!(column < self.columns, "no column {column}");
+        self.counts[level as usize * self.columns + column]
+    }
+
+    fn reset(&mut self, columns: usize, rows: usize) {
+        self.columns = columns;
+        self.rows = rows;
+        self.counts.clear();
+        self.counts.resize(columns * LEVELS, 0);
+    }
+
+    #[inline]
+    fn add(&mut self, column: usize, level: u8) {
+        self.counts[level as usize * self.columns + column] += 1;
+    }
+
+    // Into `dst` from `left` on, in shades of `tint`
+    fn draw(&self, dst: &mut RgbaImage, left: usize, tint: [u8; 3]) {
+        // A column spread evenly over every level comes out at about a fifth
+        // of full brightness, bunched up ones saturate
+        let full = (self.rows as f32 / 8.0).max(1.0);
+        let width = dst.width();
+        for (row, dst_row) in dst.rows_mut().enumerate() {
+            let level = LEVELS - 1 - row;
+            let counts = &self.counts[level * self.columns..][..self.columns];
+            let dst_row = &mut dst_row[left * 4..(left + self.columns).min(width) * 4];
+            for (&count, rgba) in counts.iter().zip(dst_row.chunks_exact_mut(4)) {
+                if count > 0 {
+                    let intensity = (count as f32 / full).sqrt().min(1.0);
+                    let lit = tint.map(|c| (c as f32 * intensity) as u8);
+                    for (out, lit) in rgba.iter_mut().zip(lit) {
+                        *out = (*out).max(lit);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// How many sampled pixels have each combination of Cb and Cr.
+#[derive(Debug, Clone)]
+pub struct Vectorscope {
+    // A row of Cb values per Cr value
+    counts: Vec<u32>,
+}
+
+impl Default for Vectorscope {
+    fn default() -> Self {
+        Self {
+            counts: vec![0; LEVELS * LEVELS],
+        }
+    }
+}
+
+impl Vectorscope {
+    pub fn count(&self, cb: u8, cr: u8) -> u32 {
+        self.counts[cr as usize * LEVELS + cb as usize]
+    }
+
+    #[inline]
+    fn add(&mut self, cb: u8, cr: u8) {
+        self.counts[cr as usize * LEVELS + cb as usize] += 1;
+    }
+}
+
+/// The four scopes for one frame, kept around and filled again frame after
+/// frame.
+#[derive(Debug, Clone, Default)]
+pub struct Scopes {
+    pub histogram: Histogram,
+    /// Of luma
+    pub waveform: Waveform,
+    /// Of red, green and blue
+    pub parade: [Waveform; 3],
+    pub vectorscope: Vectorscope,
+    /// What the Y'CbCr values are in
+    pub spec: ColorSpec,
+    /// Pixels that went into them
+    pub samples: usize,
+}
+
+impl Scopes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// From a raw Y'CbCr frame, with `spec` to get RGB from it.
+    pub fn from_yuv(&mut self, view: &YuvView, spec: ColorSpec) {
+        let to_rgb = FixedYuvToRgb::new(spec);
+        self.sample(view.width(), view.height(), spec, |x, y| {
+            let yuv = view.get(x, y)?;
+            let [luma, cb, cr] = yuv;
+            Some((yuv, to_rgb.convert(luma, cb, cr)))
+        });
+    }
+
+    /// From a picture, with its Y'CbCr worked out according to `spec`, for
+    /// frames that didn't come as Y'CbCr.
+    pub fn from_rgba(&mut self, image: &RgbaImage, spec: ColorSpec) {
+        let to_yuv = RgbToYuv::new(spec);
+        let data = image.data();
+        let width = image.width();
+        self.sample(width, image.height(), spec, |x, y| {
+            let i = (y * width + x) * 4;
+            let [r, g, b] = [data[i], data[i + 1], data[i + 2]];
+            Some((to_yuv.convert(r, g, b), [r, g, b]))
+        });
+    }
+
+    // Every scope from the pixels on the sampling grid, as Y'CbCr and RGB
+    fn sample(
+        &mut self,
+        width: usize,
+        height: usize,
+        spec: ColorSpec,
+        mut pixel: impl FnMut(usize, usize) -> Option<([u8; 3], [u8; 3])>,
+    ) {
+        let x_step = width.div_ceil(MAX_COLUMNS).max(1);
+        let y_step = height.div_ceil(MAX_ROWS).max(1);
+        let columns = width.div_ceil(x_step);
+        let rows = height.div_ceil(y_step);
+
+        self.histogram = Histogram::default();
+        self.waveform.reset(columns, rows);
+        for waveform in &mut self.parade {
+            waveform.reset(columns, rows);
+        }
+        self.vectorscope.counts.fill(0);
+        self.spec = spec;
+        self.samples = 0;
+
+        for y in (0..height).step_by(y_step) {
+            for (column, x) in (0..width).step_by(x_step).enumerate() {
+                // A short frame only counts as far as it goes
+                let Some(([luma, cb, cr], rgb)) = pixel(x, y) else {
+                    return;
+                };
+                self.histogram.luma[luma as usize] += 1;
+                self.waveform.add(column, luma);
+                for ((histogram, waveform), value) in
+                    self.histogram.rgb.iter_mut().zip(&mut self.parade).zip(rgb)
+                {
+                    histogram[value as usize] += 1;
+                    waveform.add(column, value);
+                }
+                self.vectorscope.add(cb, cr);
+                self.samples += 1;
+            }
+        }
+    }
+
+    /// The histogram, 256 pixels wide and `height` tall: luma in grey with
+    /// red, green and blue over it.
+    pub fn render_histogram(&self, height: usize, dst: &mut RgbaImage) {
+        dst.resize(LEVELS, height);
+        // Square roots so a big spike doesn't flatten everything else
+        let bars = |counts: &[u32; LEVELS]| {
+            let max = counts.iter().copied().max().unwrap_or(0).max(1) as f32;
+            counts.map(|count| ((count as f32 / max).sqrt() * height as f32).round() as usize)
+        };
+        let luma = bars(&self.histogram.luma);
+        let rgb = self.histogram.rgb.each_ref().map(bars);
+
+        for (row, dst_row) in dst.rows_mut().enumerate() {
+            let above = height - row;
+            for (level, rgba) in dst_row.chunks_exact_mut(4).enumerate() {
+                let base = if luma[level] >= above {
+                    96
+                } else {
+                    BACKGROUND[0]
+                };
+                for (channel, out) in rgba[..3].iter_mut().enumerate() {
+                    *out = if rgb[channel][level] >= above {
+                        224
+                    } else {
+                        base
+                    };
+                }
+                rgba[3] = 255;
+            }
+        }
+    }
+
+    /// The luma waveform, as many pixels wide as there were columns sampled
+    /// and 256 tall.
+    pub fn render_waveform(&self, dst: &mut RgbaImage) {
+        clear(dst, self.waveform.columns, LEVELS);
+        draw_levels(dst);
+        self.waveform.draw(dst, 0, [160, 255, 160]);
+    }
+
+    /// The red, green and blue waveforms side by side, 256 pixels tall.
+    pub fn render_parade(&self, dst: &mut RgbaImage) {
+        let columns = self.waveform.columns;
+        clear(dst, columns * 3, LEVELS);
+        draw_levels(dst);
+        let tints = [[255, 64, 64], [64, 255, 64], [96, 96, 255]];
+        for (i, (waveform, tint)) in self.parade.iter().zip(tints).enumerate() {
+            waveform.draw(dst, i * columns, tint);
+        }
+    }
+
+    /// The vectorscope, 256 by 256 with Cb to the right and Cr up, each dot
+    /// in the colour it stands for. Boxes mark where 75% bars belong.
+    pub fn render_vectorscope(&self, dst: &mut RgbaImage) {
+        clear(dst, LEVELS, LEVELS);
+        let centre = LEVELS / 2;
+        for i in 0..LEVELS {
+            put(dst, i, centre, GRATICULE);
+            put(dst, centre, i, GRATICULE);
+        }
+        let to_yuv = RgbToYuv::new(self.spec);
+        let bars = [
+            [191, 0, 0],
+            [191, 191, 0],
+            [0, 191, 0],
+            [0, 191, 191],
+            [0, 0, 191],
+            [191, 0, 191],
+        ];
+        for [r, g, b] in bars {
+            let [_, cb, cr] = to_yuv.convert(r, g, b);
+            let (x, y) = (cb as usize, LEVELS - 1 - cr as usize);
+            // A box 7 pixels across, cut off at the edges
+            let (left, right) = (x.saturating_sub(3), (x + 3).min(LEVELS - 1));
+            let (top, bottom) = (y.saturating_sub(3), (y + 3).min(LEVELS - 1));
+            for x in left..=right {
+                put(dst, x, top, GRATICULE);
+                put(dst, x, bottom, GRATICULE);
+            }
+            for y in top..=bottom {
+                put(dst, left, y, GRATICULE);
+                put(dst, right, y, GRATICULE);
+            }
+        }
+
+        let to_rgb = FixedYuvToRgb::new(self.spec);
+        let mid_grey = to_yuv.convert(128, 128, 128)[0];
+        let full = (self.samples as f32 / 2048.0).max(1.0);
+        for (cr, counts) in self.vectorscope.counts.chunks_exact(LEVELS).enumerate() {
+            for (cb, &count) in counts.iter().enumerate() {
+                if count > 0 {
+                    let intensity = (count as f32 / full).sqrt().clamp(0.25, 1.0);
+                    let rgb = to_rgb.convert(mid_grey, cb as u8, cr as u8);
+                    // Brightened, or the dots near the middle would be lost
+                    let lit = rgb.map(|c| ((c as f32 + 64.0).min(255.0) * intensity) as u8);
+                    put(dst, cb, LEVELS - 1 - cr, lit);
+                }
+            }
+        }
+    }
+}
+
+// Resize to `width` by `height` and fill with the background
+fn clear(dst: &mut RgbaImage, width: usize, height: usize) {
+    dst.resize(width, height);
+    for rgba in dst.data_mut().chunks_exact_mut(4) {
+        rgba.copy_from_slice(&[BACKGROUND[0], BACKGROUND[1], BACKGROUND[2], 255]);
+    }
+}
+
+fn put(dst: &mut RgbaImage, x: usize, y: usize, rgb: [u8; 3]) {
+    let i = (y * dst.width() + x) * 4;
+    dst.data_mut()[i..i + 3].copy_from_slice(&rgb);
+}
+
+// Lines across a waveform at every quarter of the range
+fn draw_levels(dst: &mut RgbaImage) {
+    for level in [0, 64, 128, 192, 255] {
+        for x in 0..dst.width() {
+            put(dst, x, LEVELS - 1 - level, GRATICULE);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::convert::{Matrix, Range, Transfer};
+    use crate::v4l2::{FrameFormat, PixelFormat};
+
+    // 75% bars, brightest first
+    const BARS: [[u8; 3]; 8] = [
+        [191, 191, 191],
+        [191, 191, 0],
+        [0, 191, 191],
+        [0, 191, 0],
+        [191, 0, 191],
+        [191, 0, 0],
+        [0, 0, 191],
+        [0, 0, 0],
+    ];
+    const BAR_WIDTH: usize = 40;
+    const HEIGHT: usize = 20;
+
+    const HD: ColorSpec = ColorSpec {
+        matrix: Matrix::Bt709,
+        range: Range::Limited,
+        transfer: Transfer::Sdr,
+    };
+
+    fn image(width: usize, height: usize, rgb: impl Fn(usize, usize) -> [u8; 3]) -> RgbaImage {
+        let mut image = RgbaImage::new();
+        image.resize(width, height);
+        for (i, rgba) in image.data_mut().chunks_exact_mut(4).enumerate() {
+            let [r, g, b] = rgb(i % width, i / width);
+            rgba.copy_from_slice(&[r, g, b, 255]);
+        }
+        image
+    }
+
+    fn bars() -> RgbaImage {
+        image(BAR_WIDTH * BARS.len(), HEIGHT, |x, _| BARS[x / BAR_WIDTH])
+    }
+
+    fn pixel(image: &RgbaImage, x: usize, y: usize) -> [u8; 3] {
+        let i = (y * image.width() + x) * 4;
+        image.data()[i..i + 3].try_into().unwrap()
+    }
+
+    #[test]
+    fn colour_bars() {
+        let mut scopes = Scopes::new();
+        scopes.from_rgba(&bars(), HD);
+        let bar = (BAR_WIDTH * HEIGHT) as u32;
+        assert_eq!(scopes.samples, BARS.len() * BAR_WIDTH * HEIGHT);
+
+        let to_yuv = RgbToYuv::new(HD);
+        let yuv = BARS.map(|[r, g, b]| to_yuv.convert(r, g, b));
+        // Brightest first, so every bar has a luma of its own
+        assert!(yuv.windows(2).all(|pair| pair[0][0] > pair[1][0]));
+
+        for (channel, counts) in scopes.histogram.rgb.iter().enumerate() {
+            let lit = BARS.iter().filter(|rgb| rgb[channel] == 191).count() as u32;
+            assert_eq!(counts[191], lit * bar, "channel {channel}");
+            assert_eq!(counts[0], (8 - lit) * bar, "channel {channel}");
+            assert_eq!(counts.iter().sum::<u32>(), 8 * bar);
+        }
+        for [luma, _, _] in yuv {
+            assert_eq!(scopes.histogram.luma[luma as usize], bar);
+        }
+
+        assert_eq!(scopes.waveform.columns(), BARS.len() * BAR_WIDTH);
+        for column in 0..scopes.waveform.columns() {
+            let bar = column / BAR_WIDTH;
+            assert_eq!(scopes.waveform.count(column, yuv[bar][0]), HEIGHT as u32);
+            for (channel, waveform) in scopes.parade.iter().enumerate() {
+                assert_eq!(waveform.count(column, BARS[bar][channel]), HEIGHT as u32);
+            }
+        }
+
+        // White and black in the middle, the colours each on its own
+        let [_, cb, cr] = yuv[0];
+        assert_eq!((cb, cr), (128, 128));
+        assert_eq!(scopes.vectorscope.count(128, 128), 2 * bar);
+        for [_, cb, cr] in &yuv[1..7] {
+            assert_eq!(scopes.vectorscope.count(*cb, *cr), bar);
+        }
+    }
+
+    #[test]
+    fn grey_ramp() {
+        // Full range, so the greys keep their values as luma
+        let spec = ColorSpec {
+            range: Range::Full,
+            ..HD
+        };
+        let mut scopes = Scopes::new();
+        scopes.from_rgba(&image(256, 4, |x, _| [x as u8; 3]), spec);
+
+        for level in 0..=255 {
+            assert_eq!(scopes.histogram.luma[level as usize], 4);
+            assert_eq!(scopes.waveform.count(level as usize, level), 4);
+            for (histogram, waveform) in scopes.histogram.rgb.iter().zip(&scopes.parade) {
+                assert_eq!(histogram[level as usize], 4);
+                assert_eq!(waveform.count(level as usize, level), 4);
+            }
+        }
+        assert_eq!(scopes.vectorscope.count(128, 128), 256 * 4);
+    }
+
+    #[test]
+    fn samples_big_frames_on_a_grid() {
+        let mut scopes = Scopes::new();
+        // A ramp across, stepping every 8 pixels
+        let ramp = image(2048, 1080, |x, _| [(x / 8) as u8; 3]);
+        scopes.from_rgba(&ramp, HD);
+        assert_eq!(scopes.waveform.columns(), MAX_COLUMNS);
+        let rows = 1080_usize.div_ceil(1080_usize.div_ceil(MAX_ROWS));
+        assert_eq!(scopes.samples, MAX_COLUMNS * rows);
+        assert_eq!(
+            scopes.histogram.rgb[0].iter().sum::<u32>() as usize,
+            scopes.samples
+        );
+        // Every fourth pixel, so each column sees a single level
+        for column in [0, 1, 100, 511] {
+            let level = (column * 4 / 8) as u8;
+            assert_eq!(scopes.parade[1].count(column, level), rows as u32);
+        }
+    }
+
+    #[test]
+    fn from_yuv_counts_the_raw_values() {
+        // Two YUYV pixel pairs a row, dark and blue then bright and red
+        let row = [40, 200, 50, 100, 180, 90, 190, 220];
+        let data = row.repeat(3);
+        let format = FrameFormat {
+            pixel_format: PixelFormat::YUYV,
+            width: 4,
+            height: 3,
+            ..Default::default()
+        };
+        let view = YuvView::new(&[&data], &format).unwrap();
+        let mut scopes = Scopes::new();
+        scopes.from_yuv(&view, HD);
+
+        assert_eq!(scopes.samples, 12);
+        for (column, luma) in [40, 50, 180, 190].into_iter().enumerate() {
+            assert_eq!(scopes.histogram.luma[luma as usize], 3);
+            assert_eq!(scopes.waveform.count(column, luma), 3);
+        }
+        assert_eq!(scopes.vectorscope.count(200, 100), 6);
+        assert_eq!(scopes.vectorscope.count(90, 220), 6);
+
+        let to_rgb = FixedYuvToRgb::new(HD);
+        let [r, g, b] = to_rgb.convert(40, 200, 100);
+        assert_eq!(scopes.parade[0].count(0, r), 3);
+        assert_eq!(scopes.parade[1].count(0, g), 3);
+        assert_eq!(scopes.parade[2].count(0, b), 3);
+    }
+
+    #[test]
+    fn renders() {
+        let mut scopes = Scopes::new();
+        scopes.from_rgba(&bars(), HD);
+        let background = BACKGROUND;
+        let mut dst = RgbaImage::new();
+
+        scopes.render_histogram(100, &mut dst);
+        assert_eq!((dst.width(), dst.height()), (LEVELS, 100));
+        // Red, green and blue are each at 0 and 191 half of the time
+        assert_eq!(pixel(&dst, 191, 99), [224; 3]);
+        assert_eq!(pixel(&dst, 191, 0), [224; 3]);
+        assert_eq!(pixel(&dst, 100, 99), background);
+
+        scopes.render_waveform(&mut dst);
+        let columns = BARS.len() * BAR_WIDTH;
+        assert_eq!((dst.width(), dst.height()), (columns, LEVELS));
+        let luma = RgbToYuv::new(HD).convert(191, 191, 191)[0] as usize;
+        assert_eq!(pixel(&dst, 0, LEVELS - 1 - luma), [160, 255, 160]);
+        assert_eq!(pixel(&dst, 0, 10), background);
+
+        scopes.render_parade(&mut dst);
+        assert_eq!((dst.width(), dst.height()), (columns * 3, LEVELS));
+        // White's red, green and blue, and black's
+        for (channel, tint) in [[255, 64, 64], [64, 255, 64], [96, 96, 255]]
+            .into_iter()
+            .enumerate()
+        {
+            let left = channel * columns;
+            assert_eq!(pixel(&dst, left, LEVELS - 1 - 191), tint);
+            assert_eq!(pixel(&dst, left + columns - 1, LEVELS - 1), tint);
+        }
+
+        scopes.render_vectorscope(&mut dst);
+        assert_eq!((dst.width(), dst.height()), (LEVELS, LEVELS));
+        let to_yuv = RgbToYuv::new(HD);
+        for [r, g, b] in &BARS[1..7] {
+            let [_, cb, cr] = to_yuv.convert(*r, *g, *b);
+            let dot = pixel(&dst, cb as usize, LEVELS - 1 - cr as usize);
+            assert!(
+                dot != background && dot != GRATICULE,
+                "{r} {g} {b}: {dot:?}"
+            );
+        }
+        assert_eq!(pixel(&dst, 10, 10), background);
+    }
+}
@@ -1,22 +1,13 @@
 use std::{
     convert::AsRef,
     fs::{File, OpenOptions},
-    marker::PhantomData,
+    io,
     mem::MaybeUninit,
     os::fd::AsRawFd,
-    path::Path,
+    path::{Path, PathBuf},
+    sync::Arc,
 };
 
-mod sys {
-    #![allow(non_upper_case_globals)]
-    #![allow(non_camel_case_types)]
-    #![allow(non_snake_case)]
-    #![allow(unused)]
-
-    include!(concat!(env!("OUT_DIR"), "/v4l2-bindings.rs"));
-    include!(concat!(env!("OUT_DIR"), "/v4l2_constants.rs"));
-}
-
 // For variadic function ioctl
 macro_rules! ioctl {
   ($fd: expr, $num: expr, $($args:expr),+) => {
@@ -34,236 +25,377 @@ macro_rules! ioctl {
   };
 }
 
-pub struct V4l2Frame<'fd> {
-    fd: i32,
-    width: usize,
-    height: usize,
-    buf: sys::v4l2_buffer,
-    _phantom: PhantomData<&'fd ()>,
+mod controls;
+mod format;
+mod hotplug;
+mod pool;
+mod stats;
+mod sys;
+
+pub use controls::{
+    ControlInfo, ControlKind, PtzControls, CID_HFLIP, CID_PAN_ABSOLUTE, CID_TILT_ABSOLUTE,
+    CID_VFLIP, CID_ZOOM_ABSOLUTE,
+};
+pub use format::{pixel_format_to_string, FrameFormat, PixelFormat, PlaneFormat, MAX_PLANES};
+pub use hotplug::{stable_path, DeviceWatcher, HotplugEvent};
+use pool::BufferPool;
+pub use pool::{ExhaustionPolicy, QueueMode, V4l2Frame};
+pub use stats::{CaptureStats, StatsSnapshot, HISTOGRAM_BUCKET, HISTOGRAM_BUCKETS};
+
+/// How a device queues its buffers, see `V4l2VideoDevice::with_config`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CaptureConfig {
+    /// Buffers to ask the driver for. It may grant a different number,
+    /// `V4l2VideoDevice::buffer_count` has the one we actually got.
+    pub buffer_count: u32,
+    pub queue_mode: QueueMode,
+    pub exhaustion_policy: ExhaustionPolicy,
 }
 
-impl V4l2Frame<'_> {
-    pub fn data(&self) -> &[u8] {
-        unsafe {
-            std::slice::from_raw_parts(self.buf.m.userptr as *const u8, self.buf.bytesused as usize)
+impl Default for CaptureConfig {
+    fn default() -> Self {
+        Self {
+            buffer_count: 4,
+            queue_mode: QueueMode::default(),
+            exhaustion_policy: ExhaustionPolicy::default(),
         }
     }
-    // @FIXME: Get these from actual device
-    pub fn width(&self) -> usize {
-        self.width
-    }
-    pub fn height(&self) -> usize {
-        self.height
-    }
 }
 
-impl Drop for V4l2Frame<'_> {
-    fn drop(&mut self) {
-        unsafe {
-            ioctl!(self.fd, sys::VIDIOC_QBUF, &mut self.buf).unwrap();
-        }
-    }
+/// Everything needed to bring a device back the way it was after it has been
+/// unplugged, see `V4l2VideoDevice::reopen`.
+#[derive(Debug, Clone)]
+pub struct DeviceState {
+    pub path: PathBuf,
+    pub pixel_format: PixelFormat,
+    pub width: u32,
+    pub height: u32,
+    pub controls: Vec<(u32, i32)>,
+    pub config: CaptureConfig,
 }
 
 pub struct V4l2VideoDevice {
-    handle: File,
-    width: usize,
-    height: usize,
-    _buffers: Vec<Vec<u8>>,
+    path: PathBuf,
+    handle: Arc<File>,
+    // Single or multi-planar capture
+    buf_type: sys::v4l2_buf_type,
+    format: FrameFormat,
+    config: CaptureConfig,
+    pool: Arc<BufferPool>,
+    stats: CaptureStats,
 }
 
 impl V4l2VideoDevice {
-    pub fn new<P: AsRef<Path>>(device_path: &P) -> Self {
+    pub fn new<P: AsRef<Path>>(device_path: &P) -> io::Result<Self> {
+        Self::with_config(device_path, CaptureConfig::default())
+    }
+
+    pub fn with_config<P: AsRef<Path>>(device_path: &P, config: CaptureConfig) -> io::Result<Self> {
         // Open device file
         let video_handle = OpenOptions::new()
             .read(true)
             .write(true)
-            .open(device_path)
-            .unwrap();
+            .open(device_path)?;
 
         // Get device capabilities
         let fd = video_handle.as_raw_fd();
 
         let capabilities = unsafe {
             let mut capabilities: MaybeUninit<sys::v4l2_capability> = MaybeUninit::uninit();
-            ioctl!(fd, sys::VIDIOC_QUERYCAP, capabilities.as_mut_ptr()).unwrap();
+            ioctl!(fd, sys::VIDIOC_QUERYCAP, capabilities.as_mut_ptr())?;
             capabilities.assume_init()
         };
 
-        // Assert we have correct capabilities from device
-        assert!(capabilities.capabilities & sys::V4L2_CAP_VIDEO_CAPTURE != 0);
-        assert!(capabilities.capabilities & sys::V4L2_CAP_STREAMING != 0);
+        // Check we have correct capabilities from device. ISPs and codecs
+        // often only do the multi-planar API, even for single plane formats.
+        let caps = capabilities.capabilities;
+        let buf_type = if caps & sys::V4L2_CAP_VIDEO_CAPTURE != 0 {
+            sys::v4l2_buf_type_V4L2_BUF_TYPE_VIDEO_CAPTURE
+        } else if caps & sys::V4L2_CAP_VIDEO_CAPTURE_MPLANE != 0 {
+            sys::v4l2_buf_type_V4L2_BUF_TYPE_VIDEO_CAPTURE_MPLANE
+        } else {
+            0
+        };
+        if buf_type == 0 || caps & sys::V4L2_CAP_STREAMING == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "device can't do streaming video capture",
+            ));
+        }
 
-        println!("{capabilities:?}");
+        log::debug!("{capabilities:?}");
 
         // Get format v4l2 wants to give us
         let format = unsafe {
             let mut format: sys::v4l2_format = std::mem::zeroed();
-            format.type_ = sys::v4l2_buf_type_V4L2_BUF_TYPE_VIDEO_CAPTURE;
-            ioctl!(fd, sys::VIDIOC_G_FMT, &mut format).unwrap();
+            format.type_ = buf_type;
+            ioctl!(fd, sys::VIDIOC_G_FMT, &mut format)?;
             format
         };
-
-        unsafe {
-            println!("image size: {:?}", format.fmt.pix.sizeimage);
-            println!("width: {:?}", format.fmt.pix.width);
-            println!("height: {:?}", format.fmt.pix.height);
-            println!(
-                "pixelformat: {:?}",
-                pixel_format_to_string(format.fmt.pix.pixelformat)
-            );
-            println!("field: {:?}", format.fmt.pix.field);
-
-            assert!(format.fmt.pix.pixelformat == sys::V4L2_PIX_FMT_YUYV);
-            assert!(format.fmt.pix.field == sys::v4l2_field_V4L2_FIELD_NONE);
+        let field = unsafe {
+            if buf_type == sys::v4l2_buf_type_V4L2_BUF_TYPE_VIDEO_CAPTURE {
+                format.fmt.pix.field
+            } else {
+                format.fmt.pix_mp.field
+            }
+        };
+        let format = FrameFormat::from_format(&format);
+
+        log::debug!(
+            "{}x{} {} in {} planes, {} bytes, field {field}",
+            format.width,
+            format.height,
+            format.pixel_format,
+            format.num_planes,
+            format.planes[0].size
+        );
+
+        // Interlaced frames would need putting back together first
+        if field != sys::v4l2_field_V4L2_FIELD_NONE {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("device captures interlaced frames, field {field}"),
+            ));
         }
 
         // Init the buffers, user ptr, etc (init_userp)
-        let image_size = unsafe { format.fmt.pix.sizeimage };
+        let video_handle = Arc::new(video_handle);
 
-        const NUM_BUFFERS: u32 = 4;
-        // @TODO @FIXME Unsafe cell around each buf?
-        let mut buffers = Vec::new();
+        let pool = BufferPool::new(
+            video_handle.clone(),
+            buf_type,
+            &format,
+            config.buffer_count,
+            config.exhaustion_policy,
+            config.queue_mode,
+        )?;
 
+        // Start streaming
         unsafe {
-            for _ in 0..NUM_BUFFERS {
-                buffers.push(vec![0u8; image_size.try_into().unwrap()]);
-            }
+            ioctl!(fd, sys::VIDIOC_STREAMON, &buf_type)?;
+        }
+
+        Ok(Self {
+            path: device_path.as_ref().to_path_buf(),
+            handle: video_handle,
+            buf_type,
+            format,
+            config,
+            pool,
+            stats: CaptureStats::new(),
+        })
+    }
 
-            let mut bufreq: sys::v4l2_requestbuffers = std::mem::zeroed();
-            bufreq.count = buffers.len().try_into().unwrap();
-            bufreq.type_ = sys::v4l2_buf_type_V4L2_BUF_TYPE_VIDEO_CAPTURE;
-            bufreq.memory = sys::v4l2_memory_V4L2_MEMORY_USERPTR;
+    /// Open the device described by `state` again and restore its format and controls.
+    pub fn reopen(state: &DeviceState) -> io::Result<Self> {
+        let mut device = Self::with_config(&state.path, state.config)?;
 
-            ioctl!(fd, sys::VIDIOC_REQBUFS, &mut bufreq).unwrap();
+        if device.format.pixel_format != state.pixel_format {
+            device.set_pixel_format(state.pixel_format)?;
+        }
+        if (device.format.width, device.format.height)
+            != (state.width as usize, state.height as usize)
+        {
+            device.set_size(state.width, state.height)?;
         }
 
-        // Prepare streaming
-        (0..buffers.len()).for_each(|i| unsafe {
-            let mut v4l2_buf: sys::v4l2_buffer = std::mem::zeroed();
-            v4l2_buf.type_ = sys::v4l2_buf_type_V4L2_BUF_TYPE_VIDEO_CAPTURE;
-            v4l2_buf.memory = sys::v4l2_memory_V4L2_MEMORY_USERPTR;
-            v4l2_buf.index = i.try_into().unwrap();
-            v4l2_buf.m.userptr = buffers[i].as_ptr() as u64;
-            v4l2_buf.length = buffers[i].len().try_into().unwrap();
+        for &(id, value) in &state.controls {
+            // Read-only and inactive controls refuse this, nothing to restore there
+            let _ = device.set_control(id, value);
+        }
 
-            ioctl!(fd, sys::VIDIOC_QBUF, &mut v4l2_buf).unwrap();
-        });
+        Ok(device)
+    }
+
+    /// Snapshot of the current format and controls. The path is the stable
+    /// `/dev/v4l/by-id` one when there is such a link.
+    pub fn state(&self) -> DeviceState {
+        let controls = self
+            .controls()
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|control| Some((control.id, self.control(control.id).ok()?)))
+            .collect();
+
+        DeviceState {
+            path: stable_path(&self.path).unwrap_or_else(|| self.path.clone()),
+            pixel_format: self.format.pixel_format,
+            width: self.format.width as u32,
+            height: self.format.height as u32,
+            controls,
+            config: self.config,
+        }
+    }
+
+    /// Wait for the next frame. Fails once the device is gone, e.g. after a
+    /// USB camera has been unplugged, see `is_disconnected`.
+    pub fn get_frame(&self) -> io::Result<V4l2Frame> {
+        self.pool.dequeue(self.format, &self.stats)
+    }
+
+    /// A handle to the statistics this device updates with every frame.
+    pub fn stats(&self) -> CaptureStats {
+        self.stats.clone()
+    }
+
+    /// Keep updating `stats` instead, e.g. to carry on after a reconnect.
+    pub fn set_stats(&mut self, stats: CaptureStats) {
+        stats.restart();
+        self.stats = stats;
+    }
+
+    /// The format frames are currently captured in.
+    pub fn format(&self) -> FrameFormat {
+        self.format
+    }
+
+    pub fn config(&self) -> CaptureConfig {
+        self.config
+    }
+
+    /// Number of buffers the driver actually granted.
+    pub fn buffer_count(&self) -> u32 {
+        self.pool.count()
+    }
+
+    /// Only used outside `QueueMode::NoDrop`, which always copies.
+    pub fn set_exhaustion_policy(&mut self, policy: ExhaustionPolicy) {
+        self.config.exhaustion_policy = policy;
+        self.pool.set_policy(policy);
+    }
+
+    pub fn set_queue_mode(&mut self, mode: QueueMode) {
+        self.config.queue_mode = mode;
+        self.pool.set_mode(mode);
+    }
+
+    /// Restarts streaming with a new set of buffers.
+    pub fn set_buffer_count(&mut self, count: u32) -> io::Result<()> {
+        self.config.buffer_count = count;
 
-        // Start streaming
-        let video_capture_buf_type: sys::v4l2_buf_type =
-            sys::v4l2_buf_type_V4L2_BUF_TYPE_VIDEO_CAPTURE;
         unsafe {
-            ioctl!(fd, sys::VIDIOC_STREAMON, &video_capture_buf_type).unwrap();
+            let format = self.get_format()?;
+            self.restart_streaming(format)
         }
+    }
 
-        let (width, height) = unsafe {
-            (
-                format.fmt.pix.width as usize,
-                format.fmt.pix.height as usize,
-            )
+    /// Switch to the frame size at `index` in `get_frame_sizes`.
+    pub fn set_frame_size(&mut self, index: usize) -> io::Result<()> {
+        let frame_sizes = self.get_frame_sizes()?;
+        let Some(&(width, height)) = frame_sizes.get(index) else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("no frame size {index}, there are {}", frame_sizes.len()),
+            ));
         };
+        self.set_size(width, height)
+    }
 
-        Self {
-            handle: video_handle,
-            width,
-            height,
-            _buffers: buffers,
+    pub fn set_size(&mut self, width: u32, height: u32) -> io::Result<()> {
+        unsafe {
+            let mut format = self.get_format()?;
+            let pixel_format = self.format.pixel_format;
+            request_format(&mut format, pixel_format, width, height);
+
+            self.restart_streaming(format)
         }
     }
 
-    pub fn get_frame(&self) -> V4l2Frame<'_> {
-        let fd = self.handle.as_raw_fd();
-        let revents: i16 = 0;
+    /// Switch to capturing in `pixel_format`. The driver keeps the frame size
+    /// if it can and picks the closest one otherwise, check `format()`.
+    pub fn set_pixel_format(&mut self, pixel_format: PixelFormat) -> io::Result<()> {
+        unsafe {
+            let mut format = self.get_format()?;
+            let (width, height) = (self.format.width as u32, self.format.height as u32);
+            request_format(&mut format, pixel_format, width, height);
 
-        let mut poll_fd: [sys::pollfd; 1] = [sys::pollfd {
-            fd,
-            events: sys::POLLIN as i16,
-            revents,
-        }];
+            self.restart_streaming(format)?;
+        }
 
-        unsafe {
-            let infinite_timeout = -1;
-            let _ret = sys::poll(poll_fd.as_mut_ptr(), poll_fd.len() as u64, infinite_timeout);
-            // println!("{}", ret);
-
-            let mut v4l2_buf: sys::v4l2_buffer = std::mem::zeroed();
-            v4l2_buf.type_ = sys::v4l2_buf_type_V4L2_BUF_TYPE_VIDEO_CAPTURE;
-            v4l2_buf.memory = sys::v4l2_memory_V4L2_MEMORY_USERPTR;
-
-            // Read frame
-
-            // Deque buffer. We can use them now and queue them
-            // up again after we're done.
-            ioctl!(fd, sys::VIDIOC_DQBUF, &v4l2_buf).unwrap();
-
-            V4l2Frame {
-                fd,
-                width: self.width,
-                height: self.height,
-                buf: v4l2_buf,
-                _phantom: PhantomData,
-            }
+        // Drivers fall back to something else for formats they don't have
+        if self.format.pixel_format != pixel_format {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("device doesn't capture {pixel_format}"),
+            ));
         }
+
+        Ok(())
     }
 
-    unsafe fn deque_all_buffers(&self) -> Result<(), i32> {
-        let mut v4l2_buf: sys::v4l2_buffer = std::mem::zeroed();
-        v4l2_buf.type_ = sys::v4l2_buf_type_V4L2_BUF_TYPE_VIDEO_CAPTURE;
-        v4l2_buf.memory = sys::v4l2_memory_V4L2_MEMORY_USERPTR;
+    unsafe fn get_format(&self) -> io::Result<sys::v4l2_format> {
+        let mut format: sys::v4l2_format = std::mem::zeroed();
+        format.type_ = self.buf_type;
+        ioctl!(self.handle.as_raw_fd(), sys::VIDIOC_G_FMT, &mut format)?;
+        Ok(format)
+    }
 
+    // Stop streaming and give up the buffers, which also lets another open
+    // of the device take it over
+    unsafe fn stop_streaming(&self) -> io::Result<()> {
         let fd = self.handle.as_raw_fd();
 
-        loop {
-            match ioctl!(fd, sys::VIDIOC_DQBUF, &v4l2_buf) {
-                Ok(_) => (),
-                Err(e) => {
-                    if e.kind() == std::io::ErrorKind::WouldBlock {
-                        break;
-                    } else {
-                        panic!("That went well..");
-                    }
-                }
-            }
-        }
+        // Frames still alive keep their memory, they just won't be queued again
+        self.pool.retire();
+        ioctl!(fd, sys::VIDIOC_STREAMOFF, &self.buf_type)?;
 
+        let mut bufreq: sys::v4l2_requestbuffers = std::mem::zeroed();
+        bufreq.count = 0;
+        bufreq.type_ = self.buf_type;
+        bufreq.memory = sys::v4l2_memory_V4L2_MEMORY_USERPTR;
+        ioctl!(fd, sys::VIDIOC_REQBUFS, &mut bufreq)?;
         Ok(())
     }
 
-    pub fn set_frame_size(&mut self, index: usize) {
-        let frames_sizes = self.get_frame_sizes();
+    // Stop streaming, apply `format` and start again with fresh buffers.
+    // Goes back to the old format if that fails, and if even that fails the
+    // device is left stopped and `get_frame` says it has to be opened again.
+    unsafe fn restart_streaming(&mut self, format: sys::v4l2_format) -> io::Result<()> {
+        let old_format = self.get_format()?;
 
-        assert!(index < frames_sizes.len());
+        // The driver refuses a new format while buffers are allocated
+        self.stop_streaming()?;
 
-        let (width, height) = frames_sizes[index];
-        let fd = self.handle.as_raw_fd();
-
-        unsafe {
-            let mut format: sys::v4l2_format = std::mem::zeroed();
-            format.type_ = sys::v4l2_buf_type_V4L2_BUF_TYPE_VIDEO_CAPTURE;
-            // format.fmt.pix.pixelformat = sys::V4L2_PIX_FMT_YUYV;
+        if let Err(e) = self.start_streaming(format) {
+            let _ = self.stop_streaming();
+            if let Err(restore) = self.start_streaming(old_format) {
+                log::warn!("Couldn't go back to the old format either: {restore}");
+                let _ = self.stop_streaming();
+            }
+            return Err(e);
+        }
 
-            ioctl!(fd, sys::VIDIOC_G_FMT, &mut format).unwrap();
+        Ok(())
+    }
 
-            format.fmt.pix.width = width;
-            format.fmt.pix.height = height;
+    unsafe fn start_streaming(&mut self, mut format: sys::v4l2_format) -> io::Result<()> {
+        let fd = self.handle.as_raw_fd();
 
-            let video_capture_buf_type: sys::v4l2_buf_type =
-                sys::v4l2_buf_type_V4L2_BUF_TYPE_VIDEO_CAPTURE;
+        ioctl!(fd, sys::VIDIOC_S_FMT, &mut format)?;
+        self.format = FrameFormat::from_format(&format);
 
-            ioctl!(fd, sys::VIDIOC_STREAMOFF, &video_capture_buf_type).unwrap();
+        self.pool = BufferPool::new(
+            self.handle.clone(),
+            self.buf_type,
+            &self.format,
+            self.config.buffer_count,
+            self.config.exhaustion_policy,
+            self.config.queue_mode,
+        )?;
 
-            self.deque_all_buffers().unwrap();
+        ioctl!(fd, sys::VIDIOC_STREAMON, &self.buf_type)?;
+        self.stats.restart();
 
-            ioctl!(fd, sys::VIDIOC_S_FMT, &mut format).unwrap();
-            self.width = width as usize;
-            self.height = height as usize;
+        Ok(())
+    }
 
-            ioctl!(fd, sys::VIDIOC_STREAMON, &video_capture_buf_type).unwrap();
-        }
+    /// Frame sizes of the current pixel format.
+    pub fn get_frame_sizes(&self) -> io::Result<Vec<(u32, u32)>> {
+        self.frame_sizes(self.format.pixel_format)
     }
 
-    pub fn get_frame_sizes(&self) -> Vec<(u32, u32)> {
+    /// The discrete frame sizes `pixel_format` comes in.
+    pub fn frame_sizes(&self, pixel_format: PixelFormat) -> io::Result<Vec<(u32, u32)>> {
         let mut i = 0;
         let fd = self.handle.as_raw_fd();
         let mut res = Vec::new();
@@ -272,7 +404,7 @@ impl V4l2VideoDevice {
             unsafe {
                 let mut descr: sys::v4l2_frmsizeenum = std::mem::zeroed();
                 descr.index = i;
-                descr.pixel_format = sys::V4L2_PIX_FMT_YUYV;
+                descr.pixel_format = pixel_format.0;
 
                 let ret = ioctl!(fd, sys::VIDIOC_ENUM_FRAMESIZES, &mut descr);
 
@@ -280,14 +412,12 @@ impl V4l2VideoDevice {
                     if e.kind() == std::io::ErrorKind::InvalidInput {
                         break;
                     }
-
-                    panic!("Unexpected error: {e}");
+                    return Err(e);
                 };
 
                 if descr.type_ != sys::v4l2_frmsizetypes_V4L2_FRMSIZE_TYPE_DISCRETE {
-                    // panic!("We can only handle discrete frame sizes");
-                    println!(
-                        "We don't care about non-discrete framesizes: {:?}",
+                    log::debug!(
+                        "Skipping non-discrete frame sizes: {:?}",
                         descr.__bindgen_anon_1.stepwise
                     );
                     i += 1;
@@ -303,49 +433,82 @@ impl V4l2VideoDevice {
             i += 1;
         }
 
-        res
+        Ok(res)
     }
 
-    pub fn print_formats(&self) {
+    /// Pixel formats the device can capture in.
+    pub fn formats(&self) -> io::Result<Vec<PixelFormat>> {
         let mut format_index: u32 = 0;
         let fd = self.handle.as_raw_fd();
+        let mut res = Vec::new();
+
         loop {
             unsafe {
                 let mut descr: sys::v4l2_fmtdesc = std::mem::zeroed();
                 descr.index = format_index;
-                descr.type_ = sys::v4l2_buf_type_V4L2_BUF_TYPE_VIDEO_CAPTURE;
+                descr.type_ = self.buf_type;
                 let ret = ioctl!(fd, sys::VIDIOC_ENUM_FMT, &mut descr);
 
                 if let Err(e) = ret {
                     if e.kind() == std::io::ErrorKind::InvalidInput {
                         break;
                     }
-
-                    panic!("Unexpected error: {e}");
+                    return Err(e);
                 };
 
-                // We only care about YUYV
-                if descr.pixelformat != sys::V4L2_PIX_FMT_YUYV {
-                    println!(
-                        "Don't care about pixel format: {}",
-                        pixel_format_to_string(descr.pixelformat)
-                    );
-                    format_index += 1;
-                    continue;
-                }
-
-                println!(
-                    "PIXEL_FORMAT: {}",
-                    pixel_format_to_string(descr.pixelformat)
-                );
-                // print_frame_sizes_for_format(fd, descr.pixelformat);
-
-                let frame_sizes_for_pixel_format = self.get_frame_sizes();
-                println!("Frame sizes: {frame_sizes_for_pixel_format:?}");
+                res.push(PixelFormat(descr.pixelformat));
             }
 
             format_index += 1;
         }
+
+        Ok(res)
+    }
+}
+
+impl Drop for V4l2VideoDevice {
+    fn drop(&mut self) {
+        // Frames still around hold on to the file, but not to the device.
+        // Gone already if it was unplugged.
+        let _ = unsafe { self.stop_streaming() };
+    }
+}
+
+/// Whether an error from `V4l2VideoDevice::get_frame` means the device is
+/// gone, as when a USB camera has been unplugged, rather than something that
+/// may pass. Also true once it stopped streaming for good after a failed
+/// format change; opening it again is the way out of both.
+pub fn is_disconnected(error: &io::Error) -> bool {
+    let code = error.raw_os_error();
+    code == Some(sys::ENODEV as i32)
+        || code == Some(sys::ENXIO as i32)
+        || error.kind() == io::ErrorKind::NotConnected
+}
+
+// Ask for a pixel format and size, leaving bytesperline and sizeimage to the
+// driver since the old ones are meaningless for the new format
+unsafe fn request_format(
+    format: &mut sys::v4l2_format,
+    pixel_format: PixelFormat,
+    width: u32,
+    height: u32,
+) {
+    if format.type_ == sys::v4l2_buf_type_V4L2_BUF_TYPE_VIDEO_CAPTURE_MPLANE {
+        let pix_mp = &mut format.fmt.pix_mp;
+        pix_mp.pixelformat = pixel_format.0;
+        pix_mp.width = width;
+        pix_mp.height = height;
+        for plane in &mut pix_mp.plane_fmt {
+            plane.bytesperline = 0;
+            plane.sizeimage = 0;
+        }
+    } else {
+        let pix = &mut format.fmt.pix;
+        pix.pixelformat = pixel_format.0;
+        pix.width = width;
+        pix.height = height;
+        pix.bytesperline = 0;
+        pix.sizeimage = 0;
     }
 }
 
@@ -384,286 +547,3 @@ impl V4l2VideoDevice {
 //         i += 1;
 //     }
 // }
-
-fn pixel_format_to_string(format: u32) -> &'static str {
-    match format {
-        sys::V4L2_PIX_FMT_RGB332 => "V4L2_PIX_FMT_RGB332",
-        sys::V4L2_PIX_FMT_RGB444 => "V4L2_PIX_FMT_RGB444",
-        sys::V4L2_PIX_FMT_ARGB444 => "V4L2_PIX_FMT_ARGB444",
-        sys::V4L2_PIX_FMT_XRGB444 => "V4L2_PIX_FMT_XRGB444",
-        sys::V4L2_PIX_FMT_RGBA444 => "V4L2_PIX_FMT_RGBA444",
-        sys::V4L2_PIX_FMT_RGBX444 => "V4L2_PIX_FMT_RGBX444",
-        sys::V4L2_PIX_FMT_ABGR444 => "V4L2_PIX_FMT_ABGR444",
-        sys::V4L2_PIX_FMT_XBGR444 => "V4L2_PIX_FMT_XBGR444",
-        sys::V4L2_PIX_FMT_BGRA444 => "V4L2_PIX_FMT_BGRA444",
-        sys::V4L2_PIX_FMT_BGRX444 => "V4L2_PIX_FMT_BGRX444",
-        sys::V4L2_PIX_FMT_RGB555 => "V4L2_PIX_FMT_RGB555",
-        sys::V4L2_PIX_FMT_ARGB555 => "V4L2_PIX_FMT_ARGB555",
-        sys::V4L2_PIX_FMT_XRGB555 => "V4L2_PIX_FMT_XRGB555",
-        sys::V4L2_PIX_FMT_RGBA555 => "V4L2_PIX_FMT_RGBA555",
-        sys::V4L2_PIX_FMT_RGBX555 => "V4L2_PIX_FMT_RGBX555",
-        sys::V4L2_PIX_FMT_ABGR555 => "V4L2_PIX_FMT_ABGR555",
-        sys::V4L2_PIX_FMT_XBGR555 => "V4L2_PIX_FMT_XBGR555",
-        sys::V4L2_PIX_FMT_BGRA555 => "V4L2_PIX_FMT_BGRA555",
-        sys::V4L2_PIX_FMT_BGRX555 => "V4L2_PIX_FMT_BGRX555",
-        sys::V4L2_PIX_FMT_RGB565 => "V4L2_PIX_FMT_RGB565",
-        sys::V4L2_PIX_FMT_RGB555X => "V4L2_PIX_FMT_RGB555X",
-        sys::V4L2_PIX_FMT_ARGB555X => "V4L2_PIX_FMT_ARGB555X",
-        sys::V4L2_PIX_FMT_XRGB555X => "V4L2_PIX_FMT_XRGB555X",
-        sys::V4L2_PIX_FMT_RGB565X => "V4L2_PIX_FMT_RGB565X",
-
-        /* RGB formats (3 or 4 bytes per pixel) */
-        sys::V4L2_PIX_FMT_BGR666 => "V4L2_PIX_FMT_BGR666",
-        sys::V4L2_PIX_FMT_BGR24 => "V4L2_PIX_FMT_BGR24",
-        sys::V4L2_PIX_FMT_RGB24 => "V4L2_PIX_FMT_RGB24",
-        sys::V4L2_PIX_FMT_BGR32 => "V4L2_PIX_FMT_BGR32",
-        sys::V4L2_PIX_FMT_ABGR32 => "V4L2_PIX_FMT_ABGR32",
-        sys::V4L2_PIX_FMT_XBGR32 => "V4L2_PIX_FMT_XBGR32",
-        sys::V4L2_PIX_FMT_BGRA32 => "V4L2_PIX_FMT_BGRA32",
-        sys::V4L2_PIX_FMT_BGRX32 => "V4L2_PIX_FMT_BGRX32",
-        sys::V4L2_PIX_FMT_RGB32 => "V4L2_PIX_FMT_RGB32",
-        sys::V4L2_PIX_FMT_RGBA32 => "V4L2_PIX_FMT_RGBA32",
-        sys::V4L2_PIX_FMT_RGBX32 => "V4L2_PIX_FMT_RGBX32",
-        sys::V4L2_PIX_FMT_ARGB32 => "V4L2_PIX_FMT_ARGB32",
-        sys::V4L2_PIX_FMT_XRGB32 => "V4L2_PIX_FMT_XRGB32",
-        sys::V4L2_PIX_FMT_RGBX1010102 => "V4L2_PIX_FMT_RGBX1010102",
-        sys::V4L2_PIX_FMT_RGBA1010102 => "V4L2_PIX_FMT_RGBA1010102",
-        sys::V4L2_PIX_FMT_ARGB2101010 => "V4L2_PIX_FMT_ARGB2101010",
-
-        /* RGB formats (6 or 8 bytes per pixel) */
-        sys::V4L2_PIX_FMT_BGR48_12 => "V4L2_PIX_FMT_BGR48_12",
-        sys::V4L2_PIX_FMT_ABGR64_12 => "V4L2_PIX_FMT_ABGR64_12",
-
-        /* Grey formats */
-        sys::V4L2_PIX_FMT_GREY => "V4L2_PIX_FMT_GREY",
-        sys::V4L2_PIX_FMT_Y4 => "V4L2_PIX_FMT_Y4",
-        sys::V4L2_PIX_FMT_Y6 => "V4L2_PIX_FMT_Y6",
-        sys::V4L2_PIX_FMT_Y10 => "V4L2_PIX_FMT_Y10",
-        sys::V4L2_PIX_FMT_Y12 => "V4L2_PIX_FMT_Y12",
-        sys::V4L2_PIX_FMT_Y012 => "V4L2_PIX_FMT_Y012",
-        sys::V4L2_PIX_FMT_Y14 => "V4L2_PIX_FMT_Y14",
-        sys::V4L2_PIX_FMT_Y16 => "V4L2_PIX_FMT_Y16",
-        sys::V4L2_PIX_FMT_Y16_BE => "V4L2_PIX_FMT_Y16_BE",
-
-        /* Grey bit-packed formats */
-        sys::V4L2_PIX_FMT_Y10BPACK => "V4L2_PIX_FMT_Y10BPACK",
-        sys::V4L2_PIX_FMT_Y10P => "V4L2_PIX_FMT_Y10P",
-        sys::V4L2_PIX_FMT_IPU3_Y10 => "V4L2_PIX_FMT_IPU3_Y10",
-
-        /* Palette formats */
-        sys::V4L2_PIX_FMT_PAL8 => "V4L2_PIX_FMT_PAL8",
-
-        /* Chrominance formats */
-        sys::V4L2_PIX_FMT_UV8 => "V4L2_PIX_FMT_UV8",
-
-        /* Luminance+Chrominance formats */
-        sys::V4L2_PIX_FMT_YUYV => "V4L2_PIX_FMT_YUYV",
-        sys::V4L2_PIX_FMT_YYUV => "V4L2_PIX_FMT_YYUV",
-        sys::V4L2_PIX_FMT_YVYU => "V4L2_PIX_FMT_YVYU",
-        sys::V4L2_PIX_FMT_UYVY => "V4L2_PIX_FMT_UYVY",
-        sys::V4L2_PIX_FMT_VYUY => "V4L2_PIX_FMT_VYUY",
-        sys::V4L2_PIX_FMT_Y41P => "V4L2_PIX_FMT_Y41P",
-        sys::V4L2_PIX_FMT_YUV444 => "V4L2_PIX_FMT_YUV444",
-        sys::V4L2_PIX_FMT_YUV555 => "V4L2_PIX_FMT_YUV555",
-        sys::V4L2_PIX_FMT_YUV565 => "V4L2_PIX_FMT_YUV565",
-        sys::V4L2_PIX_FMT_YUV24 => "V4L2_PIX_FMT_YUV24",
-        sys::V4L2_PIX_FMT_YUV32 => "V4L2_PIX_FMT_YUV32",
-        sys::V4L2_PIX_FMT_AYUV32 => "V4L2_PIX_FMT_AYUV32",
-        sys::V4L2_PIX_FMT_XYUV32 => "V4L2_PIX_FMT_XYUV32",
-        sys::V4L2_PIX_FMT_VUYA32 => "V4L2_PIX_FMT_VUYA32",
-        sys::V4L2_PIX_FMT_VUYX32 => "V4L2_PIX_FMT_VUYX32",
-        sys::V4L2_PIX_FMT_YUVA32 => "V4L2_PIX_FMT_YUVA32",
-        sys::V4L2_PIX_FMT_YUVX32 => "V4L2_PIX_FMT_YUVX32",
-        sys::V4L2_PIX_FMT_M420 => "V4L2_PIX_FMT_M420",
-        sys::V4L2_PIX_FMT_YUV48_12 => "V4L2_PIX_FMT_YUV48_12",
-
-        /*
-         * YCbCr packed format. For each Y2xx format, xx bits of valid data occupy the MSBs
-         * of the 16 bit components, and 16-xx bits of zero padding occupy the LSBs.
-         */
-        sys::V4L2_PIX_FMT_Y210 => "V4L2_PIX_FMT_Y210",
-        sys::V4L2_PIX_FMT_Y212 => "V4L2_PIX_FMT_Y212",
-        sys::V4L2_PIX_FMT_Y216 => "V4L2_PIX_FMT_Y216",
-
-        /* two planes -- one Y, one Cr + Cb interleaved  */
-        sys::V4L2_PIX_FMT_NV12 => "V4L2_PIX_FMT_NV12",
-        sys::V4L2_PIX_FMT_NV21 => "V4L2_PIX_FMT_NV21",
-        sys::V4L2_PIX_FMT_NV16 => "V4L2_PIX_FMT_NV16",
-        sys::V4L2_PIX_FMT_NV61 => "V4L2_PIX_FMT_NV61",
-        sys::V4L2_PIX_FMT_NV24 => "V4L2_PIX_FMT_NV24",
-        sys::V4L2_PIX_FMT_NV42 => "V4L2_PIX_FMT_NV42",
-        sys::V4L2_PIX_FMT_P010 => "V4L2_PIX_FMT_P010",
-        sys::V4L2_PIX_FMT_P012 => "V4L2_PIX_FMT_P012",
-
-        /* two non contiguous planes - one Y, one Cr + Cb interleaved  */
-        sys::V4L2_PIX_FMT_NV12M => "V4L2_PIX_FMT_NV12M",
-        sys::V4L2_PIX_FMT_NV21M => "V4L2_PIX_FMT_NV21M",
-        sys::V4L2_PIX_FMT_NV16M => "V4L2_PIX_FMT_NV16M",
-        sys::V4L2_PIX_FMT_NV61M => "V4L2_PIX_FMT_NV61M",
-        sys::V4L2_PIX_FMT_P012M => "V4L2_PIX_FMT_P012M",
-
-        /* three planes - Y Cb, Cr */
-        sys::V4L2_PIX_FMT_YUV410 => "V4L2_PIX_FMT_YUV410",
-        sys::V4L2_PIX_FMT_YVU410 => "V4L2_PIX_FMT_YVU410",
-        sys::V4L2_PIX_FMT_YUV411P => "V4L2_PIX_FMT_YUV411P",
-        sys::V4L2_PIX_FMT_YUV420 => "V4L2_PIX_FMT_YUV420",
-        sys::V4L2_PIX_FMT_YVU420 => "V4L2_PIX_FMT_YVU420",
-        sys::V4L2_PIX_FMT_YUV422P => "V4L2_PIX_FMT_YUV422P",
-
-        /* three non contiguous planes - Y, Cb, Cr */
-        sys::V4L2_PIX_FMT_YUV420M => "V4L2_PIX_FMT_YUV420M",
-        sys::V4L2_PIX_FMT_YVU420M => "V4L2_PIX_FMT_YVU420M",
-        sys::V4L2_PIX_FMT_YUV422M => "V4L2_PIX_FMT_YUV422M",
-        sys::V4L2_PIX_FMT_YVU422M => "V4L2_PIX_FMT_YVU422M",
-        sys::V4L2_PIX_FMT_YUV444M => "V4L2_PIX_FMT_YUV444M",
-        sys::V4L2_PIX_FMT_YVU444M => "V4L2_PIX_FMT_YVU444M",
-
-        /* Tiled YUV formats */
-        sys::V4L2_PIX_FMT_NV12_4L4 => "V4L2_PIX_FMT_NV12_4L4",
-        sys::V4L2_PIX_FMT_NV12_16L16 => "V4L2_PIX_FMT_NV12_16L16",
-        sys::V4L2_PIX_FMT_NV12_32L32 => "V4L2_PIX_FMT_NV12_32L32",
-        sys::V4L2_PIX_FMT_NV15_4L4 => "V4L2_PIX_FMT_NV15_4L4",
-        sys::V4L2_PIX_FMT_P010_4L4 => "V4L2_PIX_FMT_P010_4L4",
-        sys::V4L2_PIX_FMT_NV12_8L128 => "V4L2_PIX_FMT_NV12_8L128",
-        sys::V4L2_PIX_FMT_NV12_10BE_8L128 => "V4L2_PIX_FMT_NV12_10BE_8L128",
-
-        /* Tiled YUV formats, non contiguous planes */
-        sys::V4L2_PIX_FMT_NV12MT => "V4L2_PIX_FMT_NV12MT",
-        sys::V4L2_PIX_FMT_NV12MT_16X16 => "V4L2_PIX_FMT_NV12MT_16X16",
-        sys::V4L2_PIX_FMT_NV12M_8L128 => "V4L2_PIX_FMT_NV12M_8L128",
-        sys::V4L2_PIX_FMT_NV12M_10BE_8L128 => "V4L2_PIX_FMT_NV12M_10BE_8L128",
-
-        /* Bayer formats - see http://www.siliconimaging.com/RGB%20Bayer.htm */
-        sys::V4L2_PIX_FMT_SBGGR8 => "V4L2_PIX_FMT_SBGGR8",
-        sys::V4L2_PIX_FMT_SGBRG8 => "V4L2_PIX_FMT_SGBRG8",
-        sys::V4L2_PIX_FMT_SGRBG8 => "V4L2_PIX_FMT_SGRBG8",
-        sys::V4L2_PIX_FMT_SRGGB8 => "V4L2_PIX_FMT_SRGGB8",
-        sys::V4L2_PIX_FMT_SBGGR10 => "V4L2_PIX_FMT_SBGGR10",
-        sys::V4L2_PIX_FMT_SGBRG10 => "V4L2_PIX_FMT_SGBRG10",
-        sys::V4L2_PIX_FMT_SGRBG10 => "V4L2_PIX_FMT_SGRBG10",
-        sys::V4L2_PIX_FMT_SRGGB10 => "V4L2_PIX_FMT_SRGGB10",
-        /* 10bit raw bayer packed, 5 bytes for every 4 pixels */
-        sys::V4L2_PIX_FMT_SBGGR10P => "V4L2_PIX_FMT_SBGGR10P",
-        sys::V4L2_PIX_FMT_SGBRG10P => "V4L2_PIX_FMT_SGBRG10P",
-        sys::V4L2_PIX_FMT_SGRBG10P => "V4L2_PIX_FMT_SGRBG10P",
-        sys::V4L2_PIX_FMT_SRGGB10P => "V4L2_PIX_FMT_SRGGB10P",
-        /* 10bit raw bayer a-law compressed to 8 bits */
-        sys::V4L2_PIX_FMT_SBGGR10ALAW8 => "V4L2_PIX_FMT_SBGGR10ALAW8",
-        sys::V4L2_PIX_FMT_SGBRG10ALAW8 => "V4L2_PIX_FMT_SGBRG10ALAW8",
-        sys::V4L2_PIX_FMT_SGRBG10ALAW8 => "V4L2_PIX_FMT_SGRBG10ALAW8",
-        sys::V4L2_PIX_FMT_SRGGB10ALAW8 => "V4L2_PIX_FMT_SRGGB10ALAW8",
-        /* 10bit raw bayer DPCM compressed to 8 bits */
-        sys::V4L2_PIX_FMT_SBGGR10DPCM8 => "V4L2_PIX_FMT_SBGGR10DPCM8",
-        sys::V4L2_PIX_FMT_SGBRG10DPCM8 => "V4L2_PIX_FMT_SGBRG10DPCM8",
-        sys::V4L2_PIX_FMT_SGRBG10DPCM8 => "V4L2_PIX_FMT_SGRBG10DPCM8",
-        sys::V4L2_PIX_FMT_SRGGB10DPCM8 => "V4L2_PIX_FMT_SRGGB10DPCM8",
-        sys::V4L2_PIX_FMT_SBGGR12 => "V4L2_PIX_FMT_SBGGR12",
-        sys::V4L2_PIX_FMT_SGBRG12 => "V4L2_PIX_FMT_SGBRG12",
-        sys::V4L2_PIX_FMT_SGRBG12 => "V4L2_PIX_FMT_SGRBG12",
-        sys::V4L2_PIX_FMT_SRGGB12 => "V4L2_PIX_FMT_SRGGB12",
-        /* 12bit raw bayer packed, 6 bytes for every 4 pixels */
-        sys::V4L2_PIX_FMT_SBGGR12P => "V4L2_PIX_FMT_SBGGR12P",
-        sys::V4L2_PIX_FMT_SGBRG12P => "V4L2_PIX_FMT_SGBRG12P",
-        sys::V4L2_PIX_FMT_SGRBG12P => "V4L2_PIX_FMT_SGRBG12P",
-        sys::V4L2_PIX_FMT_SRGGB12P => "V4L2_PIX_FMT_SRGGB12P",
-        sys::V4L2_PIX_FMT_SBGGR14 => "V4L2_PIX_FMT_SBGGR14",
-        sys::V4L2_PIX_FMT_SGBRG14 => "V4L2_PIX_FMT_SGBRG14",
-        sys::V4L2_PIX_FMT_SGRBG14 => "V4L2_PIX_FMT_SGRBG14",
-        sys::V4L2_PIX_FMT_SRGGB14 => "V4L2_PIX_FMT_SRGGB14",
-        /* 14bit raw bayer packed, 7 bytes for every 4 pixels */
-        sys::V4L2_PIX_FMT_SBGGR14P => "V4L2_PIX_FMT_SBGGR14P",
-        sys::V4L2_PIX_FMT_SGBRG14P => "V4L2_PIX_FMT_SGBRG14P",
-        sys::V4L2_PIX_FMT_SGRBG14P => "V4L2_PIX_FMT_SGRBG14P",
-        sys::V4L2_PIX_FMT_SRGGB14P => "V4L2_PIX_FMT_SRGGB14P",
-        sys::V4L2_PIX_FMT_SBGGR16 => "V4L2_PIX_FMT_SBGGR16",
-        sys::V4L2_PIX_FMT_SGBRG16 => "V4L2_PIX_FMT_SGBRG16",
-        sys::V4L2_PIX_FMT_SGRBG16 => "V4L2_PIX_FMT_SGRBG16",
-        sys::V4L2_PIX_FMT_SRGGB16 => "V4L2_PIX_FMT_SRGGB16",
-
-        /* HSV formats */
-        sys::V4L2_PIX_FMT_HSV24 => "V4L2_PIX_FMT_HSV24",
-        sys::V4L2_PIX_FMT_HSV32 => "V4L2_PIX_FMT_HSV32",
-
-        /* compressed formats */
-        sys::V4L2_PIX_FMT_MJPEG => "V4L2_PIX_FMT_MJPEG",
-        sys::V4L2_PIX_FMT_JPEG => "V4L2_PIX_FMT_JPEG",
-        sys::V4L2_PIX_FMT_DV => "V4L2_PIX_FMT_DV",
-        sys::V4L2_PIX_FMT_MPEG => "V4L2_PIX_FMT_MPEG",
-        sys::V4L2_PIX_FMT_H264 => "V4L2_PIX_FMT_H264",
-        sys::V4L2_PIX_FMT_H264_NO_SC => "V4L2_PIX_FMT_H264_NO_SC",
-        sys::V4L2_PIX_FMT_H264_MVC => "V4L2_PIX_FMT_H264_MVC",
-        sys::V4L2_PIX_FMT_H263 => "V4L2_PIX_FMT_H263",
-        sys::V4L2_PIX_FMT_MPEG1 => "V4L2_PIX_FMT_MPEG1",
-        sys::V4L2_PIX_FMT_MPEG2 => "V4L2_PIX_FMT_MPEG2",
-        sys::V4L2_PIX_FMT_MPEG2_SLICE => "V4L2_PIX_FMT_MPEG2_SLICE",
-        sys::V4L2_PIX_FMT_MPEG4 => "V4L2_PIX_FMT_MPEG4",
-        sys::V4L2_PIX_FMT_XVID => "V4L2_PIX_FMT_XVID",
-        sys::V4L2_PIX_FMT_VC1_ANNEX_G => "V4L2_PIX_FMT_VC1_ANNEX_G",
-        sys::V4L2_PIX_FMT_VC1_ANNEX_L => "V4L2_PIX_FMT_VC1_ANNEX_L",
-        sys::V4L2_PIX_FMT_VP8 => "V4L2_PIX_FMT_VP8",
-        sys::V4L2_PIX_FMT_VP8_FRAME => "V4L2_PIX_FMT_VP8_FRAME",
-        sys::V4L2_PIX_FMT_VP9 => "V4L2_PIX_FMT_VP9",
-        sys::V4L2_PIX_FMT_VP9_FRAME => "V4L2_PIX_FMT_VP9_FRAME",
-        sys::V4L2_PIX_FMT_HEVC => "V4L2_PIX_FMT_HEVC",
-        sys::V4L2_PIX_FMT_FWHT => "V4L2_PIX_FMT_FWHT",
-
-        sys::V4L2_PIX_FMT_FWHT_STATELESS => "V4L2_PIX_FMT_FWHT_STATELESS",
-
-        sys::V4L2_PIX_FMT_H264_SLICE => "V4L2_PIX_FMT_H264_SLICE",
-        sys::V4L2_PIX_FMT_HEVC_SLICE => "V4L2_PIX_FMT_HEVC_SLICE",
-        sys::V4L2_PIX_FMT_AV1_FRAME => "V4L2_PIX_FMT_AV1_FRAME",
-        sys::V4L2_PIX_FMT_SPK => "V4L2_PIX_FMT_SPK",
-        sys::V4L2_PIX_FMT_RV30 => "V4L2_PIX_FMT_RV30",
-        sys::V4L2_PIX_FMT_RV40 => "V4L2_PIX_FMT_RV40",
-
-        /*  Vendor-specific formats   */
-        sys::V4L2_PIX_FMT_CPIA1 => "V4L2_PIX_FMT_CPIA1",
-        sys::V4L2_PIX_FMT_WNVA => "V4L2_PIX_FMT_WNVA",
-        sys::V4L2_PIX_FMT_SN9C10X => "V4L2_PIX_FMT_SN9C10X",
-        sys::V4L2_PIX_FMT_SN9C20X_I420 => "V4L2_PIX_FMT_SN9C20X_I420",
-        sys::V4L2_PIX_FMT_PWC1 => "V4L2_PIX_FMT_PWC1",
-        sys::V4L2_PIX_FMT_PWC2 => "V4L2_PIX_FMT_PWC2",
-        sys::V4L2_PIX_FMT_ET61X251 => "V4L2_PIX_FMT_ET61X251",
-        sys::V4L2_PIX_FMT_SPCA501 => "V4L2_PIX_FMT_SPCA501",
-        sys::V4L2_PIX_FMT_SPCA505 => "V4L2_PIX_FMT_SPCA505",
-        sys::V4L2_PIX_FMT_SPCA508 => "V4L2_PIX_FMT_SPCA508",
-        sys::V4L2_PIX_FMT_SPCA561 => "V4L2_PIX_FMT_SPCA561",
-        sys::V4L2_PIX_FMT_PAC207 => "V4L2_PIX_FMT_PAC207",
-        sys::V4L2_PIX_FMT_MR97310A => "V4L2_PIX_FMT_MR97310A",
-        sys::V4L2_PIX_FMT_JL2005BCD => "V4L2_PIX_FMT_JL2005BCD",
-        sys::V4L2_PIX_FMT_SN9C2028 => "V4L2_PIX_FMT_SN9C2028",
-        sys::V4L2_PIX_FMT_SQ905C => "V4L2_PIX_FMT_SQ905C",
-        sys::V4L2_PIX_FMT_PJPG => "V4L2_PIX_FMT_PJPG",
-        sys::V4L2_PIX_FMT_OV511 => "V4L2_PIX_FMT_OV511",
-        sys::V4L2_PIX_FMT_OV518 => "V4L2_PIX_FMT_OV518",
-        sys::V4L2_PIX_FMT_STV0680 => "V4L2_PIX_FMT_STV0680",
-        sys::V4L2_PIX_FMT_TM6000 => "V4L2_PIX_FMT_TM6000",
-        sys::V4L2_PIX_FMT_CIT_YYVYUY => "V4L2_PIX_FMT_CIT_YYVYUY",
-        sys::V4L2_PIX_FMT_KONICA420 => "V4L2_PIX_FMT_KONICA420",
-        sys::V4L2_PIX_FMT_JPGL => "V4L2_PIX_FMT_JPGL",
-        sys::V4L2_PIX_FMT_SE401 => "V4L2_PIX_FMT_SE401",
-        sys::V4L2_PIX_FMT_S5C_UYVY_JPG => "V4L2_PIX_FMT_S5C_UYVY_JPG",
-        sys::V4L2_PIX_FMT_Y8I => "V4L2_PIX_FMT_Y8I",
-        sys::V4L2_PIX_FMT_Y12I => "V4L2_PIX_FMT_Y12I",
-        sys::V4L2_PIX_FMT_Z16 => "V4L2_PIX_FMT_Z16",
-        sys::V4L2_PIX_FMT_MT21C => "V4L2_PIX_FMT_MT21C",
-        sys::V4L2_PIX_FMT_MM21 => "V4L2_PIX_FMT_MM21",
-        sys::V4L2_PIX_FMT_MT2110T => "V4L2_PIX_FMT_MT2110T",
-        sys::V4L2_PIX_FMT_MT2110R => "V4L2_PIX_FMT_MT2110R",
-        sys::V4L2_PIX_FMT_INZI => "V4L2_PIX_FMT_INZI",
-        sys::V4L2_PIX_FMT_CNF4 => "V4L2_PIX_FMT_CNF4",
-        sys::V4L2_PIX_FMT_HI240 => "V4L2_PIX_FMT_HI240",
-        sys::V4L2_PIX_FMT_QC08C => "V4L2_PIX_FMT_QC08C",
-        sys::V4L2_PIX_FMT_QC10C => "V4L2_PIX_FMT_QC10C",
-        sys::V4L2_PIX_FMT_AJPG => "V4L2_PIX_FMT_AJPG",
-        sys::V4L2_PIX_FMT_HEXTILE => "V4L2_PIX_FMT_HEXTILE",
-
-        /* 10bit raw packed, 32 bytes for every 25 pixels, last LSB 6 bits unused */
-        sys::V4L2_PIX_FMT_IPU3_SBGGR10 => "V4L2_PIX_FMT_IPU3_SBGGR10",
-        sys::V4L2_PIX_FMT_IPU3_SGBRG10 => "V4L2_PIX_FMT_IPU3_SGBRG10",
-        sys::V4L2_PIX_FMT_IPU3_SGRBG10 => "V4L2_PIX_FMT_IPU3_SGRBG10",
-        sys::V4L2_PIX_FMT_IPU3_SRGGB10 => "V4L2_PIX_FMT_IPU3_SRGGB10",
-
-        _ => "unknown",
-    }
-}
@@ -0,0 +1,242 @@
+use std::{
+    io,
+    os::fd::{AsRawFd, RawFd},
+};
+
+use super::{sys, V4l2VideoDevice};
+
+/// Mirrors the image left to right.
+pub const CID_HFLIP: u32 = sys::V4L2_CID_HFLIP;
+/// Turns the image upside down.
+pub const CID_VFLIP: u32 = sys::V4L2_CID_VFLIP;
+/// Optical or the camera's own digital zoom, higher is closer.
+pub const CID_ZOOM_ABSOLUTE: u32 = sys::V4L2_CID_ZOOM_ABSOLUTE;
+/// In arc seconds, positive to the right.
+pub const CID_PAN_ABSOLUTE: u32 = sys::V4L2_CID_PAN_ABSOLUTE;
+/// In arc seconds, positive upwards.
+pub const CID_TILT_ABSOLUTE: u32 = sys::V4L2_CID_TILT_ABSOLUTE;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlKind {
+    Integer,
+    Boolean,
+    Menu,
+}
+
+/// A device control as reported by `VIDIOC_QUERYCTRL`.
+#[derive(Debug, Clone)]
+pub struct ControlInfo {
+    pub id: u32,
+    pub name: String,
+    pub kind: ControlKind,
+    pub minimum: i32,
+    pub maximum: i32,
+    pub step: i32,
+    pub default_value: i32,
+    /// Values and names of the choices of a `Menu`, which can have gaps
+    pub menu: Vec<(i32, String)>,
+    /// Can be read but not set
+    pub read_only: bool,
+    /// Won't do anything as other controls are set, like an exposure time
+    /// while exposure is automatic
+    pub inactive: bool,
+}
+
+impl V4l2VideoDevice {
+    /// The integer, boolean and menu controls the device exposes, with the
+    /// choices of the menus.
+    pub fn controls(&self) -> io::Result<Vec<ControlInfo>> {
+        let fd = self.handle.as_raw_fd();
+        let mut res = Vec::new();
+        let mut id = sys::V4L2_CTRL_FLAG_NEXT_CTRL;
+
+        loop {
+            let queryctrl = match query_control(fd, id) {
+                Ok(queryctrl) => queryctrl,
+                Err(e) if e.kind() == io::ErrorKind::InvalidInput => break,
+                Err(e) => return Err(e),
+            };
+            id = queryctrl.id | sys::V4L2_CTRL_FLAG_NEXT_CTRL;
+            if let Some(mut info) = ControlInfo::from_query(&queryctrl) {
+                info.menu = query_menu(fd, &info)?;
+                res.push(info);
+            }
+        }
+
+        Ok(res)
+    }
+
+    /// Just the control `id`. Fails with `InvalidInput` if the device doesn't
+    /// have it, or it's disabled or of a kind `controls` leaves out.
+    pub fn control_info(&self, id: u32) -> io::Result<ControlInfo> {
+        let fd = self.handle.as_raw_fd();
+        let queryctrl = query_control(fd, id)?;
+        let mut info = ControlInfo::from_query(&queryctrl).ok_or(io::ErrorKind::InvalidInput)?;
+        info.menu = query_menu(fd, &info)?;
+        Ok(info)
+    }
+
+    pub fn control(&self, id: u32) -> io::Result<i32> {
+        let fd = self.handle.as_raw_fd();
+        let mut control = sys::v4l2_control { id, value: 0 };
+
+        unsafe {
+            ioctl!(fd, sys::VIDIOC_G_CTRL, &mut control)?;
+        }
+
+        Ok(control.value)
+    }
+
+    pub fn set_control(&self, id: u32, value: i32) -> io::Result<()> {
+        let fd = self.handle.as_raw_fd();
+        let mut control = sys::v4l2_control { id, value };
+
+        unsafe {
+            ioctl!(fd, sys::VIDIOC_S_CTRL, &mut control)?;
+        }
+
+        Ok(())
+    }
+
+    /// Have the device flip the image, as far as it can. Returns the flips it
+    /// couldn't do, horizontal and vertical, which are left to software.
+    pub fn set_flip(&self, horizontal: bool, vertical: bool) -> (bool, bool) {
+        // Devices without the control refuse it
+        let flip = |id, on: bool| self.set_control(id, on as i32).is_err() && on;
+        (flip(CID_HFLIP, horizontal), flip(CID_VFLIP, vertical))
+    }
+
+    /// The zoom, pan and tilt controls, for `set_ptz`.
+    pub fn ptz_controls(&self) -> PtzControls {
+        let ids = [CID_ZOOM_ABSOLUTE, CID_PAN_ABSOLUTE, CID_TILT_ABSOLUTE];
+        PtzControls {
+            controls: ids.map(|id| self.control_info(id).ok().map(|info| (info, None))),
+        }
+    }
+
+    /// Have the device zoom, pan and tilt, as far as it can, with `controls`
+    /// from `ptz_controls`. `zoom` goes from 0 for its widest to 1 for its
+    /// closest, `pan` from -1 for furthest left to 1 for furthest right and
+    /// `tilt` from -1 for furthest up to 1 for furthest down. Returns the
+    /// ones it couldn't do, zoom, pan and tilt, which are left to software.
+    ///
+    /// Each goes evenly across its control's range. For zoom that's in the
+    /// device's own units, often the lens's focal length, so how much closer
+    /// a given `zoom` gets differs from one camera to the next. Only the
+    /// controls whose value changed get set.
+    pub fn set_ptz(
+        &self,
+        controls: &mut PtzControls,
+        zoom: f32,
+        pan: f32,
+        tilt: f32,
+    ) -> (bool, bool, bool) {
+        let across = |value: f32| (value.clamp(-1.0, 1.0) + 1.0) / 2.0;
+        // From 0 to 1 across each control's range
+        let positions = [zoom, across(pan), across(-tilt)];
+        let mut left = [true; 3];
+        for ((control, position), left) in
+            controls.controls.iter_mut().zip(positions).zip(&mut left)
+        {
+            let Some((info, last)) = control else {
+                continue;
+            };
+            let range = (info.maximum as f32 - info.minimum as f32) * position.clamp(0.0, 1.0);
+            let step = info.step.max(1) as f32;
+            let value = info.minimum + ((range / step).round() * step) as i32;
+            // Some only take pan and tilt while zoomed in, so what didn't
+            // work gets tried again
+            if *last != Some(value) {
+                *last = self.set_control(info.id, value).is_ok().then_some(value);
+            }
+            *left = last.is_none();
+        }
+        let [zoom, pan, tilt] = left;
+        (zoom, pan, tilt)
+    }
+}
+
+/// A device's zoom, pan and tilt controls, looked up once so that steering
+/// it frame after frame doesn't query them every time.
+#[derive(Debug, Clone)]
+pub struct PtzControls {
+    // Zoom, pan and tilt, none for those the device doesn't have, with the
+    // value each was last set to, if that worked
+    controls: [Option<(ControlInfo, Option<i32>)>; 3],
+}
+
+impl PtzControls {
+    /// Whether the device has zoom, pan and tilt controls.
+    pub fn available(&self) -> (bool, bool, bool) {
+        let [zoom, pan, tilt] = self.controls.each_ref().map(Option::is_some);
+        (zoom, pan, tilt)
+    }
+}
+
+fn query_control(fd: RawFd, id: u32) -> io::Result<sys::v4l2_queryctrl> {
+    unsafe {
+        let mut queryctrl: sys::v4l2_queryctrl = std::mem::zeroed();
+        queryctrl.id = id;
+        ioctl!(fd, sys::VIDIOC_QUERYCTRL, &mut queryctrl)?;
+        Ok(queryctrl)
+    }
+}
+
+fn query_menu(fd: RawFd, info: &ControlInfo) -> io::Result<Vec<(i32, String)>> {
+    if info.kind != ControlKind::Menu {
+        return Ok(Vec::new());
+    }
+    let mut menu = Vec::new();
+    for index in info.minimum..=info.maximum {
+        let querymenu = unsafe {
+            let mut querymenu: sys::v4l2_querymenu = std::mem::zeroed();
+            querymenu.id = info.id;
+            querymenu.index = index as u32;
+            match ioctl!(fd, sys::VIDIOC_QUERYMENU, &mut querymenu) {
+                Ok(_) => querymenu,
+                // Not every index in the range is a choice
+                Err(e) if e.kind() == io::ErrorKind::InvalidInput => continue,
+                Err(e) => return Err(e),
+            }
+        };
+        let name = unsafe { querymenu.__bindgen_anon_1.name };
+        let len = name.iter().position(|c| *c == 0).unwrap_or(name.len());
+        menu.push((index, String::from_utf8_lossy(&name[..len]).into_owned()));
+    }
+    Ok(menu)
+}
+
+impl ControlInfo {
+    fn from_query(queryctrl: &sys::v4l2_queryctrl) -> Option<Self> {
+        if queryctrl.flags & sys::V4L2_CTRL_FLAG_DISABLED != 0 {
+            return None;
+        }
+
+        let kind = match queryctrl.type_ {
+            sys::v4l2_ctrl_type_V4L2_CTRL_TYPE_INTEGER => ControlKind::Integer,
+            sys::v4l2_ctrl_type_V4L2_CTRL_TYPE_BOOLEAN => ControlKind::Boolean,
+            sys::v4l2_ctrl_type_V4L2_CTRL_TYPE_MENU => ControlKind::Menu,
+            // Buttons, strings, class headings and friends
+            _ => return None,
+        };
+
+        let name_len = queryctrl
+            .name
+            .iter()
+            .position(|c| *c == 0)
+            .unwrap_or(queryctrl.name.len());
+
+        Some(ControlInfo {
+            id: queryctrl.id,
+            name: String::from_utf8_lossy(&queryctrl.name[..name_len]).into_owned(),
+            kind,
+            minimum: queryctrl.minimum,
+            maximum: queryctrl.maximum,
+            step: queryctrl.step,
+            default_value: queryctrl.default_value,
+            menu: Vec::new(),
+            read_only: queryctrl.flags & sys::V4L2_CTRL_FLAG_READ_ONLY != 0,
+            inactive: queryctrl.flags & sys::V4L2_CTRL_FLAG_INACTIVE != 0,
+        })
+    }
+}
@@ -0,0 +1,547 @@
+use super::sys;
+use crate::convert::{ColorSpec, Matrix, Range, Transfer};
+
+/// A V4L2 fourcc pixel format code.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PixelFormat(pub u32);
+
+impl PixelFormat {
+    pub const RGB332: Self = Self(sys::V4L2_PIX_FMT_RGB332);
+
+    pub const RGB444: Self = Self(sys::V4L2_PIX_FMT_RGB444);
+    pub const ARGB444: Self = Self(sys::V4L2_PIX_FMT_ARGB444);
+    pub const XRGB444: Self = Self(sys::V4L2_PIX_FMT_XRGB444);
+    pub const RGBA444: Self = Self(sys::V4L2_PIX_FMT_RGBA444);
+    pub const RGBX444: Self = Self(sys::V4L2_PIX_FMT_RGBX444);
+    pub const ABGR444: Self = Self(sys::V4L2_PIX_FMT_ABGR444);
+    pub const XBGR444: Self = Self(sys::V4L2_PIX_FMT_XBGR444);
+    pub const BGRA444: Self = Self(sys::V4L2_PIX_FMT_BGRA444);
+    pub const BGRX444: Self = Self(sys::V4L2_PIX_FMT_BGRX444);
+
+    pub const RGB555: Self = Self(sys::V4L2_PIX_FMT_RGB555);
+    pub const ARGB555: Self = Self(sys::V4L2_PIX_FMT_ARGB555);
+    pub const XRGB555: Self = Self(sys::V4L2_PIX_FMT_XRGB555);
+    pub const RGBA555: Self = Self(sys::V4L2_PIX_FMT_RGBA555);
+    pub const RGBX555: Self = Self(sys::V4L2_PIX_FMT_RGBX555);
+    pub const ABGR555: Self = Self(sys::V4L2_PIX_FMT_ABGR555);
+    pub const XBGR555: Self = Self(sys::V4L2_PIX_FMT_XBGR555);
+    pub const BGRA555: Self = Self(sys::V4L2_PIX_FMT_BGRA555);
+    pub const BGRX555: Self = Self(sys::V4L2_PIX_FMT_BGRX555);
+
+    pub const RGB565: Self = Self(sys::V4L2_PIX_FMT_RGB565);
+    pub const RGB555X: Self = Self(sys::V4L2_PIX_FMT_RGB555X);
+    pub const ARGB555X: Self = Self(sys::V4L2_PIX_FMT_ARGB555X);
+    pub const XRGB555X: Self = Self(sys::V4L2_PIX_FMT_XRGB555X);
+    pub const RGB565X: Self = Self(sys::V4L2_PIX_FMT_RGB565X);
+
+    pub const BGR666: Self = Self(sys::V4L2_PIX_FMT_BGR666);
+    pub const BGR24: Self = Self(sys::V4L2_PIX_FMT_BGR24);
+    pub const RGB24: Self = Self(sys::V4L2_PIX_FMT_RGB24);
+    pub const BGR32: Self = Self(sys::V4L2_PIX_FMT_BGR32);
+    pub const ABGR32: Self = Self(sys::V4L2_PIX_FMT_ABGR32);
+    pub const XBGR32: Self = Self(sys::V4L2_PIX_FMT_XBGR32);
+    pub const BGRA32: Self = Self(sys::V4L2_PIX_FMT_BGRA32);
+    pub const BGRX32: Self = Self(sys::V4L2_PIX_FMT_BGRX32);
+    pub const RGB32: Self = Self(sys::V4L2_PIX_FMT_RGB32);
+    pub const RGBA32: Self = Self(sys::V4L2_PIX_FMT_RGBA32);
+    pub const RGBX32: Self = Self(sys::V4L2_PIX_FMT_RGBX32);
+    pub const ARGB32: Self = Self(sys::V4L2_PIX_FMT_ARGB32);
+    pub const XRGB32: Self = Self(sys::V4L2_PIX_FMT_XRGB32);
+
+    pub const RGBX1010102: Self = Self(sys::V4L2_PIX_FMT_RGBX1010102);
+    pub const RGBA1010102: Self = Self(sys::V4L2_PIX_FMT_RGBA1010102);
+    pub const ARGB2101010: Self = Self(sys::V4L2_PIX_FMT_ARGB2101010);
+
+    pub const BGR48_12: Self = Self(sys::V4L2_PIX_FMT_BGR48_12);
+    pub const ABGR64_12: Self = Self(sys::V4L2_PIX_FMT_ABGR64_12);
+
+    pub const YUYV: Self = Self(sys::V4L2_PIX_FMT_YUYV);
+    pub const UYVY: Self = Self(sys::V4L2_PIX_FMT_UYVY);
+    pub const YVYU: Self = Self(sys::V4L2_PIX_FMT_YVYU);
+    pub const VYUY: Self = Self(sys::V4L2_PIX_FMT_VYUY);
+    pub const YYUV: Self = Self(sys::V4L2_PIX_FMT_YYUV);
+
+    pub const NV12: Self = Self(sys::V4L2_PIX_FMT_NV12);
+    pub const NV21: Self = Self(sys::V4L2_PIX_FMT_NV21);
+    pub const NV16: Self = Self(sys::V4L2_PIX_FMT_NV16);
+    pub const YUV420: Self = Self(sys::V4L2_PIX_FMT_YUV420);
+    pub const YVU420: Self = Self(sys::V4L2_PIX_FMT_YVU420);
+    pub const YUV422P: Self = Self(sys::V4L2_PIX_FMT_YUV422P);
+    pub const NV12M: Self = Self(sys::V4L2_PIX_FMT_NV12M);
+    pub const NV21M: Self = Self(sys::V4L2_PIX_FMT_NV21M);
+    pub const NV16M: Self = Self(sys::V4L2_PIX_FMT_NV16M);
+    pub const YUV420M: Self = Self(sys::V4L2_PIX_FMT_YUV420M);
+    pub const YVU420M: Self = Self(sys::V4L2_PIX_FMT_YVU420M);
+    pub const YUV422M: Self = Self(sys::V4L2_PIX_FMT_YUV422M);
+
+    pub const P010: Self = Self(sys::V4L2_PIX_FMT_P010);
+    pub const P012: Self = Self(sys::V4L2_PIX_FMT_P012);
+    pub const P012M: Self = Self(sys::V4L2_PIX_FMT_P012M);
+    pub const Y210: Self = Self(sys::V4L2_PIX_FMT_Y210);
+    pub const Y212: Self = Self(sys::V4L2_PIX_FMT_Y212);
+    pub const Y216: Self = Self(sys::V4L2_PIX_FMT_Y216);
+    pub const YUV48_12: Self = Self(sys::V4L2_PIX_FMT_YUV48_12);
+
+    pub const MJPEG: Self = Self(sys::V4L2_PIX_FMT_MJPEG);
+    pub const JPEG: Self = Self(sys::V4L2_PIX_FMT_JPEG);
+
+    pub const SBGGR8: Self = Self(sys::V4L2_PIX_FMT_SBGGR8);
+    pub const SGBRG8: Self = Self(sys::V4L2_PIX_FMT_SGBRG8);
+    pub const SGRBG8: Self = Self(sys::V4L2_PIX_FMT_SGRBG8);
+    pub const SRGGB8: Self = Self(sys::V4L2_PIX_FMT_SRGGB8);
+    pub const SBGGR10: Self = Self(sys::V4L2_PIX_FMT_SBGGR10);
+    pub const SGBRG10: Self = Self(sys::V4L2_PIX_FMT_SGBRG10);
+    pub const SGRBG10: Self = Self(sys::V4L2_PIX_FMT_SGRBG10);
+    pub const SRGGB10: Self = Self(sys::V4L2_PIX_FMT_SRGGB10);
+    pub const SBGGR12: Self = Self(sys::V4L2_PIX_FMT_SBGGR12);
+    pub const SGBRG12: Self = Self(sys::V4L2_PIX_FMT_SGBRG12);
+    pub const SGRBG12: Self = Self(sys::V4L2_PIX_FMT_SGRBG12);
+    pub const SRGGB12: Self = Self(sys::V4L2_PIX_FMT_SRGGB12);
+    pub const SBGGR14: Self = Self(sys::V4L2_PIX_FMT_SBGGR14);
+    pub const SGBRG14: Self = Self(sys::V4L2_PIX_FMT_SGBRG14);
+    pub const SGRBG14: Self = Self(sys::V4L2_PIX_FMT_SGRBG14);
+    pub const SRGGB14: Self = Self(sys::V4L2_PIX_FMT_SRGGB14);
+    pub const SBGGR16: Self = Self(sys::V4L2_PIX_FMT_SBGGR16);
+    pub const SGBRG16: Self = Self(sys::V4L2_PIX_FMT_SGBRG16);
+    pub const SGRBG16: Self = Self(sys::V4L2_PIX_FMT_SGRBG16);
+    pub const SRGGB16: Self = Self(sys::V4L2_PIX_FMT_SRGGB16);
+
+    pub const SBGGR10P: Self = Self(sys::V4L2_PIX_FMT_SBGGR10P);
+    pub const SGBRG10P: Self = Self(sys::V4L2_PIX_FMT_SGBRG10P);
+    pub const SGRBG10P: Self = Self(sys::V4L2_PIX_FMT_SGRBG10P);
+    pub const SRGGB10P: Self = Self(sys::V4L2_PIX_FMT_SRGGB10P);
+    pub const SBGGR12P: Self = Self(sys::V4L2_PIX_FMT_SBGGR12P);
+    pub const SGBRG12P: Self = Self(sys::V4L2_PIX_FMT_SGBRG12P);
+    pub const SGRBG12P: Self = Self(sys::V4L2_PIX_FMT_SGRBG12P);
+    pub const SRGGB12P: Self = Self(sys::V4L2_PIX_FMT_SRGGB12P);
+    pub const SBGGR14P: Self = Self(sys::V4L2_PIX_FMT_SBGGR14P);
+    pub const SGBRG14P: Self = Self(sys::V4L2_PIX_FMT_SGBRG14P);
+    pub const SGRBG14P: Self = Self(sys::V4L2_PIX_FMT_SGRBG14P);
+    pub const SRGGB14P: Self = Self(sys::V4L2_PIX_FMT_SRGGB14P);
+
+    pub const GREY: Self = Self(sys::V4L2_PIX_FMT_GREY);
+    pub const Y10: Self = Self(sys::V4L2_PIX_FMT_Y10);
+    pub const Y12: Self = Self(sys::V4L2_PIX_FMT_Y12);
+    pub const Y14: Self = Self(sys::V4L2_PIX_FMT_Y14);
+    pub const Y16: Self = Self(sys::V4L2_PIX_FMT_Y16);
+    pub const Y16_BE: Self = Self(sys::V4L2_PIX_FMT_Y16_BE);
+    pub const Y10P: Self = Self(sys::V4L2_PIX_FMT_Y10P);
+    pub const Y10BPACK: Self = Self(sys::V4L2_PIX_FMT_Y10BPACK);
+    pub const Z16: Self = Self(sys::V4L2_PIX_FMT_Z16);
+
+    pub fn name(self) -> &'static str {
+        pixel_format_to_string(self.0)
+    }
+}
+
+impl std::fmt::Display for PixelFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Most planes a frame can come in, see `FrameFormat::num_planes`.
+pub const MAX_PLANES: usize = 3;
+
+/// Layout of one separately allocated plane.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PlaneFormat {
+    pub bytes_per_line: usize,
+    /// Bytes the driver needs for the plane
+    pub size: usize,
+}
+
+/// The negotiated layout of the frames a device delivers.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FrameFormat {
+    pub pixel_format: PixelFormat,
+    pub width: usize,
+    pub height: usize,
+    /// Of the first plane
+    pub bytes_per_line: usize,
+    /// Y'CbCr matrix and range, with the V4L2 defaults filled in
+    pub color: ColorSpec,
+    /// Separately allocated planes. Only the multi-planar (`*M`) formats have
+    /// more than one, NV12 and friends keep all of theirs in the first.
+    pub num_planes: usize,
+    pub planes: [PlaneFormat; MAX_PLANES],
+}
+
+impl FrameFormat {
+    /// From a format of either the single or the multi-planar API.
+    pub(crate) fn from_format(format: &sys::v4l2_format) -> Self {
+        if format.type_ == sys::v4l2_buf_type_V4L2_BUF_TYPE_VIDEO_CAPTURE_MPLANE {
+            Self::from_pix_mp(unsafe { &format.fmt.pix_mp })
+        } else {
+            Self::from_pix(unsafe { &format.fmt.pix })
+        }
+    }
+
+    fn from_pix(pix: &sys::v4l2_pix_format) -> Self {
+        let ycbcr_enc = unsafe { pix.__bindgen_anon_1.ycbcr_enc };
+        let mut planes = [PlaneFormat::default(); MAX_PLANES];
+        planes[0] = PlaneFormat {
+            bytes_per_line: pix.bytesperline as usize,
+            size: pix.sizeimage as usize,
+        };
+
+        Self {
+            pixel_format: PixelFormat(pix.pixelformat),
+            width: pix.width as usize,
+            height: pix.height as usize,
+            bytes_per_line: pix.bytesperline as usize,
+            color: color_spec(pix.colorspace, ycbcr_enc, pix.quantization, pix.xfer_func),
+            num_planes: 1,
+            planes,
+        }
+    }
+
+    fn from_pix_mp(pix_mp: &sys::v4l2_pix_format_mplane) -> Self {
+        let ycbcr_enc = unsafe { pix_mp.__bindgen_anon_1.ycbcr_enc };
+        let num_planes = (pix_mp.num_planes as usize).clamp(1, MAX_PLANES);
+        let mut planes = [PlaneFormat::default(); MAX_PLANES];
+        for (i, plane) in planes.iter_mut().enumerate().take(num_planes) {
+            let plane_fmt = pix_mp.plane_fmt[i];
+            *plane = PlaneFormat {
+                bytes_per_line: plane_fmt.bytesperline as usize,
+                size: plane_fmt.sizeimage as usize,
+            };
+        }
+
+        Self {
+            pixel_format: PixelFormat(pix_mp.pixelformat),
+            width: pix_mp.width as usize,
+            height: pix_mp.height as usize,
+            bytes_per_line: planes[0].bytes_per_line,
+            color: color_spec(
+                pix_mp.colorspace,
+                ycbcr_enc as u32,
+                pix_mp.quantization as u32,
+                pix_mp.xfer_func as u32,
+            ),
+            num_planes,
+            planes,
+        }
+    }
+}
+
+// Same rules as the V4L2_MAP_YCBCR_ENC_DEFAULT and V4L2_MAP_QUANTIZATION_DEFAULT macros
+fn color_spec(colorspace: u32, ycbcr_enc: u32, quantization: u32, xfer_func: u32) -> ColorSpec {
+    let matrix = match ycbcr_enc {
+        sys::v4l2_ycbcr_encoding_V4L2_YCBCR_ENC_709
+        | sys::v4l2_ycbcr_encoding_V4L2_YCBCR_ENC_XV709 => Matrix::Bt709,
+        sys::v4l2_ycbcr_encoding_V4L2_YCBCR_ENC_BT2020
+        | sys::v4l2_ycbcr_encoding_V4L2_YCBCR_ENC_BT2020_CONST_LUM => Matrix::Bt2020,
+        sys::v4l2_ycbcr_encoding_V4L2_YCBCR_ENC_SMPTE240M => Matrix::Smpte240m,
+        sys::v4l2_ycbcr_encoding_V4L2_YCBCR_ENC_DEFAULT => match colorspace {
+            sys::v4l2_colorspace_V4L2_COLORSPACE_REC709
+            | sys::v4l2_colorspace_V4L2_COLORSPACE_DCI_P3 => Matrix::Bt709,
+            sys::v4l2_colorspace_V4L2_COLORSPACE_BT2020 => Matrix::Bt2020,
+            sys::v4l2_colorspace_V4L2_COLORSPACE_SMPTE240M => Matrix::Smpte240m,
+            _ => Matrix::Bt601,
+        },
+        _ => Matrix::Bt601,
+    };
+
+    let range = match quantization {
+        sys::v4l2_quantization_V4L2_QUANTIZATION_FULL_RANGE => Range::Full,
+        sys::v4l2_quantization_V4L2_QUANTIZATION_LIM_RANGE => Range::Limited,
+        _ if colorspace == sys::v4l2_colorspace_V4L2_COLORSPACE_JPEG => Range::Full,
+        _ => Range::Limited,
+    };
+
+    // V4L2 has no code for HLG, that one has to be chosen by hand
+    let transfer = match xfer_func {
+        sys::v4l2_xfer_func_V4L2_XFER_FUNC_SMPTE2084 => Transfer::Pq,
+        _ => Transfer::Sdr,
+    };
+
+    ColorSpec {
+        matrix,
+        range,
+        transfer,
+    }
+}
+
+pub fn pixel_format_to_string(format: u32) -> &'static str {
+    match format {
+        sys::V4L2_PIX_FMT_RGB332 => "V4L2_PIX_FMT_RGB332",
+        sys::V4L2_PIX_FMT_RGB444 => "V4L2_PIX_FMT_RGB444",
+        sys::V4L2_PIX_FMT_ARGB444 => "V4L2_PIX_FMT_ARGB444",
+        sys::V4L2_PIX_FMT_XRGB444 => "V4L2_PIX_FMT_XRGB444",
+        sys::V4L2_PIX_FMT_RGBA444 => "V4L2_PIX_FMT_RGBA444",
+        sys::V4L2_PIX_FMT_RGBX444 => "V4L2_PIX_FMT_RGBX444",
+        sys::V4L2_PIX_FMT_ABGR444 => "V4L2_PIX_FMT_ABGR444",
+        sys::V4L2_PIX_FMT_XBGR444 => "V4L2_PIX_FMT_XBGR444",
+        sys::V4L2_PIX_FMT_BGRA444 => "V4L2_PIX_FMT_BGRA444",
+        sys::V4L2_PIX_FMT_BGRX444 => "V4L2_PIX_FMT_BGRX444",
+        sys::V4L2_PIX_FMT_RGB555 => "V4L2_PIX_FMT_RGB555",
+        sys::V4L2_PIX_FMT_ARGB555 => "V4L2_PIX_FMT_ARGB555",
+        sys::V4L2_PIX_FMT_XRGB555 => "V4L2_PIX_FMT_XRGB555",
+        sys::V4L2_PIX_FMT_RGBA555 => "V4L2_PIX_FMT_RGBA555",
+        sys::V4L2_PIX_FMT_RGBX555 => "V4L2_PIX_FMT_RGBX555",
+        sys::V4L2_PIX_FMT_ABGR555 => "V4L2_PIX_FMT_ABGR555",
+        sys::V4L2_PIX_FMT_XBGR555 => "V4L2_PIX_FMT_XBGR555",
+        sys::V4L2_PIX_FMT_BGRA555 => "V4L2_PIX_FMT_BGRA555",
+        sys::V4L2_PIX_FMT_BGRX555 => "V4L2_PIX_FMT_BGRX555",
+        sys::V4L2_PIX_FMT_RGB565 => "V4L2_PIX_FMT_RGB565",
+        sys::V4L2_PIX_FMT_RGB555X => "V4L2_PIX_FMT_RGB555X",
+        sys::V4L2_PIX_FMT_ARGB555X => "V4L2_PIX_FMT_ARGB555X",
+        sys::V4L2_PIX_FMT_XRGB555X => "V4L2_PIX_FMT_XRGB555X",
+        sys::V4L2_PIX_FMT_RGB565X => "V4L2_PIX_FMT_RGB565X",
+
+        /* RGB formats (3 or 4 bytes per pixel) */
+        sys::V4L2_PIX_FMT_BGR666 => "V4L2_PIX_FMT_BGR666",
+        sys::V4L2_PIX_FMT_BGR24 => "V4L2_PIX_FMT_BGR24",
+        sys::V4L2_PIX_FMT_RGB24 => "V4L2_PIX_FMT_RGB24",
+        sys::V4L2_PIX_FMT_BGR32 => "V4L2_PIX_FMT_BGR32",
+        sys::V4L2_PIX_FMT_ABGR32 => "V4L2_PIX_FMT_ABGR32",
+        sys::V4L2_PIX_FMT_XBGR32 => "V4L2_PIX_FMT_XBGR32",
+        sys::V4L2_PIX_FMT_BGRA32 => "V4L2_PIX_FMT_BGRA32",
+        sys::V4L2_PIX_FMT_BGRX32 => "V4L2_PIX_FMT_BGRX32",
+        sys::V4L2_PIX_FMT_RGB32 => "V4L2_PIX_FMT_RGB32",
+        sys::V4L2_PIX_FMT_RGBA32 => "V4L2_PIX_FMT_RGBA32",
+        sys::V4L2_PIX_FMT_RGBX32 => "V4L2_PIX_FMT_RGBX32",
+        sys::V4L2_PIX_FMT_ARGB32 => "V4L2_PIX_FMT_ARGB32",
+        sys::V4L2_PIX_FMT_XRGB32 => "V4L2_PIX_FMT_XRGB32",
+        sys::V4L2_PIX_FMT_RGBX1010102 => "V4L2_PIX_FMT_RGBX1010102",
+        sys::V4L2_PIX_FMT_RGBA1010102 => "V4L2_PIX_FMT_RGBA1010102",
+        sys::V4L2_PIX_FMT_ARGB2101010 => "V4L2_PIX_FMT_ARGB2101010",
+
+        /* RGB formats (6 or 8 bytes per pixel) */
+        sys::V4L2_PIX_FMT_BGR48_12 => "V4L2_PIX_FMT_BGR48_12",
+        sys::V4L2_PIX_FMT_ABGR64_12 => "V4L2_PIX_FMT_ABGR64_12",
+
+        /* Grey formats */
+        sys::V4L2_PIX_FMT_GREY => "V4L2_PIX_FMT_GREY",
+        sys::V4L2_PIX_FMT_Y4 => "V4L2_PIX_FMT_Y4",
+        sys::V4L2_PIX_FMT_Y6 => "V4L2_PIX_FMT_Y6",
+        sys::V4L2_PIX_FMT_Y10 => "V4L2_PIX_FMT_Y10",
+        sys::V4L2_PIX_FMT_Y12 => "V4L2_PIX_FMT_Y12",
+        sys::V4L2_PIX_FMT_Y012 => "V4L2_PIX_FMT_Y012",
+        sys::V4L2_PIX_FMT_Y14 => "V4L2_PIX_FMT_Y14",
+        sys::V4L2_PIX_FMT_Y16 => "V4L2_PIX_FMT_Y16",
+        sys::V4L2_PIX_FMT_Y16_BE => "V4L2_PIX_FMT_Y16_BE",
+
+        /* Grey bit-packed formats */
+        sys::V4L2_PIX_FMT_Y10BPACK => "V4L2_PIX_FMT_Y10BPACK",
+        sys::V4L2_PIX_FMT_Y10P => "V4L2_PIX_FMT_Y10P",
+        sys::V4L2_PIX_FMT_IPU3_Y10 => "V4L2_PIX_FMT_IPU3_Y10",
+
+        /* Palette formats */
+        sys::V4L2_PIX_FMT_PAL8 => "V4L2_PIX_FMT_PAL8",
+
+        /* Chrominance formats */
+        sys::V4L2_PIX_FMT_UV8 => "V4L2_PIX_FMT_UV8",
+
+        /* Luminance+Chrominance formats */
+        sys::V4L2_PIX_FMT_YUYV => "V4L2_PIX_FMT_YUYV",
+        sys::V4L2_PIX_FMT_YYUV => "V4L2_PIX_FMT_YYUV",
+        sys::V4L2_PIX_FMT_YVYU => "V4L2_PIX_FMT_YVYU",
+        sys::V4L2_PIX_FMT_UYVY => "V4L2_PIX_FMT_UYVY",
+        sys::V4L2_PIX_FMT_VYUY => "V4L2_PIX_FMT_VYUY",
+        sys::V4L2_PIX_FMT_Y41P => "V4L2_PIX_FMT_Y41P",
+        sys::V4L2_PIX_FMT_YUV444 => "V4L2_PIX_FMT_YUV444",
+        sys::V4L2_PIX_FMT_YUV555 => "V4L2_PIX_FMT_YUV555",
+        sys::V4L2_PIX_FMT_YUV565 => "V4L2_PIX_FMT_YUV565",
+        sys::V4L2_PIX_FMT_YUV24 => "V4L2_PIX_FMT_YUV24",
+        sys::V4L2_PIX_FMT_YUV32 => "V4L2_PIX_FMT_YUV32",
+        sys::V4L2_PIX_FMT_AYUV32 => "V4L2_PIX_FMT_AYUV32",
+        sys::V4L2_PIX_FMT_XYUV32 => "V4L2_PIX_FMT_XYUV32",
+        sys::V4L2_PIX_FMT_VUYA32 => "V4L2_PIX_FMT_VUYA32",
+        sys::V4L2_PIX_FMT_VUYX32 => "V4L2_PIX_FMT_VUYX32",
+        sys::V4L2_PIX_FMT_YUVA32 => "V4L2_PIX_FMT_YUVA32",
+        sys::V4L2_PIX_FMT_YUVX32 => "V4L2_PIX_FMT_YUVX32",
+        sys::V4L2_PIX_FMT_M420 => "V4L2_PIX_FMT_M420",
+        sys::V4L2_PIX_FMT_YUV48_12 => "V4L2_PIX_FMT_YUV48_12",
+
+        /*
+         * YCbCr packed format. For each Y2xx format, xx bits of valid data occupy the MSBs
+         * of the 16 bit components, and 16-xx bits of zero padding occupy the LSBs.
+         */
+        sys::V4L2_PIX_FMT_Y210 => "V4L2_PIX_FMT_Y210",
+        sys::V4L2_PIX_FMT_Y212 => "V4L2_PIX_FMT_Y212",
+        sys::V4L2_PIX_FMT_Y216 => "V4L2_PIX_FMT_Y216",
+
+        /* two planes -- one Y, one Cr + Cb interleaved  */
+        sys::V4L2_PIX_FMT_NV12 => "V4L2_PIX_FMT_NV12",
+        sys::V4L2_PIX_FMT_NV21 => "V4L2_PIX_FMT_NV21",
+        sys::V4L2_PIX_FMT_NV16 => "V4L2_PIX_FMT_NV16",
+        sys::V4L2_PIX_FMT_NV61 => "V4L2_PIX_FMT_NV61",
+        sys::V4L2_PIX_FMT_NV24 => "V4L2_PIX_FMT_NV24",
+        sys::V4L2_PIX_FMT_NV42 => "V4L2_PIX_FMT_NV42",
+        sys::V4L2_PIX_FMT_P010 => "V4L2_PIX_FMT_P010",
+        sys::V4L2_PIX_FMT_P012 => "V4L2_PIX_FMT_P012",
+
+        /* two non contiguous planes - one Y, one Cr + Cb interleaved  */
+        sys::V4L2_PIX_FMT_NV12M => "V4L2_PIX_FMT_NV12M",
+        sys::V4L2_PIX_FMT_NV21M => "V4L2_PIX_FMT_NV21M",
+        sys::V4L2_PIX_FMT_NV16M => "V4L2_PIX_FMT_NV16M",
+        sys::V4L2_PIX_FMT_NV61M => "V4L2_PIX_FMT_NV61M",
+        sys::V4L2_PIX_FMT_P012M => "V4L2_PIX_FMT_P012M",
+
+        /* three planes - Y Cb, Cr */
+        sys::V4L2_PIX_FMT_YUV410 => "V4L2_PIX_FMT_YUV410",
+        sys::V4L2_PIX_FMT_YVU410 => "V4L2_PIX_FMT_YVU410",
+        sys::V4L2_PIX_FMT_YUV411P => "V4L2_PIX_FMT_YUV411P",
+        sys::V4L2_PIX_FMT_YUV420 => "V4L2_PIX_FMT_YUV420",
+        sys::V4L2_PIX_FMT_YVU420 => "V4L2_PIX_FMT_YVU420",
+        sys::V4L2_PIX_FMT_YUV422P => "V4L2_PIX_FMT_YUV422P",
+
+        /* three non contiguous planes - Y, Cb, Cr */
+        sys::V4L2_PIX_FMT_YUV420M => "V4L2_PIX_FMT_YUV420M",
+        sys::V4L2_PIX_FMT_YVU420M => "V4L2_PIX_FMT_YVU420M",
+        sys::V4L2_PIX_FMT_YUV422M => "V4L2_PIX_FMT_YUV422M",
+        sys::V4L2_PIX_FMT_YVU422M => "V4L2_PIX_FMT_YVU422M",
+        sys::V4L2_PIX_FMT_YUV444M => "V4L2_PIX_FMT_YUV444M",
+        sys::V4L2_PIX_FMT_YVU444M => "V4L2_PIX_FMT_YVU444M",
+
+        /* Tiled YUV formats */
+        sys::V4L2_PIX_FMT_NV12_4L4 => "V4L2_PIX_FMT_NV12_4L4",
+        sys::V4L2_PIX_FMT_NV12_16L16 => "V4L2_PIX_FMT_NV12_16L16",
+        sys::V4L2_PIX_FMT_NV12_32L32 => "V4L2_PIX_FMT_NV12_32L32",
+        sys::V4L2_PIX_FMT_NV15_4L4 => "V4L2_PIX_FMT_NV15_4L4",
+        sys::V4L2_PIX_FMT_P010_4L4 => "V4L2_PIX_FMT_P010_4L4",
+        sys::V4L2_PIX_FMT_NV12_8L128 => "V4L2_PIX_FMT_NV12_8L128",
+        sys::V4L2_PIX_FMT_NV12_10BE_8L128 => "V4L2_PIX_FMT_NV12_10BE_8L128",
+
+        /* Tiled YUV formats, non contiguous planes */
+        sys::V4L2_PIX_FMT_NV12MT => "V4L2_PIX_FMT_NV12MT",
+        sys::V4L2_PIX_FMT_NV12MT_16X16 => "V4L2_PIX_FMT_NV12MT_16X16",
+        sys::V4L2_PIX_FMT_NV12M_8L128 => "V4L2_PIX_FMT_NV12M_8L128",
+        sys::V4L2_PIX_FMT_NV12M_10BE_8L128 => "V4L2_PIX_FMT_NV12M_10BE_8L128",
+
+        /* Bayer formats - see http://www.siliconimaging.com/RGB%20Bayer.htm */
+        sys::V4L2_PIX_FMT_SBGGR8 => "V4L2_PIX_FMT_SBGGR8",
+        sys::V4L2_PIX_FMT_SGBRG8 => "V4L2_PIX_FMT_SGBRG8",
+        sys::V4L2_PIX_FMT_SGRBG8 => "V4L2_PIX_FMT_SGRBG8",
+        sys::V4L2_PIX_FMT_SRGGB8 => "V4L2_PIX_FMT_SRGGB8",
+        sys::V4L2_PIX_FMT_SBGGR10 => "V4L2_PIX_FMT_SBGGR10",
+        sys::V4L2_PIX_FMT_SGBRG10 => "V4L2_PIX_FMT_SGBRG10",
+        sys::V4L2_PIX_FMT_SGRBG10 => "V4L2_PIX_FMT_SGRBG10",
+        sys::V4L2_PIX_FMT_SRGGB10 => "V4L2_PIX_FMT_SRGGB10",
+        /* 10bit raw bayer packed, 5 bytes for every 4 pixels */
+        sys::V4L2_PIX_FMT_SBGGR10P => "V4L2_PIX_FMT_SBGGR10P",
+        sys::V4L2_PIX_FMT_SGBRG10P => "V4L2_PIX_FMT_SGBRG10P",
+        sys::V4L2_PIX_FMT_SGRBG10P => "V4L2_PIX_FMT_SGRBG10P",
+        sys::V4L2_PIX_FMT_SRGGB10P => "V4L2_PIX_FMT_SRGGB10P",
+        /* 10bit raw bayer a-law compressed to 8 bits */
+        sys::V4L2_PIX_FMT_SBGGR10ALAW8 => "V4L2_PIX_FMT_SBGGR10ALAW8",
+        sys::V4L2_PIX_FMT_SGBRG10ALAW8 => "V4L2_PIX_FMT_SGBRG10ALAW8",
+        sys::V4L2_PIX_FMT_SGRBG10ALAW8 => "V4L2_PIX_FMT_SGRBG10ALAW8",
+        sys::V4L2_PIX_FMT_SRGGB10ALAW8 => "V4L2_PIX_FMT_SRGGB10ALAW8",
+        /* 10bit raw bayer DPCM compressed to 8 bits */
+        sys::V4L2_PIX_FMT_SBGGR10DPCM8 => "V4L2_PIX_FMT_SBGGR10DPCM8",
+        sys::V4L2_PIX_FMT_SGBRG10DPCM8 => "V4L2_PIX_FMT_SGBRG10DPCM8",
+        sys::V4L2_PIX_FMT_SGRBG10DPCM8 => "V4L2_PIX_FMT_SGRBG10DPCM8",
+        sys::V4L2_PIX_FMT_SRGGB10DPCM8 => "V4L2_PIX_FMT_SRGGB10DPCM8",
+        sys::V4L2_PIX_FMT_SBGGR12 => "V4L2_PIX_FMT_SBGGR12",
+        sys::V4L2_PIX_FMT_SGBRG12 => "V4L2_PIX_FMT_SGBRG12",
+        sys::V4L2_PIX_FMT_SGRBG12 => "V4L2_PIX_FMT_SGRBG12",
+        sys::V4L2_PIX_FMT_SRGGB12 => "V4L2_PIX_FMT_SRGGB12",
+        /* 12bit raw bayer packed, 6 bytes for every 4 pixels */
+        sys::V4L2_PIX_FMT_SBGGR12P => "V4L2_PIX_FMT_SBGGR12P",
+        sys::V4L2_PIX_FMT_SGBRG12P => "V4L2_PIX_FMT_SGBRG12P",
+        sys::V4L2_PIX_FMT_SGRBG12P => "V4L2_PIX_FMT_SGRBG12P",
+        sys::V4L2_PIX_FMT_SRGGB12P => "V4L2_PIX_FMT_SRGGB12P",
+        sys::V4L2_PIX_FMT_SBGGR14 => "V4L2_PIX_FMT_SBGGR14",
+        sys::V4L2_PIX_FMT_SGBRG14 => "V4L2_PIX_FMT_SGBRG14",
+        sys::V4L2_PIX_FMT_SGRBG14 => "V4L2_PIX_FMT_SGRBG14",
+        sys::V4L2_PIX_FMT_SRGGB14 => "V4L2_PIX_FMT_SRGGB14",
+        /* 14bit raw bayer packed, 7 bytes for every 4 pixels */
+        sys::V4L2_PIX_FMT_SBGGR14P => "V4L2_PIX_FMT_SBGGR14P",
+        sys::V4L2_PIX_FMT_SGBRG14P => "V4L2_PIX_FMT_SGBRG14P",
+        sys::V4L2_PIX_FMT_SGRBG14P => "V4L2_PIX_FMT_SGRBG14P",
+        sys::V4L2_PIX_FMT_SRGGB14P => "V4L2_PIX_FMT_SRGGB14P",
+        sys::V4L2_PIX_FMT_SBGGR16 => "V4L2_PIX_FMT_SBGGR16",
+        sys::V4L2_PIX_FMT_SGBRG16 => "V4L2_PIX_FMT_SGBRG16",
+        sys::V4L2_PIX_FMT_SGRBG16 => "V4L2_PIX_FMT_SGRBG16",
+        sys::V4L2_PIX_FMT_SRGGB16 => "V4L2_PIX_FMT_SRGGB16",
+
+        /* HSV formats */
+        sys::V4L2_PIX_FMT_HSV24 => "V4L2_PIX_FMT_HSV24",
+        sys::V4L2_PIX_FMT_HSV32 => "V4L2_PIX_FMT_HSV32",
+
+        /* compressed formats */
+        sys::V4L2_PIX_FMT_MJPEG => "V4L2_PIX_FMT_MJPEG",
+        sys::V4L2_PIX_FMT_JPEG => "V4L2_PIX_FMT_JPEG",
+        sys::V4L2_PIX_FMT_DV => "V4L2_PIX_FMT_DV",
+        sys::V4L2_PIX_FMT_MPEG => "V4L2_PIX_FMT_MPEG",
+        sys::V4L2_PIX_FMT_H264 => "V4L2_PIX_FMT_H264",
+        sys::V4L2_PIX_FMT_H264_NO_SC => "V4L2_PIX_FMT_H264_NO_SC",
+        sys::V4L2_PIX_FMT_H264_MVC => "V4L2_PIX_FMT_H264_MVC",
+        sys::V4L2_PIX_FMT_H263 => "V4L2_PIX_FMT_H263",
+        sys::V4L2_PIX_FMT_MPEG1 => "V4L2_PIX_FMT_MPEG1",
+        sys::V4L2_PIX_FMT_MPEG2 => "V4L2_PIX_FMT_MPEG2",
+        sys::V4L2_PIX_FMT_MPEG2_SLICE => "V4L2_PIX_FMT_MPEG2_SLICE",
+        sys::V4L2_PIX_FMT_MPEG4 => "V4L2_PIX_FMT_MPEG4",
+        sys::V4L2_PIX_FMT_XVID => "V4L2_PIX_FMT_XVID",
+        sys::V4L2_PIX_FMT_VC1_ANNEX_G => "V4L2_PIX_FMT_VC1_ANNEX_G",
+        sys::V4L2_PIX_FMT_VC1_ANNEX_L => "V4L2_PIX_FMT_VC1_ANNEX_L",
+        sys::V4L2_PIX_FMT_VP8 => "V4L2_PIX_FMT_VP8",
+        sys::V4L2_PIX_FMT_VP8_FRAME => "V4L2_PIX_FMT_VP8_FRAME",
+        sys::V4L2_PIX_FMT_VP9 => "V4L2_PIX_FMT_VP9",
+        sys::V4L2_PIX_FMT_VP9_FRAME => "V4L2_PIX_FMT_VP9_FRAME",
+        sys::V4L2_PIX_FMT_HEVC => "V4L2_PIX_FMT_HEVC",
+        sys::V4L2_PIX_FMT_FWHT => "V4L2_PIX_FMT_FWHT",
+
+        sys::V4L2_PIX_FMT_FWHT_STATELESS => "V4L2_PIX_FMT_FWHT_STATELESS",
+
+        sys::V4L2_PIX_FMT_H264_SLICE => "V4L2_PIX_FMT_H264_SLICE",
+        sys::V4L2_PIX_FMT_HEVC_SLICE => "V4L2_PIX_FMT_HEVC_SLICE",
+        sys::V4L2_PIX_FMT_AV1_FRAME => "V4L2_PIX_FMT_AV1_FRAME",
+        sys::V4L2_PIX_FMT_SPK => "V4L2_PIX_FMT_SPK",
+        sys::V4L2_PIX_FMT_RV30 => "V4L2_PIX_FMT_RV30",
+        sys::V4L2_PIX_FMT_RV40 => "V4L2_PIX_FMT_RV40",
+
+        /*  Vendor-specific formats   */
+        sys::V4L2_PIX_FMT_CPIA1 => "V4L2_PIX_FMT_CPIA1",
+        sys::V4L2_PIX_FMT_WNVA => "V4L2_PIX_FMT_WNVA",
+        sys::V4L2_PIX_FMT_SN9C10X => "V4L2_PIX_FMT_SN9C10X",
+        sys::V4L2_PIX_FMT_SN9C20X_I420 => "V4L2_PIX_FMT_SN9C20X_I420",
+        sys::V4L2_PIX_FMT_PWC1 => "V4L2_PIX_FMT_PWC1",
+        sys::V4L2_PIX_FMT_PWC2 => "V4L2_PIX_FMT_PWC2",
+        sys::V4L2_PIX_FMT_ET61X251 => "V4L2_PIX_FMT_ET61X251",
+        sys::V4L2_PIX_FMT_SPCA501 => "V4L2_PIX_FMT_SPCA501",
+        sys::V4L2_PIX_FMT_SPCA505 => "V4L2_PIX_FMT_SPCA505",
+        sys::V4L2_PIX_FMT_SPCA508 => "V4L2_PIX_FMT_SPCA508",
+        sys::V4L2_PIX_FMT_SPCA561 => "V4L2_PIX_FMT_SPCA561",
+        sys::V4L2_PIX_FMT_PAC207 => "V4L2_PIX_FMT_PAC207",
+        sys::V4L2_PIX_FMT_MR97310A => "V4L2_PIX_FMT_MR97310A",
+        sys::V4L2_PIX_FMT_JL2005BCD => "V4L2_PIX_FMT_JL2005BCD",
+        sys::V4L2_PIX_FMT_SN9C2028 => "V4L2_PIX_FMT_SN9C2028",
+        sys::V4L2_PIX_FMT_SQ905C => "V4L2_PIX_FMT_SQ905C",
+        sys::V4L2_PIX_FMT_PJPG => "V4L2_PIX_FMT_PJPG",
+        sys::V4L2_PIX_FMT_OV511 => "V4L2_PIX_FMT_OV511",
+        sys::V4L2_PIX_FMT_OV518 => "V4L2_PIX_FMT_OV518",
+        sys::V4L2_PIX_FMT_STV0680 => "V4L2_PIX_FMT_STV0680",
+        sys::V4L2_PIX_FMT_TM6000 => "V4L2_PIX_FMT_TM6000",
+        sys::V4L2_PIX_FMT_CIT_YYVYUY => "V4L2_PIX_FMT_CIT_YYVYUY",
+        sys::V4L2_PIX_FMT_KONICA420 => "V4L2_PIX_FMT_KONICA420",
+        sys::V4L2_PIX_FMT_JPGL => "V4L2_PIX_FMT_JPGL",
+        sys::V4L2_PIX_FMT_SE401 => "V4L2_PIX_FMT_SE401",
+        sys::V4L2_PIX_FMT_S5C_UYVY_JPG => "V4L2_PIX_FMT_S5C_UYVY_JPG",
+        sys::V4L2_PIX_FMT_Y8I => "V4L2_PIX_FMT_Y8I",
+        sys::V4L2_PIX_FMT_Y12I => "V4L2_PIX_FMT_Y12I",
+        sys::V4L2_PIX_FMT_Z16 => "V4L2_PIX_FMT_Z16",
+        sys::V4L2_PIX_FMT_MT21C => "V4L2_PIX_FMT_MT21C",
+        sys::V4L2_PIX_FMT_MM21 => "V4L2_PIX_FMT_MM21",
+        sys::V4L2_PIX_FMT_MT2110T => "V4L2_PIX_FMT_MT2110T",
+        sys::V4L2_PIX_FMT_MT2110R => "V4L2_PIX_FMT_MT2110R",
+        sys::V4L2_PIX_FMT_INZI => "V4L2_PIX_FMT_INZI",
+        sys::V4L2_PIX_FMT_CNF4 => "V4L2_PIX_FMT_CNF4",
+        sys::V4L2_PIX_FMT_HI240 => "V4L2_PIX_FMT_HI240",
+        sys::V4L2_PIX_FMT_QC08C => "V4L2_PIX_FMT_QC08C",
+        sys::V4L2_PIX_FMT_QC10C => "V4L2_PIX_FMT_QC10C",
+        sys::V4L2_PIX_FMT_AJPG => "V4L2_PIX_FMT_AJPG",
+        sys::V4L2_PIX_FMT_HEXTILE => "V4L2_PIX_FMT_HEXTILE",
+
+        /* 10bit raw packed, 32 bytes for every 25 pixels, last LSB 6 bits unused */
+        sys::V4L2_PIX_FMT_IPU3_SBGGR10 => "V4L2_PIX_FMT_IPU3_SBGGR10",
+        sys::V4L2_PIX_FMT_IPU3_SGBRG10 => "V4L2_PIX_FMT_IPU3_SGBRG10",
+        sys::V4L2_PIX_FMT_IPU3_SGRBG10 => "V4L2_PIX_FMT_IPU3_SGRBG10",
+        sys::V4L2_PIX_FMT_IPU3_SRGGB10 => "V4L2_PIX_FMT_IPU3_SRGGB10",
+
+        _ => "unknown",
+    }
+}
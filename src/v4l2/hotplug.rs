@@ -0,0 +1,152 @@
+use std::{
+    ffi::{CString, OsStr},
+    fs::{self, File},
+    io::{self, Read},
+    os::{
+        fd::{AsRawFd, FromRawFd},
+        unix::ffi::OsStrExt,
+    },
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use super::sys;
+
+const BY_ID_DIR: &str = "/dev/v4l/by-id";
+
+// wd, mask, cookie and len in front of every name
+const EVENT_HEADER_LEN: usize = 16;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HotplugEvent {
+    Added(PathBuf),
+    Removed(PathBuf),
+}
+
+/// Watches a directory (normally `/dev`) for `video*` nodes coming and going.
+pub struct DeviceWatcher {
+    inotify: File,
+    dir: PathBuf,
+    buf: Vec<u8>,
+}
+
+impl DeviceWatcher {
+    pub fn new<P: AsRef<Path>>(dir: &P) -> io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+
+        let fd = unsafe { sys::inotify_init1((sys::IN_NONBLOCK | sys::IN_CLOEXEC) as i32) };
+        if fd == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        let inotify = unsafe { File::from_raw_fd(fd) };
+
+        let dir_name = CString::new(dir.as_os_str().as_bytes())?;
+        let mask = sys::IN_CREATE | sys::IN_DELETE | sys::IN_MOVED_TO | sys::IN_MOVED_FROM;
+        if unsafe { sys::inotify_add_watch(fd, dir_name.as_ptr(), mask) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Self {
+            inotify,
+            dir,
+            buf: vec![0; 4096],
+        })
+    }
+
+    /// Wait up to `timeout` (forever with `None`) and return whatever happened.
+    /// An empty list means the timeout expired.
+    pub fn wait(&mut self, timeout: Option<Duration>) -> io::Result<Vec<HotplugEvent>> {
+        let mut poll_fd: [sys::pollfd; 1] = [sys::pollfd {
+            fd: self.inotify.as_raw_fd(),
+            events: sys::POLLIN as i16,
+            revents: 0,
+        }];
+        let timeout = timeout.map_or(-1, |t| t.as_millis().try_into().unwrap_or(i32::MAX));
+
+        let ret = unsafe { sys::poll(poll_fd.as_mut_ptr(), poll_fd.len() as u64, timeout) };
+        if ret == -1 {
+            let e = io::Error::last_os_error();
+            // A signal, as good as a timeout
+            if e.kind() == io::ErrorKind::Interrupted {
+                return Ok(Vec::new());
+            }
+            return Err(e);
+        }
+
+        let mut events = Vec::new();
+        loop {
+            let len = match self.inotify.read(&mut self.buf) {
+                Ok(len) => len,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            };
+
+            let mut offset = 0;
+            while offset + EVENT_HEADER_LEN <= len {
+                let header = &self.buf[offset..offset + EVENT_HEADER_LEN];
+                let mask = u32::from_ne_bytes(header[4..8].try_into().unwrap());
+                let name_len = u32::from_ne_bytes(header[12..16].try_into().unwrap()) as usize;
+
+                let name =
+                    &self.buf[offset + EVENT_HEADER_LEN..offset + EVENT_HEADER_LEN + name_len];
+                // The name is padded with NULs
+                let name = &name[..name.iter().position(|c| *c == 0).unwrap_or(name.len())];
+                offset += EVENT_HEADER_LEN + name_len;
+
+                if !name.starts_with(b"video") {
+                    continue;
+                }
+
+                let path = self.dir.join(OsStr::from_bytes(name));
+                if mask & (sys::IN_CREATE | sys::IN_MOVED_TO) != 0 {
+                    events.push(HotplugEvent::Added(path));
+                } else if mask & (sys::IN_DELETE | sys::IN_MOVED_FROM) != 0 {
+                    events.push(HotplugEvent::Removed(path));
+                }
+            }
+        }
+
+        Ok(events)
+    }
+}
+
+/// The `/dev/v4l/by-id` link pointing at `device`, which survives replugging
+/// even if the device comes back under a different `/dev/videoN`.
+pub fn stable_path<P: AsRef<Path>>(device: &P) -> Option<PathBuf> {
+    let target = fs::canonicalize(device).ok()?;
+
+    fs::read_dir(BY_ID_DIR)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .find(|link| fs::canonicalize(link).is_ok_and(|link_target| link_target == target))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_video_nodes_coming_and_going() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut watcher = DeviceWatcher::new(&dir.path()).unwrap();
+        let timeout = Some(Duration::from_secs(1));
+
+        // Nothing happened yet
+        assert_eq!(watcher.wait(Some(Duration::ZERO)).unwrap(), vec![]);
+
+        let node = dir.path().join("video7");
+        File::create(&node).unwrap();
+        File::create(dir.path().join("media0")).unwrap();
+        assert_eq!(
+            watcher.wait(timeout).unwrap(),
+            vec![HotplugEvent::Added(node.clone())]
+        );
+
+        fs::remove_file(&node).unwrap();
+        assert_eq!(
+            watcher.wait(timeout).unwrap(),
+            vec![HotplugEvent::Removed(node)]
+        );
+    }
+}
@@ -0,0 +1,697 @@
+use std::{
+    fs::File,
+    io,
+    os::fd::AsRawFd,
+    sync::{Arc, Condvar, Mutex},
+};
+
+use super::{
+    stats::{BufferSample, CaptureStats},
+    sys, FrameFormat, MAX_PLANES,
+};
+
+/// What the pool does when the frame it is about to hand out is the last
+/// buffer the driver owns, i.e. every other buffer is held by a `V4l2Frame`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ExhaustionPolicy {
+    /// Hand the buffer out and make the next dequeue wait until a frame is dropped.
+    #[default]
+    Block,
+    /// Give the buffer straight back to the driver and wait for the next one.
+    DropNewest,
+    /// Copy the buffer into an owned frame and give it straight back to the driver.
+    Copy,
+}
+
+/// How dequeued buffers are handed out. This is a trade-off between latency
+/// and never losing a frame, pick per use case.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum QueueMode {
+    /// Every frame in capture order, as long as buffers are available.
+    #[default]
+    Fifo,
+    /// Take everything the driver has ready and hand out only the newest
+    /// frame, the older ones go straight back. For live preview.
+    LowLatency,
+    /// Every frame in capture order, keeping a reserve of buffers with the
+    /// driver and copying rather than letting it run dry. For recording.
+    ///
+    /// Always uses `ExhaustionPolicy::Copy`, whatever policy is set. Frames
+    /// that got copied say so with `V4l2Frame::is_copy` and are counted in
+    /// `StatsSnapshot::copied_frames`.
+    NoDrop,
+}
+
+// Buffers `QueueMode::NoDrop` leaves with the driver before it starts copying
+const NO_DROP_RESERVE: usize = 2;
+
+// Where the planes start within a buffer
+const PLANE_ALIGN: usize = 64;
+
+// Start and end of each plane's data within its buffer
+type PlaneRanges = [(usize, usize); MAX_PLANES];
+
+struct PoolState {
+    // Buffers currently owned by the driver
+    queued: usize,
+    // Set once the device stopped streaming on this pool; frames still alive
+    // keep their memory but are no longer re-queued.
+    retired: bool,
+    policy: ExhaustionPolicy,
+    mode: QueueMode,
+}
+
+// What becomes of a buffer just dequeued
+#[derive(Debug, PartialEq, Eq)]
+enum Handout {
+    // Handed out in a frame, going back to the driver when the frame drops
+    Lend,
+    // Straight back to the driver, to wait for the next one
+    Requeue,
+    // Handed out as a copy, the buffer going straight back to the driver
+    Copy,
+}
+
+impl PoolState {
+    // For a buffer just dequeued from a pool of `buffers`, which is already
+    // left out of `queued`
+    fn handout(&self, buffers: usize) -> Handout {
+        let (policy, min_queued) = match self.mode {
+            QueueMode::NoDrop => (ExhaustionPolicy::Copy, NO_DROP_RESERVE.min(buffers)),
+            _ => (self.policy, 1),
+        };
+        if self.queued >= min_queued {
+            return Handout::Lend;
+        }
+        match policy {
+            ExhaustionPolicy::Block => Handout::Lend,
+            ExhaustionPolicy::DropNewest => Handout::Requeue,
+            ExhaustionPolicy::Copy => Handout::Copy,
+        }
+    }
+}
+
+// The ioctls the pool needs, apart from the bookkeeping so that can be
+// tested without a device
+trait Driver: Send + Sync {
+    /// Give `buffer`, number `index`, to the driver to fill.
+    fn queue(&self, index: usize, buffer: &[u8]) -> io::Result<()>;
+    /// Take back the oldest filled buffer: its index, where the data of each
+    /// plane ended up and what to tell the stats.
+    fn dequeue(&self) -> io::Result<(usize, PlaneRanges, BufferSample)>;
+    /// Whether a filled buffer is ready, waiting up to `timeout` ms for one,
+    /// -1 meaning for ever.
+    fn poll(&self, timeout: i32) -> bool;
+}
+
+struct Device {
+    handle: Arc<File>,
+    buf_type: sys::v4l2_buf_type,
+    // Offset and length of every plane within a buffer
+    planes: Vec<(usize, usize)>,
+    buffer_size: usize,
+}
+
+/// A set of user pointer buffers registered with the driver.
+///
+/// Dequeued buffers are handed out as refcounted `V4l2Frame`s and go back to
+/// the driver when the last clone of a frame is dropped.
+///
+/// With the multi-planar API every buffer is still one allocation, holding
+/// all the planes one after the other.
+pub(crate) struct BufferPool {
+    driver: Box<dyn Driver>,
+    buffers: Vec<Box<[u8]>>,
+    state: Mutex<PoolState>,
+    returned: Condvar,
+}
+
+impl Device {
+    fn multi_planar(&self) -> bool {
+        self.buf_type == sys::v4l2_buf_type_V4L2_BUF_TYPE_VIDEO_CAPTURE_MPLANE
+    }
+}
+
+impl Driver for Device {
+    fn queue(&self, index: usize, buffer: &[u8]) -> io::Result<()> {
+        unsafe {
+            let mut planes: [sys::v4l2_plane; MAX_PLANES] = std::mem::zeroed();
+            let mut v4l2_buf: sys::v4l2_buffer = std::mem::zeroed();
+            v4l2_buf.type_ = self.buf_type;
+            v4l2_buf.memory = sys::v4l2_memory_V4L2_MEMORY_USERPTR;
+            v4l2_buf.index = index.try_into().unwrap();
+
+            if self.multi_planar() {
+                for (plane, &(offset, length)) in planes.iter_mut().zip(&self.planes) {
+                    plane.m.userptr = buffer[offset..].as_ptr() as _;
+                    plane.length = length.try_into().unwrap();
+                }
+                v4l2_buf.m.planes = planes.as_mut_ptr();
+                v4l2_buf.length = self.planes.len().try_into().unwrap();
+            } else {
+                v4l2_buf.m.userptr = buffer.as_ptr() as _;
+                v4l2_buf.length = buffer.len().try_into().unwrap();
+            }
+
+            ioctl!(self.handle.as_raw_fd(), sys::VIDIOC_QBUF, &mut v4l2_buf)?;
+        }
+
+        Ok(())
+    }
+
+    fn dequeue(&self) -> io::Result<(usize, PlaneRanges, BufferSample)> {
+        let mut ranges = [(0, 0); MAX_PLANES];
+
+        let v4l2_buf = unsafe {
+            let mut planes: [sys::v4l2_plane; MAX_PLANES] = std::mem::zeroed();
+            let mut v4l2_buf: sys::v4l2_buffer = std::mem::zeroed();
+            v4l2_buf.type_ = self.buf_type;
+            v4l2_buf.memory = sys::v4l2_memory_V4L2_MEMORY_USERPTR;
+            if self.multi_planar() {
+                v4l2_buf.m.planes = planes.as_mut_ptr();
+                v4l2_buf.length = self.planes.len().try_into().unwrap();
+            }
+
+            // Deque buffer. We can use it now and it gets queued
+            // up again once every frame referencing it is gone.
+            ioctl!(self.handle.as_raw_fd(), sys::VIDIOC_DQBUF, &mut v4l2_buf)?;
+
+            if self.multi_planar() {
+                v4l2_buf.bytesused = 0;
+                for ((range, plane), &(offset, length)) in
+                    ranges.iter_mut().zip(&planes).zip(&self.planes)
+                {
+                    // `bytesused` counts the data offset too
+                    let end = (plane.bytesused as usize).min(length);
+                    let start = (plane.data_offset as usize).min(end);
+                    *range = (offset + start, offset + end);
+                    v4l2_buf.bytesused += plane.bytesused;
+                }
+            } else {
+                let end = (v4l2_buf.bytesused as usize).min(self.buffer_size);
+                ranges[0] = (0, end);
+            }
+            v4l2_buf
+        };
+
+        let sample = BufferSample {
+            sequence: v4l2_buf.sequence,
+            flags: v4l2_buf.flags,
+            bytesused: v4l2_buf.bytesused,
+            timestamp: v4l2_buf.timestamp,
+        };
+        Ok((v4l2_buf.index as usize, ranges, sample))
+    }
+
+    fn poll(&self, timeout: i32) -> bool {
+        let mut poll_fd: [sys::pollfd; 1] = [sys::pollfd {
+            fd: self.handle.as_raw_fd(),
+            events: sys::POLLIN as i16,
+            revents: 0,
+        }];
+
+        unsafe { sys::poll(poll_fd.as_mut_ptr(), poll_fd.len() as u64, timeout) > 0 }
+    }
+}
+
+impl BufferPool {
+    /// Ask the driver for `count` buffers for frames in `format`. It may
+    /// grant more or fewer, the pool is sized to what was actually granted.
+    pub(crate) fn new(
+        handle: Arc<File>,
+        buf_type: sys::v4l2_buf_type,
+        format: &FrameFormat,
+        count: u32,
+        policy: ExhaustionPolicy,
+        mode: QueueMode,
+    ) -> io::Result<Arc<Self>> {
+        let fd = handle.as_raw_fd();
+
+        let mut buffer_size = 0;
+        let planes = format.planes[..format.num_planes]
+            .iter()
+            .map(|plane| {
+                let offset = buffer_size;
+                buffer_size = (offset + plane.size).next_multiple_of(PLANE_ALIGN);
+                (offset, plane.size)
+            })
+            .collect();
+
+        let granted = unsafe {
+            let mut bufreq: sys::v4l2_requestbuffers = std::mem::zeroed();
+            bufreq.count = count;
+            bufreq.type_ = buf_type;
+            bufreq.memory = sys::v4l2_memory_V4L2_MEMORY_USERPTR;
+
+            ioctl!(fd, sys::VIDIOC_REQBUFS, &mut bufreq)?;
+            bufreq.count
+        };
+
+        if granted == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::OutOfMemory,
+                "driver granted no buffers",
+            ));
+        }
+        if granted != count {
+            log::debug!("Asked for {count} buffers, driver granted {granted}");
+        }
+
+        let device = Device {
+            handle,
+            buf_type,
+            planes,
+            buffer_size,
+        };
+        Self::with_driver(
+            Box::new(device),
+            granted as usize,
+            buffer_size,
+            policy,
+            mode,
+        )
+    }
+
+    // `count` buffers of `buffer_size` bytes, all given to `driver`
+    fn with_driver(
+        driver: Box<dyn Driver>,
+        count: usize,
+        buffer_size: usize,
+        policy: ExhaustionPolicy,
+        mode: QueueMode,
+    ) -> io::Result<Arc<Self>> {
+        let buffers: Vec<Box<[u8]>> = (0..count)
+            .map(|_| vec![0u8; buffer_size].into_boxed_slice())
+            .collect();
+
+        let pool = Arc::new(Self {
+            driver,
+            buffers,
+            state: Mutex::new(PoolState {
+                queued: 0,
+                retired: false,
+                policy,
+                mode,
+            }),
+            returned: Condvar::new(),
+        });
+
+        for index in 0..pool.buffers.len() {
+            pool.queue(index)?;
+        }
+        pool.state.lock().unwrap().queued = pool.buffers.len();
+
+        Ok(pool)
+    }
+
+    fn queue(&self, index: usize) -> io::Result<()> {
+        self.driver.queue(index, &self.buffers[index])
+    }
+
+    // Give a buffer we dequeued but won't hand out straight back
+    fn requeue(&self, index: usize) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        self.queue(index)?;
+        state.queued += 1;
+        Ok(())
+    }
+
+    // Called when the last clone of a frame goes away
+    fn release(&self, index: usize) {
+        let mut state = self.state.lock().unwrap();
+        if state.retired {
+            return;
+        }
+
+        // If the device went away the buffer simply stays with us
+        if self.queue(index).is_ok() {
+            state.queued += 1;
+            self.returned.notify_all();
+        }
+    }
+
+    /// Stop re-queueing buffers. Must be called before the device stops streaming.
+    pub(crate) fn retire(&self) {
+        self.state.lock().unwrap().retired = true;
+        self.returned.notify_all();
+    }
+
+    /// Number of buffers the driver granted.
+    pub(crate) fn count(&self) -> u32 {
+        self.buffers.len().try_into().unwrap()
+    }
+
+    pub(crate) fn set_policy(&self, policy: ExhaustionPolicy) {
+        self.state.lock().unwrap().policy = policy;
+    }
+
+    pub(crate) fn set_mode(&self, mode: QueueMode) {
+        self.state.lock().unwrap().mode = mode;
+    }
+
+    // The buffer index and where the data of each plane ended up
+    fn dequeue_buffer(&self, stats: &CaptureStats) -> io::Result<(usize, PlaneRanges)> {
+        let (index, ranges, sample) = self.driver.dequeue()?;
+        self.state.lock().unwrap().queued -= 1;
+        stats.record(&sample);
+        Ok((index, ranges))
+    }
+
+    pub(crate) fn dequeue(
+        self: &Arc<Self>,
+        format: FrameFormat,
+        stats: &CaptureStats,
+    ) -> io::Result<V4l2Frame> {
+        loop {
+            let mode = {
+                // With nothing queued the driver has nowhere to write and poll
+                // would only report an error, so wait for a frame to come back.
+                let mut state = self.state.lock().unwrap();
+                while state.queued == 0 && !state.retired {
+                    state = self.returned.wait(state).unwrap();
+                }
+                if state.retired {
+                    return Err(io::Error::new(
+                        io::ErrorKind::NotConnected,
+                        "device stopped streaming, it has to be opened again",
+                    ));
+                }
+                state.mode
+            };
+
+            // A device that went away shows up as an error from DQBUF below
+            let infinite_timeout = -1;
+            self.driver.poll(infinite_timeout);
+            let (mut index, mut planes) = self.dequeue_buffer(stats)?;
+
+            if mode == QueueMode::LowLatency {
+                // Anything already waiting behind this buffer makes it stale
+                while self.state.lock().unwrap().queued > 0 && self.driver.poll(0) {
+                    let newer = self.dequeue_buffer(stats)?;
+                    self.requeue(index)?;
+                    (index, planes) = newer;
+                }
+            }
+
+            let mut state = self.state.lock().unwrap();
+            let storage = match state.handout(self.buffers.len()) {
+                Handout::Lend => Storage::Pooled(index),
+                Handout::Requeue => {
+                    self.queue(index)?;
+                    state.queued += 1;
+                    continue;
+                }
+                Handout::Copy => {
+                    let end = planes.iter().map(|&(_, end)| end).max().unwrap_or(0);
+                    let data = self.buffers[index][..end].to_vec();
+                    self.queue(index)?;
+                    state.queued += 1;
+                    stats.record_copy();
+                    Storage::Owned(data)
+                }
+            };
+
+            return Ok(V4l2Frame {
+                inner: Arc::new(FrameInner {
+                    pool: self.clone(),
+                    storage,
+                    planes,
+                    format,
+                }),
+            });
+        }
+    }
+}
+
+enum Storage {
+    // Index of a buffer in the pool, still checked out from the driver
+    Pooled(usize),
+    Owned(Vec<u8>),
+}
+
+struct FrameInner {
+    pool: Arc<BufferPool>,
+    storage: Storage,
+    planes: PlaneRanges,
+    format: FrameFormat,
+}
+
+impl Drop for FrameInner {
+    fn drop(&mut self) {
+        if let Storage::Pooled(index) = self.storage {
+            self.pool.release(index);
+        }
+    }
+}
+
+/// A captured frame. Cheap to clone and free to move across threads; the
+/// underlying buffer is given back to the driver when the last clone drops.
+#[derive(Clone)]
+pub struct V4l2Frame {
+    inner: Arc<FrameInner>,
+}
+
+// Frames get handed to other threads and kept around, so make sure they can be
+const _: fn() = || {
+    fn check<T: Send + 'static>() {}
+    check::<V4l2Frame>();
+};
+
+impl V4l2Frame {
+    fn buffer(&self) -> &[u8] {
+        match &self.inner.storage {
+            Storage::Pooled(index) => &self.inner.pool.buffers[*index],
+            Storage::Owned(data) => data,
+        }
+    }
+
+    /// Whether the data was copied out of the driver's buffer because it was
+    /// running out of them, see `ExhaustionPolicy::Copy`.
+    pub fn is_copy(&self) -> bool {
+        matches!(self.inner.storage, Storage::Owned(_))
+    }
+
+    /// The first plane, which for all but the multi-planar formats is the
+    /// whole frame.
+    pub fn data(&self) -> &[u8] {
+        self.planes()[0]
+    }
+
+    /// The data of every plane, empty past `format().num_planes`.
+    pub fn planes(&self) -> [&[u8]; MAX_PLANES] {
+        let buffer = self.buffer();
+        self.inner.planes.map(|(start, end)| &buffer[start..end])
+    }
+
+    pub fn width(&self) -> usize {
+        self.inner.format.width
+    }
+    pub fn height(&self) -> usize {
+        self.inner.format.height
+    }
+    pub fn format(&self) -> &FrameFormat {
+        &self.inner.format
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use super::*;
+
+    // Buffers the driver has to fill, and has filled, in order
+    #[derive(Default)]
+    struct Fake {
+        empty: VecDeque<usize>,
+        filled: VecDeque<usize>,
+        sequence: u32,
+    }
+
+    #[derive(Clone, Default)]
+    struct FakeDriver(Arc<Mutex<Fake>>);
+
+    impl FakeDriver {
+        // The next `count` empty buffers get a frame each
+        fn capture(&self, count: usize) {
+            let mut fake = self.0.lock().unwrap();
+            for _ in 0..count {
+                let index = fake.empty.pop_front().expect("no buffer to capture into");
+                fake.filled.push_back(index);
+            }
+        }
+
+        fn empty(&self) -> Vec<usize> {
+            self.0.lock().unwrap().empty.iter().copied().collect()
+        }
+    }
+
+    impl Driver for FakeDriver {
+        fn queue(&self, index: usize, _: &[u8]) -> io::Result<()> {
+            let mut fake = self.0.lock().unwrap();
+            assert!(!fake.empty.contains(&index) && !fake.filled.contains(&index));
+            fake.empty.push_back(index);
+            Ok(())
+        }
+
+        // The data is as long as the buffer's index + 1, to tell them apart
+        fn dequeue(&self) -> io::Result<(usize, PlaneRanges, BufferSample)> {
+            let mut fake = self.0.lock().unwrap();
+            let index = fake
+                .filled
+                .pop_front()
+                .ok_or_else(|| io::Error::from(io::ErrorKind::WouldBlock))?;
+            fake.sequence += 1;
+            let mut ranges = [(0, 0); MAX_PLANES];
+            ranges[0] = (0, index + 1);
+            let sample = BufferSample {
+                sequence: fake.sequence,
+                flags: 0,
+                bytesused: index as u32 + 1,
+                timestamp: sys::timeval {
+                    tv_sec: 0,
+                    tv_usec: 0,
+                },
+            };
+            Ok((index, ranges, sample))
+        }
+
+        fn poll(&self, _: i32) -> bool {
+            !self.0.lock().unwrap().filled.is_empty()
+        }
+    }
+
+    fn pool(
+        buffers: usize,
+        policy: ExhaustionPolicy,
+        mode: QueueMode,
+    ) -> (Arc<BufferPool>, FakeDriver, CaptureStats) {
+        let driver = FakeDriver::default();
+        let pool = BufferPool::with_driver(Box::new(driver.clone()), buffers, 16, policy, mode);
+        (pool.unwrap(), driver, CaptureStats::new())
+    }
+
+    // Which buffer a frame came from
+    fn index(frame: &V4l2Frame) -> usize {
+        frame.data().len() - 1
+    }
+
+    #[test]
+    fn handing_out() {
+        let state = |queued, policy, mode| PoolState {
+            queued,
+            retired: false,
+            policy,
+            mode,
+        };
+        use ExhaustionPolicy::*;
+        use QueueMode::*;
+        for mode in [Fifo, LowLatency] {
+            for policy in [Block, DropNewest, Copy] {
+                // With buffers to spare it doesn't matter
+                assert_eq!(state(1, policy, mode).handout(4), Handout::Lend);
+            }
+            assert_eq!(state(0, Block, mode).handout(4), Handout::Lend);
+            assert_eq!(state(0, DropNewest, mode).handout(4), Handout::Requeue);
+            assert_eq!(state(0, Copy, mode).handout(4), Handout::Copy);
+        }
+        // No drop keeps 2 with the driver, whatever the policy
+        for policy in [Block, DropNewest, Copy] {
+            assert_eq!(state(2, policy, NoDrop).handout(4), Handout::Lend);
+            assert_eq!(state(1, policy, NoDrop).handout(4), Handout::Copy);
+            // Or as many as there are
+            assert_eq!(state(1, policy, NoDrop).handout(1), Handout::Lend);
+            assert_eq!(state(0, policy, NoDrop).handout(1), Handout::Copy);
+        }
+    }
+
+    #[test]
+    fn fifo_hands_out_in_order() {
+        let (pool, driver, stats) = pool(3, ExhaustionPolicy::Block, QueueMode::Fifo);
+        driver.capture(2);
+        let first = pool.dequeue(FrameFormat::default(), &stats).unwrap();
+        let second = pool.dequeue(FrameFormat::default(), &stats).unwrap();
+        assert_eq!((index(&first), index(&second)), (0, 1));
+        assert!(!first.is_copy() && !second.is_copy());
+        assert_eq!(driver.empty(), [2]);
+        assert_eq!(stats.snapshot().frames, 2);
+    }
+
+    #[test]
+    fn low_latency_hands_out_the_newest() {
+        let (pool, driver, stats) = pool(4, ExhaustionPolicy::Block, QueueMode::LowLatency);
+        driver.capture(3);
+        let frame = pool.dequeue(FrameFormat::default(), &stats).unwrap();
+        assert_eq!(index(&frame), 2);
+        // The stale ones went straight back
+        assert_eq!(driver.empty(), [3, 0, 1]);
+    }
+
+    #[test]
+    fn dropping_the_last_clone_gives_the_buffer_back() {
+        let (pool, driver, stats) = pool(2, ExhaustionPolicy::Block, QueueMode::Fifo);
+        driver.capture(2);
+        let frame = pool.dequeue(FrameFormat::default(), &stats).unwrap();
+        let clone = frame.clone();
+        drop(frame);
+        assert_eq!(driver.empty(), []);
+        drop(clone);
+        assert_eq!(driver.empty(), [0]);
+
+        // But not once the device stopped streaming
+        let frame = pool.dequeue(FrameFormat::default(), &stats).unwrap();
+        pool.retire();
+        drop(frame);
+        assert_eq!(driver.empty(), [0]);
+        assert!(pool.dequeue(FrameFormat::default(), &stats).is_err());
+    }
+
+    #[test]
+    fn block_hands_out_the_last_buffer() {
+        let (pool, driver, stats) = pool(2, ExhaustionPolicy::Block, QueueMode::Fifo);
+        driver.capture(2);
+        let first = pool.dequeue(FrameFormat::default(), &stats).unwrap();
+        let last = pool.dequeue(FrameFormat::default(), &stats).unwrap();
+        assert_eq!((index(&first), index(&last)), (0, 1));
+        assert!(!last.is_copy());
+        assert_eq!(driver.empty(), []);
+    }
+
+    #[test]
+    fn drop_newest_gives_the_last_buffer_back() {
+        let (pool, driver, stats) = pool(2, ExhaustionPolicy::DropNewest, QueueMode::Fifo);
+        driver.capture(2);
+        let first = pool.dequeue(FrameFormat::default(), &stats).unwrap();
+        assert_eq!(index(&first), 0);
+        // The next one goes back and it waits for another, which the fake
+        // driver doesn't have
+        assert!(pool.dequeue(FrameFormat::default(), &stats).is_err());
+        assert_eq!(driver.empty(), [1]);
+    }
+
+    #[test]
+    fn copy_hands_out_the_last_buffer_as_a_copy() {
+        let (pool, driver, stats) = pool(2, ExhaustionPolicy::Copy, QueueMode::Fifo);
+        driver.capture(2);
+        let first = pool.dequeue(FrameFormat::default(), &stats).unwrap();
+        let last = pool.dequeue(FrameFormat::default(), &stats).unwrap();
+        assert!(!first.is_copy() && last.is_copy());
+        assert_eq!(index(&last), 1);
+        assert_eq!(driver.empty(), [1]);
+        assert_eq!(stats.snapshot().copied_frames, 1);
+    }
+
+    #[test]
+    fn no_drop_copies_rather_than_dip_into_the_reserve() {
+        let (pool, driver, stats) = pool(4, ExhaustionPolicy::Block, QueueMode::NoDrop);
+        driver.capture(3);
+        let frames: Vec<V4l2Frame> = (0..3)
+            .map(|_| pool.dequeue(FrameFormat::default(), &stats).unwrap())
+            .collect();
+        let copies: Vec<bool> = frames.iter().map(V4l2Frame::is_copy).collect();
+        assert_eq!(copies, [false, false, true]);
+        assert_eq!(frames.iter().map(index).collect::<Vec<_>>(), [0, 1, 2]);
+        assert_eq!(driver.empty(), [3, 2]);
+        assert_eq!(stats.snapshot().copied_frames, 1);
+    }
+}
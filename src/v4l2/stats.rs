@@ -0,0 +1,307 @@
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use super::sys;
+
+/// Width of one bucket of `StatsSnapshot::interval_histogram`.
+pub const HISTOGRAM_BUCKET: Duration = Duration::from_millis(1);
+/// Number of buckets, the last one collects everything longer.
+pub const HISTOGRAM_BUCKETS: usize = 100;
+
+/// What we learn from one `VIDIOC_DQBUF`.
+pub(crate) struct BufferSample {
+    pub sequence: u32,
+    pub flags: u32,
+    pub bytesused: u32,
+    pub timestamp: sys::timeval,
+}
+
+#[derive(Default)]
+struct StatsState {
+    frames: u64,
+    error_frames: u64,
+    dropped_frames: u64,
+    copied_frames: u64,
+
+    // Frames and bytes since `first_time`, for the rates
+    rate_frames: u64,
+    rate_bytes: u64,
+
+    last_sequence: Option<u32>,
+    first_time: Option<Duration>,
+    last_time: Option<Duration>,
+    last_interval: Option<Duration>,
+
+    // Welford running mean/variance of the frame interval, in seconds
+    intervals: u64,
+    interval_mean: f64,
+    interval_m2: f64,
+    histogram: Vec<u64>,
+
+    latency: Option<Duration>,
+    latency_sum: Duration,
+    latency_samples: u32,
+}
+
+impl StatsState {
+    fn restart(&mut self) {
+        self.last_sequence = None;
+        self.last_time = None;
+        self.first_time = None;
+        self.rate_frames = 0;
+        self.rate_bytes = 0;
+    }
+}
+
+/// Frame rate, timing and loss counters, updated by the device on every dequeue.
+///
+/// Clones share the same counters, so a clone can be handed to whichever
+/// thread wants to display them while capture runs elsewhere.
+#[derive(Clone, Default)]
+pub struct CaptureStats {
+    state: Arc<Mutex<StatsState>>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct StatsSnapshot {
+    pub frames: u64,
+    /// Frames the driver flagged with `V4L2_BUF_FLAG_ERROR`
+    pub error_frames: u64,
+    /// Frames missing from the sequence numbers
+    pub dropped_frames: u64,
+    /// Frames copied out because the driver was running out of buffers
+    pub copied_frames: u64,
+    /// From the latest frame interval
+    pub fps: f64,
+    pub average_fps: f64,
+    pub mean_interval: Duration,
+    /// Standard deviation of the frame interval
+    pub jitter: Duration,
+    /// Frame intervals in `HISTOGRAM_BUCKET` wide buckets
+    pub interval_histogram: Vec<u64>,
+    /// Driver timestamp to dequeue, only known for monotonic timestamps
+    pub latency: Option<Duration>,
+    pub average_latency: Option<Duration>,
+    pub bytes_per_second: f64,
+}
+
+fn monotonic_now() -> Duration {
+    let mut now = sys::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe {
+        sys::clock_gettime(sys::CLOCK_MONOTONIC as sys::clockid_t, &mut now);
+    }
+    Duration::new(now.tv_sec as u64, now.tv_nsec as u32)
+}
+
+impl CaptureStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start over from zero.
+    pub fn reset(&self) {
+        *self.state.lock().unwrap() = StatsState::default();
+    }
+
+    /// Sequence numbers and timestamps start over when streaming restarts,
+    /// so don't count the jump as dropped frames or as one long interval.
+    pub(crate) fn restart(&self) {
+        self.state.lock().unwrap().restart();
+    }
+
+    pub(crate) fn record(&self, sample: &BufferSample) {
+        let dequeued = monotonic_now();
+        let monotonic = sample.flags & sys::V4L2_BUF_FLAG_TIMESTAMP_MASK
+            == sys::V4L2_BUF_FLAG_TIMESTAMP_MONOTONIC;
+        let driver_time = Duration::new(
+            sample.timestamp.tv_sec as u64,
+            (sample.timestamp.tv_usec * 1000) as u32,
+        );
+
+        // Without a usable driver timestamp the best we have is when we got the frame
+        let time = if monotonic && !driver_time.is_zero() {
+            driver_time
+        } else {
+            dequeued
+        };
+
+        let mut state = self.state.lock().unwrap();
+        let state = &mut *state;
+
+        // Going backwards means the driver started counting over without us
+        // restarting, so it's no telling how many went missing
+        match state.last_sequence {
+            Some(last) if sample.sequence > last => {
+                state.dropped_frames += (sample.sequence - last - 1) as u64;
+            }
+            Some(_) => state.restart(),
+            None => {}
+        }
+        state.last_sequence = Some(sample.sequence);
+
+        state.frames += 1;
+        state.rate_frames += 1;
+        // Like the frames, the first one's bytes arrived before the clock started
+        if state.first_time.is_some() {
+            state.rate_bytes += sample.bytesused as u64;
+        }
+
+        if sample.flags & sys::V4L2_BUF_FLAG_ERROR != 0 {
+            state.error_frames += 1;
+        }
+
+        if let Some(last_time) = state.last_time {
+            let interval = time.saturating_sub(last_time);
+            state.last_interval = Some(interval);
+
+            state.intervals += 1;
+            let secs = interval.as_secs_f64();
+            let delta = secs - state.interval_mean;
+            state.interval_mean += delta / state.intervals as f64;
+            state.interval_m2 += delta * (secs - state.interval_mean);
+
+            if state.histogram.is_empty() {
+                state.histogram = vec![0; HISTOGRAM_BUCKETS];
+            }
+            let bucket = (interval.as_nanos() / HISTOGRAM_BUCKET.as_nanos()) as usize;
+            state.histogram[bucket.min(HISTOGRAM_BUCKETS - 1)] += 1;
+        }
+        state.first_time.get_or_insert(time);
+        state.last_time = Some(time);
+
+        if monotonic && !driver_time.is_zero() {
+            let latency = dequeued.saturating_sub(driver_time);
+            state.latency = Some(latency);
+            state.latency_sum += latency;
+            state.latency_samples += 1;
+        }
+    }
+
+    pub(crate) fn record_copy(&self) {
+        self.state.lock().unwrap().copied_frames += 1;
+    }
+
+    pub fn snapshot(&self) -> StatsSnapshot {
+        let state = self.state.lock().unwrap();
+
+        let elapsed = match (state.first_time, state.last_time) {
+            (Some(first), Some(last)) => last.saturating_sub(first).as_secs_f64(),
+            _ => 0.0,
+        };
+        let per_second = |count: f64| if elapsed > 0.0 { count / elapsed } else { 0.0 };
+
+        let jitter = if state.intervals > 1 {
+            (state.interval_m2 / (state.intervals - 1) as f64).sqrt()
+        } else {
+            0.0
+        };
+
+        StatsSnapshot {
+            frames: state.frames,
+            error_frames: state.error_frames,
+            dropped_frames: state.dropped_frames,
+            copied_frames: state.copied_frames,
+            fps: state
+                .last_interval
+                .filter(|interval| !interval.is_zero())
+                .map_or(0.0, |interval| 1.0 / interval.as_secs_f64()),
+            // Intervals, not frames, is what fits into the elapsed time
+            average_fps: per_second(state.rate_frames.saturating_sub(1) as f64),
+            mean_interval: Duration::from_secs_f64(state.interval_mean),
+            jitter: Duration::from_secs_f64(jitter),
+            interval_histogram: state.histogram.clone(),
+            latency: state.latency,
+            average_latency: (state.latency_samples > 0)
+                .then(|| state.latency_sum / state.latency_samples),
+            bytes_per_second: per_second(state.rate_bytes as f64),
+        }
+    }
+}
+
+impl fmt::Display for StatsSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:.1} fps (avg {:.1}), jitter {:.2} ms",
+            self.fps,
+            self.average_fps,
+            self.jitter.as_secs_f64() * 1000.0
+        )?;
+        if let Some(latency) = self.average_latency {
+            write!(f, ", latency {:.1} ms", latency.as_secs_f64() * 1000.0)?;
+        }
+        write!(f, ", {} dropped", self.dropped_frames)?;
+        if self.copied_frames > 0 {
+            write!(f, ", {} copied", self.copied_frames)?;
+        }
+        write!(
+            f,
+            ", {} errors, {:.2} MB/s",
+            self.error_frames,
+            self.bytes_per_second / 1_000_000.0
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A frame stamped `millis` after some point well past zero
+    fn sample(sequence: u32, millis: i64, bytesused: u32) -> BufferSample {
+        let micros = 1_000_000 + millis * 1000;
+        BufferSample {
+            sequence,
+            flags: sys::V4L2_BUF_FLAG_TIMESTAMP_MONOTONIC,
+            bytesused,
+            timestamp: sys::timeval {
+                tv_sec: micros / 1_000_000,
+                tv_usec: micros % 1_000_000,
+            },
+        }
+    }
+
+    #[test]
+    fn counts_gaps_in_the_sequence() {
+        let stats = CaptureStats::new();
+        for (sequence, millis) in [(0, 0), (1, 40), (4, 160), (5, 200)] {
+            stats.record(&sample(sequence, millis, 1000));
+        }
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.frames, 4);
+        assert_eq!(snapshot.dropped_frames, 2);
+    }
+
+    #[test]
+    fn backwards_sequence_is_a_restart() {
+        let stats = CaptureStats::new();
+        stats.record(&sample(100, 0, 1000));
+        stats.record(&sample(101, 40, 1000));
+        stats.record(&sample(0, 80, 1000));
+        stats.record(&sample(0, 120, 1000));
+        stats.record(&sample(1, 160, 1000));
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.frames, 5);
+        assert_eq!(snapshot.dropped_frames, 0);
+        // Only the two frames since the restart count towards the rates
+        assert!((snapshot.average_fps - 25.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn byte_rate_agrees_with_frame_rate() {
+        let stats = CaptureStats::new();
+        for sequence in 0..11 {
+            stats.record(&sample(sequence, sequence as i64 * 100, 5000));
+        }
+        let snapshot = stats.snapshot();
+        assert!((snapshot.average_fps - 10.0).abs() < 1e-6);
+        assert!((snapshot.bytes_per_second - 50_000.0).abs() < 1e-3);
+        assert!((snapshot.bytes_per_second / snapshot.average_fps - 5000.0).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,61 @@
+//! The kernel's V4L2 headers and the bits of libc the crate uses.
+
+#![allow(non_upper_case_globals)]
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(unused)]
+
+#[cfg(feature = "bindgen")]
+include!(concat!(env!("OUT_DIR"), "/v4l2-bindings.rs"));
+#[cfg(feature = "bindgen")]
+include!(concat!(env!("OUT_DIR"), "/v4l2_constants.rs"));
+
+#[cfg(not(feature = "bindgen"))]
+include!("sys/bindings.rs");
+// What resources/resolve.c prints
+#[cfg(not(feature = "bindgen"))]
+include!("sys/constants.rs");
+
+#[cfg(all(
+    not(feature = "bindgen"),
+    not(all(target_os = "linux", target_pointer_width = "64"))
+))]
+compile_error!("the checked in bindings are for 64 bit Linux, build with the bindgen feature");
+
+#[cfg(test)]
+mod tests {
+    use std::mem::size_of;
+
+    use super::*;
+
+    // The size of the argument, as the headers encoded it in the number
+    fn argument_size(request: u64) -> usize {
+        ((request >> 16) & 0x3fff) as usize
+    }
+
+    #[test]
+    fn structs_match_the_headers() {
+        let expected = [
+            (VIDIOC_QUERYCAP, size_of::<v4l2_capability>()),
+            (VIDIOC_G_FMT, size_of::<v4l2_format>()),
+            (VIDIOC_S_FMT, size_of::<v4l2_format>()),
+            (VIDIOC_REQBUFS, size_of::<v4l2_requestbuffers>()),
+            (VIDIOC_QBUF, size_of::<v4l2_buffer>()),
+            (VIDIOC_DQBUF, size_of::<v4l2_buffer>()),
+            (VIDIOC_STREAMON, size_of::<::std::os::raw::c_int>()),
+            (VIDIOC_STREAMOFF, size_of::<::std::os::raw::c_int>()),
+            (VIDIOC_ENUM_FMT, size_of::<v4l2_fmtdesc>()),
+            (VIDIOC_ENUM_FRAMESIZES, size_of::<v4l2_frmsizeenum>()),
+            (VIDIOC_QUERYCTRL, size_of::<v4l2_queryctrl>()),
+            (VIDIOC_QUERYMENU, size_of::<v4l2_querymenu>()),
+            (VIDIOC_G_CTRL, size_of::<v4l2_control>()),
+            (VIDIOC_S_CTRL, size_of::<v4l2_control>()),
+        ];
+        for (i, (request, size)) in expected.into_iter().enumerate() {
+            assert_eq!(argument_size(request), size, "ioctl {i}");
+        }
+        // Inside `v4l2_format`, which only has its union's size
+        assert!(size_of::<v4l2_pix_format_mplane>() <= 200);
+        assert_eq!(size_of::<v4l2_plane>(), 64);
+    }
+}
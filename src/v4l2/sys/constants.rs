@@ -0,0 +1,243 @@
+pub const V4L2_PIX_FMT_RGB332: u32 = 826427218;
+pub const V4L2_PIX_FMT_RGB444: u32 = 875836498;
+pub const V4L2_PIX_FMT_ARGB444: u32 = 842093121;
+pub const V4L2_PIX_FMT_XRGB444: u32 = 842093144;
+pub const V4L2_PIX_FMT_RGBA444: u32 = 842088786;
+pub const V4L2_PIX_FMT_RGBX444: u32 = 842094674;
+pub const V4L2_PIX_FMT_ABGR444: u32 = 842089025;
+pub const V4L2_PIX_FMT_XBGR444: u32 = 842089048;
+pub const V4L2_PIX_FMT_BGRA444: u32 = 842088775;
+pub const V4L2_PIX_FMT_BGRX444: u32 = 842094658;
+pub const V4L2_PIX_FMT_RGB555: u32 = 1329743698;
+pub const V4L2_PIX_FMT_ARGB555: u32 = 892424769;
+pub const V4L2_PIX_FMT_XRGB555: u32 = 892424792;
+pub const V4L2_PIX_FMT_RGBA555: u32 = 892420434;
+pub const V4L2_PIX_FMT_RGBX555: u32 = 892426322;
+pub const V4L2_PIX_FMT_ABGR555: u32 = 892420673;
+pub const V4L2_PIX_FMT_XBGR555: u32 = 892420696;
+pub const V4L2_PIX_FMT_BGRA555: u32 = 892420418;
+pub const V4L2_PIX_FMT_BGRX555: u32 = 892426306;
+pub const V4L2_PIX_FMT_RGB565: u32 = 1346520914;
+pub const V4L2_PIX_FMT_RGB555X: u32 = 1363298130;
+pub const V4L2_PIX_FMT_ARGB555X: u32 = 3039908417;
+pub const V4L2_PIX_FMT_XRGB555X: u32 = 3039908440;
+pub const V4L2_PIX_FMT_RGB565X: u32 = 1380075346;
+pub const V4L2_PIX_FMT_BGR666: u32 = 1213351746;
+pub const V4L2_PIX_FMT_BGR24: u32 = 861030210;
+pub const V4L2_PIX_FMT_RGB24: u32 = 859981650;
+pub const V4L2_PIX_FMT_BGR32: u32 = 877807426;
+pub const V4L2_PIX_FMT_ABGR32: u32 = 875713089;
+pub const V4L2_PIX_FMT_XBGR32: u32 = 875713112;
+pub const V4L2_PIX_FMT_BGRA32: u32 = 875708754;
+pub const V4L2_PIX_FMT_BGRX32: u32 = 875714642;
+pub const V4L2_PIX_FMT_RGB32: u32 = 876758866;
+pub const V4L2_PIX_FMT_RGBA32: u32 = 875708993;
+pub const V4L2_PIX_FMT_RGBX32: u32 = 875709016;
+pub const V4L2_PIX_FMT_ARGB32: u32 = 875708738;
+pub const V4L2_PIX_FMT_XRGB32: u32 = 875714626;
+pub const V4L2_PIX_FMT_RGBX1010102: u32 = 808671314;
+pub const V4L2_PIX_FMT_RGBA1010102: u32 = 808665426;
+pub const V4L2_PIX_FMT_ARGB2101010: u32 = 808669761;
+pub const V4L2_PIX_FMT_BGR48_12: u32 = 842085186;
+pub const V4L2_PIX_FMT_ABGR64_12: u32 = 842085442;
+pub const V4L2_PIX_FMT_GREY: u32 = 1497715271;
+pub const V4L2_PIX_FMT_Y4: u32 = 540291161;
+pub const V4L2_PIX_FMT_Y6: u32 = 540422233;
+pub const V4L2_PIX_FMT_Y10: u32 = 540029273;
+pub const V4L2_PIX_FMT_Y12: u32 = 540160345;
+pub const V4L2_PIX_FMT_Y012: u32 = 842084441;
+pub const V4L2_PIX_FMT_Y14: u32 = 540291417;
+pub const V4L2_PIX_FMT_Y16: u32 = 540422489;
+pub const V4L2_PIX_FMT_Y16_BE: u32 = 2687906137;
+pub const V4L2_PIX_FMT_Y10BPACK: u32 = 1110454617;
+pub const V4L2_PIX_FMT_Y10P: u32 = 1345335641;
+pub const V4L2_PIX_FMT_IPU3_Y10: u32 = 2033414249;
+pub const V4L2_PIX_FMT_PAL8: u32 = 944521552;
+pub const V4L2_PIX_FMT_UV8: u32 = 540563029;
+pub const V4L2_PIX_FMT_YUYV: u32 = 1448695129;
+pub const V4L2_PIX_FMT_YYUV: u32 = 1448434009;
+pub const V4L2_PIX_FMT_YVYU: u32 = 1431918169;
+pub const V4L2_PIX_FMT_UYVY: u32 = 1498831189;
+pub const V4L2_PIX_FMT_VYUY: u32 = 1498765654;
+pub const V4L2_PIX_FMT_Y41P: u32 = 1345401945;
+pub const V4L2_PIX_FMT_YUV444: u32 = 875836505;
+pub const V4L2_PIX_FMT_YUV555: u32 = 1331058009;
+pub const V4L2_PIX_FMT_YUV565: u32 = 1347835225;
+pub const V4L2_PIX_FMT_YUV24: u32 = 861295961;
+pub const V4L2_PIX_FMT_YUV32: u32 = 878073177;
+pub const V4L2_PIX_FMT_AYUV32: u32 = 1448433985;
+pub const V4L2_PIX_FMT_XYUV32: u32 = 1448434008;
+pub const V4L2_PIX_FMT_VUYA32: u32 = 1096373590;
+pub const V4L2_PIX_FMT_VUYX32: u32 = 1482249558;
+pub const V4L2_PIX_FMT_YUVA32: u32 = 1096176985;
+pub const V4L2_PIX_FMT_YUVX32: u32 = 1482052953;
+pub const V4L2_PIX_FMT_M420: u32 = 808596557;
+pub const V4L2_PIX_FMT_YUV48_12: u32 = 842085209;
+pub const V4L2_PIX_FMT_Y210: u32 = 808530521;
+pub const V4L2_PIX_FMT_Y212: u32 = 842084953;
+pub const V4L2_PIX_FMT_Y216: u32 = 909193817;
+pub const V4L2_PIX_FMT_NV12: u32 = 842094158;
+pub const V4L2_PIX_FMT_NV21: u32 = 825382478;
+pub const V4L2_PIX_FMT_NV16: u32 = 909203022;
+pub const V4L2_PIX_FMT_NV61: u32 = 825644622;
+pub const V4L2_PIX_FMT_NV24: u32 = 875714126;
+pub const V4L2_PIX_FMT_NV42: u32 = 842290766;
+pub const V4L2_PIX_FMT_P010: u32 = 808530000;
+pub const V4L2_PIX_FMT_P012: u32 = 842084432;
+pub const V4L2_PIX_FMT_NV12M: u32 = 842091854;
+pub const V4L2_PIX_FMT_NV21M: u32 = 825380174;
+pub const V4L2_PIX_FMT_NV16M: u32 = 909200718;
+pub const V4L2_PIX_FMT_NV61M: u32 = 825642318;
+pub const V4L2_PIX_FMT_P012M: u32 = 842091856;
+pub const V4L2_PIX_FMT_YUV410: u32 = 961959257;
+pub const V4L2_PIX_FMT_YVU410: u32 = 961893977;
+pub const V4L2_PIX_FMT_YUV411P: u32 = 1345401140;
+pub const V4L2_PIX_FMT_YUV420: u32 = 842093913;
+pub const V4L2_PIX_FMT_YVU420: u32 = 842094169;
+pub const V4L2_PIX_FMT_YUV422P: u32 = 1345466932;
+pub const V4L2_PIX_FMT_YUV420M: u32 = 842091865;
+pub const V4L2_PIX_FMT_YVU420M: u32 = 825380185;
+pub const V4L2_PIX_FMT_YUV422M: u32 = 909200729;
+pub const V4L2_PIX_FMT_YVU422M: u32 = 825642329;
+pub const V4L2_PIX_FMT_YUV444M: u32 = 875711833;
+pub const V4L2_PIX_FMT_YVU444M: u32 = 842288473;
+pub const V4L2_PIX_FMT_NV12_4L4: u32 = 842093654;
+pub const V4L2_PIX_FMT_NV12_16L16: u32 = 842091848;
+pub const V4L2_PIX_FMT_NV12_32L32: u32 = 842093651;
+pub const V4L2_PIX_FMT_NV15_4L4: u32 = 892425302;
+pub const V4L2_PIX_FMT_P010_4L4: u32 = 808530004;
+pub const V4L2_PIX_FMT_NV12_8L128: u32 = 842093633;
+pub const V4L2_PIX_FMT_NV12_10BE_8L128: u32 = 2989578305;
+pub const V4L2_PIX_FMT_NV12MT: u32 = 842091860;
+pub const V4L2_PIX_FMT_NV12MT_16X16: u32 = 842091862;
+pub const V4L2_PIX_FMT_NV12M_8L128: u32 = 842088782;
+pub const V4L2_PIX_FMT_NV12M_10BE_8L128: u32 = 2989577294;
+pub const V4L2_PIX_FMT_SBGGR8: u32 = 825770306;
+pub const V4L2_PIX_FMT_SGBRG8: u32 = 1196573255;
+pub const V4L2_PIX_FMT_SGRBG8: u32 = 1195528775;
+pub const V4L2_PIX_FMT_SRGGB8: u32 = 1111967570;
+pub const V4L2_PIX_FMT_SBGGR10: u32 = 808535874;
+pub const V4L2_PIX_FMT_SGBRG10: u32 = 808534599;
+pub const V4L2_PIX_FMT_SGRBG10: u32 = 808534338;
+pub const V4L2_PIX_FMT_SRGGB10: u32 = 808535890;
+pub const V4L2_PIX_FMT_SBGGR10P: u32 = 1094795888;
+pub const V4L2_PIX_FMT_SGBRG10P: u32 = 1094797168;
+pub const V4L2_PIX_FMT_SGRBG10P: u32 = 1094805360;
+pub const V4L2_PIX_FMT_SRGGB10P: u32 = 1094799984;
+pub const V4L2_PIX_FMT_SBGGR10ALAW8: u32 = 943800929;
+pub const V4L2_PIX_FMT_SGBRG10ALAW8: u32 = 943802209;
+pub const V4L2_PIX_FMT_SGRBG10ALAW8: u32 = 943810401;
+pub const V4L2_PIX_FMT_SRGGB10ALAW8: u32 = 943805025;
+pub const V4L2_PIX_FMT_SBGGR10DPCM8: u32 = 943800930;
+pub const V4L2_PIX_FMT_SGBRG10DPCM8: u32 = 943802210;
+pub const V4L2_PIX_FMT_SGRBG10DPCM8: u32 = 808535106;
+pub const V4L2_PIX_FMT_SRGGB10DPCM8: u32 = 943805026;
+pub const V4L2_PIX_FMT_SBGGR12: u32 = 842090306;
+pub const V4L2_PIX_FMT_SGBRG12: u32 = 842089031;
+pub const V4L2_PIX_FMT_SGRBG12: u32 = 842088770;
+pub const V4L2_PIX_FMT_SRGGB12: u32 = 842090322;
+pub const V4L2_PIX_FMT_SBGGR12P: u32 = 1128481392;
+pub const V4L2_PIX_FMT_SGBRG12P: u32 = 1128482672;
+pub const V4L2_PIX_FMT_SGRBG12P: u32 = 1128490864;
+pub const V4L2_PIX_FMT_SRGGB12P: u32 = 1128485488;
+pub const V4L2_PIX_FMT_SBGGR14: u32 = 875644738;
+pub const V4L2_PIX_FMT_SGBRG14: u32 = 875643463;
+pub const V4L2_PIX_FMT_SGRBG14: u32 = 875647559;
+pub const V4L2_PIX_FMT_SRGGB14: u32 = 875644754;
+pub const V4L2_PIX_FMT_SBGGR14P: u32 = 1162166896;
+pub const V4L2_PIX_FMT_SGBRG14P: u32 = 1162168176;
+pub const V4L2_PIX_FMT_SGRBG14P: u32 = 1162176368;
+pub const V4L2_PIX_FMT_SRGGB14P: u32 = 1162170992;
+pub const V4L2_PIX_FMT_SBGGR16: u32 = 844257602;
+pub const V4L2_PIX_FMT_SGBRG16: u32 = 909197895;
+pub const V4L2_PIX_FMT_SGRBG16: u32 = 909201991;
+pub const V4L2_PIX_FMT_SRGGB16: u32 = 909199186;
+pub const V4L2_PIX_FMT_HSV24: u32 = 861295432;
+pub const V4L2_PIX_FMT_HSV32: u32 = 878072648;
+pub const V4L2_PIX_FMT_MJPEG: u32 = 1196444237;
+pub const V4L2_PIX_FMT_JPEG: u32 = 1195724874;
+pub const V4L2_PIX_FMT_DV: u32 = 1685288548;
+pub const V4L2_PIX_FMT_MPEG: u32 = 1195724877;
+pub const V4L2_PIX_FMT_H264: u32 = 875967048;
+pub const V4L2_PIX_FMT_H264_NO_SC: u32 = 826496577;
+pub const V4L2_PIX_FMT_H264_MVC: u32 = 875967053;
+pub const V4L2_PIX_FMT_H263: u32 = 859189832;
+pub const V4L2_PIX_FMT_MPEG1: u32 = 826757197;
+pub const V4L2_PIX_FMT_MPEG2: u32 = 843534413;
+pub const V4L2_PIX_FMT_MPEG2_SLICE: u32 = 1395803981;
+pub const V4L2_PIX_FMT_MPEG4: u32 = 877088845;
+pub const V4L2_PIX_FMT_XVID: u32 = 1145656920;
+pub const V4L2_PIX_FMT_VC1_ANNEX_G: u32 = 1194410838;
+pub const V4L2_PIX_FMT_VC1_ANNEX_L: u32 = 1278296918;
+pub const V4L2_PIX_FMT_VP8: u32 = 808996950;
+pub const V4L2_PIX_FMT_VP8_FRAME: u32 = 1178095702;
+pub const V4L2_PIX_FMT_VP9: u32 = 809062486;
+pub const V4L2_PIX_FMT_VP9_FRAME: u32 = 1178161238;
+pub const V4L2_PIX_FMT_HEVC: u32 = 1129727304;
+pub const V4L2_PIX_FMT_FWHT: u32 = 1414027078;
+pub const V4L2_PIX_FMT_FWHT_STATELESS: u32 = 1213679187;
+pub const V4L2_PIX_FMT_H264_SLICE: u32 = 875967059;
+pub const V4L2_PIX_FMT_HEVC_SLICE: u32 = 892744275;
+pub const V4L2_PIX_FMT_AV1_FRAME: u32 = 1177638465;
+pub const V4L2_PIX_FMT_SPK: u32 = 810242131;
+pub const V4L2_PIX_FMT_RV30: u32 = 808670802;
+pub const V4L2_PIX_FMT_RV40: u32 = 808736338;
+pub const V4L2_PIX_FMT_CPIA1: u32 = 1095323715;
+pub const V4L2_PIX_FMT_WNVA: u32 = 1096175191;
+pub const V4L2_PIX_FMT_SN9C10X: u32 = 808532307;
+pub const V4L2_PIX_FMT_SN9C20X_I420: u32 = 808597843;
+pub const V4L2_PIX_FMT_PWC1: u32 = 826496848;
+pub const V4L2_PIX_FMT_PWC2: u32 = 843274064;
+pub const V4L2_PIX_FMT_ET61X251: u32 = 892483141;
+pub const V4L2_PIX_FMT_SPCA501: u32 = 825242963;
+pub const V4L2_PIX_FMT_SPCA505: u32 = 892351827;
+pub const V4L2_PIX_FMT_SPCA508: u32 = 942683475;
+pub const V4L2_PIX_FMT_SPCA561: u32 = 825636179;
+pub const V4L2_PIX_FMT_PAC207: u32 = 925905488;
+pub const V4L2_PIX_FMT_MR97310A: u32 = 808530765;
+pub const V4L2_PIX_FMT_JL2005BCD: u32 = 808602698;
+pub const V4L2_PIX_FMT_SN9C2028: u32 = 1481527123;
+pub const V4L2_PIX_FMT_SQ905C: u32 = 1127559225;
+pub const V4L2_PIX_FMT_PJPG: u32 = 1196444240;
+pub const V4L2_PIX_FMT_OV511: u32 = 825308495;
+pub const V4L2_PIX_FMT_OV518: u32 = 942749007;
+pub const V4L2_PIX_FMT_STV0680: u32 = 808990291;
+pub const V4L2_PIX_FMT_TM6000: u32 = 808865108;
+pub const V4L2_PIX_FMT_CIT_YYVYUY: u32 = 1448364355;
+pub const V4L2_PIX_FMT_KONICA420: u32 = 1229868875;
+pub const V4L2_PIX_FMT_JPGL: u32 = 1279742026;
+pub const V4L2_PIX_FMT_SE401: u32 = 825242707;
+pub const V4L2_PIX_FMT_S5C_UYVY_JPG: u32 = 1229141331;
+pub const V4L2_PIX_FMT_Y8I: u32 = 541669465;
+pub const V4L2_PIX_FMT_Y12I: u32 = 1228026201;
+pub const V4L2_PIX_FMT_Z16: u32 = 540422490;
+pub const V4L2_PIX_FMT_MT21C: u32 = 825381965;
+pub const V4L2_PIX_FMT_MM21: u32 = 825380173;
+pub const V4L2_PIX_FMT_MT2110T: u32 = 1412584525;
+pub const V4L2_PIX_FMT_MT2110R: u32 = 1379030093;
+pub const V4L2_PIX_FMT_INZI: u32 = 1230655049;
+pub const V4L2_PIX_FMT_CNF4: u32 = 877022787;
+pub const V4L2_PIX_FMT_HI240: u32 = 875710792;
+pub const V4L2_PIX_FMT_QC08C: u32 = 1127755857;
+pub const V4L2_PIX_FMT_QC10C: u32 = 1127231825;
+pub const V4L2_PIX_FMT_AJPG: u32 = 1196444225;
+pub const V4L2_PIX_FMT_HEXTILE: u32 = 1280596040;
+pub const V4L2_PIX_FMT_IPU3_SBGGR10: u32 = 1647538281;
+pub const V4L2_PIX_FMT_IPU3_SGBRG10: u32 = 1731424361;
+pub const V4L2_PIX_FMT_IPU3_SGRBG10: u32 = 1194553449;
+pub const V4L2_PIX_FMT_IPU3_SRGGB10: u32 = 1915973737;
+pub const VIDIOC_QUERYCAP: u64 = 2154321408;
+pub const VIDIOC_G_FMT: u64 = 3234878980;
+pub const VIDIOC_S_FMT: u64 = 3234878981;
+pub const VIDIOC_REQBUFS: u64 = 3222558216;
+pub const VIDIOC_QBUF: u64 = 3227014671;
+pub const VIDIOC_DQBUF: u64 = 3227014673;
+pub const VIDIOC_STREAMON: u64 = 1074026002;
+pub const VIDIOC_STREAMOFF: u64 = 1074026003;
+pub const VIDIOC_ENUM_FMT: u64 = 3225441794;
+pub const VIDIOC_ENUM_FRAMESIZES: u64 = 3224131146;
+pub const VIDIOC_ENUM_FRAMEINTERVALS: u64 = 3224655435;
+pub const VIDIOC_QUERYCTRL: u64 = 3225703972;
+pub const VIDIOC_QUERYMENU: u64 = 3224131109;
+pub const VIDIOC_G_CTRL: u64 = 3221771803;
+pub const VIDIOC_S_CTRL: u64 = 3221771804;
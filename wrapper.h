@@ -1,5 +1,11 @@
+#include <errno.h>
+
 #include <sys/ioctl.h>
 
 #include <linux/videodev2.h>
 
-#include <poll.h>
\ No newline at end of file
+#include <poll.h>
+
+#include <sys/inotify.h>
+
+#include <time.h>